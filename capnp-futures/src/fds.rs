@@ -0,0 +1,345 @@
+// Copyright (c) 2013-2016 Sandstorm Development Group, Inc. and contributors
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Asynchronous reading and writing of messages with attached file descriptors, over
+//! Unix domain sockets.
+//!
+//! The descriptors are sent as an `SCM_RIGHTS` control message along with the first bytes
+//! of the message, which is compatible with the C++ implementation.
+//!
+//! This module does not depend on any particular event loop. To use it, implement
+//! `AsyncFdRead` and `AsyncFdWrite` for your runtime's Unix socket type, typically by calling
+//! `recv_with_fds()` and `send_with_fds()` once the socket is ready.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::{FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use capnp::{message, Result, OutputSegments};
+use capnp::serialize::OwnedSegments;
+
+use futures::{AsyncRead, AsyncWrite};
+
+use crate::serialize::AsOutputSegments;
+
+/// The maximum number of file descriptors that we will receive with a single message.
+/// This matches Linux's `SCM_MAX_FD`.
+pub const MAX_FDS_PER_MESSAGE: usize = 253;
+
+/// An `AsyncRead` that can also receive file descriptors.
+pub trait AsyncFdRead: AsyncRead {
+    /// Like `poll_read()`, but additionally appends any file descriptors that arrived with the
+    /// data to `fds`.
+    fn poll_read_with_fds(self: Pin<&mut Self>, cx: &mut Context<'_>,
+                          buf: &mut [u8], fds: &mut Vec<OwnedFd>)
+                          -> Poll<io::Result<usize>>;
+}
+
+/// An `AsyncWrite` that can also send file descriptors.
+pub trait AsyncFdWrite: AsyncWrite {
+    /// Like `poll_write()`, but additionally sends `fds` along with the data. If a nonzero number
+    /// of bytes is written, the file descriptors have been sent.
+    fn poll_write_with_fds(self: Pin<&mut Self>, cx: &mut Context<'_>,
+                           buf: &[u8], fds: &[RawFd])
+                           -> Poll<io::Result<usize>>;
+}
+
+impl <R> AsyncFdRead for &mut R where R: AsyncFdRead + Unpin + ?Sized {
+    fn poll_read_with_fds(mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+                          buf: &mut [u8], fds: &mut Vec<OwnedFd>)
+                          -> Poll<io::Result<usize>>
+    {
+        Pin::new(&mut **self).poll_read_with_fds(cx, buf, fds)
+    }
+}
+
+impl <W> AsyncFdWrite for &mut W where W: AsyncFdWrite + Unpin + ?Sized {
+    fn poll_write_with_fds(mut self: Pin<&mut Self>, cx: &mut Context<'_>,
+                           buf: &[u8], fds: &[RawFd])
+                           -> Poll<io::Result<usize>>
+    {
+        Pin::new(&mut **self).poll_write_with_fds(cx, buf, fds)
+    }
+}
+
+/// A message along with the file descriptors that should be sent with it. The descriptors are
+/// owned by this struct, so they stay open until the message has been written.
+pub struct MessageWithFds<M> where M: AsOutputSegments {
+    pub message: M,
+    pub fds: Vec<OwnedFd>,
+}
+
+impl <M> AsOutputSegments for MessageWithFds<M> where M: AsOutputSegments {
+    fn as_output_segments<'a>(&'a self) -> OutputSegments<'a> {
+        self.message.as_output_segments()
+    }
+}
+
+/// Collects the file descriptors received while reading, so that we can reuse the plain
+/// stream framing.
+struct FdCollector<'a, R> where R: AsyncFdRead + Unpin {
+    reader: R,
+    fds: &'a mut Vec<OwnedFd>,
+}
+
+impl <'a, R> AsyncRead for FdCollector<'a, R> where R: AsyncFdRead + Unpin {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        Pin::new(&mut this.reader).poll_read_with_fds(cx, buf, this.fds)
+    }
+}
+
+/// Attaches file descriptors to the first nonempty write.
+struct FdAttacher<'a, W> where W: AsyncFdWrite + Unpin {
+    writer: W,
+    fds: &'a [RawFd],
+}
+
+impl <'a, W> AsyncWrite for FdAttacher<'a, W> where W: AsyncFdWrite + Unpin {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.fds.is_empty() {
+            return Pin::new(&mut this.writer).poll_write(cx, buf);
+        }
+        let result = Pin::new(&mut this.writer).poll_write_with_fds(cx, buf, this.fds);
+        if let Poll::Ready(Ok(n)) = result {
+            if n > 0 {
+                this.fds = &[];
+            }
+        }
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_close(cx)
+    }
+}
+
+/// Asynchronously reads a message from `reader`, along with any file descriptors that were
+/// attached to it. Returns `None` if `reader` is at end-of-file.
+pub async fn try_read_message_with_fds<R>(reader: R, options: message::ReaderOptions)
+                                          -> Result<Option<(message::Reader<OwnedSegments>, Vec<OwnedFd>)>>
+    where R: AsyncFdRead + Unpin
+{
+    let mut fds = Vec::new();
+    let maybe_message = crate::serialize::try_read_message(FdCollector { reader, fds: &mut fds }, options).await?;
+    Ok(maybe_message.map(|message| (message, fds)))
+}
+
+/// Writes the provided message to `writer`, attaching `fds` to it. Does not call `flush()`.
+pub async fn write_message_with_fds<W, M>(writer: W, message: M, fds: &[RawFd]) -> Result<()>
+    where W: AsyncFdWrite + Unpin, M: AsOutputSegments
+{
+    crate::serialize::write_message(FdAttacher { writer, fds }, message).await
+}
+
+/// Calls `recvmsg()` on `socket`, appending any received file descriptors to `fds`. The received
+/// descriptors have `FD_CLOEXEC` set.
+///
+/// This is a building block for implementing `AsyncFdRead`. If `socket` is nonblocking and no
+/// data is available, the returned error has kind `WouldBlock`.
+pub fn recv_with_fds(socket: RawFd, buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let control_len = unsafe { libc::CMSG_SPACE((MAX_FDS_PER_MESSAGE * mem::size_of::<RawFd>()) as u32) as usize };
+    let mut control = vec![0u8; control_len];
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = control.len() as _;
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+              target_os = "netbsd", target_os = "openbsd"))]
+    let flags = libc::MSG_CMSG_CLOEXEC;
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+                  target_os = "netbsd", target_os = "openbsd")))]
+    let flags = 0;
+
+    let n = unsafe { libc::recvmsg(socket, &mut msg, flags) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let data_len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+                for idx in 0..(data_len / mem::size_of::<RawFd>()) {
+                    let fd = std::ptr::read_unaligned(data.add(idx));
+                    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd",
+                                  target_os = "netbsd", target_os = "openbsd")))]
+                    libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                    fds.push(OwnedFd::from_raw_fd(fd));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        // `io::Error::other()` needs a newer rustc than we support.
+        #[allow(unknown_lints, clippy::io_other_error)]
+        return Err(io::Error::new(io::ErrorKind::Other, "received too many file descriptors; some were dropped"));
+    }
+
+    Ok(n as usize)
+}
+
+/// Calls `sendmsg()` on `socket`, sending `fds` along with the data. If `fds` is empty, this is
+/// equivalent to an ordinary `send()`.
+///
+/// This is a building block for implementing `AsyncFdWrite`. If `socket` is nonblocking and
+/// cannot accept more data, the returned error has kind `WouldBlock`.
+pub fn send_with_fds(socket: RawFd, buf: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let fds_len = mem::size_of_val(fds) as u32;
+    let control_len = if fds.is_empty() { 0 } else { unsafe { libc::CMSG_SPACE(fds_len) as usize } };
+    let mut control = vec![0u8; control_len];
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if !fds.is_empty() {
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+            std::ptr::copy_nonoverlapping(fds.as_ptr() as *const u8, libc::CMSG_DATA(cmsg), fds_len as usize);
+        }
+    }
+
+    // Don't raise SIGPIPE if the peer has gone away; report EPIPE instead.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = libc::MSG_NOSIGNAL;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags = 0;
+
+    let n = unsafe { libc::sendmsg(socket, &msg, flags) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+    use std::os::unix::net::UnixStream;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures::{AsyncRead, AsyncWrite};
+
+    use capnp::message;
+
+    use super::{AsyncFdRead, AsyncFdWrite, recv_with_fds, send_with_fds,
+                try_read_message_with_fds, write_message_with_fds};
+
+    /// A blocking Unix socket. Good enough for tests where the socket buffer can hold
+    /// everything that is written.
+    struct BlockingSocket(UnixStream);
+
+    impl AsyncRead for BlockingSocket {
+        fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().0.read(buf))
+        }
+    }
+
+    impl AsyncFdRead for BlockingSocket {
+        fn poll_read_with_fds(self: Pin<&mut Self>, _cx: &mut Context<'_>,
+                              buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> Poll<io::Result<usize>> {
+            Poll::Ready(recv_with_fds(self.0.as_raw_fd(), buf, fds))
+        }
+    }
+
+    impl AsyncWrite for BlockingSocket {
+        fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().0.write(buf))
+        }
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncFdWrite for BlockingSocket {
+        fn poll_write_with_fds(self: Pin<&mut Self>, _cx: &mut Context<'_>,
+                               buf: &[u8], fds: &[RawFd]) -> Poll<io::Result<usize>> {
+            Poll::Ready(send_with_fds(self.0.as_raw_fd(), buf, fds))
+        }
+    }
+
+    #[test]
+    fn fds_arrive_with_their_message() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut writer, mut reader) = (BlockingSocket(a), BlockingSocket(b));
+
+        // Pass one end of another socket pair, and check that it is still connected on arrival.
+        let (passed, mut kept) = UnixStream::pair().unwrap();
+
+        let mut m1 = message::Builder::new_default();
+        m1.set_root("first").unwrap();
+        let mut m2 = message::Builder::new_default();
+        m2.set_root("second").unwrap();
+
+        futures::executor::block_on(async {
+            write_message_with_fds(&mut writer, &m1, &[passed.as_raw_fd()]).await.unwrap();
+            write_message_with_fds(&mut writer, &m2, &[]).await.unwrap();
+            drop(passed);
+
+            let (r1, fds1) = try_read_message_with_fds(&mut reader, message::ReaderOptions::new())
+                .await.unwrap().unwrap();
+            assert_eq!(r1.get_root::<capnp::text::Reader>().unwrap(), "first");
+            assert_eq!(fds1.len(), 1);
+
+            let (r2, fds2) = try_read_message_with_fds(&mut reader, message::ReaderOptions::new())
+                .await.unwrap().unwrap();
+            assert_eq!(r2.get_root::<capnp::text::Reader>().unwrap(), "second");
+            assert!(fds2.is_empty());
+
+            let mut received = UnixStream::from(fds1.into_iter().next().unwrap());
+            received.write_all(b"hi").unwrap();
+            let mut buf = [0; 2];
+            kept.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"hi");
+        });
+    }
+}
@@ -20,7 +20,11 @@
 
 pub use read_stream::ReadStream;
 pub use write_queue::{write_queue, Sender};
+#[cfg(unix)]
+pub use write_queue::write_queue_with_fds;
 
+#[cfg(unix)]
+pub mod fds;
 pub mod serialize;
 mod read_stream;
 mod write_queue;
@@ -69,6 +69,37 @@ pub fn write_queue<W, M>(mut writer: W) -> (Sender<M>, impl Future<Output=Result
     (sender, queue)
 }
 
+/// Like `write_queue()`, but sends each message's file descriptors along with it.
+#[cfg(unix)]
+pub fn write_queue_with_fds<W, M>(mut writer: W)
+                                  -> (Sender<crate::fds::MessageWithFds<M>>, impl Future<Output=Result<(),Error>>)
+    where W: crate::fds::AsyncFdWrite + Unpin, M: AsOutputSegments
+{
+    let (tx, mut rx) = futures::channel::mpsc::unbounded::<Item<crate::fds::MessageWithFds<M>>>();
+
+    let sender = Sender { sender: tx };
+
+    let queue = async move {
+        while let Some(item) = rx.next().await {
+            match item {
+                Item::Message(m, returner) => {
+                    let fds: Vec<_> = m.fds.iter().map(::std::os::unix::io::AsRawFd::as_raw_fd).collect();
+                    crate::fds::write_message_with_fds(&mut writer, &m.message, &fds[..]).await?;
+                    writer.flush().await?;
+                    let _ = returner.send(m);
+                }
+                Item::Done(r, finisher) => {
+                    let _ = finisher.send(());
+                    return r;
+                }
+            }
+        }
+        Ok(())
+    };
+
+    (sender, queue)
+}
+
 impl <M> Sender<M> where M: AsOutputSegments  {
     /// Enqueues a message to be written. The returned future resolves once the write
     /// has completed.
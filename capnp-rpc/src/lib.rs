@@ -66,7 +66,7 @@ use futures::channel::oneshot;
 use capnp::Error;
 use capnp::capability::Promise;
 use capnp::private::capability::{ClientHook};
-use std::cell::{RefCell};
+use std::cell::{Cell, RefCell};
 use std::rc::{Rc};
 
 use crate::task_set::TaskSet;
@@ -98,11 +98,14 @@ macro_rules! pry {
 mod broken;
 mod local;
 mod queued;
+mod reconnect;
 mod rpc;
 mod attach;
 mod sender_queue;
 mod split;
 mod task_set;
+#[cfg(feature = "test-network")]
+pub mod test_network;
 pub mod twoparty;
 
 pub trait OutgoingMessage {
@@ -116,10 +119,22 @@ pub trait OutgoingMessage {
                 Rc<::capnp::message::Builder<::capnp::message::HeapAllocator>>);
 
     fn take(self: Box<Self>) -> ::capnp::message::Builder<::capnp::message::HeapAllocator>;
+
+    /// Attaches file descriptors to the message, to be sent along with it. The descriptors remain
+    /// owned by the capabilities that they belong to and only need to stay open for the duration
+    /// of this call, so a message that is not sent right away must hold duplicates of them.
+    /// Connections that cannot pass file descriptors ignore them.
+    fn set_fds(&mut self, _fds: Vec<i32>) {}
 }
 
 pub trait IncomingMessage {
     fn get_body<'a>(&'a self) -> ::capnp::Result<::capnp::any_pointer::Reader<'a>>;
+
+    /// Returns the file descriptors that arrived with the message. They are owned by the message
+    /// and are closed when it is dropped.
+    fn get_attached_fds(&self) -> &[i32] {
+        &[]
+    }
 }
 
 pub trait Connection<VatId> {
@@ -133,6 +148,15 @@ pub trait Connection<VatId> {
     // Waits until all outgoing messages have been sent, then shuts down the outgoing stream. The
     // returned promise resolves after shutdown is complete.
     fn shutdown(&mut self, result: ::capnp::Result<()>) -> Promise<(), Error>;
+
+    /// Returns true if this connection leads to the same peer vat as `other`. An `RpcSystem`
+    /// that is already connected through `other` keeps using that connection instead.
+    ///
+    /// The default treats every connection as leading to the same peer, which is right for
+    /// networks with a single peer such as `twoparty::VatNetwork`.
+    fn is_same_peer(&self, _other: &dyn Connection<VatId>) -> bool {
+        true
+    }
 }
 
 pub trait VatNetwork<VatId> {
@@ -145,28 +169,84 @@ pub trait VatNetwork<VatId> {
     fn drive_until_shutdown(&mut self) -> Promise<(), Error>;
 }
 
+/// Options controlling the messages that an `RpcSystem` sends.
+///
+/// A peer rejects any message that exceeds the limits in its `ReaderOptions`, and typically
+/// disconnects when that happens. Configuring matching limits here makes an oversized call fail
+/// locally, with a clear error, before it is transmitted.
+#[derive(Clone, Copy, Debug)]
+pub struct SendOptions {
+    /// Calls and returns larger than this many (8-byte) words fail instead of being sent.
+    /// A size limit of `None` means that no limit is enforced.
+    pub size_limit_in_words: Option<usize>,
+
+    /// Calls and returns nested more deeply than this fail instead of being sent.
+    pub nesting_limit: i32,
+
+    /// The size of the first segment allocated for each outgoing call or return, in words.
+    /// Messages that outgrow it allocate further segments.
+    pub first_segment_words: u32,
+}
+
+/// The default `SendOptions`, whose limits match the default `ReaderOptions`.
+pub const DEFAULT_SEND_OPTIONS: SendOptions =
+    SendOptions {
+        size_limit_in_words: ::capnp::message::DEFAULT_READER_OPTIONS.traversal_limit_in_words,
+        nesting_limit: ::capnp::message::DEFAULT_READER_OPTIONS.nesting_limit,
+        first_segment_words: 1024,
+    };
+
+impl Default for SendOptions {
+    fn default() -> SendOptions {
+        DEFAULT_SEND_OPTIONS
+    }
+}
+
+impl SendOptions {
+    pub fn new() -> SendOptions { DEFAULT_SEND_OPTIONS }
+
+    pub fn size_limit_in_words(&mut self, value: Option<usize>) -> &mut SendOptions {
+        self.size_limit_in_words = value;
+        self
+    }
+
+    pub fn nesting_limit(&mut self, value: i32) -> &mut SendOptions {
+        self.nesting_limit = value;
+        self
+    }
+
+    pub fn first_segment_words(&mut self, value: u32) -> &mut SendOptions {
+        self.first_segment_words = value;
+        self
+    }
+}
+
 /// A portal to objects available on the network.
 ///
 /// The RPC implemententation sits on top of an implementation of `VatNetwork`, which
 /// determines how to form connections between vats. The RPC implementation determines
 /// how to use such connections to manage object references and make method calls.
 ///
-/// At the moment, this is all rather more general than it needs to be, because the only
-/// implementation of `VatNetwork` is `twoparty::VatNetwork`. However, eventually we
-/// will need to have more sophisticated `VatNetwork` implementations, in order to support
-/// [level 3](https://capnproto.org/rpc.html#protocol-features) features.
+/// An `RpcSystem` maintains one connection per peer vat, as told apart by
+/// `Connection::is_same_peer()`. With `twoparty::VatNetwork` there is only ever one peer;
+/// `test_network::VatNetwork`, enabled by the `test-network` feature, allows any number of them.
+/// Connecting several vats in this way does not implement
+/// [level 3](https://capnproto.org/rpc.html#protocol-features) features: capabilities passed
+/// between third parties are proxied through the vat that passes them.
 ///
 /// An `RpcSystem` is a `Future` and needs to be driven by a task executor. A common way
 /// accomplish that is to pass the `RpcSystem` to `tokio_core::reactor::Handle::spawn()`.
 #[must_use = "futures do nothing unless polled"]
 pub struct RpcSystem<VatId> where VatId: 'static {
-    network: Box<dyn crate::VatNetwork<VatId>>,
+    network: Rc<RefCell<Box<dyn crate::VatNetwork<VatId>>>>,
 
     bootstrap_cap: Box<dyn ClientHook>,
 
-    // XXX To handle three or more party networks, this should be a map from connection pointers
-    // to connection states.
-    connection_state: Rc<RefCell<Option<Rc<rpc::ConnectionState<VatId>>>>>,
+    // One entry per peer vat that we are currently connected to.
+    connection_states: rpc::ConnectionStates<VatId>,
+
+    // Shared with each connection, so that changes apply to connections that are already open.
+    send_options: Rc<Cell<SendOptions>>,
 
     tasks: TaskSet<Error>,
     handle: crate::task_set::TaskSetHandle<Error>
@@ -202,92 +282,115 @@ impl <VatId> RpcSystem <VatId> {
             Promise::ok(())
         }));
 
-        let mut result = RpcSystem {
-            network: network,
-            bootstrap_cap: bootstrap_cap,
-            connection_state: Rc::new(RefCell::new(None)),
-
-            tasks: tasks,
-            handle: handle.clone(),
-        };
-
-
-        let accept_loop = result.accept_loop();
-        handle.add(accept_loop);
-        result
+        let network = Rc::new(RefCell::new(network));
+        let connection_states = Rc::new(RefCell::new(Vec::new()));
+        let send_options = Rc::new(Cell::new(SendOptions::new()));
+        handle.add(RpcSystem::accept_loop(network.clone(), connection_states.clone(),
+                                          bootstrap_cap.clone(), send_options.clone(),
+                                          handle.clone()));
+
+        RpcSystem {
+            network,
+            bootstrap_cap,
+            connection_states,
+            send_options,
+
+            tasks,
+            handle,
+        }
     }
 
     /// Connects to the given vat and returns its bootstrap interface.
     pub fn bootstrap<T>(&mut self, vat_id: VatId) -> T
         where T: ::capnp::capability::FromClientHook
     {
-        let connection = match self.network.connect(vat_id) {
+        let connection = match self.network.borrow_mut().connect(vat_id) {
             Some(connection) => connection,
             None => {
                 return T::new(self.bootstrap_cap.clone());
             }
         };
         let connection_state =
-            RpcSystem::get_connection_state(self.connection_state.clone(),
+            RpcSystem::get_connection_state(self.connection_states.clone(),
                                             self.bootstrap_cap.clone(),
+                                            self.send_options.clone(),
                                             connection, self.handle.clone());
 
         let hook = rpc::ConnectionState::bootstrap(connection_state.clone());
         T::new(hook)
     }
 
-    // not really a loop, because it doesn't need to be for the two party case
-    fn accept_loop(&mut self) -> Promise<(), Error> {
-        let connection_state_ref = self.connection_state.clone();
-        let bootstrap_cap = self.bootstrap_cap.clone();
-        let handle = self.handle.clone();
-        Promise::from_future(self.network.accept().map_ok(move |connection| {
-            RpcSystem::get_connection_state(connection_state_ref,
-                                            bootstrap_cap,
+    /// Sets the options that apply to the messages this `RpcSystem` sends, on both current and
+    /// future connections.
+    pub fn set_send_options(&mut self, options: SendOptions) {
+        self.send_options.set(options);
+    }
+
+    // Accepts connections for as long as the network keeps providing them.
+    fn accept_loop(network: Rc<RefCell<Box<dyn crate::VatNetwork<VatId>>>>,
+                   connection_states: rpc::ConnectionStates<VatId>,
+                   bootstrap_cap: Box<dyn ClientHook>,
+                   send_options: Rc<Cell<SendOptions>>,
+                   mut handle: crate::task_set::TaskSetHandle<Error>)
+                   -> Promise<(), Error>
+    {
+        let accept = network.borrow_mut().accept();
+        Promise::from_future(accept.map_ok(move |connection| {
+            RpcSystem::get_connection_state(connection_states.clone(),
+                                            bootstrap_cap.clone(),
+                                            send_options.clone(),
                                             connection,
-                                            handle);
+                                            handle.clone());
+            let next = RpcSystem::accept_loop(network, connection_states, bootstrap_cap,
+                                              send_options, handle.clone());
+            handle.add(next);
         }))
     }
 
-    // If `connection_state_ref` is not already populated, populates it with a new
-    // `ConnectionState` built from a local bootstrap capability and `connection`,
-    // spawning any background tasks onto `handle`. Returns the resulting value
-    // held in `connection_state_ref`.
-    fn get_connection_state(connection_state_ref: Rc<RefCell<Option<Rc<rpc::ConnectionState<VatId>>>>>,
+    // If `connection_states` does not already hold a live `ConnectionState` for the peer
+    // of `connection`, adds a new one built from a local bootstrap capability and `connection`,
+    // spawning any background tasks onto `handle`. Returns the `ConnectionState` for the peer.
+    fn get_connection_state(connection_states: rpc::ConnectionStates<VatId>,
                             bootstrap_cap: Box<dyn ClientHook>,
+                            send_options: Rc<Cell<SendOptions>>,
                             connection: Box<dyn crate::Connection<VatId>>,
                             mut handle: crate::task_set::TaskSetHandle<Error>)
                             -> Rc<rpc::ConnectionState<VatId>>
     {
-        // TODO this needs to be updated once we allow more general VatNetworks.
-        let (tasks, result) = match *connection_state_ref.borrow() {
-            Some(ref connection_state) => {
-                // return early.
-                return connection_state.clone()
-            }
-            None => {
-                let (on_disconnect_fulfiller, on_disconnect_promise) =
-                    oneshot::channel::<Promise<(), Error>>();
-                let connection_state_ref1 = connection_state_ref.clone();
-                handle.add(on_disconnect_promise.then(move |shutdown_promise| {
-                    *connection_state_ref1.borrow_mut() = None;
-                    match shutdown_promise {
-                        Ok(s) => s,
-                        Err(e) => Promise::err(Error::failed(format!("{}", e))),
-                    }
-                }));
-                rpc::ConnectionState::new(bootstrap_cap, connection, on_disconnect_fulfiller)
+        if let Some(connection_state) =
+            connection_states.borrow().iter().find(|c| c.is_connected_to_peer_of(&*connection))
+        {
+            // return early.
+            return connection_state.clone()
+        }
+
+        let (on_disconnect_fulfiller, on_disconnect_promise) =
+            oneshot::channel::<Promise<(), Error>>();
+        let (tasks, result) =
+            rpc::ConnectionState::new(bootstrap_cap, send_options, connection, on_disconnect_fulfiller);
+        let connection_states1 = connection_states.clone();
+        let weak_result = Rc::downgrade(&result);
+        handle.add(on_disconnect_promise.then(move |shutdown_promise| {
+            connection_states1.borrow_mut().retain(|c| !::std::ptr::eq(&**c, weak_result.as_ptr()));
+            match shutdown_promise {
+                Ok(s) => s,
+                Err(e) => Promise::err(Error::failed(format!("{}", e))),
             }
-        };
-        *connection_state_ref.borrow_mut() = Some(result.clone());
+        }));
+        connection_states.borrow_mut().push(result.clone());
         handle.add(tasks);
         result
     }
+}
 
-    /// Returns a `Disconnector` future that can be run to cleanly close the connection to this `RpcSystem`'s network.
+impl <VatId> RpcSystem <VatId> {
+    /// Returns a `Disconnector` future that can be run to cleanly close the connections to this `RpcSystem`'s network.
     /// You should get the `Disconnector` before you spawn the `RpcSystem`.
+    ///
+    /// To let in-flight calls finish before the connections are closed, call `drain()` or
+    /// `drain_with_timeout()` on the returned `Disconnector`.
     pub fn get_disconnector(&self) -> rpc::Disconnector<VatId> {
-        rpc::Disconnector::new(self.connection_state.clone())
+        rpc::Disconnector::new(self.connection_states.clone())
     }
 }
 
@@ -304,6 +407,16 @@ pub fn new_client<C, S>(s: S) -> C where C: capnp::capability::FromServer<S> {
         local::Client::new(Box::new(<C as capnp::capability::FromServer::<S>>::from_server(s)))))
 }
 
+/// Like `new_client()`, but attaches the file descriptor `fd` to the capability, so that it is
+/// passed along with the capability over connections that support it (see
+/// `twoparty::VatNetwork::new_with_fds()`). `fd` is closed once the server is dropped.
+#[cfg(unix)]
+pub fn new_client_with_fd<C, S>(s: S, fd: std::os::unix::io::OwnedFd) -> C where C: capnp::capability::FromServer<S> {
+    capnp::capability::FromClientHook::new(Box::new(
+        local::Client::new(Box::new(local::FdServer::new(
+            Box::new(<C as capnp::capability::FromServer::<S>>::from_server(s)), fd)))))
+}
+
 /// Converts a promise for a client into a client that queues up any calls that arrive
 /// before the promise resolves.
 // TODO: figure out a better way to allow construction of promise clients.
@@ -325,6 +438,94 @@ pub fn new_promise_client<T, F>(client_promise: F) -> T
     T::new(Box::new(queued_client))
 }
 
+/// Creates a client that forwards calls to the capability returned by `connect`, and calls
+/// `connect` again to get a fresh capability after a call fails with
+/// `ErrorKind::Disconnected`.
+///
+/// `connect` is called once right away. The call that observed the disconnect still fails;
+/// it is the following call that goes out over the new connection. Use
+/// `auto_reconnect_with_retry()` to transparently retry calls that are safe to repeat.
+pub fn auto_reconnect<T, F, P>(connect: F) -> T
+    where T: ::capnp::capability::FromClientHook,
+          F: FnMut() -> P + 'static,
+          P: ::futures::Future<Output=Result<capnp::capability::Client,Error>> + 'static
+{
+    T::new(Box::new(reconnect::Client::new(reconnect_fn(connect), None)))
+}
+
+/// Like `auto_reconnect()`, but a call that fails with `ErrorKind::Disconnected` is sent once
+/// more over a new connection if `is_idempotent(interface_id, method_id)` returns true.
+pub fn auto_reconnect_with_retry<T, F, P, R>(connect: F, is_idempotent: R) -> T
+    where T: ::capnp::capability::FromClientHook,
+          F: FnMut() -> P + 'static,
+          P: ::futures::Future<Output=Result<capnp::capability::Client,Error>> + 'static,
+          R: Fn(u64, u16) -> bool + 'static
+{
+    T::new(Box::new(reconnect::Client::new(reconnect_fn(connect), Some(Box::new(is_idempotent)))))
+}
+
+fn reconnect_fn<F, P>(mut connect: F) -> reconnect::ConnectFn
+    where F: FnMut() -> P + 'static,
+          P: ::futures::Future<Output=Result<capnp::capability::Client,Error>> + 'static
+{
+    Box::new(move || Promise::from_future(connect()))
+}
+
+/// The answer of `implements_interface()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterfaceSupport {
+    /// The server implements the interface.
+    Implemented,
+    /// The server does not implement the interface.
+    NotImplemented,
+    /// The server's reply does not tell.
+    Unknown,
+}
+
+/// Checks whether `client` implements the interface with ID `interface_id`, by calling the
+/// method `capnp::capability::PROBE_METHOD_ID` of that interface, which does not exist.
+///
+/// Servers generated by this version of capnpc-rust or later answer the call successfully if
+/// they implement the interface, and fail it with `capnp::capability::INTERFACE_NOT_IMPLEMENTED`
+/// if they don't. Servers generated by earlier versions of capnpc-rust fail it with "Method not
+/// implemented." in both cases, and so do C++ servers that implement the interface; for those
+/// the answer is `InterfaceSupport::Unknown`. Servers written by hand may answer anything.
+/// Errors other than `ErrorKind::Unimplemented`, for example a disconnection, are passed
+/// through.
+pub fn implements_interface(client: &capnp::capability::Client, interface_id: u64)
+                            -> Promise<InterfaceSupport, Error>
+{
+    let request = client.new_call::<capnp::any_pointer::Owned, capnp::any_pointer::Owned>(
+        interface_id, capnp::capability::PROBE_METHOD_ID, None);
+    Promise::from_future(request.send().promise.map(|result| match result {
+        Ok(_) => Ok(InterfaceSupport::Implemented),
+        Err(e) if e.kind == capnp::ErrorKind::Unimplemented => {
+            if e.description.contains(capnp::capability::INTERFACE_NOT_IMPLEMENTED) {
+                Ok(InterfaceSupport::NotImplemented)
+            } else {
+                Ok(InterfaceSupport::Unknown)
+            }
+        }
+        Err(e) => Err(e),
+    }))
+}
+
+/// Returns `client` as a `T`, if `implements_interface()` answers that it implements `T`'s
+/// interface. Returns `None` if the answer is `InterfaceSupport::NotImplemented` or
+/// `InterfaceSupport::Unknown`, so it only ever succeeds on servers generated by this version of
+/// capnpc-rust or later.
+pub fn downcast_client<T>(client: &capnp::capability::Client) -> Promise<Option<T>, Error>
+    where T: capnp::capability::FromClientHook + capnp::traits::HasTypeId
+{
+    let hook = client.hook.add_ref();
+    Promise::from_future(implements_interface(client, T::type_id()).map_ok(move |support| {
+        match support {
+            InterfaceSupport::Implemented => Some(T::new(hook)),
+            InterfaceSupport::NotImplemented | InterfaceSupport::Unknown => None,
+        }
+    }))
+}
+
 struct SystemTaskReaper;
 impl crate::task_set::TaskReaper<Error> for SystemTaskReaper {
     fn task_failed(&mut self, error: Error) {
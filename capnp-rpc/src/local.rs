@@ -278,6 +278,36 @@ impl PipelineHook for Pipeline {
     }
 }
 
+/// Wraps a server so that it reports an attached file descriptor.
+#[cfg(unix)]
+pub struct FdServer {
+    server: Box<dyn capability::Server>,
+    fd: std::os::unix::io::OwnedFd,
+}
+
+#[cfg(unix)]
+impl FdServer {
+    pub fn new(server: Box<dyn capability::Server>, fd: std::os::unix::io::OwnedFd) -> FdServer {
+        FdServer { server, fd }
+    }
+}
+
+#[cfg(unix)]
+impl capability::Server for FdServer {
+    fn dispatch_call(&mut self, interface_id: u64, method_id: u16,
+                     params: capability::Params<any_pointer::Owned>,
+                     results: capability::Results<any_pointer::Owned>)
+                     -> Promise<(), Error>
+    {
+        self.server.dispatch_call(interface_id, method_id, params, results)
+    }
+
+    fn get_fd(&self) -> Option<i32> {
+        use std::os::unix::io::AsRawFd;
+        Some(self.fd.as_raw_fd())
+    }
+}
+
 struct ClientInner {
     server: Box<dyn capability::Server>,
 }
@@ -354,4 +384,8 @@ impl ClientHook for Client {
     fn when_resolved(&self) -> Promise<(), Error> {
         crate::rpc::default_when_resolved_impl(self)
     }
+
+    fn get_fd(&self) -> Option<i32> {
+        self.inner.borrow().server.get_fd()
+    }
 }
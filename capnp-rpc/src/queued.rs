@@ -282,4 +282,11 @@ impl ClientHook for Client {
     fn when_resolved(&self) -> Promise<(), Error> {
         crate::rpc::default_when_resolved_impl(self)
     }
+
+    fn get_fd(&self) -> Option<i32> {
+        match self.inner.borrow().redirect {
+            Some(ref client) => client.get_fd(),
+            None => None,
+        }
+    }
 }
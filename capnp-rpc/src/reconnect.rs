@@ -0,0 +1,225 @@
+// Copyright (c) 2013-2017 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+use capnp::{any_pointer, message};
+use capnp::Error;
+use capnp::capability::{self, Promise};
+use capnp::private::capability::{ClientHook, ParamsHook, ResultsHook};
+use capnp::traits::{Imbue, ImbueMut};
+
+use futures::FutureExt;
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use crate::{local, queued};
+
+pub type ConnectFn = Box<dyn FnMut() -> Promise<capability::Client, Error>>;
+pub type RetryFn = Box<dyn Fn(u64, u16) -> bool>;
+
+struct Inner {
+    connect: Rc<RefCell<ConnectFn>>,
+
+    // Decides which calls are idempotent and may therefore be retried.
+    retry: Option<RetryFn>,
+
+    // The capability we are currently forwarding to, or None if we need to reconnect.
+    current: Option<Box<dyn ClientHook>>,
+
+    // Incremented on each reconnect, so that a failure on an old connection does not
+    // throw away a newer one.
+    generation: u64,
+}
+
+impl Inner {
+    fn reset(inner: &Rc<RefCell<Inner>>, generation: u64) {
+        let mut inner = inner.borrow_mut();
+        if inner.generation == generation {
+            inner.current = None;
+        }
+    }
+
+    fn reset_if_disconnected(weak_inner: &Weak<RefCell<Inner>>, generation: u64, error: &Error) {
+        if error.kind == ::capnp::ErrorKind::Disconnected {
+            if let Some(inner) = weak_inner.upgrade() {
+                Inner::reset(&inner, generation);
+            }
+        }
+    }
+
+    /// Returns the capability that calls should currently be forwarded to, connecting if necessary.
+    fn current(inner: &Rc<RefCell<Inner>>) -> (u64, Box<dyn ClientHook>) {
+        if let Some(ref current) = inner.borrow().current {
+            return (inner.borrow().generation, current.add_ref());
+        }
+
+        // Don't hold a borrow while calling into application code.
+        let connect = inner.borrow().connect.clone();
+        let promise = (*connect.borrow_mut())();
+
+        let generation = {
+            let mut inner = inner.borrow_mut();
+            inner.generation += 1;
+            inner.generation
+        };
+
+        // Queue up calls until the connection is established. If connecting fails,
+        // try again on the next call.
+        let mut queued_client = queued::Client::new(None);
+        let weak_queued = Rc::downgrade(&queued_client.inner);
+        let weak_inner = Rc::downgrade(inner);
+        queued_client.drive(promise.map(move |r| {
+            if r.is_err() {
+                if let Some(inner) = weak_inner.upgrade() {
+                    Inner::reset(&inner, generation);
+                }
+            }
+            if let Some(queued_inner) = weak_queued.upgrade() {
+                queued::ClientInner::resolve(&queued_inner, r.map(|c| c.hook));
+            }
+            Ok(())
+        }));
+        let client: Box<dyn ClientHook> = Box::new(queued_client);
+        inner.borrow_mut().current = Some(client.add_ref());
+        (generation, client)
+    }
+}
+
+pub struct Client {
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl Client {
+    pub fn new(connect: ConnectFn, retry: Option<RetryFn>) -> Client {
+        let inner = Rc::new(RefCell::new(Inner {
+            connect: Rc::new(RefCell::new(connect)),
+            retry,
+            current: None,
+            generation: 0,
+        }));
+        Inner::current(&inner);
+        Client { inner }
+    }
+
+    fn call_with_retry(&self, interface_id: u64, method_id: u16,
+                       params: Box<dyn ParamsHook>, mut results: Box<dyn ResultsHook>)
+                       -> Promise<(), Error>
+    {
+        // Keep our own copy of the params, so that we can send them again.
+        let mut message = message::Builder::new_default();
+        let mut cap_table = Vec::new();
+        {
+            let mut root: any_pointer::Builder = pry!(message.get_root());
+            root.imbue_mut(&mut cap_table);
+            pry!(root.set_as(pry!(params.get())));
+        }
+        drop(params);
+
+        let inner = self.inner.clone();
+        Promise::from_future(async move {
+            let mut retried = false;
+            loop {
+                let (generation, target) = Inner::current(&inner);
+                let mut request = target.new_call(interface_id, method_id, None);
+                {
+                    let mut params: any_pointer::Reader = message.get_root_as_reader()?;
+                    params.imbue(&cap_table);
+                    request.set(params)?;
+                }
+                match request.send().promise.await {
+                    Ok(response) => {
+                        results.get()?.set_as(response.get()?)?;
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        Inner::reset_if_disconnected(&Rc::downgrade(&inner), generation, &e);
+                        if retried || e.kind != ::capnp::ErrorKind::Disconnected {
+                            return Err(e);
+                        }
+                        retried = true;
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl ClientHook for Client {
+    fn add_ref(&self) -> Box<dyn ClientHook> {
+        Box::new(Client { inner: self.inner.clone() })
+    }
+
+    fn new_call(&self, interface_id: u64, method_id: u16,
+                size_hint: Option<::capnp::MessageSize>)
+                -> capability::Request<any_pointer::Owned, any_pointer::Owned>
+    {
+        capability::Request::new(
+            Box::new(local::Request::new(interface_id, method_id, size_hint, self.add_ref())))
+    }
+
+    fn call(&self, interface_id: u64, method_id: u16, params: Box<dyn ParamsHook>, results: Box<dyn ResultsHook>)
+        -> Promise<(), Error>
+    {
+        let retry = match self.inner.borrow().retry {
+            Some(ref is_idempotent) => is_idempotent(interface_id, method_id),
+            None => false,
+        };
+        if retry {
+            return self.call_with_retry(interface_id, method_id, params, results);
+        }
+
+        let (generation, target) = Inner::current(&self.inner);
+        let weak_inner = Rc::downgrade(&self.inner);
+        Promise::from_future(target.call(interface_id, method_id, params, results).map(move |r| {
+            if let Err(ref e) = r {
+                Inner::reset_if_disconnected(&weak_inner, generation, e);
+            }
+            r
+        }))
+    }
+
+    fn get_ptr(&self) -> usize {
+        (&*self.inner.borrow()) as * const _ as usize
+    }
+
+    fn get_brand(&self) -> usize {
+        0
+    }
+
+    fn get_resolved(&self) -> Option<Box<dyn ClientHook>> {
+        None
+    }
+
+    fn when_more_resolved(&self) -> Option<Promise<Box<dyn ClientHook>, Error>> {
+        None
+    }
+
+    fn when_resolved(&self) -> Promise<(), Error> {
+        Promise::ok(())
+    }
+
+    fn get_fd(&self) -> Option<i32> {
+        match self.inner.borrow().current {
+            Some(ref current) => current.get_fd(),
+            None => None,
+        }
+    }
+}
@@ -381,8 +381,12 @@ impl <VatId> crate::task_set::TaskReaper<capnp::Error> for ConnectionErrorHandle
     }
 }
 
+/// The set of connections that an `RpcSystem` currently maintains, shared with its `Disconnector`s.
+pub type ConnectionStates<VatId> = Rc<RefCell<Vec<Rc<ConnectionState<VatId>>>>>;
+
 pub struct ConnectionState<VatId> where VatId: 'static {
     bootstrap_cap: Box<dyn ClientHook>,
+    send_options: Rc<Cell<crate::SendOptions>>,
     exports: RefCell<ExportTable<Export>>,
     questions: RefCell<ExportTable<Question<VatId>>>,
     answers: RefCell<ImportTable<Answer<VatId>>>,
@@ -397,17 +401,25 @@ pub struct ConnectionState<VatId> where VatId: 'static {
     disconnect_fulfiller: RefCell<Option<oneshot::Sender<Promise<(), Error>>>>,
 
     client_downcast_map: RefCell<HashMap<usize, WeakClient<VatId>>>,
+
+    // Set once a graceful drain has been requested. While set, new incoming calls are refused.
+    draining: Cell<bool>,
+
+    // Notified once `draining` is set and there are no more outstanding answers.
+    drain_waiters: RefCell<crate::sender_queue::SenderQueue<(), ()>>,
 }
 
 impl <VatId> ConnectionState<VatId> {
     pub fn new(
         bootstrap_cap: Box<dyn ClientHook>,
+        send_options: Rc<Cell<crate::SendOptions>>,
         connection: Box<dyn crate::Connection<VatId>>,
         disconnect_fulfiller: oneshot::Sender<Promise<(), Error>>)
         -> (TaskSet<Error>, Rc<ConnectionState<VatId>>)
     {
         let state = Rc::new(ConnectionState {
             bootstrap_cap: bootstrap_cap,
+            send_options,
             exports: RefCell::new(ExportTable::new()),
             questions: RefCell::new(ExportTable::new()),
             answers: RefCell::new(ImportTable::new()),
@@ -418,6 +430,8 @@ impl <VatId> ConnectionState<VatId> {
             connection: RefCell::new(Ok(connection)),
             disconnect_fulfiller: RefCell::new(Some(disconnect_fulfiller)),
             client_downcast_map: RefCell::new(HashMap::new()),
+            draining: Cell::new(false),
+            drain_waiters: RefCell::new(crate::sender_queue::SenderQueue::new()),
         });
         let (mut handle, tasks) = TaskSet::new(Box::new(ConnectionErrorHandler::new(Rc::downgrade(&state))));
 
@@ -426,10 +440,35 @@ impl <VatId> ConnectionState<VatId> {
         (tasks, state)
     }
 
-    fn new_outgoing_message(&self, first_segment_words: u32) -> capnp::Result<Box<dyn crate::OutgoingMessage>> {
+    /// Returns true if this connection is still up and leads to the peer of `connection`.
+    pub fn is_connected_to_peer_of(&self, connection: &dyn crate::Connection<VatId>) -> bool {
+        match *self.connection.borrow() {
+            Ok(ref c) => connection.is_same_peer(&**c),
+            Err(_) => false,
+        }
+    }
+
+    fn new_outgoing_message(&self) -> capnp::Result<Box<dyn crate::OutgoingMessage>> {
         match self.connection.borrow_mut().as_mut() {
             Err(e) => Err(e.clone()),
-            Ok(c) => Ok(c.new_outgoing_message(first_segment_words)),
+            Ok(c) => Ok(c.new_outgoing_message(self.send_options.get().first_segment_words)),
+        }
+    }
+
+    // Fails if `message` exceeds the limits in our `SendOptions`, in which case the peer would
+    // reject it anyway.
+    fn check_outgoing_message(&self, message: &dyn crate::OutgoingMessage) -> capnp::Result<()> {
+        let options = self.send_options.get();
+        let size = message.get_body_as_reader()?
+            .target_size_with_nesting_limit(options.nesting_limit)
+            .map_err(|e| Error::failed(format!("outgoing message not sent: {}", e.description)))?;
+        match options.size_limit_in_words {
+            Some(limit) if size.word_count > limit as u64 => {
+                Err(Error::failed(format!(
+                    "outgoing message not sent: its size of {} words exceeds the limit of {} words",
+                    size.word_count, limit)))
+            }
+            _ => Ok(()),
         }
     }
 
@@ -493,6 +532,10 @@ impl <VatId> ConnectionState<VatId> {
         }
         *self.embargoes.borrow_mut() = ExportTable::new();
 
+        for (_, waiter) in self.drain_waiters.borrow_mut().drain() {
+            let _ = waiter.send(());
+        }
+
         drop(pipelines_to_release);
         drop(clients_to_release);
         drop(resolve_ops_to_release);
@@ -537,6 +580,60 @@ impl <VatId> ConnectionState<VatId> {
         }
     }
 
+    /// Stops accepting new incoming calls. Returns a promise that resolves once every call
+    /// that we were already answering has sent its `Return` (or has been canceled by a `Finish`).
+    fn drain(&self) -> Promise<(), Error> {
+        self.draining.set(true);
+        if self.connection.borrow().is_err() || !self.has_outstanding_answers() {
+            Promise::ok(())
+        } else {
+            self.drain_waiters.borrow_mut().push(())
+        }
+    }
+
+    fn has_outstanding_answers(&self) -> bool {
+        self.answers.borrow().slots.values().any(|answer| {
+            // Locally-redirected calls never send a `Return`; their results are picked up
+            // by a later `Return.takeFromOtherQuestion`.
+            answer.active && !answer.return_has_been_sent && !answer.received_finish.get() &&
+                answer.redirected_results.is_none()
+        })
+    }
+
+    fn check_drained(&self) {
+        if self.draining.get() && !self.has_outstanding_answers() {
+            for (_, waiter) in self.drain_waiters.borrow_mut().drain() {
+                let _ = waiter.send(());
+            }
+        }
+    }
+
+    /// Answers `answer_id` with an exception rather than delivering the call. Used while draining.
+    /// The answer table entry stays around until the peer sends `Finish`.
+    fn refuse_call(&self, answer_id: AnswerId) -> ::capnp::Result<()> {
+        {
+            let slots = &mut self.answers.borrow_mut().slots;
+            let answer = slots.entry(answer_id).or_insert_with(Answer::new);
+            if answer.active {
+                return Err(Error::failed("questionId is already in use".to_string()));
+            }
+            answer.active = true;
+            answer.return_has_been_sent = true;
+        }
+
+        let mut message = self.new_outgoing_message()?;
+        {
+            let root: message::Builder = message.get_body()?.init_as();
+            let mut ret = root.init_return();
+            ret.set_answer_id(answer_id);
+            ret.set_release_param_caps(false);
+            from_error(&Error::disconnected("connection is draining and not accepting new calls".to_string()),
+                       ret.init_exception());
+        }
+        let _ = message.send();
+        Ok(())
+    }
+
     // Transform a future into a promise that gets executed even if it is never polled.
     // Dropping the returned promise cancels the computation.
     fn eagerly_evaluate<T, F>(&self, task: F) -> Promise<T, Error>
@@ -622,7 +719,7 @@ impl <VatId> ConnectionState<VatId> {
 
     fn send_unimplemented(connection_state: Rc<ConnectionState<VatId>>,
                           message: Box<dyn crate::IncomingMessage>) -> capnp::Result<()> {
-        let mut out_message = connection_state.new_outgoing_message(50)?; // XXX size hint
+        let mut out_message = connection_state.new_outgoing_message()?;
         {
             let mut root: message::Builder = out_message.get_body()?.get_as()?;
             root.set_unimplemented(message.get_body()?.get_as()?)?;
@@ -688,7 +785,12 @@ impl <VatId> ConnectionState<VatId> {
                     return Ok(());
                 }
 
-                let mut response = connection_state.new_outgoing_message(50)?; // XXX size hint
+                if connection_state.draining.get() {
+                    return connection_state.refuse_call(answer_id);
+                }
+
+                let mut response = connection_state.new_outgoing_message()?;
+                let mut fds = Vec::new();
 
                 let result_exports = {
                     let mut ret = response.get_body()?.init_as::<message::Builder>().init_return();
@@ -705,8 +807,9 @@ impl <VatId> ConnectionState<VatId> {
                     assert_eq!(cap_table.len(), 1);
 
                     ConnectionState::write_descriptors(&connection_state, &cap_table,
-                                                       payload)
+                                                       payload, &mut fds)
                 };
+                response.set_fds(fds);
 
                 let slots = &mut connection_state.answers.borrow_mut().slots;
                 let answer = slots.entry(answer_id).or_insert_with(Answer::new);
@@ -742,7 +845,8 @@ impl <VatId> ConnectionState<VatId> {
 
                     (call.get_interface_id(), call.get_method_id(), call.get_question_id(),
                      ConnectionState::receive_caps(connection_state.clone(),
-                                                   payload.get_cap_table()?)?,
+                                                   payload.get_cap_table()?,
+                                                   message.get_attached_fds())?,
                      redirect_results)
                 };
 
@@ -751,6 +855,10 @@ impl <VatId> ConnectionState<VatId> {
                         format!("Received a new call on in-use question id {}", question_id)));
                 }
 
+                if connection_state.draining.get() {
+                    return connection_state.refuse_call(question_id);
+                }
+
                 let params = Params::new(message, cap_table_array);
 
                 let answer = Answer::new();
@@ -834,7 +942,8 @@ impl <VatId> ConnectionState<VatId> {
                                     return_::Results(results) => {
                                         let cap_table =
                                             ConnectionState::receive_caps(connection_state.clone(),
-                                                                          results?.get_cap_table()?)?;
+                                                                          results?.get_cap_table()?,
+                                                                          message.get_attached_fds())?;
 
                                         let question_ref = question_ref.upgrade()
                                             .expect("dangling question ref?");
@@ -905,7 +1014,8 @@ impl <VatId> ConnectionState<VatId> {
                 let answer_id = finish.get_question_id();
 
                 let mut erase = false;
-                let answers_slots = &mut connection_state.answers.borrow_mut().slots;
+                let mut answers = connection_state.answers.borrow_mut();
+                let answers_slots = &mut answers.slots;
                 match answers_slots.get_mut(&answer_id) {
                     None => {
                         return Err(Error::failed(
@@ -935,14 +1045,17 @@ impl <VatId> ConnectionState<VatId> {
                 if erase {
                     answers_slots.remove(&answer_id);
                 }
+                drop(answers);
 
                 connection_state.release_exports(&exports_to_release)?;
+                connection_state.check_drained();
             }
             Ok(message::Resolve(resolve)) => {
                 let resolve = resolve?;
                 let replacement_or_error = match resolve.which()? {
                     resolve::Cap(c) => {
-                        match ConnectionState::receive_cap(connection_state.clone(), c?)? {
+                        match ConnectionState::receive_cap(connection_state.clone(), c?,
+                                                           message.get_attached_fds())? {
                             Some(cap) => Ok(cap),
                             None => {
                                 return Err(Error::failed(
@@ -1061,7 +1174,8 @@ impl <VatId> ConnectionState<VatId> {
 
     fn answer_has_sent_return(&self, id: AnswerId, result_exports: Vec<ExportId>) {
         let mut erase = false;
-        let answers_slots = &mut self.answers.borrow_mut().slots;
+        let mut answers = self.answers.borrow_mut();
+        let answers_slots = &mut answers.slots;
         if let Some(ref mut a) = answers_slots.get_mut(&id) {
             a.return_has_been_sent = true;
             if a.received_finish.get() {
@@ -1076,6 +1190,9 @@ impl <VatId> ConnectionState<VatId> {
         if erase {
             answers_slots.remove(&id);
         }
+        drop(answers);
+
+        self.check_drained();
     }
 
     fn release_export(&self, id: ExportId, refcount: u32) -> ::capnp::Result<()> {
@@ -1233,20 +1350,22 @@ impl <VatId> ConnectionState<VatId> {
                     }
 
                     // OK, we have to send a `Resolve` message.
-                    let mut message = connection_state.new_outgoing_message(100)?; // XXX size hint?
+                    let mut message = connection_state.new_outgoing_message()?;
+                    let mut fds = Vec::new();
                     {
                         let root: message::Builder = message.get_body()?.get_as()?;
                         let mut resolve = root.init_resolve();
                         resolve.set_promise_id(export_id);
                         let _export = ConnectionState::write_descriptor(&connection_state, &resolution,
-                                                                        resolve.init_cap())?;
+                                                                        resolve.init_cap(), &mut fds)?;
                     }
+                    message.set_fds(fds);
                     let _ = message.send();
                     Ok(())
                 }
                 Err(e) => {
                     // send error resolution
-                    let mut message = connection_state.new_outgoing_message(100)?; // XXX size hint?
+                    let mut message = connection_state.new_outgoing_message()?;
                     {
                         let root: message::Builder = message.get_body()?.get_as()?;
                         let mut resolve = root.init_resolve();
@@ -1262,16 +1381,25 @@ impl <VatId> ConnectionState<VatId> {
 
     fn write_descriptor(state: &Rc<ConnectionState<VatId>>,
                         cap: &Box<dyn ClientHook>,
-                        mut descriptor: cap_descriptor::Builder) -> ::capnp::Result<Option<ExportId>> {
+                        mut descriptor: cap_descriptor::Builder,
+                        fds: &mut Vec<i32>) -> ::capnp::Result<Option<ExportId>> {
 
         // Find the innermost wrapped capability.
         let mut inner = cap.clone();
         while let Some(resolved) = inner.get_resolved() {
             inner = resolved;
         }
+
+        if let Some(fd) = inner.get_fd() {
+            // Index 255 is reserved to mean "no FD", so we can attach at most 255 per message.
+            if fds.len() < 255 {
+                descriptor.set_attached_fd(fds.len() as u8);
+                fds.push(fd);
+            }
+        }
         if inner.get_brand() == state.get_brand() {
             let result = match Client::from_ptr(inner.get_ptr(), state) {
-                Some(c) => c.write_descriptor(descriptor),
+                Some(c) => c.write_descriptor(descriptor, fds),
                 None => unreachable!(),
             };
             Ok(result)
@@ -1315,7 +1443,8 @@ impl <VatId> ConnectionState<VatId> {
 
     fn write_descriptors(state: &Rc<ConnectionState<VatId>>,
                          cap_table: &[Option<Box<dyn ClientHook>>],
-                         payload: payload::Builder)
+                         payload: payload::Builder,
+                         fds: &mut Vec<i32>)
                          -> Vec<ExportId>
     {
         let mut cap_table_builder = payload.init_cap_table(cap_table.len() as u32);
@@ -1324,7 +1453,8 @@ impl <VatId> ConnectionState<VatId> {
             match cap_table[idx] {
                 Some(ref cap) => {
                     match ConnectionState::write_descriptor(state, cap,
-                                                            cap_table_builder.reborrow().get(idx as u32)).unwrap() {
+                                                            cap_table_builder.reborrow().get(idx as u32),
+                                                            fds).unwrap() {
                         Some(export_id) => {
                             exports.push(export_id);
                         }
@@ -1340,7 +1470,9 @@ impl <VatId> ConnectionState<VatId> {
     }
 
     fn import(state: Rc<ConnectionState<VatId>>,
-              import_id: ImportId, is_promise: bool) -> Box<dyn ClientHook> {
+              import_id: ImportId, is_promise: bool, fd: Option<i32>)
+              -> ::capnp::Result<Box<dyn ClientHook>>
+    {
         let connection_state = state.clone();
 
         let import_client = {
@@ -1359,6 +1491,11 @@ impl <VatId> ConnectionState<VatId> {
         // We just received a copy of this import ID, so the remote refcount has gone up.
         import_client.borrow_mut().add_remote_ref();
 
+        if let Some(fd) = fd {
+            // We may have already received this import without an FD attached, so set it now.
+            import_client.borrow_mut().set_fd_if_missing(fd)?;
+        }
+
         if is_promise {
             // We need to construct a PromiseClient around this import, if we haven't already.
             match state.imports.borrow_mut().slots.get_mut(&import_id) {
@@ -1366,7 +1503,7 @@ impl <VatId> ConnectionState<VatId> {
                     match import.app_client {
                         Some(ref c) => {
                             // Use the existing one.
-                            Box::new(c.upgrade().expect("dangling client ref?"))
+                            Ok(Box::new(c.upgrade().expect("dangling client ref?")))
                         }
                         None => {
                             // Create a promise for this import's resolution.
@@ -1384,7 +1521,7 @@ impl <VatId> ConnectionState<VatId> {
                             import.promise_client_to_resolve = Some(Rc::downgrade(&client));
                             let client: Box<Client<VatId>> = Box::new(client.into());
                             import.app_client = Some(client.downgrade());
-                            client
+                            Ok(client)
                         }
                     }
                 }
@@ -1399,22 +1536,25 @@ impl <VatId> ConnectionState<VatId> {
                 None => { unreachable!() }
             };
 
-            client
+            Ok(client)
         }
     }
 
-    fn receive_cap(state: Rc<ConnectionState<VatId>>, descriptor: cap_descriptor::Reader)
+    fn receive_cap(state: Rc<ConnectionState<VatId>>, descriptor: cap_descriptor::Reader,
+                   fds: &[i32])
                    -> ::capnp::Result<Option<Box<dyn ClientHook>>>
     {
+        // An out-of-bounds index (including the default of 255) means that no FD is attached.
+        let fd = fds.get(descriptor.get_attached_fd() as usize).cloned();
         match descriptor.which()? {
             cap_descriptor::None(()) => {
                 Ok(None)
             }
             cap_descriptor::SenderHosted(sender_hosted) => {
-                Ok(Some(ConnectionState::import(state, sender_hosted, false)))
+                Ok(Some(ConnectionState::import(state, sender_hosted, false, fd)?))
             }
             cap_descriptor::SenderPromise(sender_promise) => {
-                Ok(Some(ConnectionState::import(state, sender_promise, true)))
+                Ok(Some(ConnectionState::import(state, sender_promise, true, fd)?))
             }
             cap_descriptor::ReceiverHosted(receiver_hosted) => {
                 if let Some(ref mut exp) = state.exports.borrow_mut().find(receiver_hosted) {
@@ -1449,12 +1589,13 @@ impl <VatId> ConnectionState<VatId> {
     }
 
     fn receive_caps(state: Rc<ConnectionState<VatId>>,
-                    cap_table: ::capnp::struct_list::Reader<cap_descriptor::Owned>)
+                    cap_table: ::capnp::struct_list::Reader<cap_descriptor::Owned>,
+                    fds: &[i32])
         -> ::capnp::Result<Vec<Option<Box<dyn ClientHook>>>>
     {
         let mut result = Vec::new();
         for idx in 0..cap_table.len() {
-            result.push(ConnectionState::receive_cap(state.clone(), cap_table.get(idx))?);
+            result.push(ConnectionState::receive_cap(state.clone(), cap_table.get(idx), fds)?);
         }
         Ok(result)
     }
@@ -1463,36 +1604,71 @@ impl <VatId> ConnectionState<VatId> {
 enum DisconnectorState
 {
     Connected,
+    Draining(Promise<(), Error>),
     Disconnecting,
     Disconnected
 }
 
-/// A `Future` that can be run to disconnect an `RpcSystem`'s ConnectionState and wait for it to be closed.
+/// A `Future` that can be run to disconnect an `RpcSystem`'s connections and wait for them to be closed.
+///
+/// By default the connection is torn down immediately, failing any calls that are still in flight.
+/// Use `drain()` or `drain_with_timeout()` to shut it down gracefully instead.
 pub struct Disconnector<VatId> where VatId: 'static {
-    connection_state: Rc<RefCell<Option<Rc<ConnectionState<VatId>>>>>,
+    connection_states: ConnectionStates<VatId>,
     state:  DisconnectorState,
+    drain: bool,
+    drain_timeout: Option<Promise<(), Error>>,
 }
 
 impl <VatId> Disconnector<VatId> {
-    pub fn new(connection_state: Rc<RefCell<Option<Rc<ConnectionState<VatId>>>>>) -> Disconnector<VatId> {
-        let state = match *(connection_state.borrow()) {
-            Some(_) => {
-                DisconnectorState::Connected
-            },
-            None => {
-                DisconnectorState::Disconnected
-            }
-        };
+    pub(crate) fn new(connection_states: ConnectionStates<VatId>) -> Disconnector<VatId> {
+        // Connections may not have been established yet (e.g. on the accepting side), so we
+        // wait until we are first polled before checking whether there is anything to disconnect.
         Disconnector {
-            connection_state: connection_state,
-            state: state,
+            connection_states,
+            state: DisconnectorState::Connected,
+            drain: false,
+            drain_timeout: None,
         }
     }
+
+    /// Makes the disconnect graceful. Instead of immediately failing in-flight calls, the connection
+    /// first stops accepting new incoming calls, answering them with a `Disconnected` exception, and
+    /// waits for the calls it is already answering to return. Once their `Return` messages have been
+    /// queued, the connection is aborted as usual. The write queue is flushed before it is shut down,
+    /// so the peer receives those `Return`s before the `Abort`.
+    pub fn drain(mut self) -> Self {
+        self.drain = true;
+        self
+    }
+
+    /// Like `drain()`, but stops waiting for outstanding calls once `timeout` completes, at which
+    /// point the connection is disconnected immediately. Any timer future works here, e.g.
+    /// `tokio::time::sleep(duration)`.
+    pub fn drain_with_timeout<F>(mut self, timeout: F) -> Self
+        where F: Future<Output=()> + 'static
+    {
+        self.drain = true;
+        self.drain_timeout = Some(Promise::from_future(timeout.map(Ok)));
+        self
+    }
+
     fn disconnect(&self) {
-        if let Some(ref state) = *(self.connection_state.borrow()) {
+        // Don't hold a borrow of the list while disconnecting.
+        let states = self.connection_states.borrow().clone();
+        for state in states {
             state.disconnect(::capnp::Error::disconnected("client requested disconnect".to_owned()));
         }
     }
+
+    fn start_drain(&mut self) -> Promise<(), Error> {
+        let drained: Vec<_> = self.connection_states.borrow().iter().map(|state| state.drain()).collect();
+        let drained = future::join_all(drained).map(|_| Ok(()));
+        match self.drain_timeout.take() {
+            Some(timeout) => Promise::from_future(future::select(Box::pin(drained), timeout).map(|_| Ok(()))),
+            None => Promise::from_future(drained),
+        }
+    }
 }
 
 impl <VatId> Future for Disconnector<VatId>
@@ -1502,13 +1678,32 @@ where
     type Output = Result<(), capnp::Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        self.state = match self.state {
+        let state = mem::replace(&mut self.state, DisconnectorState::Disconnected);
+        self.state = match state {
             DisconnectorState::Connected => {
-                self.disconnect();
-                DisconnectorState::Disconnecting
+                if self.connection_states.borrow().is_empty() {
+                    DisconnectorState::Disconnected
+                } else if self.drain {
+                    DisconnectorState::Draining(self.start_drain())
+                } else {
+                    self.disconnect();
+                    DisconnectorState::Disconnecting
+                }
+            },
+            DisconnectorState::Draining(mut drained) => {
+                match Pin::new(&mut drained).poll(cx) {
+                    Poll::Pending => {
+                        self.state = DisconnectorState::Draining(drained);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready(_) => {
+                        self.disconnect();
+                        DisconnectorState::Disconnecting
+                    }
+                }
             },
             DisconnectorState::Disconnecting => {
-                if let Some(_) = *(self.connection_state.borrow()) {
+                if !self.connection_states.borrow().is_empty() {
                     DisconnectorState::Disconnecting
                 } else {
                     DisconnectorState::Disconnected
@@ -1520,6 +1715,7 @@ where
         };
         match self.state {
             DisconnectorState::Connected => unreachable!(),
+            DisconnectorState::Draining(_) |
             DisconnectorState::Disconnecting => {
                 cx.waker().clone().wake();
                 Poll::Pending
@@ -1625,7 +1821,7 @@ impl <VatId> Request<VatId> where VatId: 'static {
            _size_hint: Option<::capnp::MessageSize>,
            target: Client<VatId>) -> ::capnp::Result<Request<VatId>> {
 
-        let message = connection_state.new_outgoing_message(100)?;
+        let message = connection_state.new_outgoing_message()?;
         Ok(Request {
             connection_state: connection_state,
             target: target,
@@ -1646,8 +1842,11 @@ impl <VatId> Request<VatId> where VatId: 'static {
                      -> (Rc<RefCell<QuestionRef<VatId>>>, Promise<Response<VatId>, Error>)
     {
         // Build the cap table.
+        let mut fds = Vec::new();
         let exports = ConnectionState::write_descriptors(&connection_state, &cap_table,
-                                                         get_call(&mut message).unwrap().get_params().unwrap());
+                                                         get_call(&mut message).unwrap().get_params().unwrap(),
+                                                         &mut fds);
+        message.set_fds(fds);
 
         // Init the question table.  Do this after writing descriptors to avoid interference.
         let mut question = Question::<VatId>::new();
@@ -1714,6 +1913,12 @@ impl <VatId> RequestHook for Request<VatId> {
                 replacement.send()
             }
             None => {
+                if let Err(e) = connection_state.check_outgoing_message(&*message) {
+                    return ::capnp::capability::RemotePromise {
+                        promise: Promise::err(e.clone()),
+                        pipeline: any_pointer::Pipeline::new(Box::new(crate::broken::Pipeline::new(e))),
+                    };
+                }
                 let (question_ref, promise) =
                     Request::send_internal(connection_state.clone(), message, cap_table, false);
                 let forked_promise1 = promise.shared();
@@ -1988,7 +2193,8 @@ impl <VatId> ResultsInner<VatId> where VatId: 'static {
         if self.variant.is_none() {
             match (self.redirect_results, self.connection_state.connection.borrow_mut().as_mut()) {
                 (false, Ok(c)) => {
-                    let mut message = c.new_outgoing_message(100); // size hint?
+                    let mut message = c.new_outgoing_message(
+                        self.connection_state.send_options.get().first_segment_words);
 
                     {
                         let root: message::Builder = message.get_body().unwrap().init_as();
@@ -2102,7 +2308,7 @@ impl <VatId> ResultsHook for Results<VatId> {
                 // optimize out the return trip.
                 if let Some((question_id, promise, pipeline)) = request.tail_send() {
 
-                    let mut message = state.new_outgoing_message(100).expect("no connection?"); // size hint?
+                    let mut message = state.new_outgoing_message().expect("no connection?");
 
                     {
                         let root: message::Builder = message.get_body().unwrap().init_as();
@@ -2161,6 +2367,12 @@ impl ResultsDone {
                 match variant {
                     None => unreachable!(),
                     Some(ResultsVariant::Rpc(mut message, cap_table)) => {
+                        // If the results are too large to send, return an error instead.
+                        let call_status = match call_status {
+                            Ok(()) if !finish_received.get() =>
+                                connection_state.check_outgoing_message(&*message),
+                            status => status,
+                        };
                         match (finish_received.get(), call_status) {
                             (true, _) => {
                                 let hook = Box::new(ResultsDone::rpc(Rc::new(message.take()), cap_table))
@@ -2188,6 +2400,7 @@ impl ResultsDone {
                                 Ok(hook)
                             }
                             (false, Ok(())) => {
+                                let mut fds = Vec::new();
                                 let exports = {
                                     let root: message::Builder = message.get_body()?.get_as()?;
                                     match root.which()? {
@@ -2196,7 +2409,8 @@ impl ResultsDone {
                                                 crate::rpc_capnp::return_::Results(Ok(payload)) => {
                                                     ConnectionState::write_descriptors(&connection_state,
                                                                                        &cap_table,
-                                                                                       payload)
+                                                                                       payload,
+                                                                                       &mut fds)
                                                 }
                                                 _ => {
                                                     unreachable!()
@@ -2208,6 +2422,7 @@ impl ResultsDone {
                                         }
                                     }
                                 };
+                                message.set_fds(fds);
 
                                 let (_promise, m) = message.send();
                                 connection_state.answer_has_sent_return(answer_id, exports);
@@ -2374,6 +2589,10 @@ struct ImportClient<VatId> where VatId: 'static {
 
     /// Number of times we've received this import from the peer.
     remote_ref_count: u32,
+
+    /// File descriptor attached to the capability by the peer, if any.
+    #[cfg(unix)]
+    fd: Option<std::os::unix::io::OwnedFd>,
 }
 
 impl <VatId> Drop for ImportClient<VatId> {
@@ -2422,12 +2641,43 @@ impl <VatId> ImportClient<VatId> where VatId: 'static {
             connection_state: connection_state.clone(),
             import_id: import_id,
             remote_ref_count: 0,
+            #[cfg(unix)]
+            fd: None,
         }))
     }
 
     fn add_remote_ref(&mut self) {
         self.remote_ref_count += 1;
     }
+
+    /// Takes a duplicate of `fd`, which is owned by the incoming message, unless an FD was
+    /// already attached to this import.
+    #[cfg(unix)]
+    fn set_fd_if_missing(&mut self, fd: i32) -> ::capnp::Result<()> {
+        use std::os::unix::io::BorrowedFd;
+        if self.fd.is_none() {
+            // The message keeps `fd` open while we are handling it.
+            let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+            self.fd = Some(borrowed.try_clone_to_owned()?);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn set_fd_if_missing(&mut self, _fd: i32) -> ::capnp::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn get_fd(&self) -> Option<i32> {
+        use std::os::unix::io::AsRawFd;
+        self.fd.as_ref().map(|fd| fd.as_raw_fd())
+    }
+
+    #[cfg(not(unix))]
+    fn get_fd(&self) -> Option<i32> {
+        None
+    }
 }
 
 impl <VatId> From<Rc<RefCell<ImportClient<VatId>>>> for Client<VatId> {
@@ -2516,7 +2766,7 @@ impl <VatId> PromiseClient<VatId> {
             let embargo = Embargo::new(fulfiller);
             let embargo_id = connection_state.embargoes.borrow_mut().push(embargo);
 
-            let mut message = connection_state.new_outgoing_message(50).expect("no connection?"); // XXX size hint
+            let mut message = connection_state.new_outgoing_message().expect("no connection?");
             {
                 let root: message::Builder = message.get_body().unwrap().init_as();
                 let mut disembargo = root.init_disembargo();
@@ -2677,7 +2927,7 @@ impl <VatId> Client<VatId> {
         }
     }
 
-    fn write_descriptor(&self, mut descriptor: cap_descriptor::Builder) -> Option<u32> {
+    fn write_descriptor(&self, mut descriptor: cap_descriptor::Builder, fds: &mut Vec<i32>) -> Option<u32> {
         match self.variant {
             ClientVariant::Import(ref import_client) => {
                 descriptor.set_receiver_hosted(import_client.borrow().import_id);
@@ -2704,7 +2954,7 @@ impl <VatId> Client<VatId> {
 
                 ConnectionState::write_descriptor(&self.connection_state.clone(),
                                                   &promise_client.borrow().cap.clone(),
-                                                  descriptor).unwrap()
+                                                  descriptor, fds).unwrap()
             }
             _ => {
                 unimplemented!()
@@ -2857,6 +3107,23 @@ impl <VatId> ClientHook for Client<VatId> {
     fn when_resolved(&self) -> Promise<(), Error> {
         default_when_resolved_impl(self)
     }
+
+    fn get_fd(&self) -> Option<i32> {
+        match self.variant {
+            ClientVariant::Import(ref import_client) => {
+                import_client.borrow().get_fd()
+            }
+            ClientVariant::Pipeline(ref _pipeline_client) => {
+                None
+            }
+            ClientVariant::Promise(ref promise_client) => {
+                promise_client.borrow().cap.get_fd()
+            }
+            ClientVariant::__NoIntercept(()) => {
+                None
+            }
+        }
+    }
 }
 
 pub(crate) fn default_when_resolved_impl<C>(client: &C) -> Promise<(), Error>
@@ -3960,7 +3960,7 @@ pub mod exception {
   }
 
   #[repr(u16)]
-  #[derive(Clone, Copy, PartialEq)]
+  #[derive(Clone, Copy, Debug, PartialEq)]
   pub enum Type {
     Failed = 0,
     Overloaded = 1,
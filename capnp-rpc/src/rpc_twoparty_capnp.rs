@@ -4,7 +4,7 @@
 
 
 #[repr(u16)]
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Side {
   Server = 0,
   Client = 1,
@@ -0,0 +1,424 @@
+// Copyright (c) 2013-2017 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! An in-process implementation of `VatNetwork` with any number of named vats, intended for tests.
+//!
+//! Any vat can connect to any other. Messages are handed over in memory without being serialized.
+//! Delivery can be delayed with `Network::set_delay()` and links can be broken with
+//! `Network::disconnect()`, which makes it possible to deterministically exercise promise
+//! pipelining, embargoes and disconnect handling across several vats.
+//!
+//! ```ignore
+//! let network = test_network::Network::new();
+//! let mut alice = RpcSystem::new(Box::new(network.add_vat("alice")), Some(alice_bootstrap));
+//! let mut bob = RpcSystem::new(Box::new(network.add_vat("bob")), None);
+//! let client: foo::Client = bob.bootstrap("alice".to_string());
+//! ```
+
+use capnp::Error;
+use capnp::capability::Promise;
+use capnp::message::{Builder, HeapAllocator};
+use futures::Future;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+/// Vats on a test network are identified by name.
+pub type VatId = String;
+
+type DelayFn = Rc<RefCell<Box<dyn FnMut(&str, &str) -> Promise<(), Error>>>>;
+
+struct NetworkInner {
+    vats: HashMap<VatId, Rc<RefCell<VatInner>>>,
+    delay: Option<DelayFn>,
+}
+
+/// A set of vats that can connect to one another.
+pub struct Network {
+    inner: Rc<RefCell<NetworkInner>>,
+}
+
+impl Network {
+    pub fn new() -> Network {
+        Network {
+            inner: Rc::new(RefCell::new(NetworkInner {
+                vats: HashMap::new(),
+                delay: None,
+            })),
+        }
+    }
+
+    /// Adds a vat named `name` to the network and returns its view of the network, suitable for
+    /// passing to `RpcSystem::new()`. Panics if there is already a vat with that name.
+    pub fn add_vat(&self, name: &str) -> VatNetwork {
+        let vat = Rc::new(RefCell::new(VatInner {
+            connections: HashMap::new(),
+            accept_queue: VecDeque::new(),
+            accept_waker: None,
+        }));
+        let previous = self.inner.borrow_mut().vats.insert(name.to_string(), vat.clone());
+        if previous.is_some() {
+            panic!("test network already has a vat named {:?}", name);
+        }
+        VatNetwork {
+            name: name.to_string(),
+            vat,
+            network: self.inner.clone(),
+        }
+    }
+
+    /// Delays the delivery of each message. `delay` is called with the names of the sending
+    /// and receiving vats when a message is sent, and the message is not delivered until the
+    /// returned promise completes. Messages are never reordered: a message whose delay has
+    /// elapsed still waits for all messages sent before it on the same connection.
+    pub fn set_delay<F>(&self, delay: F)
+        where F: FnMut(&str, &str) -> Promise<(), Error> + 'static
+    {
+        self.inner.borrow_mut().delay = Some(Rc::new(RefCell::new(Box::new(delay))));
+    }
+
+    /// Stops delaying newly sent messages.
+    pub fn clear_delay(&self) {
+        self.inner.borrow_mut().delay = None;
+    }
+
+    /// Breaks the connection between vats `a` and `b`, if there is one. Messages that have not
+    /// yet been delivered are dropped, and both sides see a `Disconnected` error. A later
+    /// `connect()` between the two vats establishes a fresh connection.
+    pub fn disconnect(&self, a: &str, b: &str) {
+        let vats = self.inner.borrow().vats.clone();
+        for (local, remote) in &[(a, b), (b, a)] {
+            if let Some(vat) = vats.get(*local) {
+                let connection = vat.borrow_mut().connections.remove(*remote);
+                if let Some(connection) = connection {
+                    let error = Error::disconnected(
+                        format!("test network: forced disconnect between {} and {}", a, b));
+                    connection.incoming.borrow_mut().close(Err(error.clone()));
+                    connection.outgoing.borrow_mut().close(Err(error));
+                }
+            }
+        }
+    }
+}
+
+impl Default for Network {
+    fn default() -> Network {
+        Network::new()
+    }
+}
+
+enum PipeState {
+    Open,
+
+    // No more messages will be sent, but the ones already in the queue will be delivered.
+    Closed,
+
+    Broken(Error),
+}
+
+// A sent message, along with a promise that resolves when it may be delivered.
+type Delayed = (Rc<Builder<HeapAllocator>>, Promise<(), Error>);
+
+// One direction of a connection.
+struct Pipe {
+    queue: VecDeque<Delayed>,
+    state: PipeState,
+    waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn new() -> Rc<RefCell<Pipe>> {
+        Rc::new(RefCell::new(Pipe { queue: VecDeque::new(), state: PipeState::Open, waker: None }))
+    }
+
+    fn close(&mut self, result: Result<(), Error>) {
+        match self.state {
+            PipeState::Open | PipeState::Closed => (),
+            PipeState::Broken(_) => return,
+        }
+        self.state = match result {
+            Ok(()) => PipeState::Closed,
+            Err(e) => {
+                self.queue.clear();
+                PipeState::Broken(e)
+            }
+        };
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct ConnectionInner {
+    local: VatId,
+    peer: VatId,
+    incoming: Rc<RefCell<Pipe>>,
+    outgoing: Rc<RefCell<Pipe>>,
+    network: Rc<RefCell<NetworkInner>>,
+}
+
+struct Connection {
+    inner: Rc<ConnectionInner>,
+}
+
+struct IncomingMessage {
+    message: Rc<Builder<HeapAllocator>>,
+}
+
+impl crate::IncomingMessage for IncomingMessage {
+    fn get_body<'a>(&'a self) -> ::capnp::Result<::capnp::any_pointer::Reader<'a>> {
+        self.message.get_root_as_reader()
+    }
+}
+
+struct OutgoingMessage {
+    message: Builder<HeapAllocator>,
+    connection: Rc<ConnectionInner>,
+}
+
+impl crate::OutgoingMessage for OutgoingMessage {
+    fn get_body<'a>(&'a mut self) -> ::capnp::Result<::capnp::any_pointer::Builder<'a>> {
+        self.message.get_root()
+    }
+
+    fn get_body_as_reader<'a>(&'a self) -> ::capnp::Result<::capnp::any_pointer::Reader<'a>> {
+        self.message.get_root_as_reader()
+    }
+
+    fn send(self: Box<Self>)
+            -> (Promise<Rc<Builder<HeapAllocator>>, Error>, Rc<Builder<HeapAllocator>>)
+    {
+        let OutgoingMessage { message, connection } = *self;
+        let m = Rc::new(message);
+
+        let delay = connection.network.borrow().delay.clone();
+        let delay = match delay {
+            Some(f) => (*f.borrow_mut())(&connection.local, &connection.peer),
+            None => Promise::ok(()),
+        };
+
+        let mut pipe = connection.outgoing.borrow_mut();
+        let result = match pipe.state {
+            PipeState::Open => {
+                pipe.queue.push_back((m.clone(), delay));
+                if let Some(waker) = pipe.waker.take() {
+                    waker.wake();
+                }
+                Promise::ok(m.clone())
+            }
+            PipeState::Closed =>
+                Promise::err(Error::disconnected("test network: connection was shut down".to_string())),
+            PipeState::Broken(ref e) => Promise::err(e.clone()),
+        };
+        (result, m)
+    }
+
+    fn take(self: Box<Self>) -> Builder<HeapAllocator> {
+        self.message
+    }
+}
+
+struct Receive {
+    pipe: Rc<RefCell<Pipe>>,
+}
+
+impl Future for Receive {
+    type Output = Result<Option<Box<dyn crate::IncomingMessage>>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut pipe = self.pipe.borrow_mut();
+        if let PipeState::Broken(ref e) = pipe.state {
+            return Poll::Ready(Err(e.clone()));
+        }
+        let delivered = match pipe.queue.front_mut() {
+            Some((_, ref mut delay)) => Pin::new(delay).poll(cx),
+            None => {
+                if let PipeState::Closed = pipe.state {
+                    return Poll::Ready(Ok(None));
+                }
+                pipe.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        };
+        match delivered {
+            Poll::Pending => {
+                pipe.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Ready(Ok(())) => {
+                let (message, _) = pipe.queue.pop_front().unwrap();
+                Poll::Ready(Ok(Some(Box::new(IncomingMessage { message }))))
+            }
+        }
+    }
+}
+
+impl crate::Connection<VatId> for Connection {
+    fn get_peer_vat_id(&self) -> VatId {
+        self.inner.peer.clone()
+    }
+
+    fn is_same_peer(&self, other: &dyn crate::Connection<VatId>) -> bool {
+        other.get_peer_vat_id() == self.inner.peer
+    }
+
+    fn new_outgoing_message(&mut self, first_segment_word_size: u32) -> Box<dyn crate::OutgoingMessage> {
+        Box::new(OutgoingMessage {
+            message: Builder::new(HeapAllocator::new().first_segment_words(first_segment_word_size)),
+            connection: self.inner.clone(),
+        })
+    }
+
+    fn receive_incoming_message(&mut self) -> Promise<Option<Box<dyn crate::IncomingMessage>>, Error> {
+        Promise::from_future(Receive { pipe: self.inner.incoming.clone() })
+    }
+
+    fn shutdown(&mut self, _result: ::capnp::Result<()>) -> Promise<(), Error> {
+        // The peer still receives everything that we have already sent, followed by end-of-stream.
+        self.inner.outgoing.borrow_mut().close(Ok(()));
+
+        // Forget the connection, so that a later `connect()` starts a fresh one.
+        let vat = self.inner.network.borrow().vats.get(&self.inner.local).cloned();
+        if let Some(vat) = vat {
+            let mut vat = vat.borrow_mut();
+            let is_current = match vat.connections.get(&self.inner.peer) {
+                Some(c) => Rc::ptr_eq(c, &self.inner),
+                None => false,
+            };
+            if is_current {
+                vat.connections.remove(&self.inner.peer);
+            }
+        }
+        Promise::ok(())
+    }
+}
+
+struct VatInner {
+    // Connections to other vats, keyed by the name of the other vat.
+    connections: HashMap<VatId, Rc<ConnectionInner>>,
+
+    // Connections initiated by other vats that have not been accepted yet.
+    accept_queue: VecDeque<Rc<ConnectionInner>>,
+    accept_waker: Option<Waker>,
+}
+
+struct Accept {
+    vat: Rc<RefCell<VatInner>>,
+}
+
+impl Future for Accept {
+    type Output = Result<Box<dyn crate::Connection<VatId>>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut vat = self.vat.borrow_mut();
+        match vat.accept_queue.pop_front() {
+            Some(inner) => Poll::Ready(Ok(Box::new(Connection { inner }))),
+            None => {
+                vat.accept_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A single vat's view of a test `Network`.
+pub struct VatNetwork {
+    name: VatId,
+    vat: Rc<RefCell<VatInner>>,
+    network: Rc<RefCell<NetworkInner>>,
+}
+
+impl VatNetwork {
+    /// Returns the name of this vat.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn new_connection(&self, peer: VatId, peer_vat: Option<Rc<RefCell<VatInner>>>) -> Rc<ConnectionInner> {
+        let to_peer = Pipe::new();
+        let from_peer = Pipe::new();
+        let local = Rc::new(ConnectionInner {
+            local: self.name.clone(),
+            peer: peer.clone(),
+            incoming: from_peer.clone(),
+            outgoing: to_peer.clone(),
+            network: self.network.clone(),
+        });
+        match peer_vat {
+            Some(peer_vat) => {
+                let remote = Rc::new(ConnectionInner {
+                    local: peer,
+                    peer: self.name.clone(),
+                    incoming: to_peer,
+                    outgoing: from_peer,
+                    network: self.network.clone(),
+                });
+                let mut peer_vat = peer_vat.borrow_mut();
+                peer_vat.connections.insert(self.name.clone(), remote.clone());
+                peer_vat.accept_queue.push_back(remote);
+                if let Some(waker) = peer_vat.accept_waker.take() {
+                    waker.wake();
+                }
+            }
+            None => {
+                let error = Error::failed(format!("test network has no vat named {:?}", peer));
+                to_peer.borrow_mut().close(Err(error.clone()));
+                from_peer.borrow_mut().close(Err(error));
+            }
+        }
+        local
+    }
+}
+
+impl crate::VatNetwork<VatId> for VatNetwork {
+    fn connect(&mut self, host_id: VatId) -> Option<Box<dyn crate::Connection<VatId>>> {
+        if host_id == self.name {
+            return None;
+        }
+        let existing = self.vat.borrow().connections.get(&host_id).cloned();
+        let inner = match existing {
+            Some(inner) => inner,
+            None => {
+                let peer_vat = self.network.borrow().vats.get(&host_id).cloned();
+                let exists = peer_vat.is_some();
+                let inner = self.new_connection(host_id.clone(), peer_vat);
+                if exists {
+                    self.vat.borrow_mut().connections.insert(host_id, inner.clone());
+                }
+                inner
+            }
+        };
+        Some(Box::new(Connection { inner }))
+    }
+
+    fn accept(&mut self) -> Promise<Box<dyn crate::Connection<VatId>>, Error> {
+        Promise::from_future(Accept { vat: self.vat.clone() })
+    }
+
+    /// Never resolves: a vat on a test network stays up until its `RpcSystem` is dropped.
+    fn drive_until_shutdown(&mut self) -> Promise<(), Error> {
+        Promise::from_future(::futures::future::pending())
+    }
+}
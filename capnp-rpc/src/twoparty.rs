@@ -23,21 +23,64 @@
 
 use capnp::message::ReaderOptions;
 use capnp::capability::Promise;
-use futures::{AsyncRead, AsyncWrite, FutureExt, TryFutureExt};
+use futures::{AsyncRead, AsyncWrite, Future, FutureExt, TryFutureExt};
 use futures::channel::oneshot;
 
 use std::cell::RefCell;
+use std::pin::Pin;
 use std::rc::{Rc, Weak};
 
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, BorrowedFd, OwnedFd};
+
 pub type VatId = crate::rpc_twoparty_capnp::Side;
 
+type OutgoingBuilder = Rc<::capnp::message::Builder<::capnp::message::HeapAllocator>>;
+
+/// The file descriptors to send with an outgoing message. On Unix these are our own duplicates,
+/// so that they stay open until the message has been written even if the capabilities that they
+/// belong to are dropped in the meantime.
+#[cfg(unix)]
+type OutgoingFds = Vec<OwnedFd>;
+#[cfg(not(unix))]
+type OutgoingFds = Vec<i32>;
+
+/// Duplicates `fds`. Fails if any of them cannot be duplicated, for example because we have run
+/// out of descriptors.
+#[cfg(unix)]
+fn duplicate_fds(fds: Vec<i32>) -> ::std::io::Result<OutgoingFds> {
+    fds.into_iter().map(|fd| {
+        // Safe because `set_fds()` requires the descriptors to be open for the duration of the call.
+        unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()
+    }).collect()
+}
+
 struct IncomingMessage {
     message: ::capnp::message::Reader<capnp::serialize::OwnedSegments>,
+
+    // The attached file descriptors. We only hold on to them so that they are closed when
+    // the message is dropped.
+    #[cfg(unix)]
+    #[allow(dead_code)]
+    fds: Vec<OwnedFd>,
+    raw_fds: Vec<i32>,
 }
 
 impl IncomingMessage {
     pub fn new(message: ::capnp::message::Reader<capnp::serialize::OwnedSegments>) -> IncomingMessage {
-        IncomingMessage { message: message }
+        IncomingMessage {
+            message,
+            #[cfg(unix)]
+            fds: Vec::new(),
+            raw_fds: Vec::new(),
+        }
+    }
+
+    #[cfg(unix)]
+    fn with_fds(message: ::capnp::message::Reader<capnp::serialize::OwnedSegments>,
+                fds: Vec<OwnedFd>) -> IncomingMessage {
+        let raw_fds = fds.iter().map(|fd| fd.as_raw_fd()).collect();
+        IncomingMessage { message, fds, raw_fds }
     }
 }
 
@@ -45,11 +88,49 @@ impl crate::IncomingMessage for IncomingMessage {
     fn get_body<'a>(&'a self) -> ::capnp::Result<::capnp::any_pointer::Reader<'a>> {
         self.message.get_root()
     }
+
+    fn get_attached_fds(&self) -> &[i32] {
+        &self.raw_fds
+    }
+}
+
+/// The sending half of a connection's write queue.
+#[derive(Clone)]
+enum Sender {
+    Plain(::capnp_futures::Sender<OutgoingBuilder>),
+    #[cfg(unix)]
+    WithFds(::capnp_futures::Sender<::capnp_futures::fds::MessageWithFds<OutgoingBuilder>>),
+}
+
+impl Sender {
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn send(&mut self, message: OutgoingBuilder, fds: OutgoingFds) -> Promise<OutgoingBuilder, ::capnp::Error> {
+        match self {
+            Sender::Plain(sender) => Promise::from_future(sender.send(message)),
+            #[cfg(unix)]
+            Sender::WithFds(sender) => {
+                let message = ::capnp_futures::fds::MessageWithFds { message, fds };
+                Promise::from_future(sender.send(message).map_ok(|m| m.message))
+            }
+        }
+    }
+
+    fn terminate(&mut self, result: ::capnp::Result<()>) -> Promise<(), ::capnp::Error> {
+        match self {
+            Sender::Plain(sender) => Promise::from_future(sender.terminate(result)),
+            #[cfg(unix)]
+            Sender::WithFds(sender) => Promise::from_future(sender.terminate(result)),
+        }
+    }
 }
 
 struct OutgoingMessage {
     message: ::capnp::message::Builder<::capnp::message::HeapAllocator>,
-    sender: ::capnp_futures::Sender<Rc<::capnp::message::Builder<::capnp::message::HeapAllocator>>>,
+    sender: Sender,
+
+    // An error here means that the descriptors could not be duplicated. The message then fails to
+    // send, as sending it without them would leave its capabilities without their descriptors.
+    fds: ::std::io::Result<OutgoingFds>,
 }
 
 impl crate::OutgoingMessage for OutgoingMessage {
@@ -67,9 +148,12 @@ impl crate::OutgoingMessage for OutgoingMessage {
          Rc<::capnp::message::Builder<::capnp::message::HeapAllocator>>)
     {
         let tmp = *self;
-        let OutgoingMessage {message, mut sender} = tmp;
+        let OutgoingMessage {message, mut sender, fds} = tmp;
         let m = Rc::new(message);
-        (Promise::from_future(sender.send(m.clone()).map_err(|e| e.into())), m)
+        match fds {
+            Ok(fds) => (sender.send(m.clone(), fds), m),
+            Err(e) => (Promise::err(e.into()), m),
+        }
     }
 
     fn take(self: Box<Self>)
@@ -77,11 +161,51 @@ impl crate::OutgoingMessage for OutgoingMessage {
     {
         self.message
     }
+
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn set_fds(&mut self, fds: Vec<i32>) {
+        // Only connections that can pass descriptors need to keep them.
+        #[cfg(unix)]
+        {
+            if let Sender::WithFds(_) = self.sender {
+                self.fds = duplicate_fds(fds);
+            }
+        }
+    }
+}
+
+type ReadResult<T> = (T, ::capnp::Result<Option<Box<dyn crate::IncomingMessage>>>);
+
+/// Reads the next message from a stream, handing the stream back along with the result.
+type ReadFn<T> = fn(T, ReaderOptions) -> Pin<Box<dyn Future<Output=ReadResult<T>>>>;
+
+fn read_message<T>(mut stream: T, options: ReaderOptions) -> Pin<Box<dyn Future<Output=ReadResult<T>>>>
+    where T: AsyncRead + Unpin + 'static
+{
+    Box::pin(async move {
+        let result = ::capnp_futures::serialize::try_read_message(&mut stream, options).await;
+        let result = result.map(|maybe_message| maybe_message.map(|message|
+            Box::new(IncomingMessage::new(message)) as Box<dyn crate::IncomingMessage>));
+        (stream, result)
+    })
+}
+
+#[cfg(unix)]
+fn read_message_with_fds<T>(mut stream: T, options: ReaderOptions) -> Pin<Box<dyn Future<Output=ReadResult<T>>>>
+    where T: ::capnp_futures::fds::AsyncFdRead + Unpin + 'static
+{
+    Box::pin(async move {
+        let result = ::capnp_futures::fds::try_read_message_with_fds(&mut stream, options).await;
+        let result = result.map(|maybe_message| maybe_message.map(|(message, fds)|
+            Box::new(IncomingMessage::with_fds(message, fds)) as Box<dyn crate::IncomingMessage>));
+        (stream, result)
+    })
 }
 
 struct ConnectionInner<T> where T: AsyncRead + 'static {
     input_stream: Rc<RefCell<Option<T>>>,
-    sender: ::capnp_futures::Sender<Rc<::capnp::message::Builder<::capnp::message::HeapAllocator>>>,
+    read_message: ReadFn<T>,
+    sender: Sender,
     side: crate::rpc_twoparty_capnp::Side,
     receive_options: ReaderOptions,
     on_disconnect_fulfiller: Option<oneshot::Sender<()>>,
@@ -105,7 +229,8 @@ impl <T> Drop for ConnectionInner<T> where T: AsyncRead {
 
 impl <T> Connection<T> where T: AsyncRead {
     fn new(input_stream: T,
-           sender: ::capnp_futures::Sender<Rc<::capnp::message::Builder<::capnp::message::HeapAllocator>>>,
+           read_message: ReadFn<T>,
+           sender: Sender,
            side: crate::rpc_twoparty_capnp::Side,
            receive_options: ReaderOptions,
            on_disconnect_fulfiller: oneshot::Sender<()>,
@@ -116,6 +241,7 @@ impl <T> Connection<T> where T: AsyncRead {
             inner: Rc::new(RefCell::new(
                 ConnectionInner {
                     input_stream: Rc::new(RefCell::new(Some(input_stream))),
+                    read_message,
                     sender: sender,
                     side: side,
                     receive_options: receive_options,
@@ -132,10 +258,12 @@ impl <T> crate::Connection<crate::rpc_twoparty_capnp::Side> for Connection<T>
         self.inner.borrow().side
     }
 
-    fn new_outgoing_message(&mut self, _first_segment_word_size: u32) -> Box<dyn crate::OutgoingMessage> {
+    fn new_outgoing_message(&mut self, first_segment_word_size: u32) -> Box<dyn crate::OutgoingMessage> {
+        let allocator = ::capnp::message::HeapAllocator::new().first_segment_words(first_segment_word_size);
         Box::new(OutgoingMessage {
-            message: ::capnp::message::Builder::new_default(),
+            message: ::capnp::message::Builder::new(allocator),
             sender: self.inner.borrow().sender.clone(),
+            fds: Ok(Vec::new()),
         })
     }
 
@@ -146,13 +274,13 @@ impl <T> crate::Connection<crate::rpc_twoparty_capnp::Side> for Connection<T>
         let maybe_input_stream = ::std::mem::replace(&mut *inner.input_stream.borrow_mut(), None);
         let return_it_here = inner.input_stream.clone();
         match maybe_input_stream {
-            Some(mut s) => {
-                let receive_options = inner.receive_options;
+            Some(s) => {
+                let read = (inner.read_message)(s, inner.receive_options);
                 Promise::from_future(async move {
-                    let maybe_message = ::capnp_futures::serialize::try_read_message(&mut s, receive_options).await?;
+                    let (s, maybe_message) = read.await;
+                    let maybe_message = maybe_message?;
                     *return_it_here.borrow_mut() = Some(s);
-                    Ok(maybe_message.map(|message|
-                                         Box::new(IncomingMessage::new(message)) as Box<dyn crate::IncomingMessage>))
+                    Ok(maybe_message)
                 })
             }
             None => {
@@ -163,7 +291,7 @@ impl <T> crate::Connection<crate::rpc_twoparty_capnp::Side> for Connection<T>
     }
 
     fn shutdown(&mut self, result: ::capnp::Result<()>) -> Promise<(), ::capnp::Error> {
-        Promise::from_future(self.inner.borrow_mut().sender.terminate(result).map_err(|e| e.into()))
+        self.inner.borrow_mut().sender.terminate(result)
     }
 }
 
@@ -195,24 +323,30 @@ impl <T> VatNetwork<T> where T: AsyncRead + Unpin {
                receive_options: ReaderOptions) -> VatNetwork<T>
         where U: AsyncWrite + 'static + Unpin,
     {
+        let (tx, write_queue) = ::capnp_futures::write_queue(output_stream);
+        VatNetwork::new_internal(input_stream, read_message, Sender::Plain(tx), write_queue,
+                                 side, receive_options)
+    }
 
+    fn new_internal<Q>(input_stream: T,
+                       read_message: ReadFn<T>,
+                       sender: Sender,
+                       write_queue: Q,
+                       side: crate::rpc_twoparty_capnp::Side,
+                       receive_options: ReaderOptions) -> VatNetwork<T>
+        where Q: Future<Output=Result<(), ::capnp::Error>> + 'static
+    {
         let (fulfiller, disconnect_promise) = oneshot::channel();
         let disconnect_promise = disconnect_promise
             .map_err(|_| ::capnp::Error::disconnected("disconnected".into()));
 
-        let (execution_driver, sender) = {
-            let (tx, write_queue) = ::capnp_futures::write_queue(output_stream);
-
-            // Don't use `.join()` here because we need to make sure to wait for `disconnect_promise` to
-            // resolve even if `write_queue` resolves to an error.
-            (Promise::from_future(
-                write_queue
-                    .then(move |r| disconnect_promise.then(move |_| futures::future::ready(r)).map_ok(|_| ()))).shared(),
-             tx)
-        };
+        // Don't use `.join()` here because we need to make sure to wait for `disconnect_promise` to
+        // resolve even if `write_queue` resolves to an error.
+        let execution_driver = Promise::from_future(
+            write_queue
+                .then(move |r| disconnect_promise.then(move |_| futures::future::ready(r)).map_ok(|_| ()))).shared();
 
-
-        let connection = Connection::new(input_stream, sender, side, receive_options, fulfiller);
+        let connection = Connection::new(input_stream, read_message, sender, side, receive_options, fulfiller);
         let weak_inner = Rc::downgrade(&connection.inner);
         VatNetwork {
             connection: Some(connection),
@@ -223,6 +357,23 @@ impl <T> VatNetwork<T> where T: AsyncRead + Unpin {
     }
 }
 
+#[cfg(unix)]
+impl <T> VatNetwork<T> where T: ::capnp_futures::fds::AsyncFdRead + Unpin {
+    /// Like `new()`, but for streams that can carry file descriptors, such as Unix domain sockets.
+    /// File descriptors attached to capabilities (see `capnp::capability::Client::get_fd()`) are
+    /// passed to the peer along with them, and likewise for capabilities that we receive.
+    pub fn new_with_fds<U>(input_stream: T,
+                           output_stream: U,
+                           side: crate::rpc_twoparty_capnp::Side,
+                           receive_options: ReaderOptions) -> VatNetwork<T>
+        where U: ::capnp_futures::fds::AsyncFdWrite + 'static + Unpin,
+    {
+        let (tx, write_queue) = ::capnp_futures::write_queue_with_fds(output_stream);
+        VatNetwork::new_internal(input_stream, read_message_with_fds, Sender::WithFds(tx), write_queue,
+                                 side, receive_options)
+    }
+}
+
 impl <T> crate::VatNetwork<VatId> for VatNetwork<T>
     where T: AsyncRead + Unpin
 {
@@ -20,15 +20,16 @@
 // THE SOFTWARE.
 
 use crate::test_capnp::{bootstrap, test_handle, test_interface, test_extends, test_pipeline,
-                        test_call_order, test_more_stuff};
+                        test_call_order, test_more_stuff, test_generic_store};
 
 
 use capnp::Error;
-use capnp::capability::Promise;
+use capnp::capability::{AsyncResult, Promise};
 
 use futures::{FutureExt, TryFutureExt};
 
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 pub struct Bootstrap;
@@ -101,6 +102,35 @@ pub struct TestInterface {
     call_count: Rc<Cell<u64>>,
 }
 
+/// A bootstrap interface whose `testInterface()` returns a capability with a file descriptor attached.
+#[cfg(unix)]
+pub struct FdBootstrap {
+    fd: Option<std::os::unix::io::OwnedFd>,
+}
+
+#[cfg(unix)]
+impl FdBootstrap {
+    pub fn new(fd: std::os::unix::io::OwnedFd) -> FdBootstrap {
+        FdBootstrap { fd: Some(fd) }
+    }
+}
+
+#[cfg(unix)]
+impl bootstrap::Server for FdBootstrap {
+    fn test_interface(&mut self,
+                      _params: bootstrap::TestInterfaceParams,
+                      mut results: bootstrap::TestInterfaceResults)
+                      -> Promise<(), Error>
+    {
+        let fd = match self.fd.take() {
+            Some(fd) => fd,
+            None => return Promise::err(Error::failed("fd was already handed out".to_string())),
+        };
+        results.get().set_cap(capnp_rpc::new_client_with_fd(TestInterface::new(), fd));
+        Promise::ok(())
+    }
+}
+
 impl TestInterface {
     pub fn new() -> TestInterface {
         TestInterface { call_count: Rc::new(Cell::new(0)) }
@@ -220,6 +250,95 @@ impl test_extends::Server for TestExtends {
     }
 }
 
+/// A `TestExtends` that answers every call it does not dispatch with "Method not implemented.",
+/// like servers generated before the interface probe of `capnp_rpc::implements_interface()`.
+pub struct PreProbeTestExtends {
+    dispatch: <test_extends::Client as capnp::capability::FromServer<TestExtends>>::Dispatch,
+}
+
+impl Default for PreProbeTestExtends {
+    fn default() -> PreProbeTestExtends {
+        PreProbeTestExtends {
+            dispatch: <test_extends::Client as capnp::capability::FromServer<TestExtends>>::from_server(TestExtends),
+        }
+    }
+}
+
+impl capnp::capability::Server for PreProbeTestExtends {
+    fn dispatch_call(&mut self, interface_id: u64, method_id: u16,
+                     params: capnp::capability::Params<capnp::any_pointer::Owned>,
+                     results: capnp::capability::Results<capnp::any_pointer::Owned>)
+                     -> Promise<(), Error>
+    {
+        let known_interface = interface_id == test_interface::_private::TYPE_ID ||
+            interface_id == test_extends::_private::TYPE_ID;
+        if !known_interface || method_id == capnp::capability::PROBE_METHOD_ID {
+            return Promise::err(Error::unimplemented("Method not implemented.".to_string()));
+        }
+        capnp::capability::Server::dispatch_call(&mut self.dispatch, interface_id, method_id, params, results)
+    }
+}
+
+impl ::core::ops::Deref for PreProbeTestExtends {
+    type Target = PreProbeTestExtends;
+    fn deref(&self) -> &PreProbeTestExtends { self }
+}
+
+impl ::core::ops::DerefMut for PreProbeTestExtends {
+    fn deref_mut(&mut self) -> &mut PreProbeTestExtends { self }
+}
+
+impl capnp::capability::FromServer<PreProbeTestExtends> for test_extends::Client {
+    type Dispatch = PreProbeTestExtends;
+    fn from_server(s: PreProbeTestExtends) -> PreProbeTestExtends { s }
+}
+
+/// Like `TestExtends`, but implemented through the `AsyncServer` traits.
+#[derive(Default)]
+pub struct AsyncTestExtends {
+    call_count: Rc<Cell<u64>>,
+}
+
+impl AsyncTestExtends {
+    pub fn get_call_count(&self) -> Rc<Cell<u64>> {
+        self.call_count.clone()
+    }
+}
+
+impl test_interface::AsyncServer for AsyncTestExtends {
+    fn foo(&self,
+           params: test_interface::FooParams,
+           mut results: test_interface::FooResults)
+           -> AsyncResult<'_>
+    {
+        Box::pin(async move {
+            let params = params.get()?;
+            if params.get_i() != 321 {
+                return Err(Error::failed("expected i to equal 321".to_string()));
+            }
+            // The future may await other promises while borrowing the server.
+            Promise::<(), Error>::ok(()).await?;
+            self.call_count.set(self.call_count.get() + 1);
+            results.get().set_x("bar");
+            Ok(())
+        })
+    }
+}
+
+impl test_extends::AsyncServer for AsyncTestExtends {
+    fn grault(&self,
+              _params: test_extends::GraultParams,
+              mut results: test_extends::GraultResults)
+              -> AsyncResult<'_>
+    {
+        Box::pin(async move {
+            self.call_count.set(self.call_count.get() + 1);
+            crate::test_util::init_test_message(results.get());
+            Ok(())
+        })
+    }
+}
+
 struct TestPipeline;
 
 impl test_pipeline::Server for TestPipeline {
@@ -490,11 +609,13 @@ impl test_more_stuff::Server for TestMoreStuff {
     }
 
     fn method_with_defaults(&mut self,
-                            _params: test_more_stuff::MethodWithDefaultsParams,
-                            _results: test_more_stuff::MethodWithDefaultsResults)
+                            params: test_more_stuff::MethodWithDefaultsParams,
+                            mut results: test_more_stuff::MethodWithDefaultsResults)
                             -> Promise<(), Error>
     {
-        unimplemented!()
+        self.call_count += 1;
+        results.get().set_d(pry!(pry!(params.get()).get_a()));
+        Promise::ok(())
     }
 
     fn call_each_capability(&mut self,
@@ -583,3 +704,105 @@ impl test_interface::Server for TestCapDestructor {
     }
 }
 
+
+pub struct GatedTestInterface {
+    started: Option<::futures::channel::oneshot::Sender<()>>,
+    gate: Option<::futures::channel::oneshot::Receiver<()>>,
+}
+
+impl GatedTestInterface {
+    /// `started` is fulfilled when `foo()` is called, which then does not return until `gate` is fulfilled.
+    pub fn new(started: ::futures::channel::oneshot::Sender<()>,
+               gate: ::futures::channel::oneshot::Receiver<()>) -> GatedTestInterface {
+        GatedTestInterface {
+            started: Some(started),
+            gate: Some(gate),
+        }
+    }
+}
+
+impl test_interface::Server for GatedTestInterface {
+    fn foo(&mut self,
+           _params: test_interface::FooParams,
+           mut results: test_interface::FooResults)
+           -> Promise<(), Error>
+    {
+        if let Some(f) = self.started.take() {
+            let _ = f.send(());
+        }
+        let gate = match self.gate.take() {
+            Some(g) => g,
+            None => return Promise::err(Error::failed("foo() was already called".to_string())),
+        };
+        Promise::from_future(gate.map(move |r| {
+            r.map_err(|_| Error::failed("gate was dropped".to_string()))?;
+            results.get().set_x("foo");
+            Ok(())
+        }))
+    }
+
+    fn bar(&mut self,
+           _params: test_interface::BarParams,
+           _results: test_interface::BarResults)
+           -> Promise<(), Error>
+    {
+        Promise::err(Error::unimplemented("bar is not implemented".to_string()))
+    }
+
+    fn baz(&mut self,
+           _params: test_interface::BazParams,
+           _results: test_interface::BazResults)
+           -> Promise<(), Error>
+    {
+        Promise::err(Error::unimplemented("baz is not implemented".to_string()))
+    }
+}
+
+#[derive(Default)]
+pub struct TestTextStore {
+    values: HashMap<String, String>,
+}
+
+impl TestTextStore {
+    fn lookup(&self, key: &str) -> Result<&str, Error> {
+        match self.values.get(key) {
+            Some(value) => Ok(value),
+            None => Err(Error::failed(format!("no value for key {:?}", key))),
+        }
+    }
+}
+
+impl test_generic_store::Server<::capnp::text::Owned> for TestTextStore {
+    fn put(&mut self,
+           params: test_generic_store::PutParams<::capnp::text::Owned>,
+           _results: test_generic_store::PutResults<::capnp::text::Owned>)
+           -> Promise<(), Error>
+    {
+        let params = pry!(params.get());
+        self.values.insert(pry!(params.get_key()).to_string(), pry!(params.get_value()).to_string());
+        Promise::ok(())
+    }
+
+    fn get(&mut self,
+           params: test_generic_store::GetParams<::capnp::text::Owned>,
+           mut results: test_generic_store::GetResults<::capnp::text::Owned>)
+           -> Promise<(), Error>
+    {
+        let value = pry!(self.lookup(pry!(pry!(params.get()).get_key())));
+        pry!(results.get().set_value(value));
+        Promise::ok(())
+    }
+
+    fn get_as(&mut self,
+              params: test_generic_store::GetAsParams<::capnp::text::Owned>,
+              results: test_generic_store::GetAsResults<::capnp::text::Owned>)
+              -> Promise<(), Error>
+    {
+        // Values are stored as text, so only callers that ask for text or data can read them.
+        let mut results: test_generic_store::GetAsResults<::capnp::text::Owned, ::capnp::text::Owned> =
+            results.cast();
+        let value = pry!(self.lookup(pry!(pry!(params.get()).get_key())));
+        pry!(results.get().set_value(value));
+        Promise::ok(())
+    }
+}
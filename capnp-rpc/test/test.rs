@@ -26,11 +26,14 @@ extern crate capnp_rpc;
 
 use capnp::Error;
 use capnp::capability::Promise;
-use capnp_rpc::{RpcSystem, rpc_twoparty_capnp, twoparty};
+use capnp_rpc::{RpcSystem, rpc_twoparty_capnp, test_network, twoparty};
 
 use futures::{Future, FutureExt, TryFutureExt};
 use futures::channel::oneshot;
 
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
 pub mod test_capnp {
   include!(concat!(env!("OUT_DIR"), "/test_capnp.rs"));
 }
@@ -154,6 +157,97 @@ fn disconnector_disconnects() {
     });
 }
 
+#[test]
+fn disconnector_drains() {
+    let mut pool = futures::executor::LocalPool::new();
+    let mut spawner = pool.spawner();
+    let (mut client_rpc_system, server_rpc_system) = disconnector_setup();
+
+    let client: test_capnp::bootstrap::Client = client_rpc_system.bootstrap(rpc_twoparty_capnp::Side::Server);
+    let server_disconnector = server_rpc_system.get_disconnector().drain();
+
+    spawn(&mut spawner, client_rpc_system);
+
+    let (tx, rx) = oneshot::channel::<()>();
+
+    //send on tx when server_rpc_system exits
+    spawn(&mut spawner, server_rpc_system.map(|x| {tx.send(()).expect("sending on tx"); x}));
+
+    pool.run_until(async move {
+        let response = client.test_more_stuff_request().send().promise.await.unwrap();
+        let more_stuff = response.get().unwrap().get_cap().unwrap();
+
+        let (started_tx, started_rx) = oneshot::channel();
+        let (release_tx, release_rx) = oneshot::channel();
+        let gated: test_capnp::test_interface::Client =
+            capnp_rpc::new_client(impls::GatedTestInterface::new(started_tx, release_rx));
+        let mut request = more_stuff.call_foo_request();
+        request.get().set_cap(gated);
+        let call_foo_promise = request.send().promise;
+
+        // Wait until the server is in the middle of answering callFoo().
+        started_rx.await.unwrap();
+
+        let mut drain = Box::pin(server_disconnector);
+        assert!(futures::poll!(&mut drain).is_pending());
+
+        // New calls are refused while draining.
+        match client.test_interface_request().send().promise.await {
+            Err(ref e) if e.kind == ::capnp::ErrorKind::Disconnected => (),
+            _ => panic!("Should have gotten a 'disconnected' error."),
+        }
+
+        // The call that was already in flight still completes.
+        release_tx.send(()).unwrap();
+        let response = call_foo_promise.await.unwrap();
+        assert_eq!(response.get().unwrap().get_s().unwrap(), "bar");
+
+        drain.await.unwrap();
+        rx.await.expect("rpc system should exit");
+    });
+}
+
+#[test]
+fn send_options_limit_outgoing_messages() {
+    let mut pool = futures::executor::LocalPool::new();
+    let mut spawner = pool.spawner();
+    let (mut client_rpc_system, mut server_rpc_system) = disconnector_setup();
+    client_rpc_system.set_send_options(*capnp_rpc::SendOptions::new().size_limit_in_words(Some(200)));
+    server_rpc_system.set_send_options(*capnp_rpc::SendOptions::new().size_limit_in_words(Some(100)));
+
+    let client: test_capnp::bootstrap::Client = client_rpc_system.bootstrap(rpc_twoparty_capnp::Side::Server);
+    spawn(&mut spawner, client_rpc_system);
+    spawn(&mut spawner, server_rpc_system);
+
+    pool.run_until(async move {
+        let response = client.test_more_stuff_request().send().promise.await?;
+        let more_stuff = response.get()?.get_cap()?;
+
+        // The params are too large for the client to send.
+        let mut request = more_stuff.method_with_defaults_request();
+        request.get().set_a(&"x".repeat(3000));
+        match request.send().promise.await {
+            Err(ref e) if e.description.contains("outgoing message not sent") => (),
+            _ => panic!("Should have gotten a 'not sent' error."),
+        }
+
+        // The params fit, but the results are too large for the server to send.
+        let mut request = more_stuff.method_with_defaults_request();
+        request.get().set_a(&"x".repeat(1000));
+        match request.send().promise.await {
+            Err(ref e) if e.description.contains("outgoing message not sent") => (),
+            _ => panic!("Should have gotten a 'not sent' error."),
+        }
+
+        // The connection is still usable.
+        let mut request = more_stuff.method_with_defaults_request();
+        request.get().set_a("foo");
+        let response = request.send().promise.await?;
+        assert_eq!(response.get()?.get_d()?, "foo");
+        Ok::<(), Error>(())
+    }).unwrap();
+}
+
 fn rpc_top_level<F, G>(main: F)
     where F: FnOnce(futures::executor::LocalSpawner, test_capnp::bootstrap::Client) -> G,
           F: Send + 'static,
@@ -242,6 +336,58 @@ fn basic_rpc_calls() {
     });
 }
 
+#[test]
+fn upcast_and_probe_interfaces() {
+    use crate::test_capnp::{test_extends, test_interface, test_pipeline};
+    rpc_top_level(|_spawner, client| async move {
+        let response = client.test_extends_request().send().promise.await?;
+        let extends = response.get()?.get_cap()?;
+
+        let base = test_interface::Client::from(extends);
+        let mut request = base.foo_request();
+        request.get().set_i(321);
+        let response = request.send().promise.await?;
+        assert_eq!(response.get()?.get_x()?, "bar");
+
+        use capnp_rpc::{implements_interface, InterfaceSupport};
+        assert_eq!(implements_interface(&base.client, test_interface::_private::TYPE_ID).await?,
+                   InterfaceSupport::Implemented);
+        assert_eq!(implements_interface(&base.client, test_extends::_private::TYPE_ID).await?,
+                   InterfaceSupport::Implemented);
+        assert_eq!(implements_interface(&base.client, test_pipeline::_private::TYPE_ID).await?,
+                   InterfaceSupport::NotImplemented);
+
+        let pipeline: Option<test_pipeline::Client> = capnp_rpc::downcast_client(&base.client).await?;
+        assert!(pipeline.is_none());
+        let extends: Option<test_extends::Client> = capnp_rpc::downcast_client(&base.client).await?;
+        let response = extends.unwrap().grault_request().send().promise.await?;
+        crate::test_util::CheckTestMessage::check_test_message(response.get()?);
+        Ok(())
+    });
+}
+
+#[test]
+fn probe_interfaces_of_pre_probe_server() {
+    use crate::test_capnp::{test_extends, test_interface, test_pipeline};
+    use capnp_rpc::{implements_interface, InterfaceSupport};
+    let extends: test_extends::Client = capnp_rpc::new_client(crate::impls::PreProbeTestExtends::default());
+    let base = test_interface::Client::from(extends);
+    let mut pool = futures::executor::LocalPool::new();
+    pool.run_until(async move {
+        // Such a server answers the same for interfaces it implements and ones it doesn't.
+        assert_eq!(implements_interface(&base.client, test_extends::_private::TYPE_ID).await?,
+                   InterfaceSupport::Unknown);
+        assert_eq!(implements_interface(&base.client, test_pipeline::_private::TYPE_ID).await?,
+                   InterfaceSupport::Unknown);
+
+        let extends: Option<test_extends::Client> = capnp_rpc::downcast_client(&base.client).await?;
+        assert!(extends.is_none());
+        let pipeline: Option<test_pipeline::Client> = capnp_rpc::downcast_client(&base.client).await?;
+        assert!(pipeline.is_none());
+        Ok::<(), Error>(())
+    }).unwrap();
+}
+
 #[test]
 fn basic_pipelining() {
     rpc_top_level(|_spawner, client| async move {
@@ -782,6 +928,66 @@ fn local_client_call_not_immediate() {
     assert_eq!(call_count.get(), 1);
 }
 
+#[test]
+fn async_server() {
+    let server = crate::impls::AsyncTestExtends::default();
+    let call_count = server.get_call_count();
+    let client: crate::test_capnp::test_extends::Client =
+        capnp_rpc::new_client(capnp::capability::FromAsync::new(server));
+    let base: crate::test_capnp::test_interface::Client =
+        capnp::capability::FromClientHook::new(client.client.hook.add_ref());
+
+    let mut request = base.foo_request();
+    request.get().set_i(321);
+    let response = futures::executor::block_on(request.send().promise).unwrap();
+    assert_eq!(response.get().unwrap().get_x().unwrap(), "bar");
+
+    let mut request = base.foo_request();
+    request.get().set_i(123);
+    let error = futures::executor::block_on(request.send().promise).err().unwrap();
+    assert!(error.description.contains("expected i to equal 321"), "{}", error.description);
+
+    // Methods that are not implemented fail.
+    let error = futures::executor::block_on(base.bar_request().send().promise).err().unwrap();
+    assert_eq!(error.kind, capnp::ErrorKind::Unimplemented);
+
+    let response = futures::executor::block_on(client.grault_request().send().promise).unwrap();
+    crate::test_util::CheckTestMessage::check_test_message(response.get().unwrap());
+    assert_eq!(call_count.get(), 2);
+}
+
+#[test]
+fn generic_interface() {
+    use crate::test_capnp::test_generic_store;
+    let client: test_generic_store::Client<capnp::text::Owned> =
+        capnp_rpc::new_client(crate::impls::TestTextStore::default());
+
+    let mut request = client.put_request();
+    request.get().set_key("greeting");
+    request.get().set_value("hello").unwrap();
+    futures::executor::block_on(request.send().promise).unwrap();
+
+    let mut request = client.get_request();
+    request.get().set_key("greeting");
+    let response = futures::executor::block_on(request.send().promise).unwrap();
+    assert_eq!(response.get().unwrap().get_value().unwrap(), "hello");
+
+    // The caller picks the type of `getAs`'s implicit parameter.
+    let mut request = client.get_as_request::<capnp::text::Owned>();
+    request.get().set_key("greeting");
+    let response = futures::executor::block_on(request.send().promise).unwrap();
+    assert_eq!(response.get().unwrap().get_value().unwrap(), "hello");
+
+    let mut request = client.get_as_request::<capnp::data::Owned>();
+    request.get().set_key("greeting");
+    let response = futures::executor::block_on(request.send().promise).unwrap();
+    assert!(response.get().unwrap().get_value().unwrap().starts_with(b"hello"));
+
+    let mut request = client.get_as_request::<capnp::text::Owned>();
+    request.get().set_key("farewell");
+    assert!(futures::executor::block_on(request.send().promise).is_err());
+}
+
 #[test]
 fn local_client_send_cap() {
     let server1 = crate::impls::TestMoreStuff::new();
@@ -838,3 +1044,283 @@ fn capability_list() {
     })
 }
 
+
+fn test_network_vat(network: &test_network::Network, name: &str) -> RpcSystem<test_network::VatId> {
+    let bootstrap: test_capnp::bootstrap::Client = capnp_rpc::new_client(impls::Bootstrap);
+    RpcSystem::new(Box::new(network.add_vat(name)), Some(bootstrap.client))
+}
+
+#[test]
+fn test_network_three_vats() {
+    let mut pool = futures::executor::LocalPool::new();
+    let mut spawner = pool.spawner();
+    let network = test_network::Network::new();
+    let alice = test_network_vat(&network, "alice");
+    let mut bob = test_network_vat(&network, "bob");
+    let carol = test_network_vat(&network, "carol");
+
+    let alice_bootstrap: test_capnp::bootstrap::Client = bob.bootstrap("alice".to_string());
+    let carol_bootstrap: test_capnp::bootstrap::Client = bob.bootstrap("carol".to_string());
+
+    spawn(&mut spawner, alice);
+    spawn(&mut spawner, bob);
+    spawn(&mut spawner, carol);
+
+    pool.run_until(async move {
+        let response = alice_bootstrap.test_interface_request().send().promise.await?;
+        let alice_cap = response.get()?.get_cap()?;
+
+        // Bob passes a capability hosted by Alice to Carol, who calls it.
+        let response = carol_bootstrap.test_more_stuff_request().send().promise.await?;
+        let carol_stuff = response.get()?.get_cap()?;
+        let mut request = carol_stuff.call_foo_request();
+        request.get().set_cap(alice_cap);
+        let response = request.send().promise.await?;
+        assert_eq!(response.get()?.get_s()?, "bar");
+        Ok::<(), Error>(())
+    }).unwrap();
+}
+
+#[test]
+fn test_network_forced_disconnect() {
+    let mut pool = futures::executor::LocalPool::new();
+    let mut spawner = pool.spawner();
+    let network = test_network::Network::new();
+    let alice = test_network_vat(&network, "alice");
+    let mut bob = test_network_vat(&network, "bob");
+
+    let client: test_capnp::bootstrap::Client = bob.bootstrap("alice".to_string());
+    let nobody: test_capnp::bootstrap::Client = bob.bootstrap("nobody".to_string());
+
+    spawn(&mut spawner, alice);
+    spawn(&mut spawner, bob);
+
+    pool.run_until(async move {
+        client.test_interface_request().send().promise.await.unwrap();
+
+        network.disconnect("alice", "bob");
+
+        match client.test_interface_request().send().promise.await {
+            Err(ref e) if e.kind == ::capnp::ErrorKind::Disconnected => (),
+            _ => panic!("Should have gotten a 'disconnected' error."),
+        }
+
+        assert!(nobody.test_interface_request().send().promise.await.is_err());
+    });
+}
+
+fn reconnect_setup(spawner: &futures::executor::LocalSpawner)
+    -> (Rc<Cell<u32>>, Rc<RefCell<Option<capnp_rpc::Disconnector<rpc_twoparty_capnp::Side>>>>,
+        impl FnMut() -> futures::future::Ready<Result<capnp::capability::Client, Error>>)
+{
+    let connect_count = Rc::new(Cell::new(0));
+    let disconnector = Rc::new(RefCell::new(None));
+    let (connect_count1, disconnector1, mut spawner) = (connect_count.clone(), disconnector.clone(), spawner.clone());
+    let connect = move || {
+        connect_count1.set(connect_count1.get() + 1);
+        let (mut client_rpc_system, server_rpc_system) = disconnector_setup();
+        let client: test_capnp::bootstrap::Client = client_rpc_system.bootstrap(rpc_twoparty_capnp::Side::Server);
+        *disconnector1.borrow_mut() = Some(client_rpc_system.get_disconnector());
+        spawn(&mut spawner, client_rpc_system);
+        spawn(&mut spawner, server_rpc_system);
+        futures::future::ready(Ok(client.client))
+    };
+    (connect_count, disconnector, connect)
+}
+
+#[test]
+fn auto_reconnect() {
+    let mut pool = futures::executor::LocalPool::new();
+    let (connect_count, disconnector, connect) = reconnect_setup(&pool.spawner());
+    let client: test_capnp::bootstrap::Client = capnp_rpc::auto_reconnect(connect);
+    assert_eq!(connect_count.get(), 1);
+
+    pool.run_until(async move {
+        client.test_interface_request().send().promise.await.unwrap();
+
+        let disconnector = disconnector.borrow_mut().take().unwrap();
+        disconnector.await.unwrap();
+
+        // The first call after the disconnect fails...
+        match client.test_interface_request().send().promise.await {
+            Err(ref e) if e.kind == ::capnp::ErrorKind::Disconnected => (),
+            _ => panic!("Should have gotten a 'disconnected' error."),
+        }
+
+        // ...and the next one goes out over a new connection.
+        client.test_interface_request().send().promise.await.unwrap();
+        assert_eq!(connect_count.get(), 2);
+    });
+}
+
+#[test]
+fn auto_reconnect_with_retry() {
+    let mut pool = futures::executor::LocalPool::new();
+    let (connect_count, disconnector, connect) = reconnect_setup(&pool.spawner());
+    let client: test_capnp::bootstrap::Client =
+        capnp_rpc::auto_reconnect_with_retry(connect, |_interface_id, _method_id| true);
+
+    pool.run_until(async move {
+        client.test_interface_request().send().promise.await.unwrap();
+
+        let disconnector = disconnector.borrow_mut().take().unwrap();
+        disconnector.await.unwrap();
+
+        client.test_interface_request().send().promise.await.unwrap();
+        assert_eq!(connect_count.get(), 2);
+    });
+}
+
+#[cfg(unix)]
+mod polling_unix_stream {
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use capnp_futures::fds::{AsyncFdRead, AsyncFdWrite, recv_with_fds, send_with_fds};
+    use futures::{AsyncRead, AsyncWrite};
+
+    /// A nonblocking Unix socket that busy-polls instead of registering with a reactor.
+    pub struct PollingUnixStream(pub std::os::unix::net::UnixStream);
+
+    fn ready<T>(cx: &mut Context<'_>, result: io::Result<T>) -> Poll<io::Result<T>> {
+        match result {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            r => Poll::Ready(r),
+        }
+    }
+
+    impl AsyncRead for PollingUnixStream {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            let result = self.get_mut().0.read(buf);
+            ready(cx, result)
+        }
+    }
+
+    impl AsyncFdRead for PollingUnixStream {
+        fn poll_read_with_fds(self: Pin<&mut Self>, cx: &mut Context<'_>,
+                              buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> Poll<io::Result<usize>> {
+            ready(cx, recv_with_fds(self.0.as_raw_fd(), buf, fds))
+        }
+    }
+
+    impl AsyncWrite for PollingUnixStream {
+        fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            let result = self.get_mut().0.write(buf);
+            ready(cx, result)
+        }
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncFdWrite for PollingUnixStream {
+        fn poll_write_with_fds(self: Pin<&mut Self>, cx: &mut Context<'_>,
+                               buf: &[u8], fds: &[RawFd]) -> Poll<io::Result<usize>> {
+            ready(cx, send_with_fds(self.0.as_raw_fd(), buf, fds))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn unix_vat_network(socket: std::os::unix::net::UnixStream, side: rpc_twoparty_capnp::Side)
+                    -> twoparty::VatNetwork<polling_unix_stream::PollingUnixStream>
+{
+    use polling_unix_stream::PollingUnixStream;
+
+    socket.set_nonblocking(true).unwrap();
+    let output = socket.try_clone().unwrap();
+    twoparty::VatNetwork::new_with_fds(PollingUnixStream(socket), PollingUnixStream(output),
+                                       side, Default::default())
+}
+
+#[cfg(unix)]
+#[test]
+fn pass_fd_with_capability() {
+    use std::io::{Read, Write};
+    use std::os::unix::io::{BorrowedFd, OwnedFd};
+    use std::os::unix::net::UnixStream;
+
+    let mut pool = futures::executor::LocalPool::new();
+    let mut spawner = pool.spawner();
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+
+    // The server hands out one end of this pair attached to a capability.
+    let (passed, mut kept) = UnixStream::pair().unwrap();
+    let bootstrap: test_capnp::bootstrap::Client =
+        capnp_rpc::new_client(impls::FdBootstrap::new(OwnedFd::from(passed)));
+
+    let mut client_rpc_system =
+        RpcSystem::new(Box::new(unix_vat_network(client_socket, rpc_twoparty_capnp::Side::Client)), None);
+    let server_rpc_system =
+        RpcSystem::new(Box::new(unix_vat_network(server_socket, rpc_twoparty_capnp::Side::Server)),
+                       Some(bootstrap.client));
+
+    let client: test_capnp::bootstrap::Client = client_rpc_system.bootstrap(rpc_twoparty_capnp::Side::Server);
+
+    spawn(&mut spawner, client_rpc_system);
+    spawn(&mut spawner, server_rpc_system);
+
+    pool.run_until(async move {
+        let response = client.test_interface_request().send().promise.await?;
+        let cap = response.get()?.get_cap()?;
+        let fd = cap.client.get_fd().expect("fd should have been passed along");
+
+        // The received descriptor refers to the same socket as the one the server handed out.
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+        UnixStream::from(fd).write_all(b"hi")?;
+        let mut buf = [0; 2];
+        kept.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"hi");
+        Ok::<(), Error>(())
+    }).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn outgoing_fds_outlive_their_capability() {
+    use capnp_rpc::VatNetwork;
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+
+    let mut pool = futures::executor::LocalPool::new();
+    let mut spawner = pool.spawner();
+    let (client_socket, server_socket) = UnixStream::pair().unwrap();
+    let mut network = unix_vat_network(client_socket, rpc_twoparty_capnp::Side::Client);
+    let mut connection = network.connect(rpc_twoparty_capnp::Side::Server).unwrap();
+    spawn(&mut spawner, network.drive_until_shutdown());
+
+    let (passed, mut kept) = UnixStream::pair().unwrap();
+    let mut message = connection.new_outgoing_message(8);
+    message.get_body().unwrap().set_as("hello").unwrap();
+    message.set_fds(vec![passed.as_raw_fd()]);
+
+    // Whatever owned the descriptor goes away before the message is written, and its number
+    // gets reused.
+    drop(passed);
+    let (_reused, _other) = UnixStream::pair().unwrap();
+
+    let (promise, _) = message.send();
+    pool.run_until(async move {
+        promise.await?;
+        let mut input = polling_unix_stream::PollingUnixStream(server_socket);
+        let (message, fds) =
+            capnp_futures::fds::try_read_message_with_fds(&mut input, Default::default()).await?.unwrap();
+        assert_eq!(message.get_root::<capnp::text::Reader>()?, "hello");
+        assert_eq!(fds.len(), 1);
+
+        UnixStream::from(fds.into_iter().next().unwrap()).write_all(b"hi")?;
+        let mut buf = [0; 2];
+        kept.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"hi");
+        Ok::<(), Error>(())
+    }).unwrap();
+}
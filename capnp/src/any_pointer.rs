@@ -62,6 +62,11 @@ impl <'a> Reader<'a> {
         self.reader.total_size()
     }
 
+    /// Like `target_size()`, but fails if the target is nested more than `nesting_limit` levels deep.
+    pub fn target_size_with_nesting_limit(&self, nesting_limit: i32) -> Result<crate::MessageSize> {
+        self.reader.total_size_with_nesting_limit(nesting_limit)
+    }
+
     #[inline]
     pub fn get_as<T: FromPointerReader<'a>>(&self) -> Result<T> {
         FromPointerReader::get_from_pointer(&self.reader, None)
@@ -24,6 +24,7 @@
 //! Roughly corresponds to capability.h in the C++ implementation.
 
 use alloc::boxed::Box;
+use alloc::rc::Rc;
 use core::future::{Future};
 use core::pin::{Pin};
 use core::marker::{PhantomData, Unpin};
@@ -35,6 +36,17 @@ use crate::{any_pointer, Error, MessageSize};
 use crate::traits::{Pipelined, Owned};
 use crate::private::capability::{ClientHook, ParamsHook, RequestHook, ResponseHook, ResultsHook};
 
+/// The method ordinal with which `capnp_rpc::implements_interface()` asks a capability whether it
+/// implements an interface. Method ordinals are assigned consecutively from zero, so no interface
+/// can declare it. Generated servers answer calls of it successfully on the interfaces that they
+/// implement.
+pub const PROBE_METHOD_ID: u16 = 0xffff;
+
+/// The description of the `Unimplemented` error with which generated servers fail calls of
+/// interfaces that they do not implement. `capnp_rpc::implements_interface()` looks for it, so
+/// changing it breaks probing servers generated before the change.
+pub const INTERFACE_NOT_IMPLEMENTED: &str = "Requested interface not implemented.";
+
 /// A computation that might eventually resolve to a value of type `T` or to an error
 ///  of type `E`. Dropping the promise cancels the computation.
 #[must_use = "futures do nothing unless polled"]
@@ -187,6 +199,15 @@ impl <T> Params <T> {
     {
         Ok(self.hook.get()?.get_as()?)
     }
+
+    /// Reinterprets the parameters as having type `U`. This is how a server picks the types of
+    /// a method's implicit parameters, which callers choose and which the server therefore
+    /// receives as `any_pointer::Owned`.
+    pub fn cast<U>(self) -> Params<U>
+        where U: for<'a> Owned<'a>
+    {
+        Params { marker: PhantomData, hook: self.hook }
+    }
 }
 
 /// The return values of a method, written in-place by the method body.
@@ -210,6 +231,13 @@ impl <T> Results<T>
     {
         self.hook.get().unwrap().set_as(other)
     }
+
+    /// Reinterprets the results as having type `U`. See [`Params::cast`].
+    pub fn cast<U>(self) -> Results<U>
+        where U: for<'a> Owned<'a>
+    {
+        Results { marker: PhantomData, hook: self.hook }
+    }
 }
 
 pub trait FromTypelessPipeline {
@@ -247,6 +275,16 @@ impl Client {
     pub fn when_resolved(&self) -> Promise<(), Error> {
         self.hook.when_resolved()
     }
+
+    /// If the capability has a file descriptor attached to it, returns that descriptor. The
+    /// descriptor remains owned by the capability and is only valid as long as the capability
+    /// is alive; duplicate it if you need to keep it longer.
+    ///
+    /// File descriptors are only passed along with capabilities over transports that support
+    /// it, such as Unix domain sockets.
+    pub fn get_fd(&self) -> Option<i32> {
+        self.hook.get_fd()
+    }
 }
 
 /// An untyped server.
@@ -255,6 +293,58 @@ pub trait Server {
                      params: Params<any_pointer::Owned>,
                      results: Results<any_pointer::Owned>)
                      -> Promise<(), Error>;
+
+    /// If this server represents a Unix file descriptor, returns that descriptor, so that it
+    /// can be attached to the capability when it is sent over a transport that supports
+    /// file descriptor passing. The descriptor must stay open for as long as the server exists.
+    fn get_fd(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// The future returned by a method of a generated `AsyncServer` trait. Unlike a `Promise`, it
+/// may borrow the server.
+pub type AsyncResult<'a> = Pin<Box<dyn Future<Output=Result<(), Error>> + 'a>>;
+
+/// Wraps an implementation of a generated `AsyncServer` trait, so that it implements the
+/// corresponding `Server` trait and can be turned into a client.
+///
+/// ```ignore
+/// impl foo::AsyncServer for FooImpl {
+///     fn bar(&self, params: foo::BarParams, mut results: foo::BarResults) -> AsyncResult<'_> {
+///         Box::pin(async move {
+///             let x = params.get()?.get_x();
+///             results.get().set_y(self.compute(x).await?);
+///             Ok(())
+///         })
+///     }
+/// }
+///
+/// let client: foo::Client = capnp_rpc::new_client(FromAsync::new(FooImpl::new()));
+/// ```
+pub struct FromAsync<T> {
+    server: Rc<T>,
+}
+
+impl <T> FromAsync<T> {
+    pub fn new(server: T) -> FromAsync<T> {
+        FromAsync { server: Rc::new(server) }
+    }
+
+    /// Returns the wrapped server.
+    pub fn get_ref(&self) -> &T {
+        &self.server
+    }
+
+    /// Calls `method` on the wrapped server and turns the future that it returns into a
+    /// `Promise` that keeps the server alive until it completes. Used by generated code.
+    pub fn call<F>(&self, method: F) -> Promise<(), Error>
+        where T: 'static,
+              F: for<'a> FnOnce(&'a T) -> AsyncResult<'a> + 'static
+    {
+        let server = self.server.clone();
+        Promise::from_future(async move { method(&server).await })
+    }
 }
 
 /// Trait to track the relationship between generated Server traits and Client structs.
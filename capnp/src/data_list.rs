@@ -25,6 +25,8 @@ use crate::traits::{FromPointerReader, FromPointerBuilder, IndexMove, ListIter};
 use crate::private::layout::*;
 use crate::Result;
 
+use alloc::vec::Vec;
+
 #[derive(Copy, Clone)]
 pub struct Owned;
 
@@ -141,3 +143,43 @@ impl <'a> ::core::iter::IntoIterator for Reader<'a> {
         self.iter()
     }
 }
+
+fn set_from_slice<D>(pointer: PointerBuilder, values: &[D]) -> Result<()> where D: AsRef<[u8]> {
+    let mut builder = Builder::init_pointer(pointer, list_element_count(values.len())?);
+    for (index, value) in values.iter().enumerate() {
+        builder.set(index as u32, value.as_ref());
+    }
+    Ok(())
+}
+
+impl <'a, 'b> crate::traits::SetPointerBuilder for &'a [&'b [u8]] {
+    fn set_pointer_builder<'c>(pointer: PointerBuilder<'c>,
+                               value: &'a [&'b [u8]],
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, value)
+    }
+}
+
+impl <'a> crate::traits::SetPointerBuilder for &'a [Vec<u8>] {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: &'a [Vec<u8>],
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, value)
+    }
+}
+
+impl <'a> crate::traits::SetPointerBuilder for Vec<&'a [u8]> {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: Vec<&'a [u8]>,
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, &value[..])
+    }
+}
+
+impl crate::traits::SetPointerBuilder for Vec<Vec<u8>> {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: Vec<Vec<u8>>,
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, &value[..])
+    }
+}
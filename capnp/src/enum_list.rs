@@ -24,9 +24,10 @@
 use crate::traits::{FromPointerReader, FromPointerBuilder,
                     ToU16, FromU16, ListIter, IndexMove};
 use crate::private::layout::{ListReader, ListBuilder, PointerReader, PointerBuilder,
-                             TwoBytes, PrimitiveElement};
+                             TwoBytes, PrimitiveElement, list_element_count};
 use crate::{NotInSchema, Result};
 
+use alloc::vec::Vec;
 use core::marker::PhantomData;
 
 #[derive(Clone, Copy)]
@@ -75,7 +76,7 @@ impl <'a, T : FromU16> Reader<'a, T> {
     }
 }
 
-impl <'a, T> crate::traits::IntoInternalListReader<'a> for Reader<'a, T> where T: PrimitiveElement {
+impl <'a, T> crate::traits::IntoInternalListReader<'a> for Reader<'a, T> where T: FromU16 {
     fn into_internal_list_reader(self) -> ListReader<'a> {
         self.reader
     }
@@ -138,3 +139,23 @@ impl <'a, T: FromU16> ::core::iter::IntoIterator for Reader<'a, T> {
         self.iter()
     }
 }
+
+impl <'a, T> crate::traits::SetPointerBuilder for &'a [T] where T: ToU16 + Copy {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: &'a [T],
+                               _canonicalize: bool) -> Result<()> {
+        let builder = pointer.init_list(TwoBytes, list_element_count(value.len())?);
+        for (index, &element) in value.iter().enumerate() {
+            PrimitiveElement::set(&builder, index as u32, element.to_u16());
+        }
+        Ok(())
+    }
+}
+
+impl <T> crate::traits::SetPointerBuilder for Vec<T> where T: ToU16 + Copy {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: Vec<T>,
+                               canonicalize: bool) -> Result<()> {
+        crate::traits::SetPointerBuilder::set_pointer_builder(pointer, &value[..], canonicalize)
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (c) 2013-2017 Sandstorm Development Group, Inc. and contributors
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Structural equality and hashing of message contents.
+//!
+//! The functions here work without reference to a schema. Two values compare equal if they hold
+//! the same data: data that is absent because a struct was written with an older, smaller
+//! version of its schema compares equal to zeroes, absent pointers compare equal to null ones,
+//! and layout details, such as segment boundaries, far pointers and whether a list is encoded
+//! as a primitive list or as a list of structs, are ignored. A null pointer does not compare
+//! equal to a pointer to an empty value, because only the schema knows which default the null
+//! pointer stands for. Capabilities compare equal if they are the same capability.
+//!
+//! With capnpc's `reader_equality` option, generated struct readers implement `PartialEq` and
+//! `Hash` by comparing the values that their getters return, so that they also know the schema:
+//! values are equal if readers of them would see the same field values. A null pointer field
+//! equals one that points to the field's default value, and only the active member of a union is
+//! compared. Floating-point fields are compared by their bits. Fields without a schema, of
+//! `AnyPointer` and interface types, are compared with the functions here.
+//!
+//! Values that compare equal have equal hashes. A value that fails to decode compares unequal
+//! to everything, including itself, which is why readers do not implement `Eq`.
+
+use core::hash::{Hash, Hasher};
+use core::ptr;
+
+use crate::private::layout::{ElementSize, ListReader, PointerReader, PointerType, StructReader};
+use crate::Result;
+
+/// Returns whether `a` and `b` hold the same values.
+pub fn struct_eq(a: StructReader, b: StructReader) -> Result<bool> {
+    if !data_eq(a.get_data_section_as_blob(), b.get_data_section_as_blob()) {
+        return Ok(false);
+    }
+    let pointer_count = core::cmp::max(a.get_pointer_section_size(), b.get_pointer_section_size());
+    for index in 0..pointer_count as usize {
+        if !pointer_eq(a.get_pointer_field(index), b.get_pointer_field(index))? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Returns whether the values pointed to by `a` and `b` are the same.
+pub fn pointer_eq(a: PointerReader, b: PointerReader) -> Result<bool> {
+    match (a.get_pointer_type()?, b.get_pointer_type()?) {
+        (PointerType::Null, PointerType::Null) => Ok(true),
+        (PointerType::Struct, PointerType::Struct) =>
+            struct_eq(a.get_struct(None)?, b.get_struct(None)?),
+        (PointerType::List, PointerType::List) =>
+            list_eq(a.get_list_any_size(ptr::null())?, b.get_list_any_size(ptr::null())?),
+        (PointerType::Capability, PointerType::Capability) =>
+            Ok(a.get_capability()?.get_ptr() == b.get_capability()?.get_ptr()),
+        _ => Ok(false),
+    }
+}
+
+/// Feeds the values held by `reader` into `state`, consistently with `struct_eq()`.
+pub fn struct_hash<H: Hasher>(reader: StructReader, state: &mut H) -> Result<()> {
+    let data = reader.get_data_section_as_blob();
+    let data_len = data.iter().rposition(|&byte| byte != 0).map_or(0, |index| index + 1);
+    data[..data_len].hash(state);
+
+    let mut pointer_count = reader.get_pointer_section_size() as usize;
+    while pointer_count > 0 && reader.get_pointer_field(pointer_count - 1).is_null() {
+        pointer_count -= 1;
+    }
+    state.write_usize(pointer_count);
+    for index in 0..pointer_count {
+        pointer_hash(reader.get_pointer_field(index), state)?;
+    }
+    Ok(())
+}
+
+/// Feeds the value pointed to by `reader` into `state`, consistently with `pointer_eq()`.
+pub fn pointer_hash<H: Hasher>(reader: PointerReader, state: &mut H) -> Result<()> {
+    match reader.get_pointer_type()? {
+        PointerType::Null => state.write_u8(0),
+        PointerType::Struct => {
+            state.write_u8(1);
+            struct_hash(reader.get_struct(None)?, state)?;
+        }
+        PointerType::List => {
+            state.write_u8(2);
+            list_hash(reader.get_list_any_size(ptr::null())?, state)?;
+        }
+        PointerType::Capability => {
+            state.write_u8(3);
+            state.write_usize(reader.get_capability()?.get_ptr());
+        }
+    }
+    Ok(())
+}
+
+/// Compares data sections, treating the bytes past the end of the shorter one as zeroes.
+fn data_eq(a: &[u8], b: &[u8]) -> bool {
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let (common, rest) = longer.split_at(shorter.len());
+    common == shorter && rest.iter().all(|&byte| byte == 0)
+}
+
+fn bit(bytes: &[u8], index: usize) -> bool {
+    bytes[index / 8] & (1 << (index % 8)) != 0
+}
+
+fn is_primitive(element_size: ElementSize) -> bool {
+    matches!(element_size,
+             ElementSize::Void | ElementSize::Byte | ElementSize::TwoBytes |
+             ElementSize::FourBytes | ElementSize::EightBytes)
+}
+
+/// Returns whether the lists `a` and `b` hold the same values.
+pub fn list_eq(a: ListReader, b: ListReader) -> Result<bool> {
+    let len = a.len();
+    if len != b.len() {
+        return Ok(false);
+    }
+    if len == 0 {
+        return Ok(true);
+    }
+    match (a.get_element_size(), b.get_element_size()) {
+        (ElementSize::Bit, ElementSize::Bit) => {
+            let (a_bytes, b_bytes) = (a.into_raw_bytes(), b.into_raw_bytes());
+            Ok((0..len as usize).all(|index| bit(a_bytes, index) == bit(b_bytes, index)))
+        }
+        // Unlike other primitive lists, bit lists cannot be read as lists of structs.
+        (ElementSize::Bit, _) | (_, ElementSize::Bit) => Ok(false),
+        (a_size, b_size) if a_size == b_size && is_primitive(a_size) =>
+            Ok(a.into_raw_bytes() == b.into_raw_bytes()),
+        _ => {
+            // Compare element by element, reading each list as a list of structs.
+            for index in 0..len {
+                if !struct_eq(a.get_struct_element(index), b.get_struct_element(index))? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+/// Feeds the values held by `list` into `state`, consistently with `list_eq()`.
+pub fn list_hash<H: Hasher>(list: ListReader, state: &mut H) -> Result<()> {
+    let len = list.len();
+    state.write_u32(len);
+    if list.get_element_size() == ElementSize::Bit {
+        let bytes = list.into_raw_bytes();
+        for index in 0..len as usize {
+            bit(bytes, index).hash(state);
+        }
+    } else {
+        for index in 0..len {
+            struct_hash(list.get_struct_element(index), state)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether `a` and `b` yield the same number of elements and `eq` holds for each pair
+/// of them.
+pub fn iter_eq<A, B>(mut a: A, mut b: B, mut eq: impl FnMut(A::Item, B::Item) -> bool) -> bool
+    where A: Iterator, B: Iterator
+{
+    loop {
+        match (a.next(), b.next()) {
+            (Some(a), Some(b)) => if !eq(a, b) { return false },
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Feeds the elements of `iter` into `state` with `hash`, consistently with `iter_eq()`.
+pub fn iter_hash<I, H>(iter: I, state: &mut H, mut hash: impl FnMut(I::Item, &mut H))
+    where I: Iterator, H: Hasher
+{
+    let mut len = 0usize;
+    for element in iter {
+        hash(element, state);
+        len += 1;
+    }
+    state.write_usize(len);
+}
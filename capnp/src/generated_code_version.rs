@@ -0,0 +1,49 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Versions of the interface between this crate and the code that `capnpc` generates.
+//!
+//! Whenever generated code starts relying on something new in this crate, `capnpc` bumps the
+//! version of the code it generates, and this crate gains a marker constant for that version.
+//! Each generated file begins with a check like
+//!
+//! ```ignore
+//! const _: () = ::capnp::generated_code_version::V1;
+//! ```
+//!
+//! so that compiling generated code against a runtime that does not support it fails right
+//! there, with an error naming the missing version, instead of with errors deep inside the
+//! generated code. The fix is to use matching versions of `capnp` and `capnpc`.
+
+/// The oldest version of generated code that this crate supports.
+pub const OLDEST: u32 = 1;
+
+/// The newest version of generated code that this crate supports.
+pub const LATEST: u32 = 1;
+
+/// Returns whether this crate supports generated code of version `version`.
+pub fn supports(version: u32) -> bool {
+    OLDEST <= version && version <= LATEST
+}
+
+/// Marks support for version 1 of generated code, the first version to carry a version check.
+pub const V1: () = ();
@@ -40,12 +40,17 @@ pub mod constant;
 pub mod data;
 pub mod data_list;
 pub mod enum_list;
+pub mod equality;
+pub mod generated_code_version;
 pub mod io;
 pub mod list_list;
 pub mod message;
 pub mod primitive_list;
 pub mod private;
+pub mod random;
 pub mod raw;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod serialize;
 pub mod serialize_packed;
 pub mod struct_list;
@@ -21,11 +21,12 @@
 
 //! List of primitives.
 
+use alloc::vec::Vec;
 use core::{marker};
 
 use crate::traits::{FromPointerReader, FromPointerBuilder, IndexMove, ListIter};
 use crate::private::layout::{ListReader, ListBuilder, PointerReader, PointerBuilder,
-                             PrimitiveElement, data_bits_per_element};
+                             PrimitiveElement, data_bits_per_element, list_element_count};
 use crate::Result;
 
 #[derive(Clone, Copy)]
@@ -168,3 +169,46 @@ impl <'a, T> ::core::iter::IntoIterator for Reader<'a, T>
         self.iter()
     }
 }
+
+fn set_from_slice<T>(pointer: PointerBuilder, values: &[T]) -> Result<()>
+    where T: PrimitiveElement + Copy
+{
+    let mut builder = Builder::<T>::init_pointer(pointer, list_element_count(values.len())?);
+    for (index, &value) in values.iter().enumerate() {
+        builder.set(index as u32, value);
+    }
+    Ok(())
+}
+
+// Slices and vectors of primitives can be set wherever a `Reader` can. A `&[u8]` is already
+// set as a `data::Reader`, which has the same encoding as a list of `u8`.
+macro_rules! primitive_slice_impls {
+    ($($t:ty),*) => {
+        $(
+            impl <'a> crate::traits::SetPointerBuilder for &'a [$t] {
+                fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                                           value: &'a [$t],
+                                           _canonicalize: bool) -> Result<()> {
+                    set_from_slice(pointer, value)
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! primitive_vec_impls {
+    ($($t:ty),*) => {
+        $(
+            impl crate::traits::SetPointerBuilder for Vec<$t> {
+                fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                                           value: Vec<$t>,
+                                           _canonicalize: bool) -> Result<()> {
+                    set_from_slice(pointer, &value[..])
+                }
+            }
+        )*
+    }
+}
+
+primitive_slice_impls!((), bool, i8, i16, i32, i64, u16, u32, u64, f32, f64);
+primitive_vec_impls!((), bool, i8, i16, i32, i64, u8, u16, u32, u64, f32, f64);
@@ -68,6 +68,12 @@ pub trait ClientHook {
 
     /// Repeatedly calls whenMoreResolved() until it returns nullptr.
     fn when_resolved(&self) -> Promise<(), crate::Error>;
+
+    /// Returns the file descriptor attached to this capability, if any. The descriptor is
+    /// owned by the capability.
+    fn get_fd(&self) -> Option<i32> {
+        None
+    }
 }
 
 impl Clone for Box<dyn ClientHook> {
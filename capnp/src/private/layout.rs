@@ -2482,6 +2482,11 @@ impl <'a> PointerReader<'a> {
         }
     }
 
+    /// Like `total_size()`, but fails if the target is nested more than `nesting_limit` levels deep.
+    pub fn total_size_with_nesting_limit(&self, nesting_limit: i32) -> Result<MessageSize> {
+        PointerReader { nesting_limit: ::core::cmp::min(self.nesting_limit, nesting_limit), .. *self }.total_size()
+    }
+
     pub fn get_struct(self, default: Option<&'a [crate::Word]>) -> Result<StructReader<'a>> {
         let reff: *const WirePointer = if self.pointer.is_null() { zero_pointer() } else { self.pointer };
         unsafe {
@@ -2506,7 +2511,7 @@ impl <'a> PointerReader<'a> {
         }
     }
 
-    fn get_list_any_size(self, default_value: *const u8) -> Result<ListReader<'a>> {
+    pub(crate) fn get_list_any_size(self, default_value: *const u8) -> Result<ListReader<'a>> {
         let reff = if self.pointer.is_null() { zero_pointer() } else { self.pointer };
         unsafe {
             wire_helpers::read_list_pointer(
@@ -3440,6 +3445,15 @@ impl <'a> ListBuilder<'a> {
 }
 
 
+/// Converts the length of a slice to the element count of a list holding its values.
+pub fn list_element_count(len: usize) -> Result<ElementCount32> {
+    if len >= 1 << 29 {
+        Err(crate::Error::failed(format!("Lists are limited to 2**29 elements, got {}", len)))
+    } else {
+        Ok(len as ElementCount32)
+    }
+}
+
 pub trait PrimitiveElement {
     fn get(list_reader: &ListReader, index: ElementCount32) -> Self;
     fn get_from_builder(list_builder: &ListBuilder, index: ElementCount32) -> Self;
@@ -32,6 +32,13 @@ pub mod units;
 mod read_limiter;
 mod zero;
 
+/// Heap-allocated types used by generated code, which cannot assume that `std` is available.
+pub mod alloc {
+    pub use ::alloc::boxed::Box;
+    pub use ::alloc::string::String;
+    pub use ::alloc::vec::Vec;
+}
+
 #[cfg(test)]
 mod layout_test;
 
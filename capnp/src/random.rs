@@ -0,0 +1,224 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Generation of random messages, for property-based testing and fuzzing.
+//!
+//! When `capnpc` is asked to generate random generators, the `Owned` type of every generated
+//! struct implements [`FillRandom`], which fills in a builder with random field values: a random
+//! variant of each union, lists of at most [`Generator::max_list_len`] elements, and nested
+//! structs and lists at most [`Generator::max_depth`] pointers deep. Capabilities and
+//! `AnyPointer` fields are left null.
+//!
+//! [`RandomMessage`] holds a random message for such a struct type. With the `quickcheck`
+//! feature it implements `quickcheck::Arbitrary`, and with the `arbitrary` feature it implements
+//! `arbitrary::Arbitrary`, for use with cargo-fuzz:
+//!
+//! ```ignore
+//! quickcheck::quickcheck(|m: capnp::random::RandomMessage<foo::Owned>| {
+//!     let foo = m.get().unwrap();
+//!     ...
+//! });
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::any_pointer;
+use crate::message;
+use crate::private::layout::PointerReader;
+use crate::traits::Owned;
+use crate::{Result, Word};
+
+/// A source of random choices, together with the bounds that generated values respect.
+pub struct Generator<'a> {
+    next: &'a mut dyn FnMut() -> u64,
+    depth: u32,
+
+    /// The maximum number of elements of a list, and of bytes of a text or data value.
+    pub max_list_len: u32,
+
+    /// The maximum number of pointers between the root and a generated value. Deeper structs are
+    /// left null, and deeper lists are empty.
+    pub max_depth: u32,
+}
+
+impl <'a> Generator<'a> {
+    /// Creates a generator that takes its random bits from `next`, with default bounds.
+    pub fn new(next: &'a mut dyn FnMut() -> u64) -> Generator<'a> {
+        Generator { next, depth: 0, max_list_len: 4, max_depth: 4 }
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        (self.next)()
+    }
+
+    /// Returns a random integer less than `bound`, which must be nonzero.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        (self.next_u64() % bound as u64) as u32
+    }
+
+    /// Returns a random value of a primitive type.
+    pub fn primitive<T: Primitive>(&mut self) -> T {
+        T::from_bits(self.next_u64())
+    }
+
+    /// Returns the length of a new list: zero once `max_depth` is reached.
+    pub fn list_len(&mut self) -> u32 {
+        if self.depth >= self.max_depth { 0 } else { self.below(self.max_list_len + 1) }
+    }
+
+    /// Returns whether to fill in an optional struct field, which is never the case once
+    /// `max_depth` is reached.
+    pub fn present(&mut self) -> bool {
+        self.depth < self.max_depth && self.below(4) != 0
+    }
+
+    /// Returns random text, of at most `max_list_len` bytes and without NUL characters.
+    pub fn text(&mut self) -> String {
+        let mut result = String::new();
+        let len = self.below(self.max_list_len + 1) as usize;
+        while result.len() < len {
+            let c = if self.below(4) == 0 {
+                core::char::from_u32(0x80 + self.below(0xd800 - 0x80)).unwrap_or('?')
+            } else {
+                (0x20 + self.below(0x5f) as u8) as char
+            };
+            if result.len() + c.len_utf8() > len { break }
+            result.push(c);
+        }
+        result
+    }
+
+    /// Returns random data, of at most `max_list_len` bytes.
+    pub fn data(&mut self) -> Vec<u8> {
+        let len = self.below(self.max_list_len + 1);
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    /// Records that the values generated until the matching `leave()` are one pointer deeper.
+    pub fn enter(&mut self) {
+        self.depth += 1;
+    }
+
+    /// Undoes the last `enter()`.
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+/// A primitive type that random values can be generated for.
+pub trait Primitive {
+    /// Returns the value made of the low bits of `bits`.
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! primitive_impls(
+    ($($typ:ty),*) => (
+        $(
+            impl Primitive for $typ {
+                fn from_bits(bits: u64) -> $typ { bits as $typ }
+            }
+        )*
+    );
+);
+
+primitive_impls!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Primitive for bool {
+    fn from_bits(bits: u64) -> bool { bits & 1 != 0 }
+}
+
+impl Primitive for f32 {
+    fn from_bits(bits: u64) -> f32 { f32::from_bits(bits as u32) }
+}
+
+impl Primitive for f64 {
+    fn from_bits(bits: u64) -> f64 { f64::from_bits(bits) }
+}
+
+/// Implemented by the `Owned` type of generated structs.
+pub trait FillRandom: for<'a> Owned<'a> {
+    /// Sets the fields of `builder`, which is expected to be freshly initialized, to random
+    /// values from `generator`.
+    fn fill_random(builder: <Self as Owned<'_>>::Builder, generator: &mut Generator<'_>) -> Result<()>;
+}
+
+/// A random message whose root is a struct of type `T`, stored in canonical form.
+pub struct RandomMessage<T> {
+    words: Vec<Word>,
+    marker: PhantomData<T>,
+}
+
+impl <T> RandomMessage<T> where T: FillRandom {
+    /// Generates a message with `generator`.
+    pub fn generate(generator: &mut Generator) -> Result<RandomMessage<T>> {
+        let mut message = message::Builder::new_default();
+        T::fill_random(message.init_root::<<T as Owned>::Builder>(), generator)?;
+        Ok(RandomMessage { words: message.into_reader().canonicalize()?, marker: PhantomData })
+    }
+
+    /// Generates a message from the random bits of `next`, with the default bounds.
+    pub fn from_fn<F>(mut next: F) -> RandomMessage<T> where F: FnMut() -> u64 {
+        // Messages built by generated code hold no capabilities, and so always canonicalize.
+        Self::generate(&mut Generator::new(&mut next)).expect("failed to generate a random message")
+    }
+
+    /// Returns the root of the message.
+    pub fn get(&self) -> Result<<T as Owned<'_>>::Reader> {
+        any_pointer::Reader::new(PointerReader::get_root_unchecked(self.words.as_ptr() as *const u8)).get_as()
+    }
+
+    /// Returns the words of the message, which has a single segment.
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
+}
+
+impl <T> Clone for RandomMessage<T> {
+    fn clone(&self) -> RandomMessage<T> {
+        RandomMessage { words: self.words.clone(), marker: PhantomData }
+    }
+}
+
+impl <T> fmt::Debug for RandomMessage<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RandomMessage").field("words", &self.words).finish()
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl <T> quickcheck::Arbitrary for RandomMessage<T> where T: FillRandom + Send + 'static {
+    fn arbitrary<G: quickcheck::Gen>(g: &mut G) -> RandomMessage<T> {
+        RandomMessage::from_fn(|| g.next_u64())
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl <'a, T> arbitrary::Arbitrary<'a> for RandomMessage<T> where T: FillRandom {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<RandomMessage<T>> {
+        // Once the input runs out, every choice becomes zero, which keeps the message finite.
+        Ok(RandomMessage::from_fn(|| u.arbitrary::<u64>().unwrap_or(0)))
+    }
+}
@@ -0,0 +1,538 @@
+// Copyright (c) 2013-2015 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Support for [serde](https://serde.rs). Enabled by the `serde` feature.
+//!
+//! When `capnpc` is asked to generate serde support, every generated struct `Reader` implements
+//! `serde::Serialize`, and every generated struct `Builder` implements
+//! `serde::de::DeserializeSeed`, so that a builder can be populated from any serde data format:
+//!
+//! ```ignore
+//! use serde::de::DeserializeSeed;
+//!
+//! let mut message = capnp::message::Builder::new_default();
+//! let root = message.init_root::<foo::Builder>();
+//! root.deserialize(&mut serde_json::Deserializer::from_str(r#"{"name": "alice"}"#))?;
+//! println!("{}", serde_json::to_string(&message.get_root_as_reader::<foo::Reader>()?)?);
+//! ```
+//!
+//! Structs are represented as maps keyed by the field names from the schema. Unions contribute
+//! a single entry for the active field. Null struct pointers are omitted. Enums are represented
+//! by their enumerant names from the schema, and data by bytes.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::{cmp, fmt};
+use core::marker::PhantomData;
+
+pub use ::serde::{de, ser};
+use ::serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor, Error as _};
+use ::serde::ser::{Serialize, Serializer, SerializeSeq, Error as _};
+
+use crate::{any_pointer, any_pointer_list, data, data_list, enum_list, list_list, message,
+            primitive_list, struct_list, text, text_list};
+use crate::private::layout::{list_element_count, PrimitiveElement};
+use crate::traits::{FromU16, IntoInternalListReader, Owned, OwnedStruct, ToU16};
+
+/// Serializes a Data value as bytes rather than as a sequence of integers.
+pub struct Bytes<'a>(pub data::Reader<'a>);
+
+impl <'a> Serialize for Bytes<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Deserializes a Data value from bytes, a string, or a sequence of integers.
+struct ByteBuf(Vec<u8>);
+
+struct ByteBufVisitor;
+
+impl <'de> Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("bytes")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<ByteBuf, E> where E: de::Error {
+        Ok(ByteBuf(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<ByteBuf, E> where E: de::Error {
+        Ok(ByteBuf(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<ByteBuf, E> where E: de::Error {
+        Ok(ByteBuf(v.as_bytes().to_vec()))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<ByteBuf, A::Error> where A: SeqAccess<'de> {
+        // The hint comes from the input, so don't let it reserve more than a little memory.
+        let mut bytes = Vec::with_capacity(cmp::min(seq.size_hint().unwrap_or(0), 4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteBuf(bytes))
+    }
+}
+
+impl <'de> de::Deserialize<'de> for ByteBuf {
+    fn deserialize<D>(deserializer: D) -> Result<ByteBuf, D::Error> where D: Deserializer<'de> {
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+/// Serializes the value of a generated enum. Human-readable formats get the enumerant with index
+/// `value` of `enumerants`, or the plain integer if there is no such enumerant (which enums with
+/// an `Unknown` variant can hold). Other formats always get the integer.
+pub fn serialize_enum<S>(serializer: S, name: &'static str, enumerants: &'static [&'static str], value: u16)
+                         -> Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    match enumerants.get(value as usize) {
+        Some(enumerant) if serializer.is_human_readable() =>
+            serializer.serialize_unit_variant(name, value as u32, enumerant),
+        _ => serializer.serialize_u16(value),
+    }
+}
+
+struct EnumVisitor<T> {
+    enumerants: &'static [&'static str],
+    marker: PhantomData<T>,
+}
+
+impl <T> EnumVisitor<T> where T: FromU16 {
+    fn value_at_index<E>(&self, index: u64) -> Result<T, E> where E: de::Error {
+        if index <= u64::from(u16::MAX) {
+            T::from_u16(index as u16).map_err(E::custom)
+        } else {
+            Err(E::invalid_value(de::Unexpected::Unsigned(index), self))
+        }
+    }
+
+    fn value_named<E>(&self, name: &str) -> Result<T, E> where E: de::Error {
+        match self.enumerants.iter().position(|enumerant| *enumerant == name) {
+            Some(index) => self.value_at_index(index as u64),
+            None => Err(E::unknown_variant(name, self.enumerants)),
+        }
+    }
+}
+
+impl <'de, T> Visitor<'de> for EnumVisitor<T> where T: FromU16 {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an enumerant name or index")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<T, E> where E: de::Error {
+        self.value_at_index(v)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<T, E> where E: de::Error {
+        self.value_named(v)
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<T, A::Error> where A: de::EnumAccess<'de> {
+        use ::serde::de::VariantAccess;
+        let (name, variant): (String, _) = data.variant()?;
+        variant.unit_variant()?;
+        self.value_named(&name)
+    }
+}
+
+/// Deserializes the value of a generated enum, as serialized by [`serialize_enum`]: from one of
+/// its enumerant names, or from its integer value.
+pub fn deserialize_enum<'de, D, T>(deserializer: D, _name: &'static str, enumerants: &'static [&'static str])
+                                   -> Result<T, D::Error>
+    where D: Deserializer<'de>, T: FromU16
+{
+    let visitor = EnumVisitor { enumerants, marker: PhantomData };
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(visitor)
+    } else {
+        deserializer.deserialize_u16(visitor)
+    }
+}
+
+/// Implemented by the generated builders of structs, to set the field named `key` (as written in
+/// the schema) to the next value of `map`. Returns `false` if the struct has no such field.
+pub trait DeserializeFields {
+    fn deserialize_field<'de, A>(&mut self, key: &str, map: &mut A) -> Result<bool, A::Error>
+        where A: de::MapAccess<'de>;
+}
+
+struct StructVisitor<B> {
+    name: &'static str,
+    builder: B,
+}
+
+impl <'de, B> Visitor<'de> for StructVisitor<B> where B: DeserializeFields {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "struct {}", self.name)
+    }
+
+    fn visit_map<A>(mut self, mut map: A) -> Result<(), A::Error> where A: de::MapAccess<'de> {
+        while let Some(key) = map.next_key::<String>()? {
+            if !self.builder.deserialize_field(&key, &mut map)? {
+                map.next_value::<de::IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Populates `builder` from a map of field names to values. Fields that are not in the schema
+/// are ignored. Called by the `DeserializeSeed` implementations of generated builders.
+pub fn deserialize_struct<'de, D, B>(deserializer: D, name: &'static str, builder: B) -> Result<(), D::Error>
+    where D: Deserializer<'de>, B: DeserializeFields
+{
+    deserializer.deserialize_map(StructVisitor { name, builder })
+}
+
+/// Implemented by the generated `Owned` types of structs, so that struct lists can be deserialized.
+pub trait DeserializeStruct: for<'a> OwnedStruct<'a> {
+    fn deserialize_struct<'a, 'de, D>(builder: <Self as OwnedStruct<'a>>::Builder, deserializer: D)
+                                      -> Result<(), D::Error>
+        where D: Deserializer<'de>;
+}
+
+/// Implemented by the `Owned` types of text, data and lists, which are deserialized into the
+/// target of a pointer once their size is known.
+pub trait DeserializePointer: for<'a> Owned<'a> {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>;
+}
+
+/// A `DeserializeSeed` that sets the target of a pointer to a value of type `T`.
+pub struct PointerSeed<'a, T> {
+    builder: any_pointer::Builder<'a>,
+    marker: PhantomData<T>,
+}
+
+impl <'a, T> PointerSeed<'a, T> where T: DeserializePointer {
+    pub fn new(builder: any_pointer::Builder<'a>) -> PointerSeed<'a, T> {
+        PointerSeed { builder, marker: PhantomData }
+    }
+}
+
+impl <'de, 'a, T> DeserializeSeed<'de> for PointerSeed<'a, T> where T: DeserializePointer {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error> where D: Deserializer<'de> {
+        T::deserialize_pointer(self.builder, deserializer)
+    }
+}
+
+struct StructSeed<'a, T> where T: for<'b> OwnedStruct<'b> {
+    builder: <T as OwnedStruct<'a>>::Builder,
+}
+
+impl <'de, 'a, T> DeserializeSeed<'de> for StructSeed<'a, T> where T: DeserializeStruct {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error> where D: Deserializer<'de> {
+        T::deserialize_struct(self.builder, deserializer)
+    }
+}
+
+struct TextVisitor<'a>(any_pointer::Builder<'a>);
+
+impl <'de, 'a> Visitor<'de> for TextVisitor<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<(), E> where E: de::Error {
+        self.0.set_as(v).map_err(E::custom)
+    }
+}
+
+impl DeserializePointer for text::Owned {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_str(TextVisitor(builder))
+    }
+}
+
+impl DeserializePointer for data::Owned {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        let bytes: ByteBuf = de::Deserialize::deserialize(deserializer)?;
+        builder.set_as(&bytes.0[..]).map_err(D::Error::custom)
+    }
+}
+
+impl <T> DeserializePointer for primitive_list::Owned<T> where T: PrimitiveElement + for<'de> de::Deserialize<'de> {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        let values: Vec<T> = de::Deserialize::deserialize(deserializer)?;
+        let len = list_element_count(values.len()).map_err(D::Error::custom)?;
+        let mut list = builder.initn_as::<primitive_list::Builder<T>>(len);
+        for (index, value) in values.into_iter().enumerate() {
+            list.set(index as u32, value);
+        }
+        Ok(())
+    }
+}
+
+impl <T> DeserializePointer for enum_list::Owned<T> where T: ToU16 + FromU16 + for<'de> de::Deserialize<'de> {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        let values: Vec<T> = de::Deserialize::deserialize(deserializer)?;
+        let len = list_element_count(values.len()).map_err(D::Error::custom)?;
+        let mut list = builder.initn_as::<enum_list::Builder<T>>(len);
+        for (index, value) in values.into_iter().enumerate() {
+            list.set(index as u32, value);
+        }
+        Ok(())
+    }
+}
+
+impl DeserializePointer for text_list::Owned {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        let values: Vec<String> = de::Deserialize::deserialize(deserializer)?;
+        let len = list_element_count(values.len()).map_err(D::Error::custom)?;
+        let mut list = builder.initn_as::<text_list::Builder>(len);
+        for (index, value) in values.iter().enumerate() {
+            list.set(index as u32, value);
+        }
+        Ok(())
+    }
+}
+
+impl DeserializePointer for data_list::Owned {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        let values: Vec<ByteBuf> = de::Deserialize::deserialize(deserializer)?;
+        let len = list_element_count(values.len()).map_err(D::Error::custom)?;
+        let mut list = builder.initn_as::<data_list::Builder>(len);
+        for (index, value) in values.iter().enumerate() {
+            list.set(index as u32, &value.0);
+        }
+        Ok(())
+    }
+}
+
+// The length of a list must be known before it can be allocated, so the elements of struct
+// lists and lists of lists are first deserialized into a scratch list that grows as needed, and
+// then copied into place.
+
+/// Returns a message to hold a scratch list. Its first segment is small, so that short lists
+/// don't cost much more memory than their elements take up.
+fn scratch_message() -> message::Builder<message::HeapAllocator> {
+    message::Builder::new(message::HeapAllocator::new().first_segment_words(16))
+}
+
+/// Returns the capacity to grow a scratch list that is full with `len` elements to.
+fn grown_capacity(len: u32) -> crate::Result<u32> {
+    list_element_count(len as usize + 1)?;
+    Ok((2 * len).clamp(4, (1 << 29) - 1))
+}
+
+struct StructListVisitor<'a, T> {
+    builder: any_pointer::Builder<'a>,
+    marker: PhantomData<T>,
+}
+
+impl <'de, 'a, T> Visitor<'de> for StructListVisitor<'a, T> where T: DeserializeStruct {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of structs")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error> where A: SeqAccess<'de> {
+        let mut scratch = scratch_message();
+        let mut len = 0;
+        let mut capacity = 0;
+        loop {
+            if len == capacity {
+                capacity = grown_capacity(len).map_err(A::Error::custom)?;
+                let mut grown = scratch_message();
+                let list = grown.init_root::<any_pointer::Builder>()
+                    .initn_as::<struct_list::Builder<T>>(capacity);
+                if len > 0 {
+                    let elements = scratch.get_root_as_reader::<struct_list::Reader<T>>()
+                        .map_err(A::Error::custom)?;
+                    for index in 0..len {
+                        list.set_with_caveats(index, elements.get(index)).map_err(A::Error::custom)?;
+                    }
+                }
+                scratch = grown;
+            }
+            let seed = StructSeed::<T> {
+                builder: scratch.get_root::<struct_list::Builder<T>>().map_err(A::Error::custom)?.get(len),
+            };
+            if seq.next_element_seed(seed)?.is_none() { break }
+            len += 1;
+        }
+
+        let list = self.builder.initn_as::<struct_list::Builder<T>>(len);
+        if len > 0 {
+            let elements = scratch.get_root_as_reader::<struct_list::Reader<T>>().map_err(A::Error::custom)?;
+            for index in 0..len {
+                list.set_with_caveats(index, elements.get(index)).map_err(A::Error::custom)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl <T> DeserializePointer for struct_list::Owned<T> where T: DeserializeStruct {
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_seq(StructListVisitor::<T> { builder, marker: PhantomData })
+    }
+}
+
+struct ListListVisitor<'a, T> {
+    builder: any_pointer::Builder<'a>,
+    marker: PhantomData<T>,
+}
+
+impl <'de, 'a, T> Visitor<'de> for ListListVisitor<'a, T>
+    where T: DeserializePointer, for<'b> <T as Owned<'b>>::Reader: IntoInternalListReader<'b>
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of lists")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error> where A: SeqAccess<'de> {
+        let mut scratch = scratch_message();
+        let mut len = 0;
+        let mut capacity = 0;
+        loop {
+            if len == capacity {
+                capacity = grown_capacity(len).map_err(A::Error::custom)?;
+                let mut grown = scratch_message();
+                let mut list = grown.init_root::<any_pointer::Builder>()
+                    .initn_as::<any_pointer_list::Builder>(capacity);
+                if len > 0 {
+                    let elements = scratch.get_root_as_reader::<any_pointer_list::Reader>()
+                        .map_err(A::Error::custom)?;
+                    for index in 0..len {
+                        list.reborrow().get(index).set_as(elements.get(index)).map_err(A::Error::custom)?;
+                    }
+                }
+                scratch = grown;
+            }
+            let element = scratch.get_root::<any_pointer_list::Builder>().map_err(A::Error::custom)?.get(len);
+            if seq.next_element_seed(PointerSeed::<T>::new(element))?.is_none() { break }
+            len += 1;
+        }
+
+        let list = self.builder.initn_as::<list_list::Builder<T>>(len);
+        if len > 0 {
+            let elements = scratch.get_root_as_reader::<any_pointer_list::Reader>().map_err(A::Error::custom)?;
+            for index in 0..len {
+                let element = elements.get(index).get_as::<<T as Owned>::Reader>().map_err(A::Error::custom)?;
+                list.set(index, element).map_err(A::Error::custom)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl <T> DeserializePointer for list_list::Owned<T>
+    where T: DeserializePointer, for<'b> <T as Owned<'b>>::Reader: IntoInternalListReader<'b>
+{
+    fn deserialize_pointer<'de, D>(builder: any_pointer::Builder, deserializer: D) -> Result<(), D::Error>
+        where D: Deserializer<'de>
+    {
+        deserializer.deserialize_seq(ListListVisitor::<T> { builder, marker: PhantomData })
+    }
+}
+
+impl <'a, T> Serialize for primitive_list::Reader<'a, T> where T: PrimitiveElement + Serialize {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut seq = serializer.serialize_seq(Some(self.len() as usize))?;
+        for index in 0..self.len() {
+            seq.serialize_element(&self.get(index))?;
+        }
+        seq.end()
+    }
+}
+
+impl <'a, T> Serialize for enum_list::Reader<'a, T> where T: FromU16 + Serialize {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut seq = serializer.serialize_seq(Some(self.len() as usize))?;
+        for index in 0..self.len() {
+            seq.serialize_element(&self.get(index).map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+impl <'a> Serialize for text_list::Reader<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut seq = serializer.serialize_seq(Some(self.len() as usize))?;
+        for value in self.iter() {
+            seq.serialize_element(value.map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+impl <'a> Serialize for data_list::Reader<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut seq = serializer.serialize_seq(Some(self.len() as usize))?;
+        for value in self.iter() {
+            seq.serialize_element(&Bytes(value.map_err(S::Error::custom)?))?;
+        }
+        seq.end()
+    }
+}
+
+impl <'a, T> Serialize for struct_list::Reader<'a, T>
+    where T: for<'b> OwnedStruct<'b>, <T as OwnedStruct<'a>>::Reader: Serialize
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl <'a, T> Serialize for list_list::Reader<'a, T>
+    where T: for<'b> Owned<'b>, <T as Owned<'a>>::Reader: Serialize
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut seq = serializer.serialize_seq(Some(self.len() as usize))?;
+        for value in self.iter() {
+            seq.serialize_element(&value.map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
@@ -22,9 +22,13 @@
 //! List of strings containing UTF-8 encoded text.
 
 use crate::traits::{FromPointerReader, FromPointerBuilder, IndexMove, ListIter};
-use crate::private::layout::{ListBuilder, ListReader, Pointer, PointerBuilder, PointerReader};
+use crate::private::layout::{ListBuilder, ListReader, Pointer, PointerBuilder, PointerReader,
+                             list_element_count};
 use crate::Result;
 
+use alloc::string::String;
+use alloc::vec::Vec;
+
 #[derive(Copy, Clone)]
 pub struct Owned;
 
@@ -137,3 +141,43 @@ impl <'a> ::core::iter::IntoIterator for Reader<'a> {
         self.iter()
     }
 }
+
+fn set_from_slice<S>(pointer: PointerBuilder, values: &[S]) -> Result<()> where S: AsRef<str> {
+    let mut builder = Builder::init_pointer(pointer, list_element_count(values.len())?);
+    for (index, value) in values.iter().enumerate() {
+        builder.set(index as u32, value.as_ref());
+    }
+    Ok(())
+}
+
+impl <'a, 'b> crate::traits::SetPointerBuilder for &'a [&'b str] {
+    fn set_pointer_builder<'c>(pointer: PointerBuilder<'c>,
+                               value: &'a [&'b str],
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, value)
+    }
+}
+
+impl <'a> crate::traits::SetPointerBuilder for &'a [String] {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: &'a [String],
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, value)
+    }
+}
+
+impl <'a> crate::traits::SetPointerBuilder for Vec<&'a str> {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: Vec<&'a str>,
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, &value[..])
+    }
+}
+
+impl crate::traits::SetPointerBuilder for Vec<String> {
+    fn set_pointer_builder<'b>(pointer: PointerBuilder<'b>,
+                               value: Vec<String>,
+                               _canonicalize: bool) -> Result<()> {
+        set_from_slice(pointer, &value[..])
+    }
+}
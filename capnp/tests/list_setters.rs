@@ -0,0 +1,45 @@
+use capnp::{data_list, message, primitive_list, text_list};
+
+#[test]
+pub fn set_primitive_list_from_slice() {
+    let mut message = message::Builder::new_default();
+    message.set_root(&[1u32, 2, 3][..]).unwrap();
+    let list: primitive_list::Reader<u32> = message.get_root_as_reader().unwrap();
+    assert_eq!(list.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    message.set_root(vec![true, false, true]).unwrap();
+    let list: primitive_list::Reader<bool> = message.get_root_as_reader().unwrap();
+    assert_eq!(list.iter().collect::<Vec<_>>(), vec![true, false, true]);
+
+    message.set_root(vec![7u8, 8]).unwrap();
+    let list: primitive_list::Reader<u8> = message.get_root_as_reader().unwrap();
+    assert_eq!(list.iter().collect::<Vec<_>>(), vec![7, 8]);
+}
+
+#[test]
+pub fn set_text_list_from_slice() {
+    let mut message = message::Builder::new_default();
+    message.set_root(vec!["foo", "bar"]).unwrap();
+    let list: text_list::Reader = message.get_root_as_reader().unwrap();
+    assert_eq!(list.iter().collect::<capnp::Result<Vec<_>>>().unwrap(), vec!["foo", "bar"]);
+
+    let strings = ["baz".to_string()];
+    message.set_root(&strings[..]).unwrap();
+    let list: text_list::Reader = message.get_root_as_reader().unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list.get(0).unwrap(), "baz");
+}
+
+#[test]
+pub fn set_data_list_from_slice() {
+    let mut message = message::Builder::new_default();
+    message.set_root(&[&b"ab"[..], &b""[..]][..]).unwrap();
+    let list: data_list::Reader = message.get_root_as_reader().unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.get(0).unwrap(), b"ab");
+    assert_eq!(list.get(1).unwrap(), b"");
+
+    message.set_root(vec![vec![1u8, 2]]).unwrap();
+    let list: data_list::Reader = message.get_root_as_reader().unwrap();
+    assert_eq!(list.get(0).unwrap(), &[1, 2]);
+}
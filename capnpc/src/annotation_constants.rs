@@ -0,0 +1,236 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Generation of constants holding the values of the annotations applied to structs, enums
+//! and interfaces and to their fields, enumerants and methods.
+
+use std::collections::HashSet;
+
+use crate::codegen::{FormattedText, GeneratorContext, constant_type, find_node, generate_constant,
+                     get_enumerant_name, get_field_name, module_name, snake_to_upper_case};
+use crate::codegen::FormattedText::{Indent, Line, Branch, BlankLine};
+use crate::schema_capnp::{annotation, node, type_, value};
+
+/// An annotation application whose value can be held in a constant.
+struct Applied<'a> {
+    id: u64,
+    name: &'a str,
+    typ: type_::Reader<'a>,
+    value: value::Reader<'a>,
+}
+
+/// The annotations applied to a field, enumerant or method.
+struct Member<'a> {
+    module: String,
+    key: u16,
+    annotations: Vec<Applied<'a>>,
+}
+
+fn applied_annotations<'a>(gen: &'a GeneratorContext,
+                           annotations: ::capnp::struct_list::Reader<'a, annotation::Owned>)
+                           -> ::capnp::Result<Vec<Applied<'a>>>
+{
+    let mut result = Vec::new();
+    for annotation in annotations.iter() {
+        let id = annotation.get_id();
+        let typ = match find_node(&gen.node_map, id) {
+            Some(node) => match node.which()? {
+                node::Annotation(annotation_reader) => annotation_reader.get_type()?,
+                _ => continue,
+            },
+            None => continue,
+        };
+        let name = match gen.scope_map.get(&id).and_then(|names| names.last()) {
+            Some(name) => name,
+            None => continue,
+        };
+        match typ.which()? {
+            // Interface values are always null, and a generic parameter has no single type.
+            type_::Interface(_) => continue,
+            type_::AnyPointer(pointer) => match pointer.which()? {
+                type_::any_pointer::Unconstrained(_) => (),
+                _ => continue,
+            },
+            _ => (),
+        }
+        result.push(Applied { id, name, typ, value: annotation.get_value()? });
+    }
+    Ok(result)
+}
+
+fn generate_constants(gen: &GeneratorContext,
+                      applied: &[Applied],
+                      display_name: &str) -> ::capnp::Result<FormattedText>
+{
+    let mut names = HashSet::new();
+    let mut result = Vec::new();
+    for annotation in applied {
+        let styled_name = snake_to_upper_case(annotation.name);
+        if !names.insert(styled_name.clone()) {
+            return Err(::capnp::Error::failed(format!(
+                "more than one annotation on {} would generate the constant {}",
+                display_name, styled_name)));
+        }
+        result.push(gen.node_doc_comment(annotation.id)?);
+        result.push(generate_constant(gen, &styled_name, annotation.typ, annotation.value)?);
+    }
+    Ok(Branch(result))
+}
+
+/// Generates a function for each annotation applied to some member, returning the value of the
+/// annotation on the member whose key (method ordinal or enumerant value) is passed, if any.
+fn generate_lookup_functions(gen: &GeneratorContext,
+                             display_name: &str,
+                             members: &[Member],
+                             kind: &str,
+                             param_name: &str,
+                             param_type: &str,
+                             key_expression: &str) -> ::capnp::Result<FormattedText>
+{
+    let mut ids = Vec::new();
+    for member in members {
+        for annotation in &member.annotations {
+            if !ids.contains(&annotation.id) {
+                ids.push(annotation.id);
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    let mut result = Vec::new();
+    for id in ids {
+        let mut arms = Vec::new();
+        let mut return_type = String::new();
+        let mut name = "";
+        for member in members {
+            for annotation in &member.annotations {
+                if annotation.id != id { continue }
+                name = annotation.name;
+                return_type = constant_type(gen, annotation.typ)?;
+                let reference = match annotation.typ.which()? {
+                    type_::List(_) | type_::Struct(_) | type_::AnyPointer(_) => "&",
+                    _ => "",
+                };
+                arms.push(Line(format!("{} => ::core::option::Option::Some({}{}::{}),",
+                                       member.key, reference, member.module,
+                                       snake_to_upper_case(name))));
+            }
+        }
+        if return_type.starts_with("::capnp::constant::Reader") {
+            return_type = format!("&'static {}", return_type);
+        }
+        arms.push(Line("_ => ::core::option::Option::None,".to_string()));
+        let function_name = format!("{}_for_{}", name.trim_end_matches('_'), kind);
+        if !names.insert(function_name.clone()) {
+            return Err(::capnp::Error::failed(format!(
+                "more than one annotation on the {}s of {} would generate the function {}",
+                kind, display_name, function_name)));
+        }
+
+        result.push(BlankLine);
+        result.push(Line(format!(
+            "/// Returns the value of the `{}` annotation on the {} identified by `{}`, if it has one.",
+            gen.node_map[&id].get_display_name()?, kind, param_name)));
+        result.push(Line(format!("pub fn {}({}: {}) -> ::core::option::Option<{}> {{",
+                                 function_name, param_name, param_type, return_type)));
+        result.push(Indent(Box::new(Branch(vec![
+            Line(format!("match {} {{", key_expression)),
+            Indent(Box::new(Branch(arms))),
+            Line("}".to_string()),
+        ]))));
+        result.push(Line("}".to_string()));
+    }
+    Ok(Branch(result))
+}
+
+/// Generates a module named `module` holding the annotation constants of the struct, enum or
+/// interface `node_id`, or nothing if neither it nor any of its members are annotated.
+pub fn generate_annotation_constants(gen: &GeneratorContext,
+                                     node_id: u64,
+                                     module: &str) -> ::capnp::Result<FormattedText>
+{
+    let node_reader = &gen.node_map[&node_id];
+    let display_name = node_reader.get_display_name()?;
+    let own = applied_annotations(gen, node_reader.get_annotations()?)?;
+
+    let mut members = Vec::new();
+    let mut lookup = None;
+    match node_reader.which()? {
+        node::Struct(struct_reader) => {
+            for field in struct_reader.get_fields()?.iter() {
+                members.push(Member {
+                    module: module_name(get_field_name(field)?),
+                    key: field.get_code_order(),
+                    annotations: applied_annotations(gen, field.get_annotations()?)?,
+                });
+            }
+        }
+        node::Enum(enum_reader) => {
+            for (index, enumerant) in enum_reader.get_enumerants()?.iter().enumerate() {
+                members.push(Member {
+                    module: module_name(get_enumerant_name(enumerant)?),
+                    key: index as u16,
+                    annotations: applied_annotations(gen, enumerant.get_annotations()?)?,
+                });
+            }
+            let param_type = format!("super::{}", gen.get_last_name(node_id)?);
+            lookup = Some(("enumerant", "value", param_type, "::capnp::traits::ToU16::to_u16(value)"));
+        }
+        node::Interface(interface) => {
+            for (index, method) in interface.get_methods()?.iter().enumerate() {
+                members.push(Member {
+                    module: module_name(method.get_name()?),
+                    key: index as u16,
+                    annotations: applied_annotations(gen, method.get_annotations()?)?,
+                });
+            }
+            lookup = Some(("method", "method_id", "u16".to_string(), "method_id"));
+        }
+        _ => (),
+    }
+    members.retain(|member| !member.annotations.is_empty());
+
+    if own.is_empty() && members.is_empty() {
+        return Ok(Branch(Vec::new()));
+    }
+
+    let mut interior = vec![generate_constants(gen, &own, display_name)?];
+    for (index, member) in members.iter().enumerate() {
+        if index > 0 || !own.is_empty() {
+            interior.push(BlankLine);
+        }
+        interior.push(Line(format!("pub mod {} {{", member.module)));
+        interior.push(Indent(Box::new(generate_constants(gen, &member.annotations, display_name)?)));
+        interior.push(Line("}".to_string()));
+    }
+    if let Some((kind, param_name, param_type, key_expression)) = lookup {
+        interior.push(generate_lookup_functions(gen, display_name, &members, kind, param_name, &param_type, key_expression)?);
+    }
+
+    Ok(Branch(vec![
+        BlankLine,
+        Line(format!("/// Values of the annotations applied to `{}` and to its members.", display_name)),
+        Line(format!("pub mod {} {{", module)),
+        Indent(Box::new(Branch(interior))),
+        Line("}".to_string()),
+    ]))
+}
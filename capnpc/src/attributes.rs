@@ -0,0 +1,309 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Extra derives and attributes for generated items, as requested by the `derive` and
+//! `attribute` annotations of `rust.capnp`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::codegen::{FormattedText, Reached, walk_scope};
+use crate::codegen::FormattedText::{Branch, Line};
+use crate::schema_capnp::{annotation, field, node, type_};
+
+const DERIVE_ANNOTATION_ID: u64 = 0xa5e3f0c4b2d81e97;
+const ATTRIBUTE_ANNOTATION_ID: u64 = 0xd3b1c8e0f6a42b5d;
+
+/// The traits that are always derived for generated enums.
+pub const ENUM_DERIVES: &[&str] = &["Clone", "Copy", "Debug", "PartialEq"];
+
+/// The traits that are always derived for plain structs and their `PlainWhich` enums.
+pub const PLAIN_DERIVES: &[&str] = &["Clone", "Debug", "PartialEq"];
+
+fn text_list_annotation_value(annotations: ::capnp::struct_list::Reader<annotation::Owned>,
+                              id: u64,
+                              name: &str) -> ::capnp::Result<Vec<String>>
+{
+    let mut result = Vec::new();
+    for annotation in annotations.iter() {
+        if annotation.get_id() == id {
+            if let crate::schema_capnp::value::List(list) = annotation.get_value()?.which()? {
+                for text in list.get_as::<::capnp::text_list::Reader>()?.iter() {
+                    result.push(text?.trim().to_string());
+                }
+            } else {
+                return Err(::capnp::Error::failed(format!(
+                    "expected rust.{} annotation value to be of type List(Text)", name)));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns `derive` without a leading `std::` or `core::` path, so that standard traits can be
+/// recognized however they are spelled.
+fn trait_name(derive: &str) -> &str {
+    if derive.starts_with("std::") || derive.starts_with("core::") {
+        derive.rsplit("::").next().unwrap_or(derive)
+    } else {
+        derive
+    }
+}
+
+/// A derive requested for a node, and whether it was requested for that node specifically, as
+/// opposed to for everything in its file.
+struct Requested {
+    derive: String,
+    explicit: bool,
+}
+
+struct Selection<'a, 'b> {
+    node_map: &'b HashMap<u64, node::Reader<'a>>,
+    plain_structs: &'b HashSet<u64>,
+    requested: HashMap<u64, Vec<Requested>>,
+}
+
+impl <'a, 'b> Selection<'a, 'b> {
+    fn request(&mut self, node_id: u64, derives: &[String], explicit: bool, builtin: &[&str]) {
+        let requested = self.requested.entry(node_id).or_default();
+        for derive in derives {
+            if builtin.contains(&trait_name(derive)) ||
+                requested.iter().any(|r| trait_name(&r.derive) == trait_name(derive))
+            {
+                continue
+            }
+            requested.push(Requested { derive: derive.clone(), explicit });
+        }
+    }
+
+    fn derives<'c>(&'c self, node_id: u64, builtin: &[&'c str]) -> Vec<&'c str> {
+        let mut result: Vec<&str> = builtin.to_vec();
+        if let Some(requested) = self.requested.get(&node_id) {
+            result.extend(requested.iter().map(|r| trait_name(&r.derive)));
+        }
+        result
+    }
+
+    /// Returns a description of why a value of type `typ` cannot derive `derive`.
+    fn type_reason(&self, typ: type_::Reader, derive: &str) -> ::capnp::Result<Option<String>> {
+        Ok(match typ.which()? {
+            type_::Float32(()) | type_::Float64(()) if ["Eq", "Ord", "Hash"].contains(&derive) =>
+                Some("floating-point values do not implement it".to_string()),
+            type_::Text(()) | type_::Data(()) | type_::List(_) | type_::Struct(_) if derive == "Copy" =>
+                Some("it holds heap-allocated values".to_string()),
+            type_::List(ot) if derive != "Default" => self.type_reason(ot.get_element_type()?, derive)?,
+            type_::Enum(en) => {
+                let id = en.get_type_id();
+                if derive == "Default" {
+                    Some("enums have no default value".to_string())
+                } else if !self.derives(id, ENUM_DERIVES).contains(&derive) {
+                    Some(format!("{} does not derive it", self.node_map[&id].get_display_name()?))
+                } else {
+                    None
+                }
+            }
+            type_::Struct(st) => {
+                let id = st.get_type_id();
+                if !self.derives(id, PLAIN_DERIVES).contains(&derive) && derive != "Default" {
+                    Some(format!("{} does not derive it", self.node_map[&id].get_display_name()?))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+    }
+
+    /// Returns a description of why `derive` cannot be derived for the enum or plain struct
+    /// `node_id`.
+    fn reason(&self, node_id: u64, derive: &str) -> ::capnp::Result<Option<String>> {
+        let node_reader = self.node_map[&node_id];
+        let builtin = match node_reader.which()? {
+            node::Enum(_) => ENUM_DERIVES,
+            _ => PLAIN_DERIVES,
+        };
+        let derives = self.derives(node_id, builtin);
+        for &(needs, prerequisite) in &[("Eq", "PartialEq"), ("PartialOrd", "PartialEq"),
+                                        ("Ord", "Eq"), ("Ord", "PartialOrd"), ("Copy", "Clone")] {
+            if derive == needs && !derives.contains(&prerequisite) {
+                return Ok(Some(format!("it requires `{}`", prerequisite)));
+            }
+        }
+        let struct_reader = match node_reader.which()? {
+            node::Enum(_) => {
+                if derive == "Default" {
+                    return Ok(Some("enums have no default value".to_string()));
+                }
+                return Ok(None);
+            }
+            node::Struct(struct_reader) => struct_reader,
+            _ => return Ok(None),
+        };
+        if derive == "Default" && struct_reader.get_discriminant_count() > 0 {
+            return Ok(Some("unions have no default value".to_string()));
+        }
+        for field in struct_reader.get_fields()?.iter() {
+            let reason = match field.which()? {
+                field::Slot(slot) => self.type_reason(slot.get_type()?, derive)?,
+                field::Group(group) => {
+                    let id = group.get_type_id();
+                    if self.derives(id, PLAIN_DERIVES).contains(&derive) {
+                        None
+                    } else {
+                        Some("its group does not derive it".to_string())
+                    }
+                }
+            };
+            if let Some(reason) = reason {
+                return Ok(Some(format!("field `{}`: {}", field.get_name()?, reason)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Determines the extra derives of the enums and plain structs of the requested files, from
+/// the `$Rust.derive` annotations. The standard traits are checked to be derivable for the
+/// generated types. Derives that cannot be applied are dropped if they were requested for a
+/// whole file, and reported as an error otherwise.
+pub fn select_derives(request: crate::schema_capnp::code_generator_request::Reader,
+                      node_map: &HashMap<u64, node::Reader>,
+                      plain_structs: &HashSet<u64>) -> ::capnp::Result<HashMap<u64, Vec<String>>> {
+    let mut selection = Selection { node_map, plain_structs, requested: HashMap::new() };
+    for requested_file in request.get_requested_files()?.iter() {
+        let id = requested_file.get_id();
+        let file_derives = match node_map.get(&id) {
+            Some(node) => text_list_annotation_value(node.get_annotations()?, DERIVE_ANNOTATION_ID, "derive")?,
+            None => Vec::new(),
+        };
+        walk_scope(node_map, id, &Vec::new(), &mut |node_id, node_reader, reached, inherited: &Vec<String>| {
+            // Groups are part of their struct, so they get the same derives.
+            let mut own = if reached == Reached::Group { inherited.clone() } else { Vec::new() };
+            own.extend(text_list_annotation_value(node_reader.get_annotations()?, DERIVE_ANNOTATION_ID, "derive")?);
+            match node_reader.which()? {
+                node::Enum(_) => {
+                    selection.request(node_id, &file_derives, false, ENUM_DERIVES);
+                    selection.request(node_id, &own, true, ENUM_DERIVES);
+                }
+                // The derives of a struct apply to its plain struct, so they have no effect if
+                // there isn't one.
+                node::Struct(_) if selection.plain_structs.contains(&node_id) => {
+                    selection.request(node_id, &file_derives, false, PLAIN_DERIVES);
+                    selection.request(node_id, &own, true, PLAIN_DERIVES);
+                }
+                _ => {}
+            }
+            Ok(Some(own))
+        })?;
+    }
+
+    loop {
+        let mut dropped = Vec::new();
+        for (&id, requested) in &selection.requested {
+            for r in requested {
+                if let Some(reason) = selection.reason(id, trait_name(&r.derive))? {
+                    if r.explicit {
+                        return Err(::capnp::Error::failed(format!(
+                            "cannot derive `{}` for {}: {}",
+                            r.derive, node_map[&id].get_display_name()?, reason)));
+                    }
+                    dropped.push((id, r.derive.clone()));
+                }
+            }
+        }
+        if dropped.is_empty() {
+            break
+        }
+        for (id, derive) in dropped {
+            if let Some(requested) = selection.requested.get_mut(&id) {
+                requested.retain(|r| r.derive != derive);
+            }
+        }
+    }
+
+    Ok(selection.requested.into_iter()
+       .map(|(id, requested)| (id, requested.into_iter().map(|r| r.derive).collect()))
+       .collect())
+}
+
+/// Collects the extra attributes of the enums, structs and interfaces of the requested files,
+/// from the `$Rust.attribute` annotations. Attributes of a file apply to each of its top-level
+/// items, except that `non_exhaustive` only applies to enums.
+pub fn select_attributes(request: crate::schema_capnp::code_generator_request::Reader,
+                         node_map: &HashMap<u64, node::Reader>)
+                         -> ::capnp::Result<HashMap<u64, Vec<String>>>
+{
+    let mut result = HashMap::new();
+    for requested_file in request.get_requested_files()?.iter() {
+        let id = requested_file.get_id();
+        let file_node = match node_map.get(&id) { Some(node) => node, None => continue, };
+        let file_attributes =
+            text_list_annotation_value(file_node.get_annotations()?, ATTRIBUTE_ANNOTATION_ID, "attribute")?;
+        walk_scope(node_map, id, &(), &mut |node_id, node_reader, reached, _| {
+            // Groups and the implicit structs of methods are generated as part of the node whose
+            // scope they are in.
+            if reached != Reached::Nested {
+                return Ok(None);
+            }
+            let own = text_list_annotation_value(node_reader.get_annotations()?, ATTRIBUTE_ANNOTATION_ID, "attribute")?;
+            // Only the nodes declared at the top level of the file get its attributes.
+            let mut attributes = if node_reader.get_scope_id() == id { file_attributes.clone() } else { Vec::new() };
+            if !matches!(node_reader.which()?, node::Enum(_)) {
+                // Structs and interfaces become modules, which can't be `#[non_exhaustive]`.
+                if own.iter().any(|a| a == "non_exhaustive") {
+                    return Err(::capnp::Error::failed(format!(
+                        "cannot apply attribute `non_exhaustive` to {}: it is only allowed on enums",
+                        node_reader.get_display_name()?)));
+                }
+                attributes.retain(|a| a != "non_exhaustive");
+            }
+            attributes.extend(own);
+            if !attributes.is_empty() {
+                result.insert(node_id, attributes);
+            }
+            Ok(Some(()))
+        })?;
+    }
+    Ok(result)
+}
+
+/// Formats the derive attribute of a generated item: `builtin` followed by `extra`.
+pub fn derive_line(builtin: &[&str], extra: Option<&Vec<String>>) -> FormattedText {
+    let mut derives: Vec<&str> = builtin.to_vec();
+    if let Some(extra) = extra {
+        derives.extend(extra.iter().map(|d| &d[..]));
+    }
+    Line(format!("#[derive({})]", derives.join(", ")))
+}
+
+/// Formats the extra attributes of a generated item. They can be given either as the contents
+/// of the attribute, like `non_exhaustive`, or in full, like `#[non_exhaustive]`.
+pub fn attribute_lines(attributes: Option<&Vec<String>>) -> FormattedText {
+    let mut result = Vec::new();
+    for attribute in attributes.into_iter().flatten() {
+        if attribute.starts_with("#[") {
+            result.push(Line(attribute.clone()));
+        } else {
+            result.push(Line(format!("#[{}]", attribute)));
+        }
+    }
+    Branch(result)
+}
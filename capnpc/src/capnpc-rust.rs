@@ -23,13 +23,168 @@
 //!
 //! [See this.](https://capnproto.org/otherlang.html#how-to-write-compiler-plugins)
 //!
+//! Code generation options can be given as `--name` or `--name=value` arguments, or, because
+//! `capnp compile -orust:DIR` runs the plugin without arguments, in the `CAPNPC_RUST_OPTIONS`
+//! environment variable, separated by whitespace. Arguments override the environment variable;
+//! in particular, `--only-nodes` arguments replace the nodes given in the environment variable.
+//! The options mirror those of [`capnpc::CompilerCommand`]:
+//!
+//! ```text
+//! --output-directory=PATH          directory to write to, relative to the DIR of -orust:DIR,
+//!                                  which is the plugin's working directory (default: .)
+//! --default-parent-module=A::B     module in which the generated code is included
+//! --raw-code-generator-request-path=PATH
+//! --entrypoint=FILE                also generate FILE, declaring the module tree
+//! --only-nodes=Foo,Bar.Baz         generate code only for these nodes (repeatable)
+//! --plain-structs  --serde  --reader-equality  --unknown-variants  --skip-interfaces
+//! --skip-structs  --async-servers  --annotation-constants  --random-generators
+//! ```
+//!
+//! Flags may also be given as `--flag=true` or `--flag=false`. For example:
+//!
+//! ```text
+//! CAPNPC_RUST_OPTIONS="--default-parent-module=schema --serde" capnp compile -orust:src foo.capnp
+//! ```
+//!
+//! Run as `capnpc-rust check-compat OLD NEW`, it instead checks that the schemas in the
+//! `CodeGeneratorRequest` saved at `NEW` are wire-compatible with those saved at `OLD`.
+//! See [`capnpc::compatibility`].
 //!
 
+fn check_compat(old: &str, new: &str) -> i32 {
+    match ::capnpc::compatibility::check_compatibility_files(old, new) {
+        Ok(ref incompatibilities) if incompatibilities.is_empty() => 0,
+        Ok(incompatibilities) => {
+            for incompatibility in &incompatibilities {
+                eprintln!("{}", incompatibility);
+            }
+            1
+        }
+        Err(e) => {
+            eprintln!("failed to check compatibility: {}", e);
+            2
+        }
+    }
+}
+
+/// Applies the option `option`, of the form `--name` or `--name=value`, to `command`. The
+/// nodes of `--only-nodes` are collected in `only_nodes`.
+fn apply_option(command: &mut ::capnpc::codegen::CodeGenerationCommand,
+                only_nodes: &mut Vec<String>,
+                option: &str) -> Result<(), String> {
+    if !option.starts_with("--") {
+        return Err(format!("expected an option of the form --name or --name=value, got {:?}", option));
+    }
+    let (name, value) = match option[2..].find('=') {
+        Some(index) => (&option[2..2 + index], Some(&option[3 + index..])),
+        None => (&option[2..], None),
+    };
+    let required = || value.ok_or_else(|| format!("option --{} requires a value", name));
+    let flag = || match value {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(other) => Err(format!("option --{} expects true or false, got {:?}", name, other)),
+    };
+    match name {
+        "output-directory" => { command.output_directory(required()?); }
+        "default-parent-module" => {
+            command.default_parent_module(required()?.split("::").map(|s| s.to_string()).collect());
+        }
+        "raw-code-generator-request-path" => { command.raw_code_generator_request_path(required()?); }
+        "entrypoint" => { command.entrypoint(required()?); }
+        "only-nodes" => only_nodes.extend(required()?.split(',').map(|s| s.to_string())),
+        "plain-structs" => { command.plain_structs(flag()?); }
+        "serde" => { command.serde(flag()?); }
+        "reader-equality" => { command.reader_equality(flag()?); }
+        "unknown-variants" => { command.unknown_variants(flag()?); }
+        "skip-interfaces" => { command.skip_interfaces(flag()?); }
+        "skip-structs" => { command.skip_structs(flag()?); }
+        "async-servers" => { command.async_servers(flag()?); }
+        "annotation-constants" => { command.annotation_constants(flag()?); }
+        "random-generators" => { command.random_generators(flag()?); }
+        _ => return Err(format!("unknown option --{}", name)),
+    }
+    Ok(())
+}
+
+/// Builds the code generation command from the whitespace-separated options in `env_options`
+/// followed by the options in `args`.
+fn code_generation_command(env_options: &str, args: &[String])
+                           -> Result<::capnpc::codegen::CodeGenerationCommand, String> {
+    let mut command = ::capnpc::codegen::CodeGenerationCommand::new();
+    command.output_directory(::std::path::Path::new("."));
+    let mut env_only_nodes = Vec::new();
+    for option in env_options.split_whitespace() {
+        apply_option(&mut command, &mut env_only_nodes, option)?;
+    }
+    let mut arg_only_nodes = Vec::new();
+    for arg in args {
+        apply_option(&mut command, &mut arg_only_nodes, arg)?;
+    }
+    let only_nodes = if arg_only_nodes.is_empty() { env_only_nodes } else { arg_only_nodes };
+    if !only_nodes.is_empty() {
+        command.only_nodes(only_nodes);
+    }
+    Ok(command)
+}
+
 pub fn main() {
     //! Generates Rust code according to a `schema_capnp::code_generator_request` read from stdin.
 
-    ::capnpc::codegen::CodeGenerationCommand::new()
-        .output_directory(::std::path::Path::new("."))
-        .run(::std::io::stdin())
-        .expect("failed to generate code");
+    let args: Vec<String> = ::std::env::args().collect();
+    if args.len() > 1 && args[1] == "check-compat" {
+        if args.len() != 4 {
+            eprintln!("usage: {} check-compat OLD_REQUEST NEW_REQUEST", args[0]);
+            ::std::process::exit(2);
+        }
+        ::std::process::exit(check_compat(&args[2], &args[3]));
+    }
+
+    let env_options = ::std::env::var("CAPNPC_RUST_OPTIONS").unwrap_or_default();
+    let mut command = match code_generation_command(&env_options, &args[1..]) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            ::std::process::exit(2);
+        }
+    };
+    command.run(::std::io::stdin()).expect("failed to generate code");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::code_generation_command;
+    use capnpc::codegen::CodeGenerationCommand;
+
+    #[test]
+    fn parse_options() {
+        let mut expected = CodeGenerationCommand::new();
+        expected.output_directory(".");
+        assert_eq!(code_generation_command("", &[]).unwrap(), expected);
+
+        // Arguments override the environment variable, including the nodes of --only-nodes.
+        let args = vec!["--serde=false".to_string(), "--only-nodes=Bar.Baz".to_string()];
+        expected
+            .default_parent_module(vec!["a".to_string(), "b".to_string()])
+            .serde(false)
+            .only_nodes(vec!["Bar.Baz".to_string()]);
+        assert_eq!(code_generation_command("--default-parent-module=a::b  --serde\n--only-nodes=Foo", &args).unwrap(),
+                   expected);
+
+        // Within the environment variable or the arguments, the nodes of --only-nodes add up.
+        expected
+            .serde(true)
+            .only_nodes(vec!["Foo".to_string(), "Bar.Baz".to_string()]);
+        assert_eq!(code_generation_command("--default-parent-module=a::b --only-nodes=Foo --serde --only-nodes=Bar.Baz", &[]).unwrap(),
+                   expected);
+        let args = vec!["--only-nodes=Foo".to_string(), "--only-nodes=Bar.Baz".to_string()];
+        assert_eq!(code_generation_command("--default-parent-module=a::b --serde --only-nodes=Qux", &args).unwrap(),
+                   expected);
+
+        let error = |options| code_generation_command(options, &[]).err().unwrap();
+        assert_eq!(error("--bogus"), "unknown option --bogus");
+        assert_eq!(error("serde"), "expected an option of the form --name or --name=value, got \"serde\"");
+        assert_eq!(error("--entrypoint"), "option --entrypoint requires a value");
+        assert_eq!(error("--serde=yes"), "option --serde expects true or false, got \"yes\"");
+    }
 }
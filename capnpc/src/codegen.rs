@@ -27,16 +27,41 @@ use capnp;
 use capnp::Error;
 
 use crate::{convert_io_err};
+use crate::annotation_constants::generate_annotation_constants;
+use crate::equality_impls::generate_reader_eq_and_hash;
+use crate::attributes::{ENUM_DERIVES, attribute_lines, derive_line, select_attributes, select_derives};
 use crate::pointer_constants::generate_pointer_constant;
+use crate::plain_structs::{generate_plain_struct, select_plain_structs};
+use crate::module_tree::generate_module_tree;
+use crate::random_impls::generate_fill_random;
+use crate::serde_impls::{generate_enum_impls, generate_struct_impls, select_serde_nodes};
+use crate::node_selection::{NodeSelection, select_nodes};
+use crate::unknown_variants::select_unknown_variants;
 use crate::schema_capnp;
-use crate::codegen_types::{ Leaf, RustTypeInfo, RustNodeInfo, TypeParameterTexts, do_branding };
+use crate::codegen_types::{ Leaf, RustTypeInfo, RustNodeInfo, TypeParameterTexts, do_branding, do_branding_in_method };
 use self::FormattedText::{Indent, Line, Branch, BlankLine};
 
+/// The version of the code that this crate generates, which the `capnp` runtime must support.
+/// See `capnp::generated_code_version`.
+pub const GENERATED_CODE_VERSION: u32 = 1;
+
 /// An invocation of the capnpc-rust code generation plugin.
+#[derive(Debug, PartialEq)]
 pub struct CodeGenerationCommand {
     output_directory: PathBuf,
     default_parent_module: Vec<String>,
     raw_code_generator_request_path: Option<PathBuf>,
+    plain_structs: bool,
+    serde: bool,
+    reader_equality: bool,
+    unknown_variants: bool,
+    only_nodes: Vec<String>,
+    skip_interfaces: bool,
+    skip_structs: bool,
+    async_servers: bool,
+    annotation_constants: bool,
+    random_generators: bool,
+    entrypoint: Option<PathBuf>,
 }
 
 impl CodeGenerationCommand {
@@ -46,6 +71,17 @@ impl CodeGenerationCommand {
             output_directory: PathBuf::new(),
             default_parent_module: Vec::new(),
             raw_code_generator_request_path: None,
+            plain_structs: false,
+            serde: false,
+            reader_equality: false,
+            unknown_variants: false,
+            only_nodes: Vec::new(),
+            skip_interfaces: false,
+            skip_structs: false,
+            async_servers: false,
+            annotation_constants: false,
+            random_generators: false,
+            entrypoint: None,
         }
     }
 
@@ -75,16 +111,144 @@ impl CodeGenerationCommand {
         self
     }
 
+    /// Sets whether to generate a plain owned `Plain` struct alongside the `Reader` and `Builder`
+    /// of each struct, with `from_reader()` and `write_into()` conversions. Text becomes `String`,
+    /// data `Vec<u8>`, lists `Vec<T>`, and unions a `PlainWhich` enum. Structs that cannot be
+    /// represented this way, such as generic structs or those containing interfaces, are skipped.
+    ///
+    /// This option can be overridden for individual files and structs by the `plain` annotation
+    /// defined in `rust.capnp`.
+    pub fn plain_structs(&mut self, plain_structs: bool) -> &mut Self
+    {
+        self.plain_structs = plain_structs;
+        self
+    }
+
+    /// Sets whether to generate serde support: an implementation of `serde::Serialize` for the
+    /// `Reader` of each struct and of `serde::de::DeserializeSeed` for its `Builder`, as well as
+    /// `Serialize` and `Deserialize` for enums. The generated code requires the `serde` feature
+    /// of the `capnp` crate. Generic structs, and fields of interface and `AnyPointer` types,
+    /// are not supported and are skipped.
+    pub fn serde(&mut self, serde: bool) -> &mut Self
+    {
+        self.serde = serde;
+        self
+    }
+
+    /// Sets whether to implement `PartialEq` and `Hash` for the `Reader` of each struct. Two
+    /// readers are equal if their fields are: union members are only compared while active,
+    /// floats are compared by their bits, and unset pointer fields are equal to their defaults.
+    /// Interface and `AnyPointer` fields are compared by their encoding.
+    pub fn reader_equality(&mut self, reader_equality: bool) -> &mut Self
+    {
+        self.reader_equality = reader_equality;
+        self
+    }
+
+    /// Sets whether to give each enum, and the `Which` enum of each union, an `Unknown(u16)`
+    /// variant that holds discriminants not listed in the schema. Such values can then be read
+    /// and written back unchanged, rather than causing `NotInSchema` errors. Builders of
+    /// structs with such a union get a `set_which_unknown()` method to write the discriminant.
+    ///
+    /// This option can be overridden for individual files, structs and enums by the
+    /// `unknownVariants` annotation defined in `rust.capnp`.
+    pub fn unknown_variants(&mut self, unknown_variants: bool) -> &mut Self
+    {
+        self.unknown_variants = unknown_variants;
+        self
+    }
+
+    /// Restricts code generation to the given nodes, and the nodes that their generated code
+    /// refers to, such as the types of struct fields and the parameters of interface methods.
+    /// Nodes can be given by ID (`"0xa4c9e2d1b3f70e85"`), by display name
+    /// (`"foo.capnp:Foo.Bar"`), or by name within their file (`"Foo.Bar"`). The modules of
+    /// enclosing structs and interfaces are still generated, but only contain the selected
+    /// nodes. Code for a node in another file is only generated if that file is also requested.
+    pub fn only_nodes(&mut self, only_nodes: Vec<String>) -> &mut Self
+    {
+        self.only_nodes = only_nodes;
+        self
+    }
+
+    /// Sets whether to skip generating code for interfaces, for crates that do not use RPC.
+    /// Interfaces that are used by the generated structs, as the types of their fields, are
+    /// still generated.
+    pub fn skip_interfaces(&mut self, skip_interfaces: bool) -> &mut Self
+    {
+        self.skip_interfaces = skip_interfaces;
+        self
+    }
+
+    /// Sets whether to skip generating code for structs, enums and constants, for crates that
+    /// only implement or call interfaces. Those that are used by the generated interfaces, as
+    /// method parameters and results and their fields, are still generated.
+    pub fn skip_structs(&mut self, skip_structs: bool) -> &mut Self
+    {
+        self.skip_structs = skip_structs;
+        self
+    }
+
+    /// Sets whether to generate, for each interface, an `AsyncServer` trait alongside the
+    /// `Server` trait. Its methods take `&self` and return a boxed future that may borrow the
+    /// server, so they can be written as `Box::pin(async move { ... })` blocks that use `?`
+    /// to return errors early. Wrapping an implementation in
+    /// `::capnp::capability::FromAsync::new()` gives a `Server`, from which a client can be
+    /// made as usual. The superclasses of an interface must be generated with this option too,
+    /// because its `FromAsync` adapter dispatches their methods through their `AsyncServer`
+    /// traits.
+    pub fn async_servers(&mut self, async_servers: bool) -> &mut Self
+    {
+        self.async_servers = async_servers;
+        self
+    }
+
+    /// Sets whether to generate constants holding the values of the annotations applied to
+    /// structs, fields, enums, enumerants, interfaces and methods. They go in an `_annotations`
+    /// module inside the module of the annotated struct or interface, or in a `foo_annotations`
+    /// module next to an enum `Foo`, with one nested module per annotated member. Interfaces and
+    /// enums also get functions like `bar_for_method(method_id)` that look up a member
+    /// annotation at runtime, for example while dispatching a call. Annotations of interface
+    /// type, or whose type is a generic parameter, are skipped.
+    pub fn annotation_constants(&mut self, annotation_constants: bool) -> &mut Self
+    {
+        self.annotation_constants = annotation_constants;
+        self
+    }
+
+    /// Sets whether to generate an implementation of `capnp::random::FillRandom` for the `Owned`
+    /// type of each struct, which fills in a builder with random values, so that
+    /// `capnp::random::RandomMessage` can produce random messages of the struct for quickcheck or
+    /// cargo-fuzz. Generic structs are left out, as are interface and `AnyPointer` fields. The
+    /// option must be set for the files that the generated files import, too.
+    pub fn random_generators(&mut self, random_generators: bool) -> &mut Self
+    {
+        self.random_generators = random_generators;
+        self
+    }
+
+    /// Sets the name of an entrypoint file, relative to the output directory, to generate next
+    /// to the code for the requested files. The entrypoint declares the module tree that the
+    /// generated code expects, according to the default parent module and the `parentModule`
+    /// annotations, and includes the code for each file in its module. The crate then only needs
+    /// to include the entrypoint at its root, for example with
+    /// `include!(concat!(env!("OUT_DIR"), "/capnp_generated.rs"));`.
+    pub fn entrypoint<P>(&mut self, file_name: P) -> &mut Self
+        where P: AsRef<Path>
+    {
+        self.entrypoint = Some(file_name.as_ref().to_path_buf());
+        self
+    }
+
     /// Generates Rust code according to a `schema_capnp::code_generator_request` read from `inp`.
     pub fn run<T>(&mut self, inp: T) -> ::capnp::Result<()>
         where T: std::io::Read
     {
         use capnp::serialize;
-        use std::io::Write;
 
         let message = serialize::read_message(ReadWrapper { inner: inp }, capnp::message::ReaderOptions::new())?;
 
-        let gen = GeneratorContext::new_with_default_parent_module(&self.default_parent_module[..], &message)?;
+        let gen = GeneratorContext::new_with_options(self, &message)?;
+        let mut entrypoint_files = Vec::new();
 
         for requested_file in gen.request.get_requested_files()?.iter() {
             let id = requested_file.get_id();
@@ -103,31 +267,33 @@ impl CodeGenerationCommand {
                 Line("// DO NOT EDIT.".to_string()),
                 Line(format!("// source: {}", requested_file.get_filename()?)),
                 BlankLine,
+                Line("// Fails to compile if the `capnp` crate does not support this code. Use matching versions of".to_string()),
+                Line("// `capnp` and `capnpc`.".to_string()),
+                Line(format!("const _: () = ::capnp::generated_code_version::V{};", GENERATED_CODE_VERSION)),
                 generate_node(&gen, id, &root_name, None)?));
 
-            let text = stringify(&lines);
+            write_file(&filepath, &stringify(&lines))?;
 
-            let previous_text = ::std::fs::read(&filepath);
-            if previous_text.is_ok() && previous_text.unwrap() == text.as_bytes() {
-                // File is unchanged. Do not write it so that builds with the
-                // output as part of the source work in read-only filesystems
-                // and so timestamp-based build systems and watchers do not get
-                // confused.
-                continue;
+            if let Some(entrypoint) = &self.entrypoint {
+                // Paths in `include!()` are relative to the including file.
+                let mut path: Vec<String> = entrypoint.parent().into_iter()
+                    .flat_map(|parent| parent.components()).map(|_| "..".to_string()).collect();
+                let relative = filepath.strip_prefix(&self.output_directory)
+                    .map_err(|e| Error::failed(format!("{}", e)))?;
+                for component in relative.components() {
+                    path.push(component.as_os_str().to_string_lossy().into_owned());
+                }
+                entrypoint_files.push((id, path.join("/")));
             }
+        }
 
-            // It would be simpler to use the ? operator instead of a pattern match, but then the error message
-            // would not include `filepath`.
-            match ::std::fs::File::create(&filepath) {
-                Ok(ref mut writer) => {
-                    writer.write_all(text.as_bytes()).map_err(convert_io_err)?;
-                }
-                Err(e) => {
-                    let _ = writeln!(&mut ::std::io::stderr(),
-                                     "could not open file {:?} for writing: {}", filepath, e);
-                    return Err(convert_io_err(e));
-                }
+        if let Some(entrypoint) = &self.entrypoint {
+            let mut filepath = self.output_directory.to_path_buf();
+            filepath.push(entrypoint);
+            if let Some(parent) = filepath.parent() {
+                ::std::fs::create_dir_all(parent).map_err(convert_io_err)?;
             }
+            write_file(&filepath, &stringify(&generate_module_tree(&gen, &entrypoint_files)?))?;
         }
 
         if let Some(raw_code_generator_request) = &self.raw_code_generator_request_path {
@@ -143,6 +309,17 @@ pub struct GeneratorContext<'a> {
     pub request: schema_capnp::code_generator_request::Reader<'a>,
     pub node_map: collections::hash_map::HashMap<u64, schema_capnp::node::Reader<'a>>,
     pub scope_map: collections::hash_map::HashMap<u64, Vec<String>>,
+    pub(crate) plain_structs: HashSet<u64>,
+    pub(crate) serde_nodes: HashSet<u64>,
+    pub(crate) random_nodes: HashSet<u64>,
+    pub(crate) unknown_variants: HashSet<u64>,
+    pub(crate) derives: HashMap<u64, Vec<String>>,
+    pub(crate) async_servers: bool,
+    pub(crate) annotation_constants: bool,
+    reader_equality: bool,
+    attributes: HashMap<u64, Vec<String>>,
+    node_selection: Option<NodeSelection>,
+    source_info: HashMap<u64, schema_capnp::node::source_info::Reader<'a>>,
 }
 
 impl <'a> GeneratorContext<'a> {
@@ -150,27 +327,45 @@ impl <'a> GeneratorContext<'a> {
         message:&'a capnp::message::Reader<capnp::serialize::OwnedSegments>)
         -> ::capnp::Result<GeneratorContext<'a>>
     {
-        GeneratorContext::new_with_default_parent_module(&[], message)
+        GeneratorContext::new_with_options(&CodeGenerationCommand::new(), message)
     }
 
-    fn new_with_default_parent_module(
-        default_parent_module: &[String],
+    fn new_with_options(
+        command: &CodeGenerationCommand,
         message:&'a capnp::message::Reader<capnp::serialize::OwnedSegments>)
         -> ::capnp::Result<GeneratorContext<'a>>
     {
         let mut default_parent_module_scope = vec!["crate".to_string()];
-        default_parent_module_scope.extend_from_slice(default_parent_module);
+        default_parent_module_scope.extend_from_slice(&command.default_parent_module);
 
         let mut gen = GeneratorContext {
             request : message.get_root()?,
             node_map: collections::hash_map::HashMap::<u64, schema_capnp::node::Reader<'a>>::new(),
             scope_map: collections::hash_map::HashMap::<u64, Vec<String>>::new(),
+            plain_structs: HashSet::new(),
+            serde_nodes: HashSet::new(),
+            random_nodes: HashSet::new(),
+            unknown_variants: HashSet::new(),
+            derives: HashMap::new(),
+            async_servers: command.async_servers,
+            annotation_constants: command.annotation_constants,
+            reader_equality: command.reader_equality,
+            attributes: HashMap::new(),
+            node_selection: None,
+            source_info: HashMap::new(),
         };
 
         for node in gen.request.get_nodes()?.iter() {
             gen.node_map.insert(node.get_id(), node);
         }
 
+        // Older versions of the schema compiler do not send any source info.
+        if gen.request.has_source_info() {
+            for info in gen.request.get_source_info()?.iter() {
+                gen.source_info.insert(info.get_id(), info);
+            }
+        }
+
         for requested_file in gen.request.get_requested_files()?.iter() {
             let id = requested_file.get_id();
 
@@ -197,10 +392,46 @@ impl <'a> GeneratorContext<'a> {
                                NameKind::Verbatim,
                                id)?;
         }
+
+        gen.plain_structs = select_plain_structs(gen.request, &gen.node_map, command.plain_structs)?;
+        gen.unknown_variants = select_unknown_variants(gen.request, &gen.node_map, command.unknown_variants)?;
+        gen.derives = select_derives(gen.request, &gen.node_map, &gen.plain_structs)?;
+        gen.attributes = select_attributes(gen.request, &gen.node_map)?;
+        gen.node_selection = select_nodes(gen.request, &gen.node_map, &command.only_nodes,
+                                          command.skip_interfaces, command.skip_structs)?;
+        if command.serde {
+            gen.serde_nodes = select_serde_nodes(gen.request, &gen.node_map)?;
+        }
+        if command.random_generators {
+            // The same non-generic structs as for serde get generators.
+            gen.random_nodes = select_serde_nodes(gen.request, &gen.node_map)?;
+        }
         Ok(gen)
     }
 
-    fn get_last_name<'b>(&'b self, id: u64) -> ::capnp::Result<&'b str> {
+    /// Returns the doc comment attached to the node, formatted as `///` lines.
+    pub(crate) fn node_doc_comment(&self, id: u64) -> ::capnp::Result<FormattedText> {
+        match self.source_info.get(&id) {
+            Some(info) if info.has_doc_comment() => Ok(doc_comment(info.get_doc_comment()?)),
+            _ => Ok(Branch(Vec::new())),
+        }
+    }
+
+    /// Returns the doc comment attached to a field, enumerant, or method of the node.
+    /// `index` is the member's position in the node's list of members.
+    pub(crate) fn member_doc_comment(&self, id: u64, index: u32) -> ::capnp::Result<FormattedText> {
+        if let Some(info) = self.source_info.get(&id) {
+            if info.has_members() {
+                let members = info.get_members()?;
+                if index < members.len() && members.get(index).has_doc_comment() {
+                    return Ok(doc_comment(members.get(index).get_doc_comment()?));
+                }
+            }
+        }
+        Ok(Branch(Vec::new()))
+    }
+
+    pub(crate) fn get_last_name(&self, id: u64) -> ::capnp::Result<&str> {
         match self.scope_map.get(&id) {
             None => Err(Error::failed(format!("node not found: {}", id))),
             Some(v) => match v.last() {
@@ -211,6 +442,34 @@ impl <'a> GeneratorContext<'a> {
     }
 }
 
+/// Writes `text` to `filepath`, unless the file already holds it.
+fn write_file(filepath: &Path, text: &str) -> ::capnp::Result<()> {
+    use std::io::Write;
+
+    let previous_text = ::std::fs::read(filepath);
+    if previous_text.is_ok() && previous_text.unwrap() == text.as_bytes() {
+        // File is unchanged. Do not write it so that builds with the
+        // output as part of the source work in read-only filesystems
+        // and so timestamp-based build systems and watchers do not get
+        // confused.
+        return Ok(());
+    }
+
+    // It would be simpler to use the ? operator instead of a pattern match, but then the error message
+    // would not include `filepath`.
+    match ::std::fs::File::create(filepath) {
+        Ok(ref mut writer) => {
+            writer.write_all(text.as_bytes()).map_err(convert_io_err)?;
+        }
+        Err(e) => {
+            let _ = writeln!(&mut ::std::io::stderr(),
+                             "could not open file {:?} for writing: {}", filepath, e);
+            return Err(convert_io_err(e));
+        }
+    }
+    Ok(())
+}
+
 fn path_to_stem_string<P: AsRef<::std::path::Path>>(path: P) -> ::capnp::Result<String> {
     match path.as_ref().file_stem() {
         None => Err(Error::failed(format!("file has no stem: {:?}", path.as_ref()))),
@@ -223,7 +482,7 @@ fn path_to_stem_string<P: AsRef<::std::path::Path>>(path: P) -> ::capnp::Result<
     }
 }
 
-fn snake_to_upper_case(s: &str) -> String {
+pub(crate) fn snake_to_upper_case(s: &str) -> String {
     let mut result_chars: Vec<char> = Vec::new();
     for c in s.chars() {
         if c == '_' {
@@ -235,7 +494,7 @@ fn snake_to_upper_case(s: &str) -> String {
     result_chars.into_iter().collect()
 }
 
-fn camel_to_snake_case(s: &str) -> String {
+pub(crate) fn camel_to_snake_case(s: &str) -> String {
     let mut result_chars: Vec<char> = Vec::new();
     let mut first_char = true;
     for c in s.chars() {
@@ -248,7 +507,7 @@ fn camel_to_snake_case(s: &str) -> String {
     result_chars.into_iter().collect()
 }
 
-fn capitalize_first_letter(s: &str) -> String {
+pub(crate) fn capitalize_first_letter(s: &str) -> String {
     let mut result_chars: Vec<char> = Vec::new();
     for c in s.chars() { result_chars.push(c) }
     result_chars[0] = result_chars[0].to_ascii_uppercase();
@@ -322,6 +581,66 @@ fn to_lines(ft : &FormattedText, indent : usize) -> Vec<String> {
     }
 }
 
+/// Formats a schema doc comment as `///` lines. Code blocks are marked as `text` so that
+/// rustdoc does not try to compile them as doctests.
+fn doc_comment(doc: &str) -> FormattedText {
+    fn is_indented_code(line: &str) -> bool {
+        line.len() - line.trim_start().len() >= 4
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_fence = false;
+    let mut in_indented_code = false;
+    for line in doc.lines() {
+        let line = line.trim_end();
+        if in_indented_code && !line.is_empty() && !is_indented_code(line) {
+            let trailing_blanks = lines.iter().rev().take_while(|l| l.is_empty()).count();
+            lines.truncate(lines.len() - trailing_blanks);
+            lines.push("```".to_string());
+            for _ in 0..trailing_blanks {
+                lines.push(String::new());
+            }
+            in_indented_code = false;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            if !in_fence && trimmed == "```" {
+                lines.push(format!("{}```text", &line[..line.len() - trimmed.len()]));
+            } else {
+                lines.push(line.to_string());
+            }
+            in_fence = !in_fence;
+        } else if !in_fence && !in_indented_code && is_indented_code(line) &&
+            lines.last().map(|l| l.is_empty()).unwrap_or(true)
+        {
+            lines.push("```text".to_string());
+            lines.push(line.to_string());
+            in_indented_code = true;
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    while lines.last().map(|l| l.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+    if in_indented_code || in_fence {
+        lines.push("```".to_string());
+    }
+
+    Branch(lines.into_iter().map(|line| {
+        if line.is_empty() { Line("///".to_string()) } else { Line(format!("/// {}", line)) }
+    }).collect())
+}
+
+#[test]
+fn test_doc_comment() {
+    assert_eq!(stringify(&doc_comment("Foo.\n\nBar.\n")), "/// Foo.\n///\n/// Bar.\n");
+    assert_eq!(stringify(&doc_comment("Example:\n\n    foo @0 :Text;\n\n    bar @1 :Text;\n\nMore.\n")),
+               "/// Example:\n///\n/// ```text\n///     foo @0 :Text;\n///\n///     bar @1 :Text;\n/// ```\n///\n/// More.\n");
+    assert_eq!(stringify(&doc_comment("```\nfoo\n```\n  ```capnp\n  bar\n  ```")),
+               "/// ```text\n/// foo\n/// ```\n///   ```capnp\n///   bar\n///   ```\n");
+}
+
 fn stringify(ft: &FormattedText) -> String {
     let mut result = to_lines(ft, 0).join("\n");
     result.push_str("\n");
@@ -341,7 +660,7 @@ const RUST_KEYWORDS : [&'static str; 53] =
      "typeof", "unsafe", "unsized", "use", "virtual",
      "where", "while", "yield"];
 
-fn module_name(camel_case: &str) -> String {
+pub(crate) fn module_name(camel_case: &str) -> String {
     let mut name = camel_to_snake_case(camel_case);
     if RUST_KEYWORDS.contains(&&*name) {
         name.push('_');
@@ -367,7 +686,7 @@ fn name_annotation_value(annotation: schema_capnp::annotation::Reader) -> capnp:
     }
 }
 
-fn get_field_name(field: schema_capnp::field::Reader) -> capnp::Result<&str> {
+pub(crate) fn get_field_name(field: schema_capnp::field::Reader) -> capnp::Result<&str> {
     for annotation in field.get_annotations()?.iter() {
         if annotation.get_id() == NAME_ANNOTATION_ID {
             return name_annotation_value(annotation);
@@ -376,7 +695,7 @@ fn get_field_name(field: schema_capnp::field::Reader) -> capnp::Result<&str> {
     field.get_name()
 }
 
-fn get_enumerant_name(enumerant: schema_capnp::enumerant::Reader) -> capnp::Result<&str> {
+pub(crate) fn get_enumerant_name(enumerant: schema_capnp::enumerant::Reader) -> capnp::Result<&str> {
     for annotation in enumerant.get_annotations()?.iter() {
         if annotation.get_id() == NAME_ANNOTATION_ID {
             return name_annotation_value(annotation);
@@ -394,6 +713,87 @@ fn get_parent_module(annotation: schema_capnp::annotation::Reader) -> capnp::Res
     }
 }
 
+/// Returns the value of the Bool annotation with ID `id` among `annotations`, if present. `name`
+/// is the annotation's name in rust.capnp, for the error message.
+pub(crate) fn bool_annotation(annotations: ::capnp::struct_list::Reader<schema_capnp::annotation::Owned>,
+                              id: u64,
+                              name: &str) -> capnp::Result<Option<bool>> {
+    for annotation in annotations.iter() {
+        if annotation.get_id() == id {
+            return if let schema_capnp::value::Bool(b) = annotation.get_value()?.which()? {
+                Ok(Some(b))
+            } else {
+                Err(capnp::Error::failed(format!("expected rust.{} annotation value to be of type Bool", name)))
+            };
+        }
+    }
+    Ok(None)
+}
+
+/// Looks up the node with ID `id`. Unused nodes in imported files might be omitted from the node
+/// map, so a missing node is not an error.
+pub(crate) fn find_node<'a>(node_map: &HashMap<u64, schema_capnp::node::Reader<'a>>, id: u64)
+                            -> Option<schema_capnp::node::Reader<'a>>
+{
+    node_map.get(&id).copied()
+}
+
+/// How `walk_scope()` reached a node from the node whose scope it is in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reached {
+    /// A node nested in a file, struct or interface.
+    Nested,
+    /// A group of a struct.
+    Group,
+    /// The implicit Params or Results struct of an interface method.
+    MethodStruct,
+}
+
+/// Walks the nodes in the scope of the node `node_id`: its nested nodes, the groups of a struct
+/// and the implicit Params and Results structs of an interface's methods, and recursively the
+/// nodes in their scopes. `visit` is called for each of them with the value that it returned
+/// for the node whose scope it is in, or `value` for the nodes in the scope of `node_id`. The
+/// scope of a node is not walked if `visit` returns `None` for it.
+pub(crate) fn walk_scope<'a, T, F>(node_map: &HashMap<u64, schema_capnp::node::Reader<'a>>,
+                                   node_id: u64,
+                                   value: &T,
+                                   visit: &mut F) -> capnp::Result<()>
+    where F: FnMut(u64, schema_capnp::node::Reader<'a>, Reached, &T) -> capnp::Result<Option<T>>
+{
+    let node_reader = match find_node(node_map, node_id) { Some(node) => node, None => return Ok(()), };
+    let mut scope = Vec::new();
+    for nested_node in node_reader.get_nested_nodes()?.iter() {
+        scope.push((nested_node.get_id(), Reached::Nested));
+    }
+    match node_reader.which()? {
+        schema_capnp::node::Struct(struct_reader) => {
+            for field in struct_reader.get_fields()?.iter() {
+                if let schema_capnp::field::Group(group) = field.which()? {
+                    scope.push((group.get_type_id(), Reached::Group));
+                }
+            }
+        }
+        schema_capnp::node::Interface(interface_reader) => {
+            // The implicit Params and Results structs of a method have no scope of their own.
+            for method in interface_reader.get_methods()?.iter() {
+                for &id in &[method.get_param_struct_type(), method.get_result_struct_type()] {
+                    if find_node(node_map, id).map(|node| node.get_scope_id()) == Some(0) {
+                        scope.push((id, Reached::MethodStruct));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    for (id, reached) in scope {
+        let node = match find_node(node_map, id) { Some(node) => node, None => continue, };
+        if let Some(inner_value) = visit(id, node, reached, value)? {
+            walk_scope(node_map, id, &inner_value, visit)?;
+        }
+    }
+    Ok(())
+}
+
 enum NameKind {
     // convert camel case to snake case, and avoid Rust keywords
     Module,
@@ -415,8 +815,7 @@ fn populate_scope_map(node_map: &collections::hash_map::HashMap<u64, schema_capn
                       mut current_node_name: String,
                       current_name_kind: NameKind,
                       node_id: u64) -> ::capnp::Result<()> {
-    // unused nodes in imported files might be omitted from the node map
-    let node_reader = match node_map.get(&node_id) { Some(node) => node, None => return Ok(()), };
+    let node_reader = match find_node(node_map, node_id) { Some(node) => node, None => return Ok(()), };
 
     for annotation in node_reader.get_annotations()?.iter() {
         if annotation.get_id() == NAME_ANNOTATION_ID {
@@ -514,7 +913,7 @@ fn prim_default(value: &schema_capnp::value::Reader) -> ::capnp::Result<Option<S
 }
 
 // Gets the full list ordered of generic parameters for a node. Outer scopes come first.
-fn get_params(gen: &GeneratorContext,
+pub(crate) fn get_params(gen: &GeneratorContext,
               mut node_id: u64) -> ::capnp::Result<Vec<String>> {
     let mut result = Vec::new();
 
@@ -762,7 +1161,8 @@ fn zero_fields_of_group(gen: &GeneratorContext, node_id: u64) -> ::capnp::Result
 
 fn generate_setter(gen: &GeneratorContext, discriminant_offset: u32,
                    styled_name: &str,
-                   field: &schema_capnp::field::Reader) -> ::capnp::Result<FormattedText> {
+                   field: &schema_capnp::field::Reader,
+                   doc: &FormattedText) -> ::capnp::Result<FormattedText> {
 
     use crate::schema_capnp::*;
 
@@ -771,6 +1171,9 @@ fn generate_setter(gen: &GeneratorContext, discriminant_offset: u32,
     let mut initter_interior = Vec::new();
     let mut initn_interior = Vec::new();
     let mut initter_params = Vec::new();
+    // For lists of primitives, enums, text and data: the element types taken by
+    // `set_foo_from_slice()` and `set_foo_from_iter()`, and the offset of the list.
+    let mut list_setters: Option<(String, String, usize)> = None;
 
     let discriminant_value = field.get_discriminant_value();
     if discriminant_value != field::NO_DISCRIMINANT {
@@ -867,6 +1270,23 @@ fn generate_setter(gen: &GeneratorContext, discriminant_offset: u32,
                     initter_interior.push(
                         Line(format!("::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field({}), size)", offset)));
 
+                    let element_type = ot1.get_element_type()?;
+                    list_setters = match element_type.which()? {
+                        type_::Text(()) =>
+                            Some(("::capnp::text::Reader<'_>".to_string(), "::capnp::text::Reader<'b>".to_string(), offset)),
+                        type_::Data(()) =>
+                            Some(("::capnp::data::Reader<'_>".to_string(), "::capnp::data::Reader<'b>".to_string(), offset)),
+                        type_::Enum(e) => {
+                            let the_mod = gen.scope_map[&e.get_type_id()].join("::");
+                            Some((the_mod.clone(), the_mod, offset))
+                        }
+                        _ if element_type.is_prim()? => {
+                            let tstr = element_type.type_string(gen, Leaf::Reader("'a"))?;
+                            Some((tstr.clone(), tstr, offset))
+                        }
+                        _ => None,
+                    };
+
                     match ot1.get_element_type()?.which()? {
                         type_::List(_) => {
                             (Some(reg_field.get_type()?.type_string(gen, Leaf::Reader("'_"))?),
@@ -881,7 +1301,7 @@ fn generate_setter(gen: &GeneratorContext, discriminant_offset: u32,
                     let id = e.get_type_id();
                     let the_mod = gen.scope_map[&id].join("::");
                     setter_interior.push(
-                        Line(format!("self.builder.set_data_field::<u16>({}, value as u16)",
+                        Line(format!("self.builder.set_data_field::<u16>({}, ::capnp::traits::ToU16::to_u16(value))",
                                      offset)));
                     (Some(format!("{}", the_mod)), None)
                 }
@@ -945,6 +1365,7 @@ fn generate_setter(gen: &GeneratorContext, discriminant_offset: u32,
     match maybe_reader_type {
         Some(ref reader_type) => {
             let return_type = if return_result { "-> ::capnp::Result<()>" } else { "" };
+            result.push(doc.clone());
             result.push(Line("#[inline]".to_string()));
             result.push(Line(format!("pub fn set_{}(&mut self, {}: {}) {} {{",
                                      styled_name, setter_param,
@@ -954,8 +1375,44 @@ fn generate_setter(gen: &GeneratorContext, discriminant_offset: u32,
         }
         None => {}
     }
+    if let Some((slice_element, iter_element, offset)) = list_setters {
+        let set_line = Line(format!(
+            "::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field({}), values, false)",
+            offset));
+        let mut slice_interior = Vec::new();
+        let mut iter_interior = Vec::new();
+        if discriminant_value != field::NO_DISCRIMINANT {
+            let set_discriminant = Line(format!("self.builder.set_data_field::<u16>({}, {});",
+                                                discriminant_offset as usize,
+                                                discriminant_value as usize));
+            slice_interior.push(set_discriminant.clone());
+            iter_interior.push(set_discriminant);
+        }
+        slice_interior.push(set_line.clone());
+        iter_interior.push(Line(format!(
+            "let values: ::capnp::private::alloc::Vec<{}> = values.into_iter().collect();", iter_element)));
+        iter_interior.push(set_line);
+
+        let lifetime = if iter_element.contains("'b") { "'b, " } else { "" };
+        result.push(doc.clone());
+        result.push(Line("#[inline]".to_string()));
+        result.push(Line(format!("pub fn set_{}_from_slice(&mut self, values: &[{}]) -> ::capnp::Result<()> {{",
+                                 styled_name, slice_element)));
+        result.push(Indent(Box::new(Branch(slice_interior))));
+        result.push(Line("}".to_string()));
+        result.push(doc.clone());
+        result.push(Line("#[inline]".to_string()));
+        result.push(Line(format!("pub fn set_{}_from_iter<{}I>(&mut self, values: I) -> ::capnp::Result<()>",
+                                 styled_name, lifetime)));
+        result.push(Indent(Box::new(Line(format!(
+            "where I: ::core::iter::IntoIterator<Item = {}>", iter_element)))));
+        result.push(Line("{".to_string()));
+        result.push(Indent(Box::new(Branch(iter_interior))));
+        result.push(Line("}".to_string()));
+    }
     match maybe_builder_type {
         Some(builder_type) => {
+            result.push(doc.clone());
             result.push(Line("#[inline]".to_string()));
             let args = initter_params.join(", ");
             result.push(Line(format!("pub fn init_{}(self, {}) -> {} {{",
@@ -1082,6 +1539,8 @@ fn used_params_of_brand(gen: &GeneratorContext,
 fn generate_union(gen: &GeneratorContext,
                   discriminant_offset: u32,
                   fields: &[schema_capnp::field::Reader],
+                  docs: &[FormattedText],
+                  unknown_variant: bool,
                   is_reader: bool,
                   params: &TypeParameterTexts)
                   -> ::capnp::Result<(FormattedText, FormattedText, FormattedText, Vec<FormattedText>)>
@@ -1106,7 +1565,7 @@ fn generate_union(gen: &GeneratorContext,
 
     let doffset = discriminant_offset as usize;
 
-    for field in fields.iter() {
+    for (field, doc) in fields.iter().zip(docs) {
 
         let dvalue = field.get_discriminant_value() as usize;
 
@@ -1151,6 +1610,7 @@ fn generate_union(gen: &GeneratorContext,
             _ => ty
         };
 
+        enum_interior.push(doc.clone());
         enum_interior.push(Line(format!("{}({}),", enumerant_name, ty1)));
     }
 
@@ -1159,7 +1619,13 @@ fn generate_union(gen: &GeneratorContext,
                             else {"".to_string()} );
 
 
-    getter_interior.push(Line("x => ::core::result::Result::Err(::capnp::NotInSchema(x))".to_string()));
+    if unknown_variant {
+        enum_interior.push(Line("/// A member of the union that is not in the schema known to this code.".to_string()));
+        enum_interior.push(Line("Unknown(u16),".to_string()));
+        getter_interior.push(Line("x => ::core::result::Result::Ok(Unknown(x))".to_string()));
+    } else {
+        getter_interior.push(Line("x => ::core::result::Result::Err(::capnp::NotInSchema(x))".to_string()));
+    }
 
     interior.push(
         Branch(vec!(Line(format!("pub enum {} {{", enum_name)),
@@ -1170,17 +1636,20 @@ fn generate_union(gen: &GeneratorContext,
 
     let field_name = if is_reader { "reader" } else { "builder" };
 
-    let concrete_type =
-            format!("Which{}{}",
-                    if is_reader {"Reader"} else {"Builder"},
-                    if ty_params.len() > 0 {
-                        format!("<'a,{}>",
-                                params.expanded_list
+    // The type of `which()`, borrowing from the message for `lifetime`.
+    let concrete_type_with_lifetime = |lifetime: &str| {
+        format!("Which{}{}",
+                if is_reader {"Reader"} else {"Builder"},
+                if ty_params.len() > 0 {
+                    let mut args = vec![lifetime.to_string()];
+                    args.extend(params.expanded_list
                                 .iter()
                                 .filter(|s: &&String| used_params.contains(*s))
-                                .map(|s| s.clone())
-                                .collect::<Vec<String>>().join(","))
-                    } else { "".to_string() });
+                                .cloned());
+                    format!("<{}>", args.join(","))
+                } else { "".to_string() })
+    };
+    let concrete_type = concrete_type_with_lifetime("'a");
 
     let typedef =
         Line(format!("pub type {} = Which{};",
@@ -1188,6 +1657,17 @@ fn generate_union(gen: &GeneratorContext,
                      if ty_args.len() > 0 {format!("<{}>",
                                                    ty_args.join(","))} else {"".to_string()}));
 
+    let which_mut = if is_reader {
+        Branch(Vec::new())
+    } else {
+        // Like `which()`, but borrows the builder instead of consuming it.
+        Branch(vec!(Line("#[inline]".to_string()),
+                    Line(format!("pub fn which_mut(&mut self) -> ::core::result::Result<{}, ::capnp::NotInSchema> {{",
+                                 concrete_type_with_lifetime("'_"))),
+                    Indent(Box::new(Line("self.reborrow().which()".to_string()))),
+                    Line("}".to_string())))
+    };
+
     let getter_result =
         Branch(vec!(Line("#[inline]".to_string()),
                     Line(format!("pub fn which(self) -> ::core::result::Result<{}, ::capnp::NotInSchema> {{",
@@ -1196,7 +1676,8 @@ fn generate_union(gen: &GeneratorContext,
                         Line(format!("match self.{}.get_data_field::<u16>({}) {{", field_name, doffset)),
                         Indent(Box::new(Branch(getter_interior))),
                         Line("}".to_string()))))),
-                    Line("}".to_string())));
+                    Line("}".to_string()),
+                    which_mut));
 
     // TODO set_which() for builders?
 
@@ -1245,6 +1726,55 @@ fn generate_haser(discriminant_offset: u32,
     Ok(Branch(result))
 }
 
+/// Generates `is_foo()`, which tells whether the union field `foo` is the one that is set.
+fn generate_union_predicate(discriminant_offset: u32,
+                            styled_name: &str,
+                            field: &schema_capnp::field::Reader,
+                            is_reader: bool) -> FormattedText {
+    let discriminant_value = field.get_discriminant_value();
+    if discriminant_value == schema_capnp::field::NO_DISCRIMINANT {
+        return Branch(Vec::new());
+    }
+    let member = if is_reader { "reader" } else { "builder" };
+    Branch(vec!(
+        Line("#[inline]".to_string()),
+        Line(format!("pub fn is_{}(&self) -> bool {{", styled_name)),
+        Indent(Box::new(Line(format!("self.{}.get_data_field::<u16>({}) == {}",
+                                     member, discriminant_offset as usize, discriminant_value as usize)))),
+        Line("}".to_string())))
+}
+
+/// Generates `clear_foo()` for a pointer field, which zeroes the object that the field points
+/// to and sets the pointer to null. For a union field, it also makes the field the one that
+/// is set.
+fn generate_clearer(discriminant_offset: u32,
+                    styled_name: &str,
+                    field: &schema_capnp::field::Reader) -> ::capnp::Result<FormattedText> {
+    use crate::schema_capnp::*;
+
+    let offset = match field.which()? {
+        field::Group(_) => return Ok(Branch(Vec::new())),
+        field::Slot(reg_field) => match reg_field.get_type()?.which()? {
+            type_::Text(()) | type_::Data(()) | type_::List(_) | type_::Struct(_) |
+            type_::Interface(_) | type_::AnyPointer(_) => reg_field.get_offset(),
+            _ => return Ok(Branch(Vec::new())),
+        },
+    };
+
+    let mut interior = Vec::new();
+    let discriminant_value = field.get_discriminant_value();
+    if discriminant_value != field::NO_DISCRIMINANT {
+        interior.push(Line(format!("self.builder.set_data_field::<u16>({}, {});",
+                                   discriminant_offset as usize, discriminant_value as usize)));
+    }
+    interior.push(Line(format!("self.builder.get_pointer_field({}).clear()", offset)));
+    Ok(Branch(vec!(
+        Line("#[inline]".to_string()),
+        Line(format!("pub fn clear_{}(&mut self) {{", styled_name)),
+        Indent(Box::new(Branch(interior))),
+        Line("}".to_string()))))
+}
+
 fn generate_pipeline_getter(gen: &GeneratorContext,
                             field: schema_capnp::field::Reader) -> ::capnp::Result<FormattedText> {
     use crate::schema_capnp::{field, type_};
@@ -1303,24 +1833,69 @@ fn generate_pipeline_getter(gen: &GeneratorContext,
 
 // We need this to work around the fact that Rust does not allow typedefs
 // with unused type parameters.
+/// Returns the names of the type parameters used by `brand`, in the order in which they appear
+/// in `in_scope`, followed by any others in alphabetical order.
 fn get_ty_params_of_brand(gen: &GeneratorContext,
-                          brand: crate::schema_capnp::brand::Reader<>) -> ::capnp::Result<String>
+                          brand: crate::schema_capnp::brand::Reader<>,
+                          in_scope: &[String]) -> ::capnp::Result<Vec<String>>
 {
-    let mut acc = HashSet::new();
+    let mut acc = TypeParameterUses::default();
     get_ty_params_of_brand_helper(gen, &mut acc, brand)?;
-    let mut result = String::new();
-    for (scope_id, parameter_index) in acc.into_iter() {
+    let mut names = ::std::collections::BTreeSet::new();
+    for (scope_id, parameter_index) in acc.parameters.into_iter() {
         let node = gen.node_map[&scope_id];
         let p = node.get_parameters()?.get(parameter_index as u32);
-        result.push_str(p.get_name()?);
-        result.push_str(",");
+        names.insert(p.get_name()?.to_string());
     }
 
+    let mut result: Vec<String> = in_scope.iter().filter(|name| names.contains(*name)).cloned().collect();
+    for name in names {
+        if !result.contains(&name) {
+            result.push(name);
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the indices of the method implicit parameters used by `brand`.
+fn get_implicit_params_of_brand(gen: &GeneratorContext,
+                                brand: crate::schema_capnp::brand::Reader<>)
+                                -> ::capnp::Result<::std::collections::BTreeSet<u16>>
+{
+    let mut acc = TypeParameterUses::default();
+    get_ty_params_of_brand_helper(gen, &mut acc, brand)?;
+    Ok(acc.implicit_parameters)
+}
+
+#[derive(Default)]
+struct TypeParameterUses {
+    /// The scope id and index of each generic parameter that is used.
+    parameters: HashSet<(u64, u16)>,
+
+    /// The index of each method implicit parameter that is used.
+    implicit_parameters: ::std::collections::BTreeSet<u16>,
+}
+
+/// Returns the Rust names of the implicit type parameters of `method`, i.e. the `U` in
+/// `foo @0 [U] (...) -> (...)`. Names that clash with a type parameter of the interface
+/// are prefixed with `Method`.
+fn implicit_parameter_names(method: crate::schema_capnp::method::Reader,
+                            interface_params: &[String]) -> ::capnp::Result<Vec<String>>
+{
+    let mut result = Vec::new();
+    for parameter in method.get_implicit_parameters()?.iter() {
+        let name = parameter.get_name()?;
+        if interface_params.iter().any(|p| p == name) {
+            result.push(format!("Method{}", name));
+        } else {
+            result.push(name.to_string());
+        }
+    }
     Ok(result)
 }
 
 fn get_ty_params_of_type_helper(gen: &GeneratorContext,
-                                accumulator: &mut HashSet<(u64, u16)>,
+                                accumulator: &mut TypeParameterUses,
                                 typ: crate::schema_capnp::type_::Reader<>)
     -> ::capnp::Result<()>
 {
@@ -1337,10 +1912,10 @@ fn get_ty_params_of_type_helper(gen: &GeneratorContext,
             match p.which()? {
                 type_::any_pointer::Unconstrained(_) => (),
                 type_::any_pointer::Parameter(p) => {
-                    accumulator.insert((p.get_scope_id(), p.get_parameter_index()));
+                    accumulator.parameters.insert((p.get_scope_id(), p.get_parameter_index()));
                 }
-                type_::any_pointer::ImplicitMethodParameter(_) => {
-                    // XXX
+                type_::any_pointer::ImplicitMethodParameter(p) => {
+                    accumulator.implicit_parameters.insert(p.get_parameter_index());
                 }
             }
         }
@@ -1366,7 +1941,7 @@ fn get_ty_params_of_type_helper(gen: &GeneratorContext,
 }
 
 fn get_ty_params_of_brand_helper(gen: &GeneratorContext,
-                         accumulator: &mut HashSet<(u64, u16)>,
+                         accumulator: &mut TypeParameterUses,
                          brand: crate::schema_capnp::brand::Reader<>)
                          -> ::capnp::Result<()>
 {
@@ -1386,7 +1961,7 @@ fn get_ty_params_of_brand_helper(gen: &GeneratorContext,
             crate::schema_capnp::brand::scope::Inherit(()) => {
                 let parameters = gen.node_map[&scope_id].get_parameters()?;
                 for idx in 0..parameters.len() {
-                    accumulator.insert((scope_id, idx as u16));
+                    accumulator.parameters.insert((scope_id, idx as u16));
                 }
             }
         }
@@ -1394,6 +1969,109 @@ fn get_ty_params_of_brand_helper(gen: &GeneratorContext,
     Ok(())
 }
 
+/// Formats a `pub const` item named `styled_name` holding `value`, which must be of type `typ`.
+pub(crate) fn generate_constant(gen: &GeneratorContext,
+                                styled_name: &str,
+                                typ: schema_capnp::type_::Reader,
+                                value: schema_capnp::value::Reader) -> ::capnp::Result<FormattedText> {
+    use crate::schema_capnp::{node, type_, value};
+    Ok(match (typ.which()?, value.which()?) {
+        (type_::Void(()), value::Void(())) => Line(format!("pub const {}: () = ();", styled_name)),
+        (type_::Bool(()), value::Bool(b)) => Line(format!("pub const {}: bool = {};", styled_name, b)),
+        (type_::Int8(()), value::Int8(i)) => Line(format!("pub const {}: i8 = {};", styled_name, i)),
+        (type_::Int16(()), value::Int16(i)) => Line(format!("pub const {}: i16 = {};", styled_name, i)),
+        (type_::Int32(()), value::Int32(i)) => Line(format!("pub const {}: i32 = {};", styled_name, i)),
+        (type_::Int64(()), value::Int64(i)) => Line(format!("pub const {}: i64 = {};", styled_name, i)),
+        (type_::Uint8(()), value::Uint8(i)) => Line(format!("pub const {}: u8 = {};", styled_name, i)),
+        (type_::Uint16(()), value::Uint16(i)) => Line(format!("pub const {}: u16 = {};", styled_name, i)),
+        (type_::Uint32(()), value::Uint32(i)) => Line(format!("pub const {}: u32 = {};", styled_name, i)),
+        (type_::Uint64(()), value::Uint64(i)) => Line(format!("pub const {}: u64 = {};", styled_name, i)),
+
+        (type_::Float32(()), value::Float32(f)) =>
+            Line(format!("pub const {}: f32 = {:e}f32;", styled_name, f)),
+
+        (type_::Float64(()), value::Float64(f)) =>
+            Line(format!("pub const {}: f64 = {:e}f64;", styled_name, f)),
+
+        (type_::Enum(e), value::Enum(v)) => {
+            if let Some(node) = gen.node_map.get(&e.get_type_id()) {
+                match node.which()? {
+                    node::Enum(e) => {
+                        let enumerants = e.get_enumerants()?;
+                        if (v as u32) < enumerants.len() {
+                            let variant =
+                                capitalize_first_letter(get_enumerant_name(enumerants.get(v as u32))?);
+                            let type_string = typ.type_string(gen, Leaf::Owned)?;
+                            Line(format!("pub const {}: {} = {}::{};",
+                                         styled_name,
+                                         &type_string,
+                                         &type_string,
+                                         variant))
+                        } else {
+                            return Err(Error::failed(format!("enumerant out of range: {}", v)));
+                        }
+                    }
+                    _ => {
+                        return Err(Error::failed(format!("bad enum type ID: {}", e.get_type_id())));
+                    }
+                }
+            } else {
+                return Err(Error::failed(format!("bad enum type ID: {}", e.get_type_id())));
+            }
+        }
+
+        (type_::Text(()), value::Text(t)) =>
+            Line(format!("pub const {}: &'static str = {:?};", styled_name, t?)),
+        (type_::Data(()), value::Data(d)) =>
+            Line(format!("pub const {}: &'static [u8] = &{:?};", styled_name, d?)),
+
+        (type_::List(_), value::List(v)) => generate_pointer_constant(gen, styled_name, typ, v)?,
+        (type_::Struct(_), value::Struct(v)) => generate_pointer_constant(gen, styled_name, typ, v)?,
+
+        (type_::AnyPointer(pointer), value::AnyPointer(v)) => match pointer.which()? {
+            type_::any_pointer::Unconstrained(_) => generate_pointer_constant(gen, styled_name, typ, v)?,
+            _ => return Err(Error::unimplemented("generic constants".to_string())),
+        },
+
+        (type_::Interface(_t), value::Interface(())) => {
+            return Err(Error::unimplemented(format!("interface constants")));
+        }
+
+        _ => { return Err(Error::failed(format!("type does not match value"))); }
+    })
+}
+
+/// The Rust type of the item that `generate_constant()` emits for a value of type `typ`.
+pub(crate) fn constant_type(gen: &GeneratorContext,
+                            typ: schema_capnp::type_::Reader) -> ::capnp::Result<String> {
+    use crate::schema_capnp::type_;
+    Ok(match typ.which()? {
+        type_::Void(()) => "()".to_string(),
+        type_::Bool(()) => "bool".to_string(),
+        type_::Int8(()) => "i8".to_string(),
+        type_::Int16(()) => "i16".to_string(),
+        type_::Int32(()) => "i32".to_string(),
+        type_::Int64(()) => "i64".to_string(),
+        type_::Uint8(()) => "u8".to_string(),
+        type_::Uint16(()) => "u16".to_string(),
+        type_::Uint32(()) => "u32".to_string(),
+        type_::Uint64(()) => "u64".to_string(),
+        type_::Float32(()) => "f32".to_string(),
+        type_::Float64(()) => "f64".to_string(),
+        type_::Text(()) => "&'static str".to_string(),
+        type_::Data(()) => "&'static [u8]".to_string(),
+        type_::Enum(_) => typ.type_string(gen, Leaf::Owned)?,
+        type_::List(_) | type_::Struct(_) =>
+            format!("::capnp::constant::Reader<{}>", typ.type_string(gen, Leaf::Owned)?),
+        type_::Interface(_) => return Err(Error::unimplemented("interface constants".to_string())),
+        type_::AnyPointer(pointer) => match pointer.which()? {
+            type_::any_pointer::Unconstrained(_) =>
+                "::capnp::constant::Reader<::capnp::any_pointer::Owned>".to_string(),
+            _ => return Err(Error::unimplemented("generic constants".to_string())),
+        },
+    })
+}
+
 fn generate_node(gen: &GeneratorContext,
                  node_id: u64,
                  node_name: &str,
@@ -1407,12 +2085,34 @@ fn generate_node(gen: &GeneratorContext,
     let mut nested_output: Vec<FormattedText> = Vec::new();
 
     let node_reader = &gen.node_map[&node_id];
+    let is_container_only = match gen.node_selection {
+        Some(ref selection) if !selection.generated.contains(&node_id) => {
+            if let node::File(()) = node_reader.which()? {
+                false
+            } else if selection.containers.contains(&node_id) {
+                true
+            } else {
+                return Ok(Branch(output));
+            }
+        }
+        _ => false,
+    };
+
     let nested_nodes = node_reader.get_nested_nodes()?;
     for nested_node in nested_nodes.iter() {
         let id = nested_node.get_id();
         nested_output.push(generate_node(gen, id, gen.get_last_name(id)?, None)?);
     }
 
+    if is_container_only {
+        // Only the selected nested nodes are generated.
+        output.push(BlankLine);
+        output.push(Line(format!("pub mod {} {{", node_name)));
+        output.push(Indent(Box::new(Branch(nested_output))));
+        output.push(Line("}".to_string()));
+        return Ok(Branch(output));
+    }
+
     match node_reader.which()? {
         node::File(()) => {
             output.push(Branch(nested_output));
@@ -1421,6 +2121,8 @@ fn generate_node(gen: &GeneratorContext,
             let params = node_reader.parameters_texts(gen, parent_node_id);
             output.push(BlankLine);
 
+            output.push(gen.node_doc_comment(node_id)?);
+            output.push(attribute_lines(gen.attributes.get(&node_id)));
             let is_generic = node_reader.get_is_generic();
             if is_generic {
                 output.push(Line(format!("pub mod {} {{ /* {} */", node_name, params.expanded_list.join(","))));
@@ -1433,6 +2135,7 @@ fn generate_node(gen: &GeneratorContext,
             let mut builder_members = Vec::new();
             let mut reader_members = Vec::new();
             let mut union_fields = Vec::new();
+            let mut union_docs = Vec::new();
             let mut which_enums = Vec::new();
             let mut pipeline_impl_interior = Vec::new();
             let mut private_mod_interior = Vec::new();
@@ -1443,9 +2146,10 @@ fn generate_node(gen: &GeneratorContext,
             let discriminant_offset = struct_reader.get_discriminant_offset();
 
             let fields = struct_reader.get_fields()?;
-            for field in fields.iter() {
+            for (index, field) in fields.iter().enumerate() {
                 let name = get_field_name(field)?;
                 let styled_name = camel_to_snake_case(name);
+                let doc = gen.member_doc_comment(node_id, index as u32)?;
 
                 let discriminant_value = field.get_discriminant_value();
                 let is_union_field = discriminant_value != field::NO_DISCRIMINANT;
@@ -1458,6 +2162,7 @@ fn generate_node(gen: &GeneratorContext,
                     }
                     reader_members.push(
                         Branch(vec!(
+                            doc.clone(),
                             Line("#[inline]".to_string()),
                             Line(format!("pub fn get_{}(self) {} {{", styled_name, ty)),
                             Indent(Box::new(get)),
@@ -1466,6 +2171,7 @@ fn generate_node(gen: &GeneratorContext,
                     let (ty_b, get_b, _) = getter_text(gen, &field, false, true)?;
                     builder_members.push(
                         Branch(vec!(
+                            doc.clone(),
                             Line("#[inline]".to_string()),
                             Line(format!("pub fn get_{}(self) {} {{", styled_name, ty_b)),
                             Indent(Box::new(get_b)),
@@ -1473,13 +2179,17 @@ fn generate_node(gen: &GeneratorContext,
 
                 } else {
                     union_fields.push(field);
+                    union_docs.push(doc.clone());
                 }
 
                 builder_members.push(generate_setter(gen, discriminant_offset,
-                                                     &styled_name, &field)?);
+                                                     &styled_name, &field, &doc)?);
 
                 reader_members.push(generate_haser(discriminant_offset, &styled_name, &field, true)?);
                 builder_members.push(generate_haser(discriminant_offset, &styled_name, &field, false)?);
+                reader_members.push(generate_union_predicate(discriminant_offset, &styled_name, &field, true));
+                builder_members.push(generate_union_predicate(discriminant_offset, &styled_name, &field, false));
+                builder_members.push(generate_clearer(discriminant_offset, &styled_name, &field)?);
 
                 match field.which() {
                     Ok(field::Group(group)) => {
@@ -1493,8 +2203,9 @@ fn generate_node(gen: &GeneratorContext,
             }
 
             if discriminant_count > 0 {
+                let unknown_variant = gen.unknown_variants.contains(&node_id);
                 let (which_enums1, union_getter, typedef, mut default_decls) =
-                    generate_union(gen, discriminant_offset, &union_fields, true, &params)?;
+                    generate_union(gen, discriminant_offset, &union_fields, &union_docs, unknown_variant, true, &params)?;
                 which_enums.push(which_enums1);
                 which_enums.push(typedef);
                 reader_members.push(union_getter);
@@ -1502,7 +2213,7 @@ fn generate_node(gen: &GeneratorContext,
                 private_mod_interior.append(&mut default_decls);
 
                 let (_, union_getter, typedef, _) =
-                    generate_union(gen, discriminant_offset, &union_fields, false, &params)?;
+                    generate_union(gen, discriminant_offset, &union_fields, &union_docs, unknown_variant, false, &params)?;
                 which_enums.push(typedef);
                 builder_members.push(union_getter);
 
@@ -1512,6 +2223,18 @@ fn generate_node(gen: &GeneratorContext,
                 for f in union_fields.iter(){
                     whichs.push(capitalize_first_letter(get_field_name(*f)?));
                 }
+                if unknown_variant {
+                    whichs.push("Unknown".to_string());
+                    builder_members.push(Branch(vec![
+                        Line("/// Sets the union's discriminant to `discriminant`, for writing back a value".to_string()),
+                        Line("/// that was read as `Unknown(discriminant)`.".to_string()),
+                        Line("#[inline]".to_string()),
+                        Line("pub fn set_which_unknown(&mut self, discriminant: u16) {".to_string()),
+                        Indent(Box::new(Line(format!("self.builder.set_data_field::<u16>({}, discriminant);",
+                                                     discriminant_offset)))),
+                        Line("}".to_string()),
+                    ]));
+                }
                 reexports.push_str(&whichs.join(","));
                 reexports.push_str("};");
                 preamble.push(Line(reexports));
@@ -1555,6 +2278,12 @@ fn generate_node(gen: &GeneratorContext,
                     Line("}".to_string()),
                     BlankLine]);
 
+            let reader_eq_and_hash = if gen.reader_equality {
+                Branch(vec![generate_reader_eq_and_hash(gen, node_id, &params)?, BlankLine])
+            } else {
+                Branch(Vec::new())
+            };
+
             let accessors = vec![
                 Branch(preamble),
                 (if !is_generic {
@@ -1636,6 +2365,7 @@ fn generate_node(gen: &GeneratorContext,
                         Line("}".to_string()))))),
                 Line("}".to_string()),
                 BlankLine,
+                reader_eq_and_hash,
                 Line(format!("impl <'a,{0}> Reader<'a,{0}> {1} {{", params.params, params.where_clause)),
                 Indent(
                     Box::new(Branch(vec![
@@ -1746,8 +2476,36 @@ fn generate_node(gen: &GeneratorContext,
                 Line("}".to_string()),
             ];
 
+            let plain = if gen.plain_structs.contains(&node_id) {
+                generate_plain_struct(gen, node_id)?
+            } else {
+                Branch(Vec::new())
+            };
+
+            let serde_impls = if gen.serde_nodes.contains(&node_id) {
+                generate_struct_impls(gen, node_id)?
+            } else {
+                Branch(Vec::new())
+            };
+
+            let fill_random = if gen.random_nodes.contains(&node_id) {
+                generate_fill_random(gen, node_id)?
+            } else {
+                Branch(Vec::new())
+            };
+
+            let annotations = if gen.annotation_constants {
+                generate_annotation_constants(gen, node_id, "_annotations")?
+            } else {
+                Branch(Vec::new())
+            };
+
             output.push(Indent(Box::new(Branch(vec!(Branch(accessors),
                                                     Branch(which_enums),
+                                                    plain,
+                                                    serde_impls,
+                                                    fill_random,
+                                                    annotations,
                                                     Branch(nested_output))))));
             output.push(Line("}".to_string()));
         }
@@ -1756,20 +2514,47 @@ fn generate_node(gen: &GeneratorContext,
             let last_name = gen.get_last_name(node_id)?;
             output.push(BlankLine);
 
+            let unknown_variant = gen.unknown_variants.contains(&node_id);
             let mut members = Vec::new();
             let mut match_branches = Vec::new();
+            let mut to_u16_branches = Vec::new();
             let enumerants = enum_reader.get_enumerants()?;
             for ii in 0..enumerants.len() {
                 let enumerant = capitalize_first_letter(get_enumerant_name(enumerants.get(ii))?);
-                members.push(Line(format!("{} = {},", enumerant, ii)));
+                members.push(gen.member_doc_comment(node_id, ii)?);
+                if unknown_variant {
+                    members.push(Line(format!("{},", enumerant)));
+                    to_u16_branches.push(Line(format!("{}::{} => {},", last_name, enumerant, ii)));
+                } else {
+                    members.push(Line(format!("{} = {},", enumerant, ii)));
+                }
                 match_branches.push(
                     Line(format!("{} => ::core::result::Result::Ok({}::{}),", ii, last_name, enumerant)));
             }
-            match_branches.push(Line("n => ::core::result::Result::Err(::capnp::NotInSchema(n)),".to_string()));
+            let to_u16 = if unknown_variant {
+                members.push(Line("/// A value that is not in the schema known to this code.".to_string()));
+                members.push(Line("Unknown(u16),".to_string()));
+                match_branches.push(Line(format!("n => ::core::result::Result::Ok({}::Unknown(n)),", last_name)));
+                to_u16_branches.push(Line(format!("{}::Unknown(n) => n,", last_name)));
+                Branch(vec![
+                    Line("fn to_u16(self) -> u16 {".to_string()),
+                    Indent(Box::new(Branch(vec![
+                        Line("match self {".to_string()),
+                        Indent(Box::new(Branch(to_u16_branches))),
+                        Line("}".to_string()),
+                    ]))),
+                    Line("}".to_string()),
+                ])
+            } else {
+                match_branches.push(Line("n => ::core::result::Result::Err(::capnp::NotInSchema(n)),".to_string()));
+                Line("fn to_u16(self) -> u16 { self as u16 }".to_string())
+            };
 
             output.push(Branch(vec!(
-                Line("#[repr(u16)]".to_string()),
-                Line("#[derive(Clone, Copy, Debug, PartialEq)]".to_string()),
+                gen.node_doc_comment(node_id)?,
+                attribute_lines(gen.attributes.get(&node_id)),
+                if unknown_variant { Branch(Vec::new()) } else { Line("#[repr(u16)]".to_string()) },
+                derive_line(ENUM_DERIVES, gen.derives.get(&node_id)),
                 Line(format!("pub enum {} {{", last_name)),
                 Indent(Box::new(Branch(members))),
                 Line("}".to_string()))));
@@ -1793,8 +2578,7 @@ fn generate_node(gen: &GeneratorContext,
                     Line("}".to_string()),
                     Line(format!("impl ::capnp::traits::ToU16 for {} {{", last_name)),
                     Indent(Box::new(Line("#[inline]".to_string()))),
-                    Indent(
-                        Box::new(Line("fn to_u16(self) -> u16 { self as u16 }".to_string()))),
+                    Indent(Box::new(to_u16)),
                     Line("}".to_string()))));
 
             output.push(
@@ -1804,6 +2588,15 @@ fn generate_node(gen: &GeneratorContext,
                     Indent(
                         Box::new(Line(format!("fn type_id() -> u64 {{ {}u64 }}", format_u64(node_id)).to_string()))),
                     Line("}".to_string()))));
+
+            if gen.serde_nodes.contains(&node_id) {
+                output.push(generate_enum_impls(gen, node_id)?);
+            }
+
+            if gen.annotation_constants {
+                let module = format!("{}_annotations", module_name(last_name));
+                output.push(generate_annotation_constants(gen, node_id, &module)?);
+            }
         }
 
         node::Interface(interface) => {
@@ -1815,6 +2608,8 @@ fn generate_node(gen: &GeneratorContext,
             let names = &gen.scope_map[&node_id];
             let mut client_impl_interior = Vec::new();
             let mut server_interior = Vec::new();
+            let mut async_server_interior = Vec::new();
+            let mut from_async_interior = Vec::new();
             let mut mod_interior = Vec::new();
             let mut dispatch_arms = Vec::new();
             let mut private_mod_interior = Vec::new();
@@ -1829,8 +2624,10 @@ fn generate_node(gen: &GeneratorContext,
             for ordinal in 0..methods.len() {
                 let method = methods.get(ordinal);
                 let name = method.get_name()?;
+                let doc = gen.member_doc_comment(node_id, ordinal)?;
 
                 method.get_code_order();
+                let implicit_params = implicit_parameter_names(method, &params.expanded_list)?;
                 let param_id = method.get_param_struct_type();
                 let param_node = &gen.node_map[&param_id];
                 let (param_scopes, params_ty_params) = if param_node.get_scope_id() == 0 {
@@ -1838,13 +2635,14 @@ fn generate_node(gen: &GeneratorContext,
                     let local_name = module_name(&format!("{}Params", name));
                     nested_output.push(generate_node(gen, param_id, &*local_name, Some(node_id))?);
                     names.push(local_name);
-                    (names, params.params.clone())
+                    (names, params.expanded_list.clone())
                 } else {
                     (gen.scope_map[&param_node.get_id()].clone(),
-                     get_ty_params_of_brand(gen, method.get_param_brand()?)?)
+                     get_ty_params_of_brand(gen, method.get_param_brand()?, &params.expanded_list)?)
                 };
-                let param_type = do_branding(&gen, param_id, method.get_param_brand()?,
-                                             Leaf::Owned, param_scopes.join("::"), Some(node_id))?;
+                let param_type = do_branding_in_method(gen, param_id, method.get_param_brand()?,
+                                                       Leaf::Owned, param_scopes.join("::"), Some(node_id),
+                                                       &implicit_params)?;
 
                 let result_id = method.get_result_struct_type();
                 let result_node = &gen.node_map[&result_id];
@@ -1853,13 +2651,33 @@ fn generate_node(gen: &GeneratorContext,
                     let local_name = module_name(&format!("{}Results", name));
                     nested_output.push(generate_node(gen, result_id, &*local_name, Some(node_id))?);
                     names.push(local_name);
-                    (names, params.params.clone())
+                    (names, params.expanded_list.clone())
                 } else {
                     (gen.scope_map[&result_node.get_id()].clone(),
-                     get_ty_params_of_brand(gen, method.get_result_brand()?)?)
+                     get_ty_params_of_brand(gen, method.get_result_brand()?, &params.expanded_list)?)
                 };
-                let result_type = do_branding(&gen, result_id, method.get_result_brand()?,
-                                              Leaf::Owned, result_scopes.join("::"), Some(node_id))?;
+                let result_type = do_branding_in_method(gen, result_id, method.get_result_brand()?,
+                                                        Leaf::Owned, result_scopes.join("::"), Some(node_id),
+                                                        &implicit_params)?;
+
+                // The aliases take the method's implicit parameters last, defaulting to
+                // `any_pointer::Owned` so that servers can leave them unspecified.
+                let params_implicit = get_implicit_params_of_brand(gen, method.get_param_brand()?)?;
+                let results_implicit = get_implicit_params_of_brand(gen, method.get_result_brand()?)?;
+                let alias_params = |ty_params: &[String], used: &::std::collections::BTreeSet<u16>| -> String {
+                    ty_params.iter().cloned()
+                        .chain(used.iter().map(|&i| format!("{} = ::capnp::any_pointer::Owned",
+                                                            implicit_params[i as usize])))
+                        .collect::<Vec<String>>().join(", ")
+                };
+                let params_alias_params = alias_params(&params_ty_params, &params_implicit);
+                let results_alias_params = alias_params(&results_ty_params, &results_implicit);
+                let request_params: Vec<&String> = implicit_params.iter().enumerate()
+                    .filter(|&(i, _)| params_implicit.contains(&(i as u16)) || results_implicit.contains(&(i as u16)))
+                    .map(|(_, p)| p)
+                    .collect();
+                let params_ty_params = params_ty_params.join(",");
+                let results_ty_params = results_ty_params.join(",");
 
                 dispatch_arms.push(
                     Line(format!(
@@ -1868,11 +2686,12 @@ fn generate_node(gen: &GeneratorContext,
                 mod_interior.push(
                     Line(format!(
                         "pub type {}Params<{}> = ::capnp::capability::Params<{}>;",
-                        capitalize_first_letter(name), params_ty_params, param_type)));
+                        capitalize_first_letter(name), params_alias_params, param_type)));
                 mod_interior.push(
                     Line(format!(
                         "pub type {}Results<{}> = ::capnp::capability::Results<{}>;",
-                        capitalize_first_letter(name), results_ty_params, result_type)));
+                        capitalize_first_letter(name), results_alias_params, result_type)));
+                server_interior.push(doc.clone());
                 server_interior.push(
                     Line(format!(
                         "fn {}(&mut self, _: {}Params<{}>, _: {}Results<{}>) -> ::capnp::capability::Promise<(), ::capnp::Error> {{ ::capnp::capability::Promise::err(::capnp::Error::unimplemented(\"method not implemented\".to_string())) }}",
@@ -1880,10 +2699,43 @@ fn generate_node(gen: &GeneratorContext,
                         capitalize_first_letter(name), params_ty_params,
                         capitalize_first_letter(name), results_ty_params
                     )));
+                if gen.async_servers {
+                    async_server_interior.push(doc.clone());
+                    async_server_interior.push(
+                        Line(format!(
+                            "fn {}(&self, _: {}Params<{}>, _: {}Results<{}>) -> ::capnp::capability::AsyncResult<'_> {{ ::capnp::private::alloc::Box::pin(::capnp::capability::Promise::err(::capnp::Error::unimplemented(\"method not implemented\".to_string()))) }}",
+                            module_name(name),
+                            capitalize_first_letter(name), params_ty_params,
+                            capitalize_first_letter(name), results_ty_params
+                        )));
+                    from_async_interior.push(Branch(vec![
+                        Line(format!(
+                            "fn {}(&mut self, params: {}Params<{}>, results: {}Results<{}>) -> ::capnp::capability::Promise<(), ::capnp::Error> {{",
+                            module_name(name),
+                            capitalize_first_letter(name), params_ty_params,
+                            capitalize_first_letter(name), results_ty_params)),
+                        Indent(Box::new(Line(format!(
+                            "::capnp::capability::FromAsync::call(self, move |server| server.{}(params, results))",
+                            module_name(name))))),
+                        Line("}".to_string()),
+                    ]));
+                }
 
-                client_impl_interior.push(
-                    Line(format!("pub fn {}_request(&self) -> ::capnp::capability::Request<{},{}> {{",
-                                 camel_to_snake_case(name), param_type, result_type)));
+                client_impl_interior.push(doc);
+                if request_params.is_empty() {
+                    client_impl_interior.push(
+                        Line(format!("pub fn {}_request(&self) -> ::capnp::capability::Request<{},{}> {{",
+                                     camel_to_snake_case(name), param_type, result_type)));
+                } else {
+                    client_impl_interior.push(
+                        Line(format!("pub fn {}_request<{}>(&self) -> ::capnp::capability::Request<{},{}> where {} {{",
+                                     camel_to_snake_case(name),
+                                     request_params.iter().map(|p| p.as_str()).collect::<Vec<&str>>().join(","),
+                                     param_type, result_type,
+                                     request_params.iter()
+                                         .map(|p| format!("{}: for<'c> ::capnp::traits::Owned<'c>", p))
+                                         .collect::<Vec<String>>().join(", "))));
+                }
 
                 client_impl_interior.push(Indent(
                     Box::new(Line(format!("self.client.new_call(_private::TYPE_ID, {}, None)", ordinal)))));
@@ -1894,6 +2746,8 @@ fn generate_node(gen: &GeneratorContext,
 
             let mut base_dispatch_arms = Vec::new();
 
+            let mut async_base_traits = Vec::new();
+            let mut superclass_clients = Vec::new();
             let server_base = {
                 let mut base_traits = Vec::new();
 
@@ -1915,8 +2769,13 @@ fn generate_node(gen: &GeneratorContext,
 
                 let mut extends = Vec::new();
                 find_super_interfaces(interface, &mut extends, gen)?;
+                let mut seen = HashSet::new();
                 for interface in extends.iter() {
                     let type_id = interface.get_id();
+                    if !seen.insert(type_id) {
+                        // Reached more than once, through different superclasses.
+                        continue;
+                    }
                     let brand = interface.get_brand()?;
                     let the_mod = gen.scope_map[&type_id].join("::");
 
@@ -1925,6 +2784,10 @@ fn generate_node(gen: &GeneratorContext,
                         type_id,
                         do_branding(
                             gen, type_id, brand, Leaf::ServerDispatch, the_mod.clone(), None)?)));
+                    async_base_traits.push(
+                        do_branding(gen, type_id, brand, Leaf::AsyncServer, the_mod.clone(), None)?);
+                    superclass_clients.push(
+                        do_branding(gen, type_id, brand, Leaf::Client, the_mod.clone(), None)?);
                     base_traits.push(
                         do_branding(gen, type_id, brand, Leaf::Server, the_mod, None)?);
                 }
@@ -2025,6 +2888,18 @@ fn generate_node(gen: &GeneratorContext,
                     Indent(Box::new(Line("}".to_string()))),
                     Line("}".to_string()))));
 
+            // Upcasts to the clients of all superclasses, including indirect ones.
+            for superclass_client in superclass_clients {
+                mod_interior.push(
+                    Branch(vec!(
+                        Line(format!("impl {0} ::core::convert::From<Client{0}> for {1} {2} {{",
+                                     bracketed_params, superclass_client, params.where_clause)),
+                        Indent(Box::new(Line(format!("fn from(client: Client{}) -> Self {{", bracketed_params)))),
+                        Indent(Box::new(Indent(Box::new(Line("::capnp::capability::FromClientHook::new(client.client.hook)".to_string()))))),
+                        Indent(Box::new(Line("}".to_string()))),
+                        Line("}".to_string()))));
+            }
+
             mod_interior.push(
                 Branch(vec!(Line(format!("impl {0} Client{0} {{", bracketed_params)),
                             Indent(Box::new(Branch(client_impl_interior))),
@@ -2034,6 +2909,25 @@ fn generate_node(gen: &GeneratorContext,
                                           Indent(Box::new(Branch(server_interior))),
                                           Line("}".to_string()))));
 
+            if gen.async_servers {
+                let async_server_base = if async_base_traits.is_empty() {
+                    "".to_string()
+                } else {
+                    format!(": {}", async_base_traits.join(" + "))
+                };
+                mod_interior.push(Branch(vec![
+                    Line(format!("pub trait AsyncServer<{}> {} {} {{", params.params, async_server_base, params.where_clause)),
+                    Indent(Box::new(Branch(async_server_interior))),
+                    Line("}".to_string()),
+                ]));
+                mod_interior.push(Branch(vec![
+                    Line(format!("impl <_T: AsyncServer{1} + 'static, {0}> Server{1} for ::capnp::capability::FromAsync<_T> {2} {{",
+                                 params.params, bracketed_params, params.where_clause_with_static)),
+                    Indent(Box::new(Branch(from_async_interior))),
+                    Line("}".to_string()),
+                ]));
+            }
+
             mod_interior.push(Branch(vec!(Line(format!("pub struct ServerDispatch<_T,{}> {{", params.params)),
                                           Indent(Box::new(Line("pub server: _T,".to_string()))),
                                           Indent(Box::new(Branch(if is_generic {
@@ -2088,7 +2982,7 @@ fn generate_node(gen: &GeneratorContext,
                     Indent(Box::new(Indent(Box::new(Indent(
                         Box::new(Line(format!("_private::TYPE_ID => ServerDispatch::<_T, {}>::dispatch_call_internal(&mut self.server, method_id, params, results),",params.params)))))))),
                     Indent(Box::new(Indent(Box::new(Indent(Box::new(Branch(base_dispatch_arms))))))),
-                    Indent(Box::new(Indent(Box::new(Indent(Box::new(Line("_ => { ::capnp::capability::Promise::err(::capnp::Error::unimplemented(\"Method not implemented.\".to_string())) }".to_string()))))))),
+                    Indent(Box::new(Indent(Box::new(Indent(Box::new(Line("_ => { ::capnp::capability::Promise::err(::capnp::Error::unimplemented(::capnp::capability::INTERFACE_NOT_IMPLEMENTED.to_string())) }".to_string()))))))),
                     Indent(Box::new(Indent(Box::new(Line("}".to_string()))))),
                     Indent(Box::new(Line("}".to_string()))),
                     Line("}".to_string()))));
@@ -2103,6 +2997,9 @@ fn generate_node(gen: &GeneratorContext,
                     Indent(Box::new(Line("pub fn dispatch_call_internal(server: &mut _T, method_id: u16, params: ::capnp::capability::Params<::capnp::any_pointer::Owned>, results: ::capnp::capability::Results<::capnp::any_pointer::Owned>) -> ::capnp::capability::Promise<(), ::capnp::Error> {".to_string()))),
                     Indent(Box::new(Indent(Box::new(Line("match method_id {".to_string()))))),
                     Indent(Box::new(Indent(Box::new(Indent(Box::new(Branch(dispatch_arms))))))),
+                    // No method can have the probe ordinal, so answering it successfully tells the
+                    // caller that this interface is implemented.
+                    Indent(Box::new(Indent(Box::new(Indent(Box::new(Line("::capnp::capability::PROBE_METHOD_ID => ::capnp::capability::Promise::ok(()),".to_string()))))))),
                     Indent(Box::new(Indent(Box::new(Indent(Box::new(Line("_ => { ::capnp::capability::Promise::err(::capnp::Error::unimplemented(\"Method not implemented.\".to_string())) }".to_string()))))))),
                     Indent(Box::new(Indent(Box::new(Line("}".to_string()))))),
                     Indent(Box::new(Line("}".to_string()))),
@@ -2115,9 +3012,15 @@ fn generate_node(gen: &GeneratorContext,
                     Line("}".to_string()),
                     )));
 
+            if gen.annotation_constants {
+                mod_interior.push(generate_annotation_constants(gen, node_id, "_annotations")?);
+            }
+
             mod_interior.push(Branch(vec!(Branch(nested_output))));
 
             output.push(BlankLine);
+            output.push(gen.node_doc_comment(node_id)?);
+            output.push(attribute_lines(gen.attributes.get(&node_id)));
             if is_generic {
                 output.push(Line(format!("pub mod {} {{ /* ({}) */", node_name, params.expanded_list.join(","))));
             } else {
@@ -2131,69 +3034,14 @@ fn generate_node(gen: &GeneratorContext,
             let styled_name = snake_to_upper_case(gen.get_last_name(node_id)?);
 
             let typ = c.get_type()?;
-            let formatted_text = match (typ.which()?, c.get_value()?.which()?) {
-                (type_::Void(()), value::Void(())) => Line(format!("pub const {}: () = ();", styled_name)),
-                (type_::Bool(()), value::Bool(b)) => Line(format!("pub const {}: bool = {};", styled_name, b)),
-                (type_::Int8(()), value::Int8(i)) => Line(format!("pub const {}: i8 = {};", styled_name, i)),
-                (type_::Int16(()), value::Int16(i)) => Line(format!("pub const {}: i16 = {};", styled_name, i)),
-                (type_::Int32(()), value::Int32(i)) => Line(format!("pub const {}: i32 = {};", styled_name, i)),
-                (type_::Int64(()), value::Int64(i)) => Line(format!("pub const {}: i64 = {};", styled_name, i)),
-                (type_::Uint8(()), value::Uint8(i)) => Line(format!("pub const {}: u8 = {};", styled_name, i)),
-                (type_::Uint16(()), value::Uint16(i)) => Line(format!("pub const {}: u16 = {};", styled_name, i)),
-                (type_::Uint32(()), value::Uint32(i)) => Line(format!("pub const {}: u32 = {};", styled_name, i)),
-                (type_::Uint64(()), value::Uint64(i)) => Line(format!("pub const {}: u64 = {};", styled_name, i)),
-
-                (type_::Float32(()), value::Float32(f)) =>
-                    Line(format!("pub const {}: f32 = {:e}f32;", styled_name, f)),
-
-                (type_::Float64(()), value::Float64(f)) =>
-                    Line(format!("pub const {}: f64 = {:e}f64;", styled_name, f)),
-
-                (type_::Enum(e), value::Enum(v)) => {
-                    if let Some(node) = gen.node_map.get(&e.get_type_id()) {
-                        match node.which()? {
-                            node::Enum(e) => {
-                                let enumerants = e.get_enumerants()?;
-                                if (v as u32) < enumerants.len() {
-                                    let variant =
-                                        capitalize_first_letter(get_enumerant_name(enumerants.get(v as u32))?);
-                                    let type_string = typ.type_string(gen, Leaf::Owned)?;
-                                    Line(format!("pub const {}: {} = {}::{};",
-                                                 styled_name,
-                                                 &type_string,
-                                                 &type_string,
-                                                 variant))
-                                } else {
-                                    return Err(Error::failed(format!("enumerant out of range: {}", v)));
-                                }
-                            }
-                            _ => {
-                                return Err(Error::failed(format!("bad enum type ID: {}", e.get_type_id())));
-                            }
-                        }
-                    } else {
-                        return Err(Error::failed(format!("bad enum type ID: {}", e.get_type_id())));
-                    }
-                }
-
-                (type_::Text(()), value::Text(t)) =>
-                    Line(format!("pub const {}: &'static str = {:?};", styled_name, t?)),
-                (type_::Data(()), value::Data(d)) =>
-                    Line(format!("pub const {}: &'static [u8] = &{:?};", styled_name, d?)),
-
-                (type_::List(_), value::List(v)) => generate_pointer_constant(gen, &styled_name, typ, v)?,
-                (type_::Struct(_), value::Struct(v)) => generate_pointer_constant(gen, &styled_name, typ, v)?,
-
-                (type_::Interface(_t), value::Interface(())) => {
-                    return Err(Error::unimplemented(format!("interface constants")));
-                }
-                (type_::AnyPointer(_), value::AnyPointer(_pr)) => {
-                    return Err(Error::unimplemented(format!("anypointer constants")));
-                }
-
-                _ => { return Err(Error::failed(format!("type does not match value"))); }
-            };
+            // Interface constants can only be null, so there is nothing to generate for them.
+            if let type_::Interface(_) = typ.which()? {
+                return Ok(Branch(output));
+            }
+            let formatted_text = generate_constant(gen, &styled_name, typ, c.get_value()?)?;
 
+            output.push(gen.node_doc_comment(node_id)?);
+            output.push(attribute_lines(gen.attributes.get(&node_id)));
             output.push(formatted_text);
         }
 
@@ -2205,8 +3053,8 @@ fn generate_node(gen: &GeneratorContext,
 
 // The capnp crate defines a blanket impl of capnp::Read for R where R: std::io::Read,
 // but we can't use that here because it lives behind the "std" feature flag.
-struct ReadWrapper<R> where R: std::io::Read {
-    inner: R,
+pub(crate) struct ReadWrapper<R> where R: std::io::Read {
+    pub(crate) inner: R,
 }
 
 impl <R> capnp::io::Read for ReadWrapper<R> where R: std::io::Read {
@@ -32,6 +32,7 @@ pub enum Leaf {
     Owned,
     Client,
     Server,
+    AsyncServer,
     ServerDispatch,
     Pipeline
 }
@@ -44,6 +45,7 @@ impl ::std::fmt::Display for Leaf {
             &Leaf::Owned => "Owned".to_string(),
             &Leaf::Client => "Client".to_string(),
             &Leaf::Server => "Server".to_string(),
+            &Leaf::AsyncServer => "AsyncServer".to_string(),
             &Leaf::ServerDispatch => "ServerDispatch".to_string(),
             &Leaf::Pipeline => "Pipeline".to_string(),
         };
@@ -59,6 +61,7 @@ impl Leaf {
             &Leaf::Owned => "Owned",
             &Leaf::Client => "Client",
             &Leaf::Server => "Server",
+            &Leaf::AsyncServer => "AsyncServer",
             &Leaf::ServerDispatch => "ServerDispatch",
             &Leaf::Pipeline => "Pipeline",
         }
@@ -67,7 +70,8 @@ impl Leaf {
     fn _have_lifetime(&self) -> bool {
         match self {
             &Leaf::Reader(_) | &Leaf::Builder(_) => true,
-            &Leaf::Owned | &Leaf::Client | &Leaf::Server | &Leaf::ServerDispatch | &Leaf::Pipeline => false,
+            &Leaf::Owned | &Leaf::Client | &Leaf::Server | &Leaf::AsyncServer | &Leaf::ServerDispatch |
+            &Leaf::Pipeline => false,
         }
     }
 }
@@ -302,7 +306,62 @@ pub fn do_branding(gen: &GeneratorContext,
                    brand: brand::Reader,
                    leaf: Leaf,
                    the_mod: String,
-                   mut parent_scope_id: Option<u64>) -> Result<String, Error> {
+                   parent_scope_id: Option<u64>) -> Result<String, Error> {
+    do_branding_in_method(gen, node_id, brand, leaf, the_mod, parent_scope_id, &[])
+}
+
+/// Returns the type that `typ` is bound to in a brand, where `implicit_parameters` names the
+/// implicit type parameters of the method that the brand belongs to, if any.
+fn bound_type_string(gen: &GeneratorContext, typ: type_::Reader,
+                     implicit_parameters: &[String]) -> Result<String, Error> {
+    if implicit_parameters.is_empty() {
+        return typ.type_string(gen, Leaf::Owned);
+    }
+    match typ.which()? {
+        type_::AnyPointer(pointer) => {
+            if let type_::any_pointer::ImplicitMethodParameter(def) = pointer.which()? {
+                if let Some(name) = implicit_parameters.get(def.get_parameter_index() as usize) {
+                    return Ok(name.clone());
+                }
+            }
+        }
+        type_::Struct(st) => {
+            return do_branding_in_method(gen, st.get_type_id(), st.get_brand()?, Leaf::Owned,
+                                         gen.scope_map[&st.get_type_id()].join("::"), None,
+                                         implicit_parameters);
+        }
+        type_::Interface(interface) => {
+            return do_branding_in_method(gen, interface.get_type_id(), interface.get_brand()?, Leaf::Owned,
+                                         gen.scope_map[&interface.get_type_id()].join("::"), None,
+                                         implicit_parameters);
+        }
+        type_::List(ot) => {
+            let element_type = ot.get_element_type()?;
+            match element_type.which()? {
+                type_::Struct(_) =>
+                    return Ok(format!("::capnp::struct_list::Owned<{}>",
+                                      bound_type_string(gen, element_type, implicit_parameters)?)),
+                type_::List(_) =>
+                    return Ok(format!("::capnp::list_list::Owned<{}>",
+                                      bound_type_string(gen, element_type, implicit_parameters)?)),
+                _ => (),
+            }
+        }
+        _ => (),
+    }
+    typ.type_string(gen, Leaf::Owned)
+}
+
+/// Like `do_branding()`, for the parameter or result type of a method whose implicit type
+/// parameters are named `implicit_parameters`. Bindings to those parameters become the names,
+/// rather than `::capnp::any_pointer::Owned`.
+pub fn do_branding_in_method(gen: &GeneratorContext,
+                             node_id: u64,
+                             brand: brand::Reader,
+                             leaf: Leaf,
+                             the_mod: String,
+                             mut parent_scope_id: Option<u64>,
+                             implicit_parameters: &[String]) -> Result<String, Error> {
     let scopes = brand.get_scopes()?;
     let mut brand_scopes = HashMap::new();
     for scope in scopes.iter() {
@@ -340,7 +399,7 @@ pub fn do_branding(gen: &GeneratorContext,
                                     arguments.push("::capnp::any_pointer::Owned".to_string());
                                 }
                                 brand::binding::Type(t) => {
-                                    arguments.push(t?.type_string(gen, Leaf::Owned)?);
+                                    arguments.push(bound_type_string(gen, t?, implicit_parameters)?);
                                 }
                             }
                         }
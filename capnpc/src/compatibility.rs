@@ -0,0 +1,465 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Checking that a new version of a schema is wire-compatible with an old one.
+//!
+//! The checker takes two `CodeGeneratorRequest`s, such as ones saved with
+//! [`CompilerCommand::raw_code_generator_request_path`](crate::CompilerCommand::raw_code_generator_request_path),
+//! and reports the changes that would stop old and new code from understanding each other's
+//! messages. Nodes are matched by ID, so renaming a node or a member is not an incompatibility.
+//!
+//! ```ignore
+//! let incompatibilities = capnpc::compatibility::check_compatibility_files("old.bin", "new.bin")?;
+//! for incompatibility in &incompatibilities {
+//!     eprintln!("{}", incompatibility);
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use capnp::{any_pointer, message};
+
+use crate::codegen::ReadWrapper;
+use crate::schema_capnp::{code_generator_request, field, node, type_, value};
+
+/// A change between two versions of a schema that breaks wire compatibility.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Incompatibility {
+    /// The display name of the node that changed, such as `"foo.capnp:Foo"`.
+    pub node_name: String,
+
+    /// A description of the change.
+    pub description: String,
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}: {}", self.node_name, self.description)
+    }
+}
+
+fn node_kind(node: node::Reader) -> ::capnp::Result<&'static str> {
+    Ok(match node.which()? {
+        node::File(()) => "file",
+        node::Struct(_) => "struct",
+        node::Enum(_) => "enum",
+        node::Interface(_) => "interface",
+        node::Const(_) => "const",
+        node::Annotation(_) => "annotation",
+    })
+}
+
+/// Describes `typ`, naming struct, enum and interface types after the nodes in `nodes`, or by
+/// their IDs if `nodes` is `None`.
+fn describe_type(nodes: Option<&HashMap<u64, node::Reader>>, typ: type_::Reader) -> ::capnp::Result<String> {
+    let named = |id: u64| -> ::capnp::Result<String> {
+        match nodes.and_then(|nodes| nodes.get(&id)) {
+            Some(node) => Ok(node.get_display_name()?.to_string()),
+            None => Ok(format!("0x{:016x}", id)),
+        }
+    };
+    Ok(match typ.which()? {
+        type_::Void(()) => "Void".into(),
+        type_::Bool(()) => "Bool".into(),
+        type_::Int8(()) => "Int8".into(),
+        type_::Int16(()) => "Int16".into(),
+        type_::Int32(()) => "Int32".into(),
+        type_::Int64(()) => "Int64".into(),
+        type_::Uint8(()) => "UInt8".into(),
+        type_::Uint16(()) => "UInt16".into(),
+        type_::Uint32(()) => "UInt32".into(),
+        type_::Uint64(()) => "UInt64".into(),
+        type_::Float32(()) => "Float32".into(),
+        type_::Float64(()) => "Float64".into(),
+        type_::Text(()) => "Text".into(),
+        type_::Data(()) => "Data".into(),
+        type_::List(list) => format!("List({})", describe_type(nodes, list.get_element_type()?)?),
+        type_::Enum(e) => named(e.get_type_id())?,
+        type_::Struct(s) => named(s.get_type_id())?,
+        type_::Interface(i) => named(i.get_type_id())?,
+        type_::AnyPointer(_) => "AnyPointer".into(),
+    })
+}
+
+/// Copies `value` into a canonical message so that pointer values can be compared bytewise.
+fn canonical_bytes(value: any_pointer::Reader) -> ::capnp::Result<Vec<u8>> {
+    let mut message = message::Builder::new_default();
+    message.set_root_canonical(value)?;
+    Ok(message.get_segments_for_output()[0].to_vec())
+}
+
+/// Encodes a default value so that two of them can be compared.
+fn value_bytes(value: value::Reader) -> ::capnp::Result<Vec<u8>> {
+    Ok(match value.which()? {
+        value::Void(()) | value::Interface(()) => Vec::new(),
+        value::Bool(b) => vec![b as u8],
+        value::Int8(v) => v.to_le_bytes().to_vec(),
+        value::Int16(v) => v.to_le_bytes().to_vec(),
+        value::Int32(v) => v.to_le_bytes().to_vec(),
+        value::Int64(v) => v.to_le_bytes().to_vec(),
+        value::Uint8(v) => v.to_le_bytes().to_vec(),
+        value::Uint16(v) => v.to_le_bytes().to_vec(),
+        value::Uint32(v) => v.to_le_bytes().to_vec(),
+        value::Uint64(v) => v.to_le_bytes().to_vec(),
+        value::Float32(v) => v.to_bits().to_le_bytes().to_vec(),
+        value::Float64(v) => v.to_bits().to_le_bytes().to_vec(),
+        value::Enum(v) => v.to_le_bytes().to_vec(),
+        value::Text(t) => t?.as_bytes().to_vec(),
+        value::Data(d) => d?.to_vec(),
+        value::List(p) | value::Struct(p) | value::AnyPointer(p) => canonical_bytes(p)?,
+    })
+}
+
+fn describe_discriminant(discriminant: u16) -> String {
+    if discriminant == field::NO_DISCRIMINANT {
+        "none".into()
+    } else {
+        discriminant.to_string()
+    }
+}
+
+struct Checker<'a> {
+    old_nodes: HashMap<u64, node::Reader<'a>>,
+    new_nodes: HashMap<u64, node::Reader<'a>>,
+    new_names: HashMap<&'a str, u64>,
+    incompatibilities: Vec<Incompatibility>,
+}
+
+impl <'a> Checker<'a> {
+    fn report(&mut self, node_name: &str, description: String) {
+        self.incompatibilities.push(Incompatibility { node_name: node_name.to_string(), description });
+    }
+
+    /// Finds the node in the new schema that corresponds to `old`: the node with the same ID,
+    /// or else the node with the same display name, whose ID must then have changed.
+    fn counterpart(&mut self, old: node::Reader<'a>) -> ::capnp::Result<Option<node::Reader<'a>>> {
+        if let Some(&new) = self.new_nodes.get(&old.get_id()) {
+            return Ok(Some(new));
+        }
+        let name = old.get_display_name()?;
+        match self.new_names.get(name) {
+            Some(&new_id) => {
+                self.report(name, format!("ID changed from 0x{:016x} to 0x{:016x}", old.get_id(), new_id));
+                Ok(Some(self.new_nodes[&new_id]))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn check_node(&mut self, old: node::Reader<'a>, new: node::Reader<'a>) -> ::capnp::Result<()> {
+        let name = old.get_display_name()?;
+        match (old.which()?, new.which()?) {
+            (node::Struct(_), node::Struct(_)) => self.check_struct(name, old, new),
+            (node::Enum(old_enum), node::Enum(new_enum)) => {
+                let old_enumerants = old_enum.get_enumerants()?;
+                let new_enumerants = new_enum.get_enumerants()?;
+                for index in new_enumerants.len()..old_enumerants.len() {
+                    self.report(name, format!("enumerant `{}` (@{}) was removed",
+                                              old_enumerants.get(index).get_name()?, index));
+                }
+                Ok(())
+            }
+            (node::Interface(old_interface), node::Interface(new_interface)) => {
+                let new_superclasses = new_interface.get_superclasses()?;
+                for superclass in old_interface.get_superclasses()?.iter() {
+                    if !new_superclasses.iter().any(|s| s.get_id() == superclass.get_id()) {
+                        let description = match self.old_nodes.get(&superclass.get_id()) {
+                            Some(node) => format!("no longer extends {}", node.get_display_name()?),
+                            None => format!("no longer extends 0x{:016x}", superclass.get_id()),
+                        };
+                        self.report(name, description);
+                    }
+                }
+                let old_methods = old_interface.get_methods()?;
+                let new_methods = new_interface.get_methods()?;
+                for (index, old_method) in old_methods.iter().enumerate() {
+                    if index as u32 >= new_methods.len() {
+                        self.report(name, format!("method `{}` (@{}) was removed", old_method.get_name()?, index));
+                        continue;
+                    }
+                    let new_method = new_methods.get(index as u32);
+                    let method_name = format!("{}.{}", name, old_method.get_name()?);
+                    for &(old_id, new_id) in &[(old_method.get_param_struct_type(), new_method.get_param_struct_type()),
+                                               (old_method.get_result_struct_type(), new_method.get_result_struct_type())] {
+                        // Structs with the same ID get checked on their own.
+                        if old_id != new_id {
+                            if let (Some(&old_struct), Some(&new_struct)) = (self.old_nodes.get(&old_id), self.new_nodes.get(&new_id)) {
+                                self.check_struct(&method_name, old_struct, new_struct)?;
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+            (node::File(()), node::File(())) |
+            (node::Const(_), node::Const(_)) |
+            (node::Annotation(_), node::Annotation(_)) => Ok(()),
+            _ => {
+                self.report(name, format!("changed from {} to {}", node_kind(old)?, node_kind(new)?));
+                Ok(())
+            }
+        }
+    }
+
+    fn check_struct(&mut self, name: &str, old: node::Reader<'a>, new: node::Reader<'a>) -> ::capnp::Result<()> {
+        let (old_struct, new_struct) = match (old.which()?, new.which()?) {
+            (node::Struct(old_struct), node::Struct(new_struct)) => (old_struct, new_struct),
+            _ => {
+                self.report(name, format!("changed from {} to {}", node_kind(old)?, node_kind(new)?));
+                return Ok(());
+            }
+        };
+
+        if new_struct.get_data_word_count() < old_struct.get_data_word_count() {
+            self.report(name, format!("data section shrank from {} to {} words",
+                                      old_struct.get_data_word_count(), new_struct.get_data_word_count()));
+        }
+        if new_struct.get_pointer_count() < old_struct.get_pointer_count() {
+            self.report(name, format!("pointer section shrank from {} to {} pointers",
+                                      old_struct.get_pointer_count(), new_struct.get_pointer_count()));
+        }
+        if old_struct.get_discriminant_count() > 0 && new_struct.get_discriminant_count() > 0 &&
+            old_struct.get_discriminant_offset() != new_struct.get_discriminant_offset()
+        {
+            self.report(name, format!("union discriminant moved from offset {} to {}",
+                                      old_struct.get_discriminant_offset(), new_struct.get_discriminant_offset()));
+        }
+
+        let new_fields = new_struct.get_fields()?;
+        for old_field in old_struct.get_fields()?.iter() {
+            let field_name = old_field.get_name()?;
+            let new_field = match old_field.which()? {
+                field::Slot(_) => {
+                    let ordinal = match old_field.get_ordinal().which()? {
+                        field::ordinal::Explicit(ordinal) => ordinal,
+                        field::ordinal::Implicit(()) => continue,
+                    };
+                    let found = new_fields.iter().find(|f| match f.get_ordinal().which() {
+                        Ok(field::ordinal::Explicit(o)) => o == ordinal,
+                        _ => false,
+                    });
+                    match found {
+                        Some(f) => f,
+                        None => {
+                            self.report(name, format!("field `{}` (@{}) was removed or renumbered", field_name, ordinal));
+                            continue;
+                        }
+                    }
+                }
+                field::Group(old_group) => {
+                    // Group IDs are derived from the parent's ID and the group's position, so
+                    // they stay the same across renames.
+                    let found = new_fields.iter().find(|f| match f.which() {
+                        Ok(field::Group(g)) => g.get_type_id() == old_group.get_type_id(),
+                        _ => false,
+                    });
+                    match found {
+                        Some(f) => f,
+                        None => {
+                            self.report(name, format!("group `{}` was removed", field_name));
+                            continue;
+                        }
+                    }
+                }
+            };
+            self.check_field(name, old_field, new_field)?;
+        }
+        Ok(())
+    }
+
+    fn check_field(&mut self, name: &str, old: field::Reader<'a>, new: field::Reader<'a>) -> ::capnp::Result<()> {
+        let old_name = old.get_name()?;
+        let new_name = new.get_name()?;
+        if old.get_discriminant_value() != new.get_discriminant_value() {
+            self.report(name, format!("field `{}` changed union discriminant from {} to {}", old_name,
+                                      describe_discriminant(old.get_discriminant_value()),
+                                      describe_discriminant(new.get_discriminant_value())));
+        }
+        match (old.which()?, new.which()?) {
+            (field::Slot(old_slot), field::Slot(new_slot)) => {
+                let ordinal = match old.get_ordinal().which()? {
+                    field::ordinal::Explicit(ordinal) => ordinal,
+                    field::ordinal::Implicit(()) => 0,
+                };
+                let old_type = old_slot.get_type()?;
+                let new_type = new_slot.get_type()?;
+                if describe_type(None, old_type)? != describe_type(None, new_type)? {
+                    let old_type_name = describe_type(Some(&self.old_nodes), old_type)?;
+                    let new_type_name = describe_type(Some(&self.new_nodes), new_type)?;
+                    let description = if old_name != new_name {
+                        format!("ordinal @{} was reused: field `{}` of type {} became field `{}` of type {}",
+                                ordinal, old_name, old_type_name, new_name, new_type_name)
+                    } else {
+                        format!("field `{}` (@{}) changed type from {} to {}",
+                                old_name, ordinal, old_type_name, new_type_name)
+                    };
+                    self.report(name, description);
+                    // Offsets and defaults of different types are not comparable.
+                    return Ok(());
+                }
+                if old_slot.get_offset() != new_slot.get_offset() {
+                    self.report(name, format!("field `{}` (@{}) moved from offset {} to {}",
+                                              old_name, ordinal, old_slot.get_offset(), new_slot.get_offset()));
+                }
+                if value_bytes(old_slot.get_default_value()?)? != value_bytes(new_slot.get_default_value()?)? {
+                    self.report(name, format!("field `{}` (@{}) changed its default value", old_name, ordinal));
+                }
+            }
+            (field::Group(_), field::Group(_)) => {}
+            (field::Slot(_), field::Group(_)) => {
+                self.report(name, format!("field `{}` became a group", old_name));
+            }
+            (field::Group(_), field::Slot(_)) => {
+                self.report(name, format!("group `{}` became a field", old_name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds the changes in `new` that break wire compatibility with `old`.
+///
+/// Only nodes present in both requests are compared, so nodes that were removed, or that
+/// belong to files that are missing from `new`, are ignored. Additions are always compatible.
+pub fn check_compatibility(old: code_generator_request::Reader,
+                           new: code_generator_request::Reader)
+                           -> ::capnp::Result<Vec<Incompatibility>>
+{
+    let mut checker = Checker {
+        old_nodes: HashMap::new(),
+        new_nodes: HashMap::new(),
+        new_names: HashMap::new(),
+        incompatibilities: Vec::new(),
+    };
+    for node in old.get_nodes()?.iter() {
+        checker.old_nodes.insert(node.get_id(), node);
+    }
+    for node in new.get_nodes()?.iter() {
+        checker.new_nodes.insert(node.get_id(), node);
+        checker.new_names.insert(node.get_display_name()?, node.get_id());
+    }
+
+    let mut old_nodes: Vec<_> = old.get_nodes()?.iter().collect();
+    old_nodes.sort_by_key(|node| node.get_display_name().unwrap_or(""));
+    for old_node in old_nodes {
+        if let Some(new_node) = checker.counterpart(old_node)? {
+            checker.check_node(old_node, new_node)?;
+        }
+    }
+    Ok(checker.incompatibilities)
+}
+
+fn read_request(path: &Path) -> ::capnp::Result<message::Reader<::capnp::serialize::OwnedSegments>> {
+    let file = ::std::fs::File::open(path).map_err(|e| {
+        ::capnp::Error::failed(format!("could not open {}: {}", path.display(), e))
+    })?;
+    ::capnp::serialize::read_message(ReadWrapper { inner: ::std::io::BufReader::new(file) },
+                                     message::ReaderOptions::new())
+}
+
+/// Like [`check_compatibility`], but reads the requests from files, such as ones written by
+/// [`CompilerCommand::raw_code_generator_request_path`](crate::CompilerCommand::raw_code_generator_request_path).
+pub fn check_compatibility_files<P, Q>(old: P, new: Q) -> ::capnp::Result<Vec<Incompatibility>>
+    where P: AsRef<Path>, Q: AsRef<Path>
+{
+    let old_message = read_request(old.as_ref())?;
+    let new_message = read_request(new.as_ref())?;
+    check_compatibility(old_message.get_root()?, new_message.get_root()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::check_compatibility;
+
+    fn check(name: &str, old: &str, new: &str) -> Vec<String> {
+        let dir = ::std::env::temp_dir().join(format!("capnpc-compatibility-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("old")).unwrap();
+        fs::create_dir_all(dir.join("new")).unwrap();
+        fs::write(dir.join("old/foo.capnp"), format!("@0xe6f94f52f7d3a8c1;\n{}", old)).unwrap();
+        fs::write(dir.join("new/foo.capnp"), format!("@0xe6f94f52f7d3a8c1;\n{}", new)).unwrap();
+        let no_imports: &[&str] = &[];
+        let old_message = crate::compiler::compile(&[dir.join("old/foo.capnp")], &[dir.join("old")], no_imports).unwrap();
+        let new_message = crate::compiler::compile(&[dir.join("new/foo.capnp")], &[dir.join("new")], no_imports).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        check_compatibility(old_message.get_root_as_reader().unwrap(), new_message.get_root_as_reader().unwrap())
+            .unwrap()
+            .iter()
+            .map(|i| i.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn compatible_changes() {
+        let result = check(
+            "compatible",
+            "struct Foo { a @0 :Int32; b @1 :Text; }\nenum E { x @0; }\ninterface I { m @0 (a :Foo); }\n",
+            "struct Foo { renamed @0 :Int32; b @1 :Text; c @2 :List(Foo); }\nenum E { x @0; y @1; }\n\
+             interface I { m @0 (a :Foo, b :Bool); n @1 (); }\nstruct Bar {}\n");
+        assert_eq!(result, Vec::<String>::new());
+    }
+
+    #[test]
+    fn field_changes() {
+        let result = check(
+            "fields",
+            "struct Foo { a @0 :Int32 = 5; b @1 :Text; c @2 :Int16; d @3 :Bool; }\n",
+            "struct Foo { a @0 :Int32 = 6; b @1 :Data; other @2 :Float32; union { d @3 :Bool; e @4 :Void; } }\n");
+        assert_eq!(result, vec![
+            "foo.capnp:Foo: field `a` (@0) changed its default value".to_string(),
+            "foo.capnp:Foo: field `b` (@1) changed type from Text to Data".to_string(),
+            "foo.capnp:Foo: ordinal @2 was reused: field `c` of type Int16 became field `other` of type Float32".to_string(),
+            "foo.capnp:Foo: field `d` changed union discriminant from none to 0".to_string(),
+            "foo.capnp:Foo: field `d` (@3) moved from offset 48 to 64".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn removals_and_shrinkage() {
+        let result = check(
+            "removals",
+            "struct Foo { a @0 :Int64; b @1 :Text; }\nenum E { x @0; y @1; }\ninterface I { m @0 (); n @1 (); }\n",
+            "struct Foo { a @0 :Int64; }\nenum E { x @0; }\ninterface I { m @0 (); }\n");
+        assert_eq!(result, vec![
+            "foo.capnp:E: enumerant `y` (@1) was removed".to_string(),
+            "foo.capnp:Foo: pointer section shrank from 1 to 0 pointers".to_string(),
+            "foo.capnp:Foo: field `b` (@1) was removed or renumbered".to_string(),
+            "foo.capnp:I: method `n` (@1) was removed".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn id_and_kind_changes() {
+        let result = check(
+            "ids",
+            "struct Foo @0xa6d1e84c0b8a5e21 {}\nstruct Bar {}\n",
+            "struct Foo @0xa6d1e84c0b8a5e22 {}\nenum Bar { x @0; }\n");
+        assert_eq!(result, vec![
+            "foo.capnp:Bar: changed from struct to enum".to_string(),
+            "foo.capnp:Foo: ID changed from 0xa6d1e84c0b8a5e21 to 0xa6d1e84c0b8a5e22".to_string(),
+        ]);
+    }
+}
@@ -0,0 +1,475 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Assignment of field offsets. Fields are laid out one at a time in ordinal order, so that
+//! adding a new field never moves an existing one. This follows the C++ compiler's algorithm
+//! exactly, since the resulting offsets are part of the wire format.
+
+const HOLE_COUNT: u32 = 6;
+
+/// A set of "holes" within a segment of allocated space, up to one hole of each power-of-two
+/// size between 1 bit and 32 bits.
+///
+/// There can never be more than one hole of any particular size, because every data field has
+/// a power-of-two size and must be aligned to a multiple of its size. Each hole's offset is
+/// stored as a multiple of its size. Zero means that there is no hole, since offset zero is
+/// always the first thing allocated.
+#[derive(Clone, Copy, Default)]
+struct HoleSet {
+    holes: [u32; HOLE_COUNT as usize],
+}
+
+impl HoleSet {
+    /// Tries to find space for a field of size 2^lg_size within the set of holes. If found,
+    /// removes it from the holes and returns its offset (as a multiple of its size).
+    fn try_allocate(&mut self, lg_size: u32) -> Option<u32> {
+        if lg_size >= HOLE_COUNT {
+            None
+        } else if self.holes[lg_size as usize] != 0 {
+            let result = self.holes[lg_size as usize];
+            self.holes[lg_size as usize] = 0;
+            Some(result)
+        } else {
+            let next = self.try_allocate(lg_size + 1)?;
+            let result = next * 2;
+            self.holes[lg_size as usize] = result + 1;
+            Some(result)
+        }
+    }
+
+    /// Adds holes of progressively larger sizes in the range [lg_size, limit_lg_size), starting
+    /// from the given offset. This is what's left after allocating a 2^lg_size field from the
+    /// start of a 2^limit_lg_size space.
+    fn add_holes_at_end(&mut self, mut lg_size: u32, mut offset: u32, limit_lg_size: u32) {
+        while lg_size < limit_lg_size {
+            self.holes[lg_size as usize] = offset;
+            lg_size += 1;
+            offset = offset / 2 + offset % 2;
+        }
+    }
+
+    /// Tries to expand the value at the given location by combining it with subsequent holes,
+    /// so that its lg size grows by `expansion_factor`.
+    fn try_expand(&mut self, old_lg_size: u32, old_offset: u32, expansion_factor: u32) -> bool {
+        if expansion_factor == 0 {
+            return true;
+        }
+        if old_lg_size == HOLE_COUNT {
+            // The old value is already a full word.
+            return false;
+        }
+        if self.holes[old_lg_size as usize] != old_offset + 1 {
+            // The space immediately after the location is not a hole.
+            return false;
+        }
+
+        if self.try_expand(old_lg_size + 1, old_offset >> 1, expansion_factor - 1) {
+            self.holes[old_lg_size as usize] = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the lg size of the smallest hole that is at least 2^lg_size.
+    fn smallest_at_least(&self, lg_size: u32) -> Option<u32> {
+        (lg_size..HOLE_COUNT).find(|&i| self.holes[i as usize] != 0)
+    }
+}
+
+/// A place where fields can be added: either the struct itself or a group within a union.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    Top,
+    Group(usize),
+}
+
+#[derive(Clone, Copy)]
+struct DataLocation {
+    lg_size: u32,
+    offset: u32,
+}
+
+struct Union {
+    parent: Scope,
+    group_count: u32,
+    discriminant_offset: Option<u32>,
+    data_locations: Vec<DataLocation>,
+    pointer_locations: Vec<u32>,
+}
+
+/// How much of one of its union's data locations a group has used.
+#[derive(Clone, Copy, Default)]
+struct DataLocationUsage {
+    is_used: bool,
+    lg_size_used: u32,
+    // Offsets are relative to the start of the location.
+    holes: HoleSet,
+}
+
+impl DataLocationUsage {
+    /// Finds the smallest single hole that can hold a field of the given size, returning the
+    /// hole's lg size.
+    fn smallest_hole_at_least(&self, location: DataLocation, lg_size: u32) -> Option<u32> {
+        if !self.is_used {
+            // The location is effectively one big hole.
+            if lg_size <= location.lg_size { Some(location.lg_size) } else { None }
+        } else if lg_size >= self.lg_size_used {
+            // Won't fit in any current hole, but we could expand within the location.
+            if lg_size < location.lg_size { Some(lg_size) } else { None }
+        } else if let Some(result) = self.holes.smallest_at_least(lg_size) {
+            Some(result)
+        } else if self.lg_size_used < location.lg_size {
+            // We could double our usage and put the new field in the second half.
+            Some(self.lg_size_used)
+        } else {
+            None
+        }
+    }
+
+    fn allocate_from_hole(&mut self, location: DataLocation, lg_size: u32) -> u32 {
+        let location_offset = location.offset << (location.lg_size - lg_size);
+        if !self.is_used {
+            self.is_used = true;
+            self.lg_size_used = lg_size;
+            location_offset
+        } else if lg_size >= self.lg_size_used {
+            // Expand to double the requested size and return the second half.
+            self.holes.add_holes_at_end(self.lg_size_used, 1, lg_size);
+            self.lg_size_used = lg_size + 1;
+            location_offset + 1
+        } else if let Some(result) = self.holes.try_allocate(lg_size) {
+            location_offset + result
+        } else {
+            // Double our usage, then allocate from the new space.
+            let result = 1 << (self.lg_size_used - lg_size);
+            self.holes.add_holes_at_end(lg_size, result + 1, self.lg_size_used);
+            self.lg_size_used += 1;
+            location_offset + result
+        }
+    }
+}
+
+struct Group {
+    parent: usize,
+    // Corresponds to the parent union's `data_locations`.
+    parent_data_location_usage: Vec<DataLocationUsage>,
+    parent_pointer_location_usage: usize,
+    has_members: bool,
+}
+
+pub struct StructLayout {
+    pub data_word_count: u32,
+    pub pointer_count: u32,
+    holes: HoleSet,
+    unions: Vec<Union>,
+    groups: Vec<Group>,
+}
+
+impl StructLayout {
+    pub fn new() -> StructLayout {
+        StructLayout {
+            data_word_count: 0,
+            pointer_count: 0,
+            holes: HoleSet::default(),
+            unions: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    /// Creates a union whose members will be allocated within `parent`.
+    pub fn new_union(&mut self, parent: Scope) -> usize {
+        self.unions.push(Union {
+            parent,
+            group_count: 0,
+            discriminant_offset: None,
+            data_locations: Vec::new(),
+            pointer_locations: Vec::new(),
+        });
+        self.unions.len() - 1
+    }
+
+    /// Creates a member of the given union, whose fields overlap with those of the union's
+    /// other members.
+    pub fn new_group(&mut self, union: usize) -> Scope {
+        self.groups.push(Group {
+            parent: union,
+            parent_data_location_usage: Vec::new(),
+            parent_pointer_location_usage: 0,
+            has_members: false,
+        });
+        Scope::Group(self.groups.len() - 1)
+    }
+
+    pub fn discriminant_offset(&self, union: usize) -> Option<u32> {
+        self.unions[union].discriminant_offset
+    }
+
+    /// Allocates the union's discriminant, if that hasn't happened already.
+    pub fn add_discriminant(&mut self, union: usize) -> bool {
+        if self.unions[union].discriminant_offset.is_none() {
+            let parent = self.unions[union].parent;
+            let offset = self.add_data(parent, 4);
+            self.unions[union].discriminant_offset = Some(offset);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn add_void(&mut self, scope: Scope) {
+        if let Scope::Group(group) = scope {
+            self.add_member(group);
+            // Make sure that the enclosing union's group is also considered used.
+            let parent = self.unions[self.groups[group].parent].parent;
+            self.add_void(parent);
+        }
+    }
+
+    /// Allocates space for a 2^lg_size bit field, returning its offset as a multiple of its size.
+    pub fn add_data(&mut self, scope: Scope, lg_size: u32) -> u32 {
+        match scope {
+            Scope::Top => {
+                if let Some(hole) = self.holes.try_allocate(lg_size) {
+                    hole
+                } else {
+                    let offset = self.data_word_count << (6 - lg_size);
+                    self.data_word_count += 1;
+                    self.holes.add_holes_at_end(lg_size, offset + 1, HOLE_COUNT);
+                    offset
+                }
+            }
+            Scope::Group(group) => self.group_add_data(group, lg_size),
+        }
+    }
+
+    pub fn add_pointer(&mut self, scope: Scope) -> u32 {
+        match scope {
+            Scope::Top => {
+                self.pointer_count += 1;
+                self.pointer_count - 1
+            }
+            Scope::Group(group) => {
+                self.add_member(group);
+                let union = self.groups[group].parent;
+                let used = self.groups[group].parent_pointer_location_usage;
+                self.groups[group].parent_pointer_location_usage += 1;
+                if used < self.unions[union].pointer_locations.len() {
+                    self.unions[union].pointer_locations[used]
+                } else {
+                    let parent = self.unions[union].parent;
+                    let offset = self.add_pointer(parent);
+                    self.unions[union].pointer_locations.push(offset);
+                    offset
+                }
+            }
+        }
+    }
+
+    /// Tries to expand an existing field to be larger.
+    fn try_expand_data(&mut self, scope: Scope, old_lg_size: u32, old_offset: u32,
+                       expansion_factor: u32) -> bool {
+        let group = match scope {
+            Scope::Top => return self.holes.try_expand(old_lg_size, old_offset, expansion_factor),
+            Scope::Group(group) => group,
+        };
+
+        if old_lg_size + expansion_factor > 6 || (old_offset & ((1 << expansion_factor) - 1)) != 0 {
+            // The new size is too large or the offset is not properly aligned.
+            return false;
+        }
+
+        let union = self.groups[group].parent;
+        for i in 0..self.groups[group].parent_data_location_usage.len() {
+            let location = self.unions[union].data_locations[i];
+            if location.lg_size >= old_lg_size &&
+                old_offset >> (location.lg_size - old_lg_size) == location.offset
+            {
+                // The location we're trying to expand is a subset of this data location.
+                let local_old_offset = old_offset - (location.offset << (location.lg_size - old_lg_size));
+                let usage = self.groups[group].parent_data_location_usage[i];
+                return if local_old_offset == 0 && usage.lg_size_used == old_lg_size {
+                    // This location contains exactly the requested data, so expand the whole thing.
+                    self.try_expand_usage(group, i, old_lg_size + expansion_factor, false)
+                } else {
+                    // The data must have been allocated from a hole, which must be expanded in place.
+                    self.groups[group].parent_data_location_usage[i].holes
+                        .try_expand(old_lg_size, local_old_offset, expansion_factor)
+                };
+            }
+        }
+
+        panic!("Tried to expand field that was never allocated.");
+    }
+
+    fn add_member(&mut self, group: usize) {
+        if !self.groups[group].has_members {
+            self.groups[group].has_members = true;
+            let union = self.groups[group].parent;
+            self.unions[union].group_count += 1;
+            if self.unions[union].group_count == 2 {
+                self.add_discriminant(union);
+            }
+        }
+    }
+
+    fn group_add_data(&mut self, group: usize, lg_size: u32) -> u32 {
+        self.add_member(group);
+        let union = self.groups[group].parent;
+
+        let mut best: Option<(u32, usize)> = None;
+        for i in 0..self.unions[union].data_locations.len() {
+            if self.groups[group].parent_data_location_usage.len() == i {
+                self.groups[group].parent_data_location_usage.push(DataLocationUsage::default());
+            }
+            let usage = &self.groups[group].parent_data_location_usage[i];
+            if let Some(hole) = usage.smallest_hole_at_least(self.unions[union].data_locations[i], lg_size) {
+                match best {
+                    Some((best_size, _)) if best_size <= hole => (),
+                    _ => best = Some((hole, i)),
+                }
+            }
+        }
+
+        if let Some((_, i)) = best {
+            let location = self.unions[union].data_locations[i];
+            return self.groups[group].parent_data_location_usage[i].allocate_from_hole(location, lg_size);
+        }
+
+        // No holes are big enough, so try expanding the locations to fit.
+        for i in 0..self.unions[union].data_locations.len() {
+            if let Some(result) = self.try_allocate_by_expanding(group, i, lg_size) {
+                return result;
+            }
+        }
+
+        // Couldn't find any space in the existing locations, so add a new one.
+        let parent = self.unions[union].parent;
+        let offset = self.add_data(parent, lg_size);
+        self.unions[union].data_locations.push(DataLocation { lg_size, offset });
+        self.groups[group].parent_data_location_usage.push(DataLocationUsage {
+            is_used: true,
+            lg_size_used: lg_size,
+            holes: HoleSet::default(),
+        });
+        offset
+    }
+
+    fn try_allocate_by_expanding(&mut self, group: usize, location_index: usize, lg_size: u32) -> Option<u32> {
+        let usage = self.groups[group].parent_data_location_usage[location_index];
+        if !usage.is_used {
+            if self.try_expand_location(group, location_index, lg_size) {
+                let location = self.unions[self.groups[group].parent].data_locations[location_index];
+                let usage = &mut self.groups[group].parent_data_location_usage[location_index];
+                usage.is_used = true;
+                usage.lg_size_used = lg_size;
+                Some(location.offset << (location.lg_size - lg_size))
+            } else {
+                None
+            }
+        } else {
+            // We are already using the location, so we need to expand it to make room.
+            let new_size = ::std::cmp::max(usage.lg_size_used, lg_size) + 1;
+            if self.try_expand_usage(group, location_index, new_size, true) {
+                let location = self.unions[self.groups[group].parent].data_locations[location_index];
+                let result = self.groups[group].parent_data_location_usage[location_index]
+                    .holes.try_allocate(lg_size).expect("expanded location has no hole");
+                Some((location.offset << (location.lg_size - lg_size)) + result)
+            } else {
+                None
+            }
+        }
+    }
+
+    fn try_expand_usage(&mut self, group: usize, location_index: usize, desired_usage: u32,
+                        new_holes: bool) -> bool {
+        let union = self.groups[group].parent;
+        if desired_usage > self.unions[union].data_locations[location_index].lg_size &&
+            !self.try_expand_location(group, location_index, desired_usage)
+        {
+            return false;
+        }
+
+        let usage = &mut self.groups[group].parent_data_location_usage[location_index];
+        if new_holes {
+            usage.holes.add_holes_at_end(usage.lg_size_used, 1, desired_usage);
+        }
+        usage.lg_size_used = desired_usage;
+        true
+    }
+
+    /// Asks the union's parent to grow one of the union's data locations.
+    fn try_expand_location(&mut self, group: usize, location_index: usize, new_lg_size: u32) -> bool {
+        let union = self.groups[group].parent;
+        let location = self.unions[union].data_locations[location_index];
+        if new_lg_size <= location.lg_size {
+            return true;
+        }
+        let parent = self.unions[union].parent;
+        let factor = new_lg_size - location.lg_size;
+        if self.try_expand_data(parent, location.lg_size, location.offset, factor) {
+            let location = &mut self.unions[union].data_locations[location_index];
+            location.offset >>= factor;
+            location.lg_size = new_lg_size;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_level_packing() {
+        let mut layout = StructLayout::new();
+        assert_eq!(layout.add_data(Scope::Top, 6), 0);
+        assert_eq!(layout.add_data(Scope::Top, 5), 2);
+        assert_eq!(layout.add_data(Scope::Top, 0), 96);
+        assert_eq!(layout.add_data(Scope::Top, 4), 7);
+        assert_eq!(layout.add_data(Scope::Top, 3), 13);
+        assert_eq!(layout.add_data(Scope::Top, 6), 2);
+        assert_eq!(layout.data_word_count, 3);
+        assert_eq!(layout.add_pointer(Scope::Top), 0);
+        assert_eq!(layout.add_pointer(Scope::Top), 1);
+    }
+
+    #[test]
+    fn union_members_overlap() {
+        // struct { a @0 :UInt64; union { b @1 :UInt16; c @2 :UInt32; d @3 :Text; } }
+        let mut layout = StructLayout::new();
+        assert_eq!(layout.add_data(Scope::Top, 6), 0);
+        let union = layout.new_union(Scope::Top);
+        let b = layout.new_group(union);
+        let c = layout.new_group(union);
+        let d = layout.new_group(union);
+        assert_eq!(layout.add_data(b, 4), 4);
+        assert_eq!(layout.discriminant_offset(union), None);
+        assert_eq!(layout.add_data(c, 5), 3);
+        // The discriminant is allocated when the second member is added.
+        assert_eq!(layout.discriminant_offset(union), Some(5));
+        assert_eq!(layout.add_pointer(d), 0);
+        assert_eq!(layout.data_word_count, 2);
+        assert_eq!(layout.pointer_count, 1);
+    }
+}
@@ -0,0 +1,599 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Splits a schema file into statements, each of which is a sequence of tokens terminated
+//! either by a semicolon or by a block of nested statements.
+
+/// A range of byte offsets in a source file.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start, end: other.end }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Identifier(String),
+    StringLiteral(Vec<u8>),
+    BinaryLiteral(Vec<u8>),
+    IntegerLiteral(u64),
+    FloatLiteral(f64),
+    Operator(String),
+    ParenthesizedList(Vec<Vec<Token>>),
+    BracketedList(Vec<Vec<Token>>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatementKind {
+    Line,
+    Block(Vec<Statement>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub tokens: Vec<Token>,
+    pub kind: StatementKind,
+    pub doc_comment: Option<String>,
+    pub span: Span,
+}
+
+/// Lexes a whole file. Errors are appended to `errors`; statements that fail to lex are
+/// skipped.
+pub fn lex(text: &str, errors: &mut Vec<(Span, String)>) -> Vec<Statement> {
+    let mut lexer = Lexer { bytes: text.as_bytes(), pos: 0, errors };
+    let statements = lexer.statement_sequence();
+    if lexer.pos < lexer.bytes.len() {
+        // The only thing that stops a top-level statement sequence early is a stray '}'.
+        let pos = lexer.pos;
+        lexer.error(Span::new(pos, pos + 1), "Unmatched '}'.");
+    }
+    statements
+}
+
+struct Lexer<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    errors: &'a mut Vec<(Span, String)>,
+}
+
+fn is_operator_char(c: u8) -> bool {
+    b"!$%&*+-./:<=>?@^|~".contains(&c)
+}
+
+fn is_identifier_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
+
+fn is_identifier_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+impl <'a> Lexer<'a> {
+    fn error(&mut self, span: Span, message: &str) {
+        self.errors.push((span, message.to_string()));
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).cloned()
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c' => self.pos += 1,
+                b'#' => {
+                    while let Some(c) = self.peek() {
+                        if c == b'\n' { break }
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn skip_line_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses the comment lines that may directly follow a ';', '{' or '}'. The comment may
+    /// start on the same line or on the line after.
+    fn doc_comment(&mut self) -> Option<String> {
+        let start = self.pos;
+        self.skip_line_whitespace();
+        if self.peek() == Some(b'\r') && self.peek_at(1) == Some(b'\n') {
+            self.pos += 2;
+        } else if self.peek() == Some(b'\n') {
+            self.pos += 1;
+        }
+
+        let mut result = String::new();
+        loop {
+            let line_start = self.pos;
+            self.skip_line_whitespace();
+            if self.peek() != Some(b'#') {
+                self.pos = line_start;
+                break;
+            }
+            self.pos += 1;
+            if self.peek() == Some(b' ') {
+                self.pos += 1;
+            }
+            let text_start = self.pos;
+            while let Some(c) = self.peek() {
+                if c == b'\n' { break }
+                self.pos += 1;
+            }
+            let mut line = &self.bytes[text_start..self.pos];
+            if line.last() == Some(&b'\r') {
+                line = &line[..line.len() - 1];
+            }
+            result.push_str(&String::from_utf8_lossy(line));
+            result.push('\n');
+            if self.peek() == Some(b'\n') {
+                self.pos += 1;
+            }
+        }
+
+        if result.is_empty() {
+            self.pos = start;
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Parses statements until end of input or an unmatched '}'.
+    fn statement_sequence(&mut self) -> Vec<Statement> {
+        let mut result = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                None | Some(b'}') => return result,
+                _ => (),
+            }
+            if let Some(statement) = self.statement() {
+                result.push(statement);
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Option<Statement> {
+        let start = self.pos;
+        let tokens = match self.token_sequence() {
+            Some(tokens) => tokens,
+            None => {
+                self.skip_statement();
+                return None;
+            }
+        };
+
+        match self.peek() {
+            Some(b';') => {
+                self.pos += 1;
+                let span = Span::new(start, self.pos);
+                let doc_comment = self.doc_comment();
+                Some(Statement { tokens, kind: StatementKind::Line, doc_comment, span })
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let doc_comment = self.doc_comment();
+                let statements = self.statement_sequence();
+                if self.peek() != Some(b'}') {
+                    self.error(Span::new(start, self.pos), "Missing '}'.");
+                    return None;
+                }
+                self.pos += 1;
+                let span = Span::new(start, self.pos);
+                let late_comment = self.doc_comment();
+                Some(Statement {
+                    tokens,
+                    kind: StatementKind::Block(statements),
+                    doc_comment: doc_comment.or(late_comment),
+                    span,
+                })
+            }
+            _ => {
+                let pos = self.pos;
+                if pos >= self.bytes.len() {
+                    self.error(Span::new(start, pos), "Missing ';' at end of statement.");
+                } else {
+                    self.error(Span::new(pos, pos + 1), "Parse error.");
+                    self.skip_statement();
+                }
+                None
+            }
+        }
+    }
+
+    /// Error recovery: skips to just past the end of the current statement.
+    fn skip_statement(&mut self) {
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            match c {
+                b';' if depth == 0 => {
+                    self.pos += 1;
+                    return;
+                }
+                b'{' => depth += 1,
+                b'}' => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos += 1;
+                        return;
+                    }
+                }
+                b'#' => {
+                    self.skip_whitespace_and_comments();
+                    continue;
+                }
+                _ => (),
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Parses tokens up to (but not including) the next character that can't start a token.
+    fn token_sequence(&mut self) -> Option<Vec<Token>> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                None | Some(b';') | Some(b'{') | Some(b'}') | Some(b')') | Some(b']') | Some(b',') => {
+                    return Some(tokens);
+                }
+                _ => tokens.push(self.token()?),
+            }
+        }
+    }
+
+    fn token(&mut self) -> Option<Token> {
+        let start = self.pos;
+        let c = self.peek()?;
+        let kind = if is_identifier_start(c) {
+            while let Some(c) = self.peek() {
+                if !is_identifier_char(c) { break }
+                self.pos += 1;
+            }
+            TokenKind::Identifier(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+        } else if c == b'"' {
+            TokenKind::StringLiteral(self.string_literal()?)
+        } else if c == b'0' && self.peek_at(1) == Some(b'x') && self.peek_at(2) == Some(b'"') {
+            TokenKind::BinaryLiteral(self.binary_literal()?)
+        } else if c.is_ascii_digit() {
+            self.number()?
+        } else if is_operator_char(c) {
+            while let Some(c) = self.peek() {
+                if !is_operator_char(c) { break }
+                self.pos += 1;
+            }
+            TokenKind::Operator(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+        } else if c == b'(' {
+            TokenKind::ParenthesizedList(self.comma_delimited_list(b')')?)
+        } else if c == b'[' {
+            TokenKind::BracketedList(self.comma_delimited_list(b']')?)
+        } else {
+            self.error(Span::new(start, start + 1), "Parse error.");
+            return None;
+        };
+        Some(Token { kind, span: Span::new(start, self.pos) })
+    }
+
+    fn comma_delimited_list(&mut self, close: u8) -> Option<Vec<Vec<Token>>> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            let item = self.token_sequence()?;
+            match self.peek() {
+                Some(b',') => {
+                    self.pos += 1;
+                    items.push(item);
+                }
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    if !(items.is_empty() && item.is_empty()) {
+                        items.push(item);
+                    }
+                    return Some(items);
+                }
+                _ => {
+                    let message = format!("Missing '{}'.", close as char);
+                    self.error(Span::new(start, self.pos), &message);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn string_literal(&mut self) -> Option<Vec<u8>> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut result = Vec::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    self.error(Span::new(start, self.pos), "Unterminated string literal.");
+                    return None;
+                }
+            };
+            self.pos += 1;
+            match c {
+                b'"' => return Some(result),
+                b'\\' => {
+                    let escape_start = self.pos - 1;
+                    let c = match self.peek() {
+                        Some(c) => c,
+                        None => continue,
+                    };
+                    self.pos += 1;
+                    match c {
+                        b'a' => result.push(0x07),
+                        b'b' => result.push(0x08),
+                        b'f' => result.push(0x0c),
+                        b'n' => result.push(b'\n'),
+                        b'r' => result.push(b'\r'),
+                        b't' => result.push(b'\t'),
+                        b'v' => result.push(0x0b),
+                        b'\'' | b'"' | b'\\' | b'?' => result.push(c),
+                        b'x' => {
+                            match (self.peek().and_then(hex_value), self.peek_at(1).and_then(hex_value)) {
+                                (Some(high), Some(low)) => {
+                                    self.pos += 2;
+                                    result.push(high * 16 + low);
+                                }
+                                _ => self.error(Span::new(escape_start, self.pos), "Invalid escape sequence."),
+                            }
+                        }
+                        b'0'..=b'7' => {
+                            let mut value = u32::from(c - b'0');
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(c @ b'0'..=b'7') => {
+                                        self.pos += 1;
+                                        value = value * 8 + u32::from(c - b'0');
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            result.push(value as u8);
+                        }
+                        _ => self.error(Span::new(escape_start, self.pos), "Invalid escape sequence."),
+                    }
+                }
+                _ => result.push(c),
+            }
+        }
+    }
+
+    fn binary_literal(&mut self) -> Option<Vec<u8>> {
+        let start = self.pos;
+        self.pos += 3;
+        let mut result = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Some(result);
+                }
+                Some(c) => {
+                    match (hex_value(c), self.peek_at(1).and_then(hex_value)) {
+                        (Some(high), Some(low)) => {
+                            self.pos += 2;
+                            result.push(high * 16 + low);
+                        }
+                        _ => {
+                            self.error(Span::new(self.pos, self.pos + 1), "Invalid hex digit in binary literal.");
+                            return None;
+                        }
+                    }
+                }
+                None => {
+                    self.error(Span::new(start, self.pos), "Unterminated binary literal.");
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<TokenKind> {
+        let start = self.pos;
+        if self.peek() == Some(b'0') && (self.peek_at(1) == Some(b'x') || self.peek_at(1) == Some(b'X')) &&
+            self.peek_at(2).and_then(hex_value).is_some()
+        {
+            self.pos += 2;
+            let digits_start = self.pos;
+            while self.peek().and_then(hex_value).is_some() {
+                self.pos += 1;
+            }
+            return self.integer(start, digits_start, 16);
+        }
+
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+
+        let mut is_float = false;
+        if self.peek() == Some(b'.') && matches!(self.peek_at(1), Some(b'0'..=b'9')) {
+            is_float = true;
+            self.pos += 1;
+            while let Some(b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let mut exponent = self.pos + 1;
+            if let Some(b'+') | Some(b'-') = self.bytes.get(exponent) {
+                exponent += 1;
+            }
+            if let Some(b'0'..=b'9') = self.bytes.get(exponent) {
+                is_float = true;
+                self.pos = exponent;
+                while let Some(b'0'..=b'9') = self.peek() {
+                    self.pos += 1;
+                }
+            }
+        }
+
+        if is_float {
+            let text = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap();
+            match text.parse::<f64>() {
+                Ok(value) => Some(TokenKind::FloatLiteral(value)),
+                Err(_) => {
+                    self.error(Span::new(start, self.pos), "Invalid floating-point literal.");
+                    None
+                }
+            }
+        } else if self.bytes[start] == b'0' && self.pos - start > 1 {
+            self.integer(start, start + 1, 8)
+        } else {
+            self.integer(start, start, 10)
+        }
+    }
+
+    fn integer(&mut self, start: usize, digits_start: usize, radix: u32) -> Option<TokenKind> {
+        let text = std::str::from_utf8(&self.bytes[digits_start..self.pos]).unwrap();
+        match u64::from_str_radix(text, radix) {
+            Ok(value) => Some(TokenKind::IntegerLiteral(value)),
+            Err(_) => {
+                self.error(Span::new(start, self.pos), "Integer literal is too big.");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex_ok(text: &str) -> Vec<Statement> {
+        let mut errors = Vec::new();
+        let result = lex(text, &mut errors);
+        assert!(errors.is_empty(), "{:?}", errors);
+        result
+    }
+
+    fn kinds(statement: &Statement) -> Vec<TokenKind> {
+        statement.tokens.iter().map(|t| t.kind.clone()).collect()
+    }
+
+    #[test]
+    fn tokens() {
+        let statements = lex_ok("foo @0x12 :List(Int32) = [1, -2.5e3, \"a\\tb\", 0x\"0a ff\"];");
+        assert_eq!(statements.len(), 1);
+        let ident = |s: &str| TokenKind::Identifier(s.to_string());
+        let op = |s: &str| TokenKind::Operator(s.to_string());
+        let tok = |kind| Token { kind, span: Span::default() };
+        let strip = |kinds: Vec<TokenKind>| -> Vec<TokenKind> {
+            kinds.into_iter().map(|k| match k {
+                TokenKind::ParenthesizedList(l) => TokenKind::ParenthesizedList(
+                    l.into_iter().map(|i| i.into_iter().map(|t| tok(t.kind)).collect()).collect()),
+                TokenKind::BracketedList(l) => TokenKind::BracketedList(
+                    l.into_iter().map(|i| i.into_iter().map(|t| tok(t.kind)).collect()).collect()),
+                k => k,
+            }).collect()
+        };
+        assert_eq!(strip(kinds(&statements[0])), vec![
+            ident("foo"), op("@"), TokenKind::IntegerLiteral(0x12), op(":"), ident("List"),
+            TokenKind::ParenthesizedList(vec![vec![tok(ident("Int32"))]]),
+            op("="),
+            TokenKind::BracketedList(vec![
+                vec![tok(TokenKind::IntegerLiteral(1))],
+                vec![tok(op("-")), tok(TokenKind::FloatLiteral(2.5e3))],
+                vec![tok(TokenKind::StringLiteral(b"a\tb".to_vec()))],
+                vec![tok(TokenKind::BinaryLiteral(vec![0x0a, 0xff]))],
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn integer_radixes() {
+        let statements = lex_ok("0 017 0x1F 123;");
+        assert_eq!(kinds(&statements[0]), vec![
+            TokenKind::IntegerLiteral(0), TokenKind::IntegerLiteral(0o17),
+            TokenKind::IntegerLiteral(0x1f), TokenKind::IntegerLiteral(123),
+        ]);
+    }
+
+    #[test]
+    fn doc_comments() {
+        let statements = lex_ok(
+            "struct Foo {  # Foo's doc\n  # continued\n\n  # not a doc comment\n  \
+             a @0 :Int32;\n  # a's doc\n  b @1 :Int32;\n\n  # not b's doc\n}\n");
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].doc_comment.as_ref().unwrap(), "Foo's doc\ncontinued\n");
+        match statements[0].kind {
+            StatementKind::Block(ref block) => {
+                assert_eq!(block.len(), 2);
+                assert_eq!(block[0].doc_comment.as_ref().unwrap(), "a's doc\n");
+                assert_eq!(block[1].doc_comment, None);
+            }
+            _ => panic!("expected a block"),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut errors = Vec::new();
+        let statements = lex("foo bar\nbaz;\nqux ~ `;\nok;", &mut errors);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0.start, "foo bar\nbaz;\nqux ~ ".len());
+    }
+}
@@ -0,0 +1,617 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! A schema compiler written in Rust, so that code can be generated without the `capnp`
+//! executable. It follows the C++ implementation closely, and in particular assigns
+//! the same IDs, field offsets and ordinals.
+//!
+//! Compilation happens in two phases. First every declaration of every loaded file is
+//! translated into a `node::Node`, which fixes the layout of all structs. Then the values
+//! of constants, default values and annotations are compiled, since those may depend on the
+//! layout of arbitrary other structs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use capnp::message;
+
+use crate::schema_capnp::code_generator_request;
+
+pub(crate) mod lexer;
+pub(crate) mod parser;
+pub(crate) mod type_id;
+mod layout;
+mod node;
+mod resolve;
+mod translate;
+mod value;
+
+use self::lexer::Span;
+use self::parser::{DeclKind, Declaration};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EntityKind {
+    File,
+    Struct,
+    Enum,
+    Interface,
+    Const,
+    Annotation,
+    Alias,
+}
+
+/// A named declaration that can be the target of name lookup.
+struct Entity {
+    kind: EntityKind,
+    /// Zero for aliases, which don't have nodes.
+    id: u64,
+    parent: Option<usize>,
+    file: usize,
+    display_name: String,
+    /// Nested declarations, including aliases, by name.
+    members: HashMap<String, usize>,
+    /// Nested declarations other than aliases, in code order.
+    nested: Vec<usize>,
+    /// The declaration itself, with `nested` reduced to those declarations that aren't entities.
+    decl: Rc<Declaration>,
+}
+
+struct SourceFile {
+    path: PathBuf,
+    canonical: PathBuf,
+    display_name: String,
+    text: String,
+    entity: usize,
+    /// The import expressions used by the file, as written, mapped to the imported file.
+    imports: BTreeMap<String, usize>,
+}
+
+struct Error {
+    file: usize,
+    span: Span,
+    message: String,
+}
+
+struct Compiler {
+    import_paths: Vec<PathBuf>,
+    files: Vec<SourceFile>,
+    entities: Vec<Entity>,
+    /// Entities that haven't been translated into nodes yet are `entities[translated..]`.
+    translated: usize,
+    nodes: BTreeMap<u64, node::Node>,
+    node_ids: HashMap<u64, (usize, Span)>,
+    errors: Vec<Error>,
+    values: value::ValueJobs,
+    /// Aliases currently being resolved, to detect cycles.
+    alias_stack: Vec<usize>,
+}
+
+/// Compiles the given schema files and returns the resulting `CodeGeneratorRequest`.
+///
+/// `src_prefixes` and `import_paths` have the same meaning as the `--src-prefix` and
+/// `--import-path` flags of `capnp compile`. On failure, the error's description lists every
+/// problem that was found, one per line.
+pub fn compile<P1, P2, P3>(files: &[P1], src_prefixes: &[P2], import_paths: &[P3])
+                           -> ::capnp::Result<message::Builder<message::HeapAllocator>>
+    where P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>
+{
+    let mut compiler = Compiler {
+        import_paths: import_paths.iter().map(|p| p.as_ref().to_path_buf()).collect(),
+        files: Vec::new(),
+        entities: Vec::new(),
+        translated: 0,
+        nodes: BTreeMap::new(),
+        node_ids: HashMap::new(),
+        errors: Vec::new(),
+        values: value::ValueJobs::default(),
+        alias_stack: Vec::new(),
+    };
+
+    let mut requested = Vec::new();
+    for file in files {
+        let file = file.as_ref();
+        let display_name = source_display_name(file, src_prefixes);
+        match compiler.load_file(file, display_name) {
+            Ok(idx) => requested.push(idx),
+            Err(e) => {
+                return Err(::capnp::Error::failed(format!("{}: {}", file.display(), e)));
+            }
+        }
+    }
+
+    compiler.translate_pending();
+    compiler.compile_values();
+
+    if !compiler.errors.is_empty() {
+        return Err(::capnp::Error::failed(compiler.format_errors()));
+    }
+
+    compiler.build_request(&requested)
+}
+
+/// The name of a requested file in the generated code, i.e. its path with the longest
+/// matching source prefix removed.
+fn source_display_name<P: AsRef<Path>>(file: &Path, src_prefixes: &[P]) -> String {
+    let mut best: Option<&Path> = None;
+    for prefix in src_prefixes {
+        if let Ok(stripped) = file.strip_prefix(prefix.as_ref()) {
+            match best {
+                Some(b) if b.components().count() <= stripped.components().count() => (),
+                _ => best = Some(stripped),
+            }
+        }
+    }
+    path_to_display_name(best.unwrap_or(file))
+}
+
+fn path_to_display_name(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if parts.is_empty() || parts.last().unwrap() == ".." {
+                    parts.push("..".to_string());
+                } else {
+                    parts.pop();
+                }
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::RootDir | Component::Prefix(_) => parts.clear(),
+        }
+    }
+    parts.join("/")
+}
+
+impl Compiler {
+    fn error(&mut self, file: usize, span: Span, message: String) {
+        self.errors.push(Error { file, span, message });
+    }
+
+    fn format_errors(&self) -> String {
+        let mut result = String::new();
+        for error in &self.errors {
+            let file = &self.files[error.file];
+            let prefix = &file.text.as_bytes()[..::std::cmp::min(error.span.start, file.text.len())];
+            let line = prefix.iter().filter(|&&c| c == b'\n').count() + 1;
+            let column = prefix.iter().rev().take_while(|&&c| c != b'\n').count() + 1;
+            if !result.is_empty() {
+                result.push('\n');
+            }
+            result.push_str(&format!("{}:{}:{}: error: {}", file.path.display(), line, column, error.message));
+        }
+        result
+    }
+
+    /// Loads, parses and registers a file, unless it has been loaded already. Returns the
+    /// file's index.
+    fn load_file(&mut self, path: &Path, display_name: String) -> Result<usize, String> {
+        let canonical = path.canonicalize().map_err(|e| e.to_string())?;
+        for (idx, file) in self.files.iter().enumerate() {
+            if file.canonical == canonical {
+                return Ok(idx);
+            }
+        }
+
+        let text = ::std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut errors = Vec::new();
+        let statements = lexer::lex(&text, &mut errors);
+        let root = parser::parse_file(&display_name, statements, &mut errors);
+
+        let file = self.files.len();
+        self.files.push(SourceFile {
+            path: path.to_path_buf(),
+            canonical,
+            display_name,
+            text,
+            entity: self.entities.len(),
+            imports: BTreeMap::new(),
+        });
+        for (span, message) in errors {
+            self.error(file, span, message);
+        }
+        self.add_entity(file, None, root);
+        Ok(file)
+    }
+
+    /// Resolves an `import` expression appearing in `file`.
+    fn import(&mut self, file: usize, name: &str, span: Span) -> Option<usize> {
+        if let Some(&idx) = self.files[file].imports.get(name) {
+            return Some(idx);
+        }
+
+        let found = if let Ok(relative) = Path::new(name).strip_prefix("/") {
+            self.import_paths.iter()
+                .map(|dir| dir.join(relative))
+                .find(|candidate| candidate.is_file())
+                .map(|path| (path, path_to_display_name(Path::new(relative))))
+        } else {
+            let path = self.files[file].path.parent().unwrap_or_else(|| Path::new("")).join(name);
+            let display = Path::new(&self.files[file].display_name)
+                .parent().unwrap_or_else(|| Path::new("")).join(name);
+            if path.is_file() { Some((path, path_to_display_name(&display))) } else { None }
+        };
+
+        let result = match found {
+            Some((path, display_name)) => self.load_file(&path, display_name).ok(),
+            None => None,
+        };
+        match result {
+            Some(idx) => {
+                self.files[file].imports.insert(name.to_string(), idx);
+                Some(idx)
+            }
+            None => {
+                self.error(file, span, format!("Import failed: {}", name));
+                None
+            }
+        }
+    }
+
+    /// Reads the file named by an `embed` expression appearing in `file`.
+    fn read_embed(&mut self, file: usize, name: &str, span: Span) -> Option<Vec<u8>> {
+        let path = if let Ok(relative) = Path::new(name).strip_prefix("/") {
+            self.import_paths.iter().map(|dir| dir.join(relative)).find(|candidate| candidate.is_file())
+        } else {
+            Some(self.files[file].path.parent().unwrap_or_else(|| Path::new("")).join(name))
+        };
+        match path.map(::std::fs::read) {
+            Some(Ok(bytes)) => Some(bytes),
+            _ => {
+                self.error(file, span, format!("Couldn't read file for embed: {}", name));
+                None
+            }
+        }
+    }
+
+    fn add_entity(&mut self, file: usize, parent: Option<usize>, mut decl: Declaration) -> usize {
+        let kind = match decl.kind {
+            DeclKind::File => EntityKind::File,
+            DeclKind::Struct => EntityKind::Struct,
+            DeclKind::Enum => EntityKind::Enum,
+            DeclKind::Interface { .. } => EntityKind::Interface,
+            DeclKind::Const { .. } => EntityKind::Const,
+            DeclKind::Annotation { .. } => EntityKind::Annotation,
+            DeclKind::Using(_) => EntityKind::Alias,
+            _ => unreachable!("not an entity"),
+        };
+
+        self.check_names(file, &decl.nested, &decl.kind);
+
+        let id = match (kind, parent) {
+            (EntityKind::Alias, _) => 0,
+            (_, parent) => match decl.id {
+                parser::DeclId::Uid(ref uid) => uid.value,
+                _ => {
+                    match parent {
+                        Some(parent) => type_id::generate_child_id(self.entities[parent].id, &decl.name.value),
+                        None => {
+                            let id = type_id::generate_child_id(0, &self.files[file].display_name);
+                            self.error(file, Span::default(), format!(
+                                "File does not declare an ID.  I've generated one for you.  \
+                                 Add this line to your file: @0x{:016x};", id));
+                            id
+                        }
+                    }
+                }
+            },
+        };
+
+        if kind != EntityKind::Alias {
+            let span = match decl.id {
+                parser::DeclId::Uid(ref uid) => uid.span,
+                _ => decl.name.span,
+            };
+            if let Some(&(other_file, other_span)) = self.node_ids.get(&id) {
+                self.error(file, span, format!("Duplicate ID @0x{:016x}.", id));
+                self.error(other_file, other_span, format!("ID @0x{:016x} originally used here.", id));
+            } else {
+                self.node_ids.insert(id, (file, span));
+            }
+        }
+
+        let display_name = match parent {
+            None => self.files[file].display_name.clone(),
+            Some(parent) => {
+                let parent = &self.entities[parent];
+                let separator = if parent.parent.is_none() { ':' } else { '.' };
+                format!("{}{}{}", parent.display_name, separator, decl.name.value)
+            }
+        };
+
+        let (children, members): (Vec<Declaration>, Vec<Declaration>) =
+            decl.nested.drain(..).partition(|d| is_entity(d) && kind != EntityKind::Alias);
+        decl.nested = members;
+
+        let idx = self.entities.len();
+        self.entities.push(Entity {
+            kind,
+            id,
+            parent,
+            file,
+            display_name,
+            members: HashMap::new(),
+            nested: Vec::new(),
+            decl: Rc::new(decl),
+        });
+
+        for child in children {
+            let name = child.name.value.clone();
+            let is_alias = matches!(child.kind, DeclKind::Using(_));
+            let child_idx = self.add_entity(file, Some(idx), child);
+            self.entities[idx].members.entry(name).or_insert(child_idx);
+            if !is_alias {
+                self.entities[idx].nested.push(child_idx);
+            }
+        }
+        idx
+    }
+
+    /// Reports duplicate names and declarations that appear in the wrong kind of scope.
+    fn check_names(&mut self, file: usize, nested: &[Declaration], parent_kind: &DeclKind) {
+        let mut names: HashMap<&str, Span> = HashMap::new();
+        self.check_names_in(file, nested, parent_kind, &mut names);
+    }
+
+    fn check_names_in<'a>(&mut self, file: usize, nested: &'a [Declaration], parent_kind: &DeclKind,
+                          names: &mut HashMap<&'a str, Span>) {
+        for decl in nested {
+            let name = &decl.name.value[..];
+            if let Some(&previous) = names.get(name) {
+                if name.is_empty() {
+                    self.error(file, decl.name.span, "An unnamed union is already defined in this scope.".to_string());
+                    self.error(file, previous, "Previously defined here.".to_string());
+                } else {
+                    self.error(file, decl.name.span, format!("'{}' is already defined in this scope.", name));
+                    self.error(file, previous, format!("'{}' previously defined here.", name));
+                }
+            } else {
+                names.insert(name, decl.name.span);
+            }
+
+            match decl.kind {
+                DeclKind::Using(_) | DeclKind::Const { .. } | DeclKind::Enum | DeclKind::Struct |
+                DeclKind::Interface { .. } | DeclKind::Annotation { .. } => {
+                    match *parent_kind {
+                        DeclKind::File | DeclKind::Struct | DeclKind::Interface { .. } => (),
+                        _ => self.error(file, decl.span, "This kind of declaration doesn't belong here.".to_string()),
+                    }
+                }
+                DeclKind::Enumerant => {
+                    if let DeclKind::Enum = *parent_kind {} else {
+                        self.error(file, decl.span, "Enumerants can only appear in enums.".to_string());
+                    }
+                }
+                DeclKind::Method { .. } => {
+                    if let DeclKind::Interface { .. } = *parent_kind {} else {
+                        self.error(file, decl.span, "Methods can only appear in interfaces.".to_string());
+                    }
+                }
+                DeclKind::Field { .. } | DeclKind::Union | DeclKind::Group => {
+                    match *parent_kind {
+                        DeclKind::Struct | DeclKind::Union | DeclKind::Group => (),
+                        _ => self.error(file, decl.span, "This declaration can only appear in structs.".to_string()),
+                    }
+                    if name.is_empty() {
+                        // An unnamed union's members are in the same scope as the union.
+                        self.check_names_in(file, &decl.nested, &decl.kind, names);
+                    } else {
+                        self.check_names(file, &decl.nested, &decl.kind);
+                    }
+                }
+                DeclKind::File => {
+                    self.error(file, decl.span, "This kind of declaration doesn't belong here.".to_string());
+                }
+            }
+        }
+    }
+
+    /// Translates all entities that have been loaded but not yet translated.
+    fn translate_pending(&mut self) {
+        while self.translated < self.entities.len() {
+            let idx = self.translated;
+            self.translated += 1;
+            if self.entities[idx].kind != EntityKind::Alias {
+                self.translate(idx);
+            }
+        }
+    }
+
+    fn build_request(&self, requested: &[usize])
+                     -> ::capnp::Result<message::Builder<message::HeapAllocator>> {
+        let mut message = message::Builder::new_default();
+        {
+            let mut request = message.init_root::<code_generator_request::Builder>();
+            {
+                let mut version = request.reborrow().init_capnp_version();
+                version.set_major(0);
+                version.set_minor(8);
+                version.set_micro(0);
+            }
+            {
+                let mut nodes = request.reborrow().init_nodes(self.nodes.len() as u32);
+                for (idx, n) in self.nodes.values().enumerate() {
+                    node::write_node(n, nodes.reborrow().get(idx as u32))?;
+                }
+            }
+            {
+                let mut source_info = request.reborrow().init_source_info(self.nodes.len() as u32);
+                for (idx, n) in self.nodes.values().enumerate() {
+                    node::write_source_info(n, source_info.reborrow().get(idx as u32));
+                }
+            }
+            let mut requested_files = request.init_requested_files(requested.len() as u32);
+            for (idx, &file) in requested.iter().enumerate() {
+                let file = &self.files[file];
+                let mut requested_file = requested_files.reborrow().get(idx as u32);
+                requested_file.set_id(self.entities[file.entity].id);
+                requested_file.set_filename(&file.display_name);
+                let mut imports = requested_file.init_imports(file.imports.len() as u32);
+                for (idx, (name, &imported)) in file.imports.iter().enumerate() {
+                    let mut import = imports.reborrow().get(idx as u32);
+                    import.set_id(self.entities[self.files[imported].entity].id);
+                    import.set_name(name);
+                }
+            }
+        }
+        Ok(message)
+    }
+}
+
+fn is_entity(decl: &Declaration) -> bool {
+    matches!(decl.kind,
+             DeclKind::Struct | DeclKind::Enum | DeclKind::Interface { .. } | DeclKind::Const { .. } |
+             DeclKind::Annotation { .. } | DeclKind::Using(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::codegen::CodeGenerationCommand;
+
+    const CXX_CAPNP: &str = r#"@0xbdf87d7bb8304e81;
+annotation namespace @0xb9c6f99ebf805f2c (file) :Text;
+annotation name @0xf264a779fef191ce (field, enumerant, struct, enum, interface, method, param, group, union) :Text;
+"#;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = ::std::env::temp_dir().join(format!("capnpc-compiler-{}-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Compiles `file` natively and checks that the generated code matches `expected`.
+    fn check_generated(name: &str, src_prefix: &Path, file: &str, expected: &Path) {
+        let dir = temp_dir(name);
+        fs::create_dir_all(dir.join("capnp")).unwrap();
+        fs::write(dir.join("capnp/c++.capnp"), CXX_CAPNP).unwrap();
+        fs::create_dir_all(dir.join("out")).unwrap();
+
+        let message = super::compile(&[src_prefix.join(file)], &[src_prefix], &[&dir])
+            .unwrap_or_else(|e| panic!("{}", e));
+        let mut bytes = Vec::new();
+        capnp::serialize::write_message(&mut bytes, &message).unwrap();
+        CodeGenerationCommand::new().output_directory(dir.join("out")).run(&bytes[..]).unwrap();
+
+        let generated = fs::read_to_string(dir.join("out").join(expected.file_name().unwrap())).unwrap();
+        let expected = fs::read_to_string(expected).unwrap();
+        assert!(generated == expected, "generated code for {} differs; see {}", file, dir.display());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn schema_capnp() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        check_generated("schema", manifest_dir, "schema.capnp", &manifest_dir.join("src/schema_capnp.rs"));
+    }
+
+    /// Compiles `schema`, which can import `/rust.capnp`, and generates code for it with plain
+    /// structs and annotation constants enabled.
+    fn generate_with_rust_annotations(name: &str, schema: &str) -> capnp::Result<()> {
+        let dir = temp_dir(name);
+        fs::write(dir.join("foo.capnp"), schema).unwrap();
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let message = super::compile(&[dir.join("foo.capnp")], &[&dir], &[manifest_dir])
+            .unwrap_or_else(|e| panic!("{}", e));
+        let mut bytes = Vec::new();
+        capnp::serialize::write_message(&mut bytes, &message).unwrap();
+        let result = CodeGenerationCommand::new()
+            .output_directory(&dir).plain_structs(true).annotation_constants(true).run(&bytes[..]);
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    #[test]
+    fn derive_validation() {
+        const PREAMBLE: &str = "@0xd508eebdc2dc42b8;\nusing Rust = import \"/rust.capnp\";\n";
+        let error = |name: &str, schema: &str| {
+            match generate_with_rust_annotations(name, &format!("{}{}", PREAMBLE, schema)) {
+                Ok(()) => panic!("expected an error"),
+                Err(e) => e.description,
+            }
+        };
+
+        assert_eq!(error("derive-float", "struct Foo $Rust.derive([\"Hash\"]) { x @0 :Float64; }"),
+                   "cannot derive `Hash` for foo.capnp:Foo: field `x`: floating-point values do not implement it");
+        assert_eq!(error("derive-prerequisite", "enum Foo $Rust.derive([\"Ord\"]) { a @0; }"),
+                   "cannot derive `Ord` for foo.capnp:Foo: it requires `Eq`");
+        assert_eq!(error("derive-default", "enum Foo $Rust.derive([\"Default\"]) { a @0; }"),
+                   "cannot derive `Default` for foo.capnp:Foo: enums have no default value");
+        assert_eq!(error("derive-nested",
+                         "struct Foo $Rust.derive([\"Eq\"]) { bar @0 :Bar; }\nstruct Bar { x @0 :Text; }"),
+                   "cannot derive `Eq` for foo.capnp:Foo: field `bar`: foo.capnp:Bar does not derive it");
+        assert_eq!(error("attribute-non-exhaustive", "struct Foo $Rust.attribute([\"non_exhaustive\"]) {}"),
+                   "cannot apply attribute `non_exhaustive` to foo.capnp:Foo: it is only allowed on enums");
+
+        // Derives requested for a whole file are skipped where they don't apply, and derives
+        // that are passed through are not checked.
+        generate_with_rust_annotations(
+            "derive-file",
+            &format!("{}$Rust.derive([\"Eq\", \"Hash\"]);\n\
+                      struct Foo {{ x @0 :Float64; }}\nstruct Bar {{ foo @0 :Foo; y @1 :Text; }}\n\
+                      enum Baz $Rust.derive([\"my::Trait\"]) {{ a @0; }}", PREAMBLE)).unwrap();
+        // Likewise, `non_exhaustive` on a file only applies to its enums.
+        generate_with_rust_annotations(
+            "attribute-file",
+            &format!("{}$Rust.attribute([\"non_exhaustive\"]);\n\
+                      struct Foo {{}}\ninterface Bar {{}}\nenum Baz {{ a @0; }}", PREAMBLE)).unwrap();
+    }
+
+    #[test]
+    fn duplicate_annotation_lookup_functions() {
+        // Two annotations with the same name, declared in different scopes.
+        let result = generate_with_rust_annotations(
+            "duplicate-lookup",
+            "@0xd508eebdc2dc42b8;\n\
+             annotation scope @0xf2b6c9e4a8d13b70 (method) :Text;\n\
+             struct Other {\n  annotation scope @0xc4a7e2b9d1f06b35 (method) :Text;\n}\n\
+             interface Foo {\n  a @0 () -> () $scope(\"x\");\n  b @1 () -> () $Other.scope(\"y\");\n}\n");
+        match result {
+            Ok(()) => panic!("expected an error"),
+            Err(e) => assert_eq!(
+                e.description,
+                "more than one annotation on the methods of foo.capnp:Foo would generate the function scope_for_method"),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let dir = temp_dir("errors");
+        fs::write(dir.join("foo.capnp"), "@0xd508eebdc2dc42b8;\nstruct Foo {\n  a @0 :Bar;\n  b @2 :Int8 = 300;\n}\n")
+            .unwrap();
+        let error = match super::compile(&[dir.join("foo.capnp")], &[&dir], &[] as &[PathBuf]) {
+            Ok(_) => panic!("expected errors"),
+            Err(e) => e,
+        };
+        let _ = fs::remove_dir_all(&dir);
+        let prefix = format!("{}/", dir.display());
+        let lines: Vec<&str> = error.description.lines().map(|l| l.trim_start_matches(&prefix[..])).collect();
+        assert_eq!(lines, [
+            "foo.capnp:3:9: error: Not defined: Bar",
+            "foo.capnp:4:6: error: Skipped ordinal @1.  Ordinals must be sequential with no holes.",
+            "foo.capnp:4:16: error: Integer value out of range.",
+        ]);
+    }
+}
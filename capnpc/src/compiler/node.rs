@@ -0,0 +1,748 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Compiled schema nodes, mirroring the structs of schema.capnp. Values are filled in after
+//! all nodes have been laid out, so they live in shared cells.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use capnp::private::layout::{ElementSize, PointerBuilder, StructBuilder, StructSize};
+use capnp::{any_pointer, message, serialize};
+
+use crate::schema_capnp::{annotation, brand, field, method, node, type_, value};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnyKind {
+    Any,
+    Struct,
+    List,
+    Capability,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Void,
+    Bool,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float32,
+    Float64,
+    Text,
+    Data,
+    List(Box<Type>),
+    Enum(u64, Option<Brand>),
+    Struct(u64, Option<Brand>),
+    Interface(u64, Option<Brand>),
+    AnyPointer(AnyKind),
+    Parameter { scope_id: u64, index: u16 },
+    ImplicitMethodParameter(u16),
+}
+
+impl Type {
+    pub fn is_pointer(&self) -> bool {
+        matches!(*self,
+                 Type::Text | Type::Data | Type::List(_) | Type::Struct(..) | Type::Interface(..) |
+                 Type::AnyPointer(_) | Type::Parameter { .. } | Type::ImplicitMethodParameter(_))
+    }
+
+    /// The log2 of the size of the type in bits, if it's a data type. Void is -1.
+    pub fn lg_data_size(&self) -> Option<i32> {
+        match *self {
+            Type::Void => Some(-1),
+            Type::Bool => Some(0),
+            Type::Int8 | Type::UInt8 => Some(3),
+            Type::Int16 | Type::UInt16 | Type::Enum(..) => Some(4),
+            Type::Int32 | Type::UInt32 | Type::Float32 => Some(5),
+            Type::Int64 | Type::UInt64 | Type::Float64 => Some(6),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrandScope {
+    pub scope_id: u64,
+    /// `None` means the parameters are inherited from the surrounding scope.
+    pub bind: Option<Vec<Type>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Brand {
+    pub scopes: Vec<BrandScope>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StructObject {
+    pub data: Vec<u8>,
+    pub pointers: Vec<Option<Object>>,
+}
+
+impl StructObject {
+    pub fn new(data_words: u16, pointer_count: u16) -> StructObject {
+        StructObject {
+            data: vec![0; data_words as usize * 8],
+            pointers: vec![None; pointer_count as usize],
+        }
+    }
+
+    /// Stores `bits` (of which the low 2^lg_size are significant) at `offset`, which is a
+    /// multiple of the value's size.
+    pub fn set_bits(&mut self, lg_size: i32, offset: u32, bits: u64) {
+        if lg_size < 0 {
+            return;
+        }
+        if lg_size == 0 {
+            let byte = &mut self.data[(offset / 8) as usize];
+            if bits & 1 == 1 {
+                *byte |= 1 << (offset % 8);
+            } else {
+                *byte &= !(1 << (offset % 8));
+            }
+            return;
+        }
+        let size = 1usize << (lg_size - 3);
+        let start = offset as usize * size;
+        self.data[start..start + size].copy_from_slice(&bits.to_le_bytes()[..size]);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ListObject {
+    Void(u32),
+    Bit(Vec<bool>),
+    Byte(Vec<u8>),
+    TwoBytes(Vec<u16>),
+    FourBytes(Vec<u32>),
+    EightBytes(Vec<u64>),
+    Pointer(Vec<Option<Object>>),
+    InlineComposite { data_words: u16, pointer_count: u16, elements: Vec<StructObject> },
+}
+
+/// The target of a non-null pointer within a value.
+#[derive(Clone, Debug)]
+pub enum Object {
+    Text(String),
+    Data(Vec<u8>),
+    Struct(StructObject),
+    List(ListObject),
+    /// The root of a serialized message, as produced by `embed`.
+    Message(Rc<Vec<u8>>),
+}
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Void,
+    Bool(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    Float32(f32),
+    Float64(f64),
+    Text(Option<Object>),
+    Data(Option<Object>),
+    List(Option<Object>),
+    Enum(u16),
+    Struct(Option<Object>),
+    Interface,
+    AnyPointer(Option<Object>),
+}
+
+impl Value {
+    /// The value's bits, if it's a data value, for use in a struct's data section.
+    pub fn bits(&self) -> Option<u64> {
+        Some(match *self {
+            Value::Void => 0,
+            Value::Bool(v) => v as u64,
+            Value::Int8(v) => v as u8 as u64,
+            Value::Int16(v) => v as u16 as u64,
+            Value::Int32(v) => v as u32 as u64,
+            Value::Int64(v) => v as u64,
+            Value::UInt8(v) => u64::from(v),
+            Value::UInt16(v) => u64::from(v),
+            Value::UInt32(v) => u64::from(v),
+            Value::UInt64(v) => v,
+            Value::Float32(v) => u64::from(v.to_bits()),
+            Value::Float64(v) => v.to_bits(),
+            Value::Enum(v) => u64::from(v),
+            _ => return None,
+        })
+    }
+
+    /// The default value of a field of the given type that doesn't declare a default.
+    pub fn default_for(type_: &Type) -> Value {
+        match *type_ {
+            Type::Void => Value::Void,
+            Type::Bool => Value::Bool(false),
+            Type::Int8 => Value::Int8(0),
+            Type::Int16 => Value::Int16(0),
+            Type::Int32 => Value::Int32(0),
+            Type::Int64 => Value::Int64(0),
+            Type::UInt8 => Value::UInt8(0),
+            Type::UInt16 => Value::UInt16(0),
+            Type::UInt32 => Value::UInt32(0),
+            Type::UInt64 => Value::UInt64(0),
+            Type::Float32 => Value::Float32(0.0),
+            Type::Float64 => Value::Float64(0.0),
+            Type::Text => Value::Text(None),
+            Type::Data => Value::Data(None),
+            Type::List(_) => Value::List(None),
+            Type::Enum(..) => Value::Enum(0),
+            Type::Struct(..) => Value::Struct(None),
+            Type::Interface(..) => Value::Interface,
+            Type::AnyPointer(_) | Type::Parameter { .. } | Type::ImplicitMethodParameter(_) => Value::AnyPointer(None),
+        }
+    }
+
+    pub fn into_object(self) -> Option<Object> {
+        match self {
+            Value::Text(o) | Value::Data(o) | Value::List(o) | Value::Struct(o) | Value::AnyPointer(o) => o,
+            _ => None,
+        }
+    }
+}
+
+/// A value that is compiled after the node containing it has been built.
+pub type ValueCell = Rc<RefCell<Value>>;
+
+pub fn value_cell() -> ValueCell {
+    Rc::new(RefCell::new(Value::Void))
+}
+
+#[derive(Clone, Debug)]
+pub struct Annotation {
+    pub id: u64,
+    pub brand: Option<Brand>,
+    pub value: ValueCell,
+}
+
+#[derive(Clone, Debug)]
+pub enum FieldKind {
+    Slot { offset: u32, type_: Type, default_value: ValueCell, had_explicit_default: bool },
+    Group(u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub code_order: u16,
+    pub annotations: Option<Vec<Annotation>>,
+    pub discriminant_value: u16,
+    pub kind: FieldKind,
+    /// `None` for method parameters, whose ordinals are implicit.
+    pub ordinal: Option<u16>,
+}
+
+pub const NO_DISCRIMINANT: u16 = 0xffff;
+
+#[derive(Clone, Debug, Default)]
+pub struct Struct {
+    pub data_word_count: u16,
+    pub pointer_count: u16,
+    pub is_group: bool,
+    pub discriminant_count: u16,
+    pub discriminant_offset: u32,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Enumerant {
+    pub name: String,
+    pub code_order: u16,
+    pub annotations: Option<Vec<Annotation>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Method {
+    pub name: String,
+    pub code_order: u16,
+    pub implicit_parameters: Vec<String>,
+    pub param_struct_type: u64,
+    pub param_brand: Option<Brand>,
+    pub result_struct_type: u64,
+    pub result_brand: Option<Brand>,
+    pub annotations: Option<Vec<Annotation>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Superclass {
+    pub id: u64,
+    pub brand: Option<Brand>,
+}
+
+#[derive(Clone, Debug)]
+pub enum NodeBody {
+    File,
+    Struct(Struct),
+    Enum(Vec<Enumerant>),
+    Interface { methods: Vec<Method>, superclasses: Vec<Superclass> },
+    Const { type_: Type, value: ValueCell },
+    Annotation { type_: Type, targets: [bool; 12] },
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub id: u64,
+    pub display_name: String,
+    pub display_name_prefix_length: u32,
+    pub scope_id: u64,
+    pub parameters: Vec<String>,
+    pub is_generic: bool,
+    pub nested_nodes: Vec<(String, u64)>,
+    pub annotations: Option<Vec<Annotation>>,
+    pub body: NodeBody,
+    pub doc_comment: Option<String>,
+    /// Doc comments of fields, enumerants or methods, in the same order as the schema lists them.
+    pub member_doc_comments: Vec<Option<String>>,
+}
+
+impl Node {
+    pub fn new(id: u64, display_name: String, scope_id: u64, body: NodeBody) -> Node {
+        // Like the C++ compiler, use the position after the last '.', or after a later ':' or '/'.
+        let mut prefix_length = display_name.rfind('.').map_or(0, |i| i + 1);
+        for &separator in &[':', '/'] {
+            if let Some(position) = display_name.rfind(separator) {
+                if position > prefix_length {
+                    prefix_length = position + 1;
+                }
+            }
+        }
+        Node {
+            id,
+            display_name_prefix_length: prefix_length as u32,
+            display_name,
+            scope_id,
+            parameters: Vec::new(),
+            is_generic: false,
+            nested_nodes: Vec::new(),
+            annotations: None,
+            body,
+            doc_comment: None,
+            member_doc_comments: Vec::new(),
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&Struct> {
+        match self.body {
+            NodeBody::Struct(ref s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Serialization.
+
+pub fn write_node(node: &Node, mut builder: node::Builder) -> capnp::Result<()> {
+    builder.set_id(node.id);
+    builder.set_display_name(&node.display_name);
+    builder.set_display_name_prefix_length(node.display_name_prefix_length);
+    builder.set_scope_id(node.scope_id);
+    if !node.parameters.is_empty() {
+        let mut parameters = builder.reborrow().init_parameters(node.parameters.len() as u32);
+        for (idx, name) in node.parameters.iter().enumerate() {
+            parameters.reborrow().get(idx as u32).set_name(name);
+        }
+    }
+    builder.set_is_generic(node.is_generic);
+    {
+        let mut nested = builder.reborrow().init_nested_nodes(node.nested_nodes.len() as u32);
+        for (idx, &(ref name, id)) in node.nested_nodes.iter().enumerate() {
+            let mut nested_node = nested.reborrow().get(idx as u32);
+            nested_node.set_name(name);
+            nested_node.set_id(id);
+        }
+    }
+    if let Some(ref annotations) = node.annotations {
+        write_annotations(annotations, builder.reborrow().init_annotations(annotations.len() as u32))?;
+    }
+
+    match node.body {
+        NodeBody::File => builder.set_file(()),
+        NodeBody::Struct(ref s) => {
+            let mut struct_ = builder.init_struct();
+            struct_.set_data_word_count(s.data_word_count);
+            struct_.set_pointer_count(s.pointer_count);
+            struct_.set_preferred_list_encoding(crate::schema_capnp::ElementSize::InlineComposite);
+            struct_.set_is_group(s.is_group);
+            struct_.set_discriminant_count(s.discriminant_count);
+            struct_.set_discriminant_offset(s.discriminant_offset);
+            let mut fields = struct_.init_fields(s.fields.len() as u32);
+            for (idx, f) in s.fields.iter().enumerate() {
+                write_field(f, fields.reborrow().get(idx as u32))?;
+            }
+        }
+        NodeBody::Enum(ref enumerants) => {
+            let mut list = builder.init_enum().init_enumerants(enumerants.len() as u32);
+            for (idx, e) in enumerants.iter().enumerate() {
+                let mut enumerant = list.reborrow().get(idx as u32);
+                enumerant.set_name(&e.name);
+                enumerant.set_code_order(e.code_order);
+                if let Some(ref annotations) = e.annotations {
+                    write_annotations(annotations, enumerant.init_annotations(annotations.len() as u32))?;
+                }
+            }
+        }
+        NodeBody::Interface { ref methods, ref superclasses } => {
+            let mut interface = builder.init_interface();
+            {
+                let mut list = interface.reborrow().init_methods(methods.len() as u32);
+                for (idx, m) in methods.iter().enumerate() {
+                    write_method(m, list.reborrow().get(idx as u32))?;
+                }
+            }
+            let mut list = interface.init_superclasses(superclasses.len() as u32);
+            for (idx, s) in superclasses.iter().enumerate() {
+                let mut superclass = list.reborrow().get(idx as u32);
+                superclass.set_id(s.id);
+                if let Some(ref brand) = s.brand {
+                    write_brand(brand, superclass.init_brand());
+                }
+            }
+        }
+        NodeBody::Const { ref type_, ref value } => {
+            let mut const_ = builder.init_const();
+            write_type(type_, const_.reborrow().init_type());
+            const_.set_value(value_message(&value.borrow())?.get_root_as_reader()?)?;
+        }
+        NodeBody::Annotation { ref type_, ref targets } => {
+            let mut annotation = builder.init_annotation();
+            write_type(type_, annotation.reborrow().init_type());
+            annotation.set_targets_file(targets[0]);
+            annotation.set_targets_const(targets[1]);
+            annotation.set_targets_enum(targets[2]);
+            annotation.set_targets_enumerant(targets[3]);
+            annotation.set_targets_struct(targets[4]);
+            annotation.set_targets_field(targets[5]);
+            annotation.set_targets_union(targets[6]);
+            annotation.set_targets_group(targets[7]);
+            annotation.set_targets_interface(targets[8]);
+            annotation.set_targets_method(targets[9]);
+            annotation.set_targets_param(targets[10]);
+            annotation.set_targets_annotation(targets[11]);
+        }
+    }
+    Ok(())
+}
+
+pub fn write_source_info(node: &Node, mut builder: node::source_info::Builder) {
+    builder.set_id(node.id);
+    if let Some(ref doc) = node.doc_comment {
+        builder.set_doc_comment(doc);
+    }
+    let member_count = match node.body {
+        NodeBody::Struct(ref s) => s.fields.len(),
+        NodeBody::Enum(ref e) => e.len(),
+        NodeBody::Interface { ref methods, .. } => methods.len(),
+        _ => return,
+    };
+    let mut members = builder.init_members(member_count as u32);
+    for (idx, doc) in node.member_doc_comments.iter().enumerate() {
+        if let Some(ref doc) = *doc {
+            members.reborrow().get(idx as u32).set_doc_comment(doc);
+        }
+    }
+}
+
+fn write_field(f: &Field, mut builder: field::Builder) -> capnp::Result<()> {
+    builder.set_name(&f.name);
+    builder.set_code_order(f.code_order);
+    if let Some(ref annotations) = f.annotations {
+        write_annotations(annotations, builder.reborrow().init_annotations(annotations.len() as u32))?;
+    }
+    builder.set_discriminant_value(f.discriminant_value);
+    match f.kind {
+        FieldKind::Slot { offset, ref type_, ref default_value, had_explicit_default } => {
+            let mut slot = builder.reborrow().init_slot();
+            slot.set_offset(offset);
+            write_type(type_, slot.reborrow().init_type());
+            slot.set_default_value(value_message(&default_value.borrow())?.get_root_as_reader()?)?;
+            slot.set_had_explicit_default(had_explicit_default);
+        }
+        FieldKind::Group(id) => builder.reborrow().init_group().set_type_id(id),
+    }
+    match f.ordinal {
+        Some(ordinal) => builder.init_ordinal().set_explicit(ordinal),
+        None => builder.init_ordinal().set_implicit(()),
+    }
+    Ok(())
+}
+
+fn write_method(m: &Method, mut builder: method::Builder) -> capnp::Result<()> {
+    builder.set_name(&m.name);
+    builder.set_code_order(m.code_order);
+    {
+        let mut implicit = builder.reborrow().init_implicit_parameters(m.implicit_parameters.len() as u32);
+        for (idx, name) in m.implicit_parameters.iter().enumerate() {
+            implicit.reborrow().get(idx as u32).set_name(name);
+        }
+    }
+    builder.set_param_struct_type(m.param_struct_type);
+    if let Some(ref brand) = m.param_brand {
+        write_brand(brand, builder.reborrow().init_param_brand());
+    }
+    builder.set_result_struct_type(m.result_struct_type);
+    if let Some(ref brand) = m.result_brand {
+        write_brand(brand, builder.reborrow().init_result_brand());
+    }
+    if let Some(ref annotations) = m.annotations {
+        write_annotations(annotations, builder.init_annotations(annotations.len() as u32))?;
+    }
+    Ok(())
+}
+
+fn write_annotations(annotations: &[Annotation],
+                     mut builder: capnp::struct_list::Builder<annotation::Owned>) -> capnp::Result<()> {
+    for (idx, a) in annotations.iter().enumerate() {
+        let mut annotation = builder.reborrow().get(idx as u32);
+        annotation.set_id(a.id);
+        if let Some(ref brand) = a.brand {
+            write_brand(brand, annotation.reborrow().init_brand());
+        }
+        annotation.set_value(value_message(&a.value.borrow())?.get_root_as_reader()?)?;
+    }
+    Ok(())
+}
+
+pub fn write_type(t: &Type, mut builder: type_::Builder) {
+    match *t {
+        Type::Void => builder.set_void(()),
+        Type::Bool => builder.set_bool(()),
+        Type::Int8 => builder.set_int8(()),
+        Type::Int16 => builder.set_int16(()),
+        Type::Int32 => builder.set_int32(()),
+        Type::Int64 => builder.set_int64(()),
+        Type::UInt8 => builder.set_uint8(()),
+        Type::UInt16 => builder.set_uint16(()),
+        Type::UInt32 => builder.set_uint32(()),
+        Type::UInt64 => builder.set_uint64(()),
+        Type::Float32 => builder.set_float32(()),
+        Type::Float64 => builder.set_float64(()),
+        Type::Text => builder.set_text(()),
+        Type::Data => builder.set_data(()),
+        Type::List(ref element) => write_type(element, builder.init_list().init_element_type()),
+        Type::Enum(id, ref brand) => {
+            let mut enum_ = builder.init_enum();
+            enum_.set_type_id(id);
+            if let Some(ref brand) = *brand {
+                write_brand(brand, enum_.init_brand());
+            }
+        }
+        Type::Struct(id, ref brand) => {
+            let mut struct_ = builder.init_struct();
+            struct_.set_type_id(id);
+            if let Some(ref brand) = *brand {
+                write_brand(brand, struct_.init_brand());
+            }
+        }
+        Type::Interface(id, ref brand) => {
+            let mut interface = builder.init_interface();
+            interface.set_type_id(id);
+            if let Some(ref brand) = *brand {
+                write_brand(brand, interface.init_brand());
+            }
+        }
+        Type::AnyPointer(ref kind) => {
+            let mut unconstrained = builder.init_any_pointer().init_unconstrained();
+            match *kind {
+                AnyKind::Any => unconstrained.set_any_kind(()),
+                AnyKind::Struct => unconstrained.set_struct(()),
+                AnyKind::List => unconstrained.set_list(()),
+                AnyKind::Capability => unconstrained.set_capability(()),
+            }
+        }
+        Type::Parameter { scope_id, index } => {
+            let mut parameter = builder.init_any_pointer().init_parameter();
+            parameter.set_scope_id(scope_id);
+            parameter.set_parameter_index(index);
+        }
+        Type::ImplicitMethodParameter(index) => {
+            builder.init_any_pointer().init_implicit_method_parameter().set_parameter_index(index);
+        }
+    }
+}
+
+fn write_brand(b: &Brand, builder: brand::Builder) {
+    let mut scopes = builder.init_scopes(b.scopes.len() as u32);
+    for (idx, s) in b.scopes.iter().enumerate() {
+        let mut scope = scopes.reborrow().get(idx as u32);
+        scope.set_scope_id(s.scope_id);
+        match s.bind {
+            None => scope.set_inherit(()),
+            Some(ref bindings) => {
+                let mut list = scope.init_bind(bindings.len() as u32);
+                for (idx, t) in bindings.iter().enumerate() {
+                    write_type(t, list.reborrow().get(idx as u32).init_type());
+                }
+            }
+        }
+    }
+}
+
+/// A root pointer that we fill in with the low-level layout API.
+struct RawPointer<'a>(PointerBuilder<'a>);
+
+impl <'a> capnp::traits::FromPointerBuilder<'a> for RawPointer<'a> {
+    fn init_pointer(builder: PointerBuilder<'a>, _length: u32) -> RawPointer<'a> {
+        RawPointer(builder)
+    }
+    fn get_from_pointer(builder: PointerBuilder<'a>, _default: Option<&'a [capnp::Word]>) -> capnp::Result<RawPointer<'a>> {
+        Ok(RawPointer(builder))
+    }
+}
+
+/// Builds a message whose root is a `schema.capnp` `Value`. Generated setters can't express
+/// everything we need (e.g. a text value with a null pointer), so we write the value with the
+/// low-level API and then copy it into place.
+fn value_message(v: &Value) -> capnp::Result<message::Builder<message::HeapAllocator>> {
+    let mut message = message::Builder::new_default();
+    {
+        let RawPointer(root) = message.init_root();
+        let builder = root.init_struct(StructSize { data: 2, pointers: 1 });
+        write_value(v, builder)?;
+    }
+    // Make sure that the result really is a `Value`.
+    message.get_root_as_reader::<value::Reader>()?;
+    Ok(message)
+}
+
+fn write_value(v: &Value, builder: StructBuilder) -> capnp::Result<()> {
+    let (discriminant, object) = match *v {
+        Value::Void => (0, None),
+        Value::Bool(b) => {
+            builder.set_bool_field(16, b);
+            (1, None)
+        }
+        Value::Int8(x) => { builder.set_data_field::<i8>(2, x); (2, None) }
+        Value::Int16(x) => { builder.set_data_field::<i16>(1, x); (3, None) }
+        Value::Int32(x) => { builder.set_data_field::<i32>(1, x); (4, None) }
+        Value::Int64(x) => { builder.set_data_field::<i64>(1, x); (5, None) }
+        Value::UInt8(x) => { builder.set_data_field::<u8>(2, x); (6, None) }
+        Value::UInt16(x) => { builder.set_data_field::<u16>(1, x); (7, None) }
+        Value::UInt32(x) => { builder.set_data_field::<u32>(1, x); (8, None) }
+        Value::UInt64(x) => { builder.set_data_field::<u64>(1, x); (9, None) }
+        Value::Float32(x) => { builder.set_data_field::<f32>(1, x); (10, None) }
+        Value::Float64(x) => { builder.set_data_field::<f64>(1, x); (11, None) }
+        Value::Text(ref o) => (12, o.as_ref()),
+        Value::Data(ref o) => (13, o.as_ref()),
+        Value::List(ref o) => (14, o.as_ref()),
+        Value::Enum(x) => { builder.set_data_field::<u16>(1, x); (15, None) }
+        Value::Struct(ref o) => (16, o.as_ref()),
+        Value::Interface => (17, None),
+        Value::AnyPointer(ref o) => (18, o.as_ref()),
+    };
+    builder.set_data_field::<u16>(0, discriminant);
+    if let Some(object) = object {
+        write_object(object, builder.get_pointer_field(0))?;
+    }
+    Ok(())
+}
+
+fn write_struct_object(s: &StructObject, builder: StructBuilder) -> capnp::Result<()> {
+    for (idx, &byte) in s.data.iter().enumerate() {
+        if byte != 0 {
+            builder.set_data_field::<u8>(idx, byte);
+        }
+    }
+    for (idx, pointer) in s.pointers.iter().enumerate() {
+        if let Some(ref object) = *pointer {
+            write_object(object, builder.get_pointer_field(idx))?;
+        }
+    }
+    Ok(())
+}
+
+fn struct_size(s: &StructObject) -> StructSize {
+    StructSize { data: (s.data.len() / 8) as u16, pointers: s.pointers.len() as u16 }
+}
+
+fn write_object(object: &Object, builder: PointerBuilder) -> capnp::Result<()> {
+    use capnp::private::layout::PrimitiveElement;
+
+    match *object {
+        Object::Text(ref text) => builder.set_text(text),
+        Object::Data(ref data) => builder.set_data(data),
+        Object::Struct(ref s) => write_struct_object(s, builder.init_struct(struct_size(s)))?,
+        Object::List(ListObject::Void(count)) => { builder.init_list(ElementSize::Void, count); }
+        Object::List(ListObject::Bit(ref elements)) => {
+            let list = builder.init_list(ElementSize::Bit, elements.len() as u32);
+            for (idx, &e) in elements.iter().enumerate() {
+                PrimitiveElement::set(&list, idx as u32, e);
+            }
+        }
+        Object::List(ListObject::Byte(ref elements)) => {
+            let list = builder.init_list(ElementSize::Byte, elements.len() as u32);
+            for (idx, &e) in elements.iter().enumerate() {
+                PrimitiveElement::set(&list, idx as u32, e);
+            }
+        }
+        Object::List(ListObject::TwoBytes(ref elements)) => {
+            let list = builder.init_list(ElementSize::TwoBytes, elements.len() as u32);
+            for (idx, &e) in elements.iter().enumerate() {
+                PrimitiveElement::set(&list, idx as u32, e);
+            }
+        }
+        Object::List(ListObject::FourBytes(ref elements)) => {
+            let list = builder.init_list(ElementSize::FourBytes, elements.len() as u32);
+            for (idx, &e) in elements.iter().enumerate() {
+                PrimitiveElement::set(&list, idx as u32, e);
+            }
+        }
+        Object::List(ListObject::EightBytes(ref elements)) => {
+            let list = builder.init_list(ElementSize::EightBytes, elements.len() as u32);
+            for (idx, &e) in elements.iter().enumerate() {
+                PrimitiveElement::set(&list, idx as u32, e);
+            }
+        }
+        Object::List(ListObject::Pointer(ref elements)) => {
+            let mut list = builder.init_list(ElementSize::Pointer, elements.len() as u32);
+            for (idx, e) in elements.iter().enumerate() {
+                if let Some(ref e) = *e {
+                    write_object(e, list.reborrow().get_pointer_element(idx as u32))?;
+                }
+            }
+        }
+        Object::List(ListObject::InlineComposite { data_words, pointer_count, ref elements }) => {
+            let mut list = builder.init_struct_list(
+                elements.len() as u32, StructSize { data: data_words, pointers: pointer_count });
+            for (idx, e) in elements.iter().enumerate() {
+                write_struct_object(e, list.reborrow().get_struct_element(idx as u32))?;
+            }
+        }
+        Object::Message(ref bytes) => {
+            let message = serialize::read_message(&mut &bytes[..], message::ReaderOptions::new())?;
+            let root: any_pointer::Reader = message.get_root()?;
+            any_pointer::Builder::new(builder).set_as(root)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,984 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Turns lexed statements into a tree of declarations.
+
+use crate::compiler::lexer::{Span, Statement, StatementKind, Token, TokenKind};
+
+#[derive(Clone, Debug)]
+pub struct Located<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum ExpressionKind {
+    PositiveInt(u64),
+    NegativeInt(u64),
+    Float(f64),
+    String(Vec<u8>),
+    Binary(Vec<u8>),
+    RelativeName(String),
+    AbsoluteName(String),
+    Import(Located<String>),
+    Embed(Located<String>),
+    List(Vec<Expression>),
+    Tuple(Vec<Param>),
+    Application(Box<Expression>, Vec<Param>),
+    Member(Box<Expression>, Located<String>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+/// An element of a tuple or of a generic application, e.g. `foo = 123` or just `Text`.
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub name: Option<Located<String>>,
+    pub value: Expression,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnnotationApplication {
+    pub name: Expression,
+    pub value: Option<Expression>,
+}
+
+/// A member of a method's parameter or result list.
+#[derive(Clone, Debug)]
+pub struct ParamDecl {
+    pub name: Located<String>,
+    pub type_: Expression,
+    pub default_value: Option<Expression>,
+    pub annotations: Vec<AnnotationApplication>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ParamList {
+    Named(Vec<ParamDecl>),
+    Type(Expression),
+    Stream(Span),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnnotationTarget {
+    File = 0,
+    Const,
+    Enum,
+    Enumerant,
+    Struct,
+    Field,
+    Union,
+    Group,
+    Interface,
+    Method,
+    Param,
+    Annotation,
+}
+
+pub const ANNOTATION_TARGET_COUNT: usize = 12;
+
+#[derive(Clone, Debug)]
+pub enum DeclKind {
+    File,
+    Using(Expression),
+    Const { type_: Expression, value: Expression },
+    Enum,
+    Enumerant,
+    Struct,
+    Field { type_: Expression, default_value: Option<Expression> },
+    Union,
+    Group,
+    Interface { superclasses: Vec<Expression> },
+    Method { params: ParamList, results: Option<ParamList> },
+    Annotation { type_: Expression, targets: [bool; ANNOTATION_TARGET_COUNT] },
+}
+
+#[derive(Clone, Debug)]
+pub enum DeclId {
+    Unspecified,
+    Uid(Located<u64>),
+    Ordinal(Located<u64>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Declaration {
+    pub name: Located<String>,
+    pub id: DeclId,
+    pub parameters: Vec<Located<String>>,
+    pub annotations: Vec<AnnotationApplication>,
+    pub nested: Vec<Declaration>,
+    pub doc_comment: Option<String>,
+    pub kind: DeclKind,
+    pub span: Span,
+}
+
+impl Declaration {
+    pub fn ordinal(&self) -> Option<&Located<u64>> {
+        match self.id {
+            DeclId::Ordinal(ref ordinal) => Some(ordinal),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            DeclKind::File => "file",
+            DeclKind::Using(_) => "using",
+            DeclKind::Const { .. } => "const",
+            DeclKind::Enum => "enum",
+            DeclKind::Enumerant => "enumerant",
+            DeclKind::Struct => "struct",
+            DeclKind::Field { .. } => "field",
+            DeclKind::Union => "union",
+            DeclKind::Group => "group",
+            DeclKind::Interface { .. } => "interface",
+            DeclKind::Method { .. } => "method",
+            DeclKind::Annotation { .. } => "annotation",
+        }
+    }
+}
+
+/// Parses the statements of a file into a `File` declaration named `file_name`.
+pub fn parse_file(file_name: &str, statements: Vec<Statement>,
+                  errors: &mut Vec<(Span, String)>) -> Declaration {
+    let mut parser = Parser { errors };
+    let mut id = DeclId::Unspecified;
+    let mut annotations = Vec::new();
+    let mut nested = Vec::new();
+    let mut span = Span::default();
+    for statement in statements {
+        span.end = statement.span.end;
+        match statement.tokens.first().map(|t| &t.kind) {
+            Some(TokenKind::Operator(op)) if op == "@" => {
+                let mut cursor = Cursor::new(&statement.tokens, statement.span);
+                let uid = parser.uid(&mut cursor);
+                if let Some(uid) = uid {
+                    if parser.expect_end(&cursor) && parser.expect_line(&statement) {
+                        if let DeclId::Uid(_) = id {
+                            parser.error(uid.span, "File can only have one ID.");
+                        } else {
+                            id = DeclId::Uid(uid);
+                        }
+                    }
+                }
+            }
+            Some(TokenKind::Operator(op)) if op == "$" => {
+                let mut cursor = Cursor::new(&statement.tokens, statement.span);
+                let result = parser.annotations(&mut cursor);
+                if parser.expect_end(&cursor) && parser.expect_line(&statement) {
+                    annotations.extend(result);
+                }
+            }
+            _ => {
+                if let Some(decl) = parser.declaration(statement) {
+                    nested.push(decl);
+                }
+            }
+        }
+    }
+
+    Declaration {
+        name: Located { value: file_name.to_string(), span: Span::default() },
+        id,
+        parameters: Vec::new(),
+        annotations,
+        nested,
+        doc_comment: None,
+        kind: DeclKind::File,
+        span,
+    }
+}
+
+struct Cursor<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    // Used for errors at the end of the token sequence.
+    end: Span,
+}
+
+impl <'a> Cursor<'a> {
+    fn new(tokens: &'a [Token], span: Span) -> Cursor<'a> {
+        Cursor { tokens, pos: 0, end: Span::new(span.end, span.end) }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let result = self.tokens.get(self.pos);
+        if result.is_some() {
+            self.pos += 1;
+        }
+        result
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// The span of the next token, or of the end of the sequence.
+    fn span(&self) -> Span {
+        match self.peek() {
+            Some(token) => token.span,
+            None => self.end,
+        }
+    }
+
+    fn last_span(&self) -> Span {
+        if self.pos == 0 { self.end } else { self.tokens[self.pos - 1].span }
+    }
+
+    fn is_operator(&self, op: &str) -> bool {
+        match self.peek() {
+            Some(&Token { kind: TokenKind::Operator(ref o), .. }) => o == op,
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(&Token { kind: TokenKind::Identifier(ref i), .. }) => i == keyword,
+            _ => false,
+        }
+    }
+
+    /// Like `eat_keyword()`, but only if the keyword is followed by a name, so that keywords
+    /// can still be used as the names of fields, enumerants and methods.
+    fn eat_decl_keyword(&mut self, keyword: &str) -> bool {
+        let named = match self.tokens.get(self.pos + 1) {
+            Some(&Token { kind: TokenKind::Identifier(_), .. }) => true,
+            Some(&Token { kind: TokenKind::Operator(ref op), .. }) => keyword == "using" && op == ".",
+            _ => false,
+        };
+        named && self.eat_keyword(keyword)
+    }
+
+    fn eat_operator(&mut self, op: &str) -> bool {
+        if self.is_operator(op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct Parser<'e> {
+    errors: &'e mut Vec<(Span, String)>,
+}
+
+fn string_value(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+impl <'e> Parser<'e> {
+    fn error(&mut self, span: Span, message: &str) {
+        self.errors.push((span, message.to_string()));
+    }
+
+    fn expect_end(&mut self, cursor: &Cursor) -> bool {
+        if cursor.at_end() {
+            true
+        } else {
+            self.error(cursor.span(), "Parse error.");
+            false
+        }
+    }
+
+    fn expect_line(&mut self, statement: &Statement) -> bool {
+        match statement.kind {
+            StatementKind::Line => true,
+            StatementKind::Block(_) => {
+                self.error(statement.span, "This declaration can't have a block.");
+                false
+            }
+        }
+    }
+
+    fn identifier(&mut self, cursor: &mut Cursor) -> Option<Located<String>> {
+        match cursor.peek() {
+            Some(&Token { kind: TokenKind::Identifier(ref name), span }) => {
+                cursor.pos += 1;
+                Some(Located { value: name.clone(), span })
+            }
+            _ => {
+                self.error(cursor.span(), "Expected identifier.");
+                None
+            }
+        }
+    }
+
+    fn expect_operator(&mut self, cursor: &mut Cursor, op: &str) -> Option<()> {
+        if cursor.eat_operator(op) {
+            Some(())
+        } else {
+            self.error(cursor.span(), &format!("Expected '{}'.", op));
+            None
+        }
+    }
+
+    fn integer(&mut self, cursor: &mut Cursor) -> Option<Located<u64>> {
+        match cursor.peek() {
+            Some(&Token { kind: TokenKind::IntegerLiteral(value), span }) => {
+                cursor.pos += 1;
+                Some(Located { value, span })
+            }
+            _ => {
+                self.error(cursor.span(), "Expected integer.");
+                None
+            }
+        }
+    }
+
+    fn uid(&mut self, cursor: &mut Cursor) -> Option<Located<u64>> {
+        let start = cursor.span();
+        self.expect_operator(cursor, "@")?;
+        let value = self.integer(cursor)?;
+        if value.value < (1 << 63) {
+            self.error(value.span, "Invalid ID.  Please generate a new one with 'capnpc -i'.");
+        }
+        Some(Located { value: value.value, span: start.to(value.span) })
+    }
+
+    fn ordinal(&mut self, cursor: &mut Cursor) -> Option<Located<u64>> {
+        self.expect_operator(cursor, "@")?;
+        let value = self.integer(cursor)?;
+        if value.value >= 65536 {
+            self.error(value.span, "Ordinals cannot be greater than 65535.");
+        }
+        Some(value)
+    }
+
+    fn optional_uid(&mut self, cursor: &mut Cursor) -> Option<DeclId> {
+        if cursor.is_operator("@") {
+            Some(DeclId::Uid(self.uid(cursor)?))
+        } else {
+            Some(DeclId::Unspecified)
+        }
+    }
+
+    fn optional_ordinal(&mut self, cursor: &mut Cursor) -> Option<DeclId> {
+        if cursor.is_operator("@") {
+            Some(DeclId::Ordinal(self.ordinal(cursor)?))
+        } else {
+            Some(DeclId::Unspecified)
+        }
+    }
+
+    fn annotations(&mut self, cursor: &mut Cursor) -> Vec<AnnotationApplication> {
+        let mut result = Vec::new();
+        while cursor.eat_operator("$") {
+            let expression = match self.expression(cursor) {
+                Some(e) => e,
+                None => break,
+            };
+            result.push(match expression.kind {
+                ExpressionKind::Application(function, mut params) => {
+                    // The value was parsed as an application on the annotation name. Pull it
+                    // back apart.
+                    let value = if params.len() == 1 && params[0].name.is_none() {
+                        params.pop().unwrap().value
+                    } else {
+                        let span = match (params.first(), params.last()) {
+                            (Some(first), Some(last)) => first.value.span.to(last.value.span),
+                            _ => Span::new(function.span.end, expression.span.end),
+                        };
+                        Expression { kind: ExpressionKind::Tuple(params), span }
+                    };
+                    AnnotationApplication { name: *function, value: Some(value) }
+                }
+                _ => AnnotationApplication { name: expression, value: None },
+            });
+        }
+        result
+    }
+
+    fn brand_parameters(&mut self, token: &Token, lists: &[Vec<Token>]) -> Option<Vec<Located<String>>> {
+        let mut result = Vec::new();
+        for item in lists {
+            let mut cursor = Cursor::new(item, token.span);
+            let name = self.identifier(&mut cursor)?;
+            if !self.expect_end(&cursor) {
+                return None;
+            }
+            result.push(name);
+        }
+        Some(result)
+    }
+
+    fn optional_parameters(&mut self, cursor: &mut Cursor) -> Option<Vec<Located<String>>> {
+        match cursor.peek() {
+            Some(token @ &Token { kind: TokenKind::ParenthesizedList(ref items), .. }) => {
+                cursor.pos += 1;
+                self.brand_parameters(token, items)
+            }
+            _ => Some(Vec::new()),
+        }
+    }
+
+    pub fn expression(&mut self, cursor: &mut Cursor) -> Option<Expression> {
+        let start = cursor.span();
+        let token = match cursor.next() {
+            Some(token) => token,
+            None => {
+                self.error(start, "Expected expression.");
+                return None;
+            }
+        };
+
+        let kind = match token.kind {
+            TokenKind::IntegerLiteral(value) => ExpressionKind::PositiveInt(value),
+            TokenKind::FloatLiteral(value) => ExpressionKind::Float(value),
+            TokenKind::Operator(ref op) if op == "-" => {
+                match cursor.next().map(|t| &t.kind) {
+                    Some(&TokenKind::IntegerLiteral(value)) => ExpressionKind::NegativeInt(value),
+                    Some(&TokenKind::FloatLiteral(value)) => ExpressionKind::Float(-value),
+                    Some(TokenKind::Identifier(name)) if name == "inf" => {
+                        ExpressionKind::Float(f64::NEG_INFINITY)
+                    }
+                    _ => {
+                        self.error(cursor.last_span(), "Expected number after '-'.");
+                        return None;
+                    }
+                }
+            }
+            TokenKind::Operator(ref op) if op == "." => {
+                let name = self.identifier(cursor)?;
+                ExpressionKind::AbsoluteName(name.value)
+            }
+            TokenKind::StringLiteral(ref value) => {
+                let mut value = value.clone();
+                while let Some(&Token { kind: TokenKind::StringLiteral(ref more), .. }) = cursor.peek() {
+                    cursor.pos += 1;
+                    value.extend_from_slice(more);
+                }
+                ExpressionKind::String(value)
+            }
+            TokenKind::BinaryLiteral(ref value) => ExpressionKind::Binary(value.clone()),
+            TokenKind::BracketedList(ref items) => {
+                let mut elements = Vec::new();
+                for item in items {
+                    let mut item_cursor = Cursor::new(item, token.span);
+                    let element = self.expression(&mut item_cursor)?;
+                    if !self.expect_end(&item_cursor) {
+                        return None;
+                    }
+                    elements.push(element);
+                }
+                ExpressionKind::List(elements)
+            }
+            TokenKind::ParenthesizedList(ref items) => ExpressionKind::Tuple(self.params(token, items)?),
+            TokenKind::Identifier(ref name) if (name == "import" || name == "embed") &&
+                matches!(cursor.peek(), Some(&Token { kind: TokenKind::StringLiteral(_), .. })) =>
+            {
+                let file = match cursor.next() {
+                    Some(&Token { kind: TokenKind::StringLiteral(ref value), span }) => {
+                        Located { value: string_value(value), span }
+                    }
+                    _ => unreachable!(),
+                };
+                if name == "import" {
+                    ExpressionKind::Import(file)
+                } else {
+                    ExpressionKind::Embed(file)
+                }
+            }
+            TokenKind::Identifier(ref name) => ExpressionKind::RelativeName(name.clone()),
+            TokenKind::Operator(_) => {
+                self.error(token.span, "Expected expression.");
+                return None;
+            }
+        };
+
+        let mut result = Expression { kind, span: start.to(cursor.last_span()) };
+        loop {
+            if cursor.is_operator(".") {
+                cursor.pos += 1;
+                let name = self.identifier(cursor)?;
+                let span = result.span.to(name.span);
+                result = Expression { kind: ExpressionKind::Member(Box::new(result), name), span };
+            } else if let Some(token @ &Token { kind: TokenKind::ParenthesizedList(ref items), .. }) = cursor.peek() {
+                cursor.pos += 1;
+                let params = self.params(token, items)?;
+                let span = result.span.to(token.span);
+                result = Expression { kind: ExpressionKind::Application(Box::new(result), params), span };
+            } else {
+                return Some(result);
+            }
+        }
+    }
+
+    fn params(&mut self, token: &Token, items: &[Vec<Token>]) -> Option<Vec<Param>> {
+        let mut result = Vec::new();
+        for item in items {
+            let mut cursor = Cursor::new(item, token.span);
+            let is_named = match (item.first(), item.get(1)) {
+                (Some(&Token { kind: TokenKind::Identifier(_), .. }),
+                 Some(&Token { kind: TokenKind::Operator(ref op), .. })) => op == "=",
+                _ => false,
+            };
+            let name = if is_named {
+                let name = self.identifier(&mut cursor)?;
+                cursor.pos += 1;
+                Some(name)
+            } else {
+                None
+            };
+            let value = self.expression(&mut cursor)?;
+            if !self.expect_end(&cursor) {
+                return None;
+            }
+            result.push(Param { name, value });
+        }
+        Some(result)
+    }
+
+    fn param_list(&mut self, cursor: &mut Cursor) -> Option<ParamList> {
+        match cursor.peek() {
+            Some(token @ &Token { kind: TokenKind::ParenthesizedList(ref items), .. }) => {
+                cursor.pos += 1;
+                let mut params = Vec::new();
+                for item in items {
+                    let mut item_cursor = Cursor::new(item, token.span);
+                    let name = self.identifier(&mut item_cursor)?;
+                    self.expect_operator(&mut item_cursor, ":")?;
+                    let type_ = self.expression(&mut item_cursor)?;
+                    let default_value = if item_cursor.eat_operator("=") {
+                        Some(self.expression(&mut item_cursor)?)
+                    } else {
+                        None
+                    };
+                    let annotations = self.annotations(&mut item_cursor);
+                    if !self.expect_end(&item_cursor) {
+                        return None;
+                    }
+                    params.push(ParamDecl { name, type_, default_value, annotations });
+                }
+                Some(ParamList::Named(params))
+            }
+            _ if cursor.is_keyword("stream") => {
+                let span = cursor.span();
+                cursor.pos += 1;
+                Some(ParamList::Stream(span))
+            }
+            _ => Some(ParamList::Type(self.expression(cursor)?)),
+        }
+    }
+
+    fn annotation_targets(&mut self, cursor: &mut Cursor) -> Option<[bool; ANNOTATION_TARGET_COUNT]> {
+        let (token, items) = match cursor.peek() {
+            Some(token @ &Token { kind: TokenKind::ParenthesizedList(ref items), .. }) => (token, items),
+            _ => {
+                self.error(cursor.span(), "Expected list of annotation targets.");
+                return None;
+            }
+        };
+        cursor.pos += 1;
+
+        let mut targets = [false; ANNOTATION_TARGET_COUNT];
+        for item in items {
+            let target = match item.as_slice() {
+                [Token { kind: TokenKind::Operator(ref op), .. }] if op == "*" => {
+                    targets = [true; ANNOTATION_TARGET_COUNT];
+                    continue;
+                }
+                [Token { kind: TokenKind::Identifier(ref name), span }] => {
+                    match &name[..] {
+                        "file" => AnnotationTarget::File,
+                        "const" => AnnotationTarget::Const,
+                        "enum" => AnnotationTarget::Enum,
+                        "enumerant" => AnnotationTarget::Enumerant,
+                        "struct" => AnnotationTarget::Struct,
+                        "field" => AnnotationTarget::Field,
+                        "union" => AnnotationTarget::Union,
+                        "group" => AnnotationTarget::Group,
+                        "interface" => AnnotationTarget::Interface,
+                        "method" => AnnotationTarget::Method,
+                        "param" => AnnotationTarget::Param,
+                        "annotation" => AnnotationTarget::Annotation,
+                        _ => {
+                            self.error(*span, "Not a valid annotation target.");
+                            return None;
+                        }
+                    }
+                }
+                _ => {
+                    self.error(token.span, "Not a valid annotation target.");
+                    return None;
+                }
+            };
+            targets[target as usize] = true;
+        }
+        Some(targets)
+    }
+
+    fn declaration(&mut self, statement: Statement) -> Option<Declaration> {
+        let Statement { tokens, kind, doc_comment, span } = statement;
+        let mut cursor = Cursor::new(&tokens, span);
+        let mut parameters = Vec::new();
+        let mut annotations = Vec::new();
+
+        let (name, id, decl_kind) = if cursor.eat_decl_keyword("using") {
+            let name = if let (Some(&Token { kind: TokenKind::Identifier(_), .. }), true) =
+                (cursor.peek(), matches!(cursor.tokens.get(cursor.pos + 1),
+                                         Some(Token { kind: TokenKind::Operator(op), .. }) if op == "="))
+            {
+                let name = self.identifier(&mut cursor)?;
+                cursor.pos += 1;
+                Some(name)
+            } else {
+                None
+            };
+            let target = self.expression(&mut cursor)?;
+            let name = match name {
+                Some(name) => name,
+                None => match target.kind {
+                    ExpressionKind::Member(_, ref member) => member.clone(),
+                    _ => {
+                        self.error(target.span, "'using' declaration without '=' must specify a named \
+                                                 declaration from a different scope.");
+                        return None;
+                    }
+                },
+            };
+            (name, DeclId::Unspecified, DeclKind::Using(target))
+        } else if cursor.eat_decl_keyword("const") {
+            let name = self.identifier(&mut cursor)?;
+            let id = self.optional_uid(&mut cursor)?;
+            self.expect_operator(&mut cursor, ":")?;
+            let type_ = self.expression(&mut cursor)?;
+            self.expect_operator(&mut cursor, "=")?;
+            let value = self.expression(&mut cursor)?;
+            annotations = self.annotations(&mut cursor);
+            (name, id, DeclKind::Const { type_, value })
+        } else if cursor.eat_decl_keyword("enum") {
+            let name = self.identifier(&mut cursor)?;
+            let id = self.optional_uid(&mut cursor)?;
+            annotations = self.annotations(&mut cursor);
+            (name, id, DeclKind::Enum)
+        } else if cursor.eat_decl_keyword("struct") {
+            let name = self.identifier(&mut cursor)?;
+            let id = self.optional_uid(&mut cursor)?;
+            parameters = self.optional_parameters(&mut cursor)?;
+            annotations = self.annotations(&mut cursor);
+            (name, id, DeclKind::Struct)
+        } else if cursor.eat_decl_keyword("interface") {
+            let name = self.identifier(&mut cursor)?;
+            let id = self.optional_uid(&mut cursor)?;
+            parameters = self.optional_parameters(&mut cursor)?;
+            let mut superclasses = Vec::new();
+            if cursor.eat_keyword("extends") {
+                match cursor.next() {
+                    Some(token @ &Token { kind: TokenKind::ParenthesizedList(ref items), .. }) => {
+                        for item in items {
+                            let mut item_cursor = Cursor::new(item, token.span);
+                            superclasses.push(self.expression(&mut item_cursor)?);
+                            if !self.expect_end(&item_cursor) {
+                                return None;
+                            }
+                        }
+                    }
+                    _ => {
+                        self.error(cursor.last_span(), "Expected '(' after 'extends'.");
+                        return None;
+                    }
+                }
+            }
+            annotations = self.annotations(&mut cursor);
+            (name, id, DeclKind::Interface { superclasses })
+        } else if cursor.eat_decl_keyword("annotation") {
+            let name = self.identifier(&mut cursor)?;
+            let id = self.optional_uid(&mut cursor)?;
+            let targets = self.annotation_targets(&mut cursor)?;
+            self.expect_operator(&mut cursor, ":")?;
+            let type_ = self.expression(&mut cursor)?;
+            annotations = self.annotations(&mut cursor);
+            (name, id, DeclKind::Annotation { type_, targets })
+        } else if cursor.is_keyword("union") && !matches!(cursor.tokens.get(cursor.pos + 1),
+                                                          Some(Token { kind: TokenKind::Operator(op), .. }) if op == ":") {
+            // An unnamed union.
+            let union_span = cursor.span();
+            cursor.pos += 1;
+            let id = self.optional_ordinal(&mut cursor)?;
+            annotations = self.annotations(&mut cursor);
+            (Located { value: String::new(), span: union_span }, id, DeclKind::Union)
+        } else {
+            let name = self.identifier(&mut cursor)?;
+            let id = if cursor.is_operator("@") {
+                DeclId::Ordinal(self.ordinal(&mut cursor)?)
+            } else {
+                DeclId::Unspecified
+            };
+            let exclamation = match id {
+                DeclId::Ordinal(_) => cursor.eat_operator("!"),
+                _ => false,
+            };
+
+            if exclamation && !(cursor.is_operator(":") && matches!(cursor.tokens.get(cursor.pos + 1),
+                                                                    Some(Token { kind: TokenKind::Identifier(i), .. }) if i == "union")) {
+                self.error(cursor.span(), "Parse error.");
+                return None;
+            }
+
+            if cursor.eat_operator(":") {
+                if cursor.is_keyword("union") {
+                    cursor.pos += 1;
+                    if let DeclId::Ordinal(ref ordinal) = id {
+                        if !exclamation {
+                            self.error(ordinal.span,
+                                       "As of Cap'n Proto v0.3, it is no longer necessary to assign numbers to \
+                                        unions. However, removing the number will break binary compatibility. \
+                                        If this is an old schema file and you want to retain compatibility, \
+                                        please add an exclamation point after the number to indicate that it is \
+                                        really needed, e.g. `foo @1! :union {`. If this is a new schema file or \
+                                        you are OK with breaking compatibility with your existing code, then \
+                                        just delete the number. (I can't just allow you to delete the number \
+                                        because I might be wrong about it having been added by a previous \
+                                        version.)");
+                        }
+                    }
+                    annotations = self.annotations(&mut cursor);
+                    (name, id, DeclKind::Union)
+                } else if cursor.is_keyword("group") {
+                    cursor.pos += 1;
+                    if let DeclId::Ordinal(ref ordinal) = id {
+                        self.error(ordinal.span, "Groups don't have ordinals.");
+                    }
+                    annotations = self.annotations(&mut cursor);
+                    (name, DeclId::Unspecified, DeclKind::Group)
+                } else {
+                    if let DeclId::Unspecified = id {
+                        self.error(name.span, "Missing ordinal for field.");
+                        return None;
+                    }
+                    let type_ = self.expression(&mut cursor)?;
+                    let default_value = if cursor.eat_operator("=") {
+                        Some(self.expression(&mut cursor)?)
+                    } else {
+                        None
+                    };
+                    annotations = self.annotations(&mut cursor);
+                    (name, id, DeclKind::Field { type_, default_value })
+                }
+            } else if let DeclId::Ordinal(_) = id {
+                // Anything other than annotations after the ordinal makes this a method.
+                let is_method = match cursor.peek() {
+                    None => false,
+                    Some(_) => !cursor.is_operator("$"),
+                };
+                if is_method {
+                    if let Some(token @ &Token { kind: TokenKind::BracketedList(ref items), .. }) = cursor.peek() {
+                        cursor.pos += 1;
+                        parameters = self.brand_parameters(token, items)?;
+                    }
+                    let params = self.param_list(&mut cursor)?;
+                    let results = if cursor.eat_operator("->") {
+                        Some(self.param_list(&mut cursor)?)
+                    } else {
+                        None
+                    };
+                    annotations = self.annotations(&mut cursor);
+                    (name, id, DeclKind::Method { params, results })
+                } else {
+                    annotations = self.annotations(&mut cursor);
+                    (name, id, DeclKind::Enumerant)
+                }
+            } else {
+                self.error(cursor.span(), "Parse error.");
+                return None;
+            }
+        };
+
+        if !self.expect_end(&cursor) {
+            return None;
+        }
+
+        let needs_block = matches!(decl_kind,
+                                   DeclKind::Enum | DeclKind::Struct | DeclKind::Union | DeclKind::Group |
+                                   DeclKind::Interface { .. });
+
+        let mut nested = Vec::new();
+        match kind {
+            StatementKind::Line => {
+                if needs_block {
+                    self.error(span, &format!("Expected '{{' after {} declaration.", decl_kind_name(&decl_kind)));
+                    return None;
+                }
+            }
+            StatementKind::Block(statements) => {
+                if !needs_block {
+                    self.error(span, "This declaration can't have a block.");
+                    return None;
+                }
+                for statement in statements {
+                    if let Some(decl) = self.declaration(statement) {
+                        nested.push(decl);
+                    }
+                }
+            }
+        }
+
+        if name.value.contains('_') {
+            self.error(name.span, "Cap'n Proto declaration names should use camelCase and must not contain \
+                                   underscores. (Code generators may convert names to the appropriate style \
+                                   for the target language.)");
+        }
+
+        Some(Declaration {
+            name,
+            id,
+            parameters,
+            annotations,
+            nested,
+            doc_comment,
+            kind: decl_kind,
+            span,
+        })
+    }
+}
+
+fn decl_kind_name(kind: &DeclKind) -> &'static str {
+    match kind {
+        DeclKind::Enum => "enum",
+        DeclKind::Struct => "struct",
+        DeclKind::Union => "union",
+        DeclKind::Group => "group",
+        DeclKind::Interface { .. } => "interface",
+        _ => "this",
+    }
+}
+
+/// Renders an expression roughly as it appeared in the source, for error messages.
+pub fn expression_string(expression: &Expression) -> String {
+    match expression.kind {
+        ExpressionKind::PositiveInt(value) => format!("{}", value),
+        ExpressionKind::NegativeInt(value) => format!("-{}", value),
+        ExpressionKind::Float(value) => format!("{}", value),
+        ExpressionKind::String(ref value) => format!("{:?}", string_value(value)),
+        ExpressionKind::Binary(ref value) => {
+            format!("0x\"{}\"", value.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+        }
+        ExpressionKind::RelativeName(ref name) => name.clone(),
+        ExpressionKind::AbsoluteName(ref name) => format!(".{}", name),
+        ExpressionKind::Import(ref file) => format!("import {:?}", file.value),
+        ExpressionKind::Embed(ref file) => format!("embed {:?}", file.value),
+        ExpressionKind::List(ref elements) => {
+            format!("[{}]", elements.iter().map(expression_string).collect::<Vec<_>>().join(", "))
+        }
+        ExpressionKind::Tuple(ref params) => format!("({})", params_string(params)),
+        ExpressionKind::Application(ref function, ref params) => {
+            format!("{}({})", expression_string(function), params_string(params))
+        }
+        ExpressionKind::Member(ref parent, ref name) => format!("{}.{}", expression_string(parent), name.value),
+    }
+}
+
+fn params_string(params: &[Param]) -> String {
+    params.iter().map(|param| match param.name {
+        Some(ref name) => format!("{} = {}", name.value, expression_string(&param.value)),
+        None => expression_string(&param.value),
+    }).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer::lex;
+
+    fn parse(text: &str) -> (Declaration, Vec<(Span, String)>) {
+        let mut errors = Vec::new();
+        let statements = lex(text, &mut errors);
+        let file = parse_file("foo.capnp", statements, &mut errors);
+        (file, errors)
+    }
+
+    #[test]
+    fn declarations() {
+        let (file, errors) = parse(
+            "@0xbf5147cbbecf40c1;\n\
+             struct Foo(T) $bar(1) {\n\
+               a @0 :List(T) = [];\n\
+               union { b @1 :Void; c @2 :Text; }\n\
+               d :group { e @3 :UInt8; }\n\
+               f @4! :union $baz { g @5 :Void; h @6 :Void; }\n\
+               struct @7 :Void;\n\
+             }\n\
+             interface Qux extends(import \"other.capnp\".Base) {\n\
+               m @0 [U] (x :U = .default) -> (y :Foo(Text));\n\
+               n @1 Foo(Text) -> stream;\n\
+             }\n\
+             annotation bar(struct, field) :Int32;\n\
+             enum E { zero @0; one @1 $bar(2); }\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+        match file.id {
+            DeclId::Uid(ref id) => assert_eq!(id.value, 0xbf5147cbbecf40c1),
+            _ => panic!("expected file ID"),
+        }
+        let kinds: Vec<&str> = file.nested.iter().map(|d| d.kind_name()).collect();
+        assert_eq!(kinds, ["struct", "interface", "annotation", "enum"]);
+
+        let foo = &file.nested[0];
+        assert_eq!(foo.parameters[0].value, "T");
+        assert_eq!(foo.annotations.len(), 1);
+        let members: Vec<(&str, &str)> = foo.nested.iter().map(|d| (&d.name.value[..], d.kind_name())).collect();
+        assert_eq!(members, [("a", "field"), ("", "union"), ("d", "group"), ("f", "union"), ("struct", "field")]);
+        assert_eq!(foo.nested[3].ordinal().unwrap().value, 4);
+
+        match file.nested[1].nested[0].kind {
+            DeclKind::Method { params: ParamList::Named(ref params), results: Some(ParamList::Named(ref results)) } => {
+                assert_eq!(params[0].name.value, "x");
+                assert!(params[0].default_value.is_some());
+                assert_eq!(expression_string(&results[0].type_), "Foo(Text)");
+            }
+            _ => panic!("expected method"),
+        }
+        assert_eq!(file.nested[1].nested[0].parameters[0].value, "U");
+        match file.nested[1].nested[1].kind {
+            DeclKind::Method { params: ParamList::Type(ref params), results: Some(ParamList::Stream(_)) } => {
+                assert_eq!(expression_string(params), "Foo(Text)");
+            }
+            _ => panic!("expected streaming method"),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let (_, errors) = parse("struct Foo { a @0 :Int32; b_c @1 :Int32; d :Int32; }");
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].1.contains("must not contain underscores"));
+        assert_eq!(errors[1].1, "Missing ordinal for field.");
+    }
+}
@@ -0,0 +1,431 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Name lookup and the compilation of type expressions, including generic brands.
+
+use std::rc::Rc;
+
+use crate::compiler::node::{AnyKind, Brand, BrandScope as CompiledScope, Type};
+use crate::compiler::parser::{expression_string, DeclKind, Expression, ExpressionKind};
+use crate::compiler::{Compiler, EntityKind};
+
+/// The generic parameter bindings in effect for a declaration and each of its lexical parents.
+pub struct BrandScope {
+    parent: Option<Rc<BrandScope>>,
+    leaf_id: u64,
+    leaf_param_count: usize,
+    /// True if the parameters are those of the surrounding scope, i.e. we are inside the
+    /// declaration itself.
+    inherited: bool,
+    params: Vec<Type>,
+}
+
+impl BrandScope {
+    fn new(leaf_id: u64) -> Rc<BrandScope> {
+        Rc::new(BrandScope { parent: None, leaf_id, leaf_param_count: 0, inherited: false, params: Vec::new() })
+    }
+
+    /// Returns the scope for the declaration with the given ID, which should be this scope or one
+    /// of its parents.
+    fn pop(self: &Rc<Self>, id: u64) -> Rc<BrandScope> {
+        if self.leaf_id == id {
+            self.clone()
+        } else {
+            match self.parent {
+                Some(ref parent) => parent.pop(id),
+                None => BrandScope::new(id),
+            }
+        }
+    }
+
+    /// Returns a scope for a declaration nested within this one, with no bindings.
+    fn push(self: &Rc<Self>, id: u64, param_count: usize) -> Rc<BrandScope> {
+        Rc::new(BrandScope {
+            parent: Some(self.clone()),
+            leaf_id: id,
+            leaf_param_count: param_count,
+            inherited: false,
+            params: Vec::new(),
+        })
+    }
+
+    /// Returns the binding of a generic parameter, or `None` if the parameter is unbound
+    /// because we are inside its scope.
+    fn lookup_parameter(&self, scope_id: u64, index: u16) -> Option<Type> {
+        if self.leaf_id == scope_id {
+            if (index as usize) < self.params.len() {
+                Some(self.params[index as usize].clone())
+            } else if self.inherited {
+                None
+            } else {
+                Some(Type::AnyPointer(AnyKind::Any))
+            }
+        } else {
+            match self.parent {
+                Some(ref parent) => parent.lookup_parameter(scope_id, index),
+                None => None,
+            }
+        }
+    }
+
+    pub fn compile(&self) -> Option<Brand> {
+        let mut scopes = Vec::new();
+        let mut scope = Some(self);
+        while let Some(s) = scope {
+            if s.inherited {
+                if s.leaf_param_count > 0 {
+                    scopes.push(CompiledScope { scope_id: s.leaf_id, bind: None });
+                }
+            } else if !s.params.is_empty() {
+                scopes.push(CompiledScope { scope_id: s.leaf_id, bind: Some(s.params.clone()) });
+            }
+            scope = s.parent.as_deref();
+        }
+        if scopes.is_empty() { None } else { Some(Brand { scopes }) }
+    }
+}
+
+/// What an expression in a type position refers to.
+#[derive(Clone)]
+pub enum Decl {
+    Entity(usize, Rc<BrandScope>),
+    /// The builtin `List`, which needs to be applied to an element type.
+    List,
+    Type(Type),
+}
+
+/// Where an expression appears, which determines how names in it are looked up.
+#[derive(Clone)]
+pub struct Context {
+    pub entity: usize,
+    pub brand: Rc<BrandScope>,
+    /// The implicit generic parameters of the method whose parameter list we're compiling, and
+    /// the ID of the scope that they belong to (zero if they refer to the method itself).
+    pub implicit_params: Rc<(u64, Vec<String>)>,
+}
+
+fn builtin(name: &str) -> Option<Decl> {
+    Some(Decl::Type(match name {
+        "Void" => Type::Void,
+        "Bool" => Type::Bool,
+        "Int8" => Type::Int8,
+        "Int16" => Type::Int16,
+        "Int32" => Type::Int32,
+        "Int64" => Type::Int64,
+        "UInt8" => Type::UInt8,
+        "UInt16" => Type::UInt16,
+        "UInt32" => Type::UInt32,
+        "UInt64" => Type::UInt64,
+        "Float32" => Type::Float32,
+        "Float64" => Type::Float64,
+        "Text" => Type::Text,
+        "Data" => Type::Data,
+        "AnyPointer" => Type::AnyPointer(AnyKind::Any),
+        "AnyStruct" => Type::AnyPointer(AnyKind::Struct),
+        "AnyList" => Type::AnyPointer(AnyKind::List),
+        "Capability" => Type::AnyPointer(AnyKind::Capability),
+        "List" => return Some(Decl::List),
+        _ => return None,
+    }))
+}
+
+impl Compiler {
+    /// The context for expressions appearing directly in the given entity's declaration.
+    pub(crate) fn context(&self, entity: usize) -> Context {
+        Context {
+            entity,
+            brand: self.lexical_brand(entity),
+            implicit_params: Rc::new((0, Vec::new())),
+        }
+    }
+
+    /// The brand for use inside of `entity`, in which the generic parameters of the entity and
+    /// all of its parents are unbound.
+    fn lexical_brand(&self, entity: usize) -> Rc<BrandScope> {
+        let e = &self.entities[entity];
+        Rc::new(BrandScope {
+            parent: e.parent.map(|p| self.lexical_brand(p)),
+            leaf_id: e.id,
+            leaf_param_count: e.decl.parameters.len(),
+            inherited: true,
+            params: Vec::new(),
+        })
+    }
+
+    /// The brand with which a method refers to its parameter or result struct, binding the
+    /// struct's parameters to the method's implicit parameters.
+    pub(crate) fn method_param_brand(&self, interface: usize, struct_id: u64, implicit_count: usize) -> Option<Brand> {
+        let scope = self.lexical_brand(interface).push(struct_id, implicit_count);
+        let scope = if implicit_count > 0 {
+            Rc::new(BrandScope {
+                parent: scope.parent.clone(),
+                leaf_id: struct_id,
+                leaf_param_count: implicit_count,
+                inherited: false,
+                params: (0..implicit_count).map(|i| Type::ImplicitMethodParameter(i as u16)).collect(),
+            })
+        } else {
+            scope
+        };
+        scope.compile()
+    }
+
+    /// True if the entity or any of its parents has generic parameters.
+    pub(crate) fn is_generic(&self, entity: usize) -> bool {
+        let mut scope = Some(entity);
+        while let Some(e) = scope {
+            if !self.entities[e].decl.parameters.is_empty() {
+                return true;
+            }
+            scope = self.entities[e].parent;
+        }
+        false
+    }
+
+    pub(crate) fn error_at(&mut self, ctx: &Context, span: crate::compiler::lexer::Span, message: String) {
+        let file = self.entities[ctx.entity].file;
+        self.error(file, span, message);
+    }
+
+    pub(crate) fn compile_decl_expression(&mut self, expression: &Expression, ctx: &Context) -> Option<Decl> {
+        match expression.kind {
+            ExpressionKind::RelativeName(ref name) => {
+                let (scope_id, ref implicit) = *ctx.implicit_params;
+                if let Some(index) = implicit.iter().position(|p| p == name) {
+                    return Some(Decl::Type(if scope_id == 0 {
+                        Type::ImplicitMethodParameter(index as u16)
+                    } else {
+                        Type::Parameter { scope_id, index: index as u16 }
+                    }));
+                }
+
+                let mut scope = Some(ctx.entity);
+                while let Some(s) = scope {
+                    if let Some(&member) = self.entities[s].members.get(name) {
+                        return self.interpret_member(s, member, &ctx.brand, expression, ctx);
+                    }
+                    if let Some(index) = self.entities[s].decl.parameters.iter().position(|p| &p.value == name) {
+                        let scope_id = self.entities[s].id;
+                        return Some(Decl::Type(ctx.brand.lookup_parameter(scope_id, index as u16)
+                                               .unwrap_or(Type::Parameter { scope_id, index: index as u16 })));
+                    }
+                    scope = self.entities[s].parent;
+                }
+
+                let result = builtin(name);
+                if result.is_none() {
+                    self.error_at(ctx, expression.span, format!("Not defined: {}", name));
+                }
+                result
+            }
+            ExpressionKind::AbsoluteName(ref name) => {
+                let file = self.files[self.entities[ctx.entity].file].entity;
+                match self.entities[file].members.get(name) {
+                    Some(&member) => self.interpret_member(file, member, &ctx.brand, expression, ctx),
+                    None => {
+                        self.error_at(ctx, expression.span, format!("Not defined: {}", name));
+                        None
+                    }
+                }
+            }
+            ExpressionKind::Import(ref name) => {
+                let file = self.entities[ctx.entity].file;
+                let imported = self.import(file, &name.value, name.span)?;
+                let entity = self.files[imported].entity;
+                let brand = Rc::new(BrandScope {
+                    parent: None,
+                    leaf_id: self.entities[entity].id,
+                    leaf_param_count: 0,
+                    inherited: true,
+                    params: Vec::new(),
+                });
+                Some(Decl::Entity(entity, brand))
+            }
+            ExpressionKind::Application(ref function, ref params) => {
+                let decl = self.compile_decl_expression(function, ctx)?;
+                let mut compiled = Vec::new();
+                let mut failed = false;
+                for param in params {
+                    if let Some(ref name) = param.name {
+                        self.error_at(ctx, name.span, "Named parameter not allowed here.".to_string());
+                    }
+                    match self.compile_type(&param.value, ctx) {
+                        Some(t) => compiled.push((t, param.value.span)),
+                        None => failed = true,
+                    }
+                }
+                if failed {
+                    return Some(decl);
+                }
+                Some(self.apply_params(decl, compiled, expression, ctx))
+            }
+            ExpressionKind::Member(ref parent, ref name) => {
+                let decl = self.compile_decl_expression(parent, ctx)?;
+                let member = match decl {
+                    Decl::Entity(entity, ref brand) => {
+                        match self.entities[entity].members.get(&name.value) {
+                            Some(&member) => return self.interpret_member(entity, member, brand, expression, ctx),
+                            None => None,
+                        }
+                    }
+                    Decl::Type(Type::Parameter { .. }) | Decl::Type(Type::ImplicitMethodParameter(_)) => {
+                        self.error_at(ctx, expression.span, "Can't lookup members of a generic parameter.".to_string());
+                        return None;
+                    }
+                    _ => None,
+                };
+                if member.is_none() {
+                    self.error_at(ctx, name.span, format!(
+                        "'{}' has no member named '{}'", expression_string(parent), name.value));
+                }
+                member
+            }
+            _ => {
+                self.error_at(ctx, expression.span, "Expected a declaration name.".to_string());
+                None
+            }
+        }
+    }
+
+    /// Interprets a reference to `member`, found as a member of `scope` and accessed through
+    /// `brand`.
+    fn interpret_member(&mut self, scope: usize, member: usize, brand: &Rc<BrandScope>,
+                        expression: &Expression, ctx: &Context) -> Option<Decl> {
+        let scope_brand = brand.pop(self.entities[scope].id);
+        if self.entities[member].kind != EntityKind::Alias {
+            let count = self.entities[member].decl.parameters.len();
+            return Some(Decl::Entity(member, scope_brand.push(self.entities[member].id, count)));
+        }
+
+        // Aliases are compiled lazily, in the scope where they're declared.
+        if self.alias_stack.contains(&member) {
+            self.error_at(ctx, expression.span, format!(
+                "'{}' is defined in terms of itself.", expression_string(expression)));
+            return None;
+        }
+        let decl = self.entities[member].decl.clone();
+        let target = match decl.kind {
+            DeclKind::Using(ref target) => target,
+            _ => unreachable!(),
+        };
+        let alias_ctx = Context {
+            entity: scope,
+            brand: scope_brand,
+            implicit_params: Rc::new((0, Vec::new())),
+        };
+        self.alias_stack.push(member);
+        let result = self.compile_decl_expression(target, &alias_ctx);
+        self.alias_stack.pop();
+        result
+    }
+
+    fn apply_params(&mut self, decl: Decl, params: Vec<(Type, crate::compiler::lexer::Span)>,
+                    expression: &Expression, ctx: &Context) -> Decl {
+        match decl {
+            Decl::List => {
+                if params.len() != 1 {
+                    self.error_at(ctx, expression.span, "'List' requires exactly one parameter.".to_string());
+                    return Decl::List;
+                }
+                let (element, span) = params.into_iter().next().unwrap();
+                let unsupported = match element {
+                    Type::AnyPointer(AnyKind::Any) => Some("List(AnyPointer)"),
+                    Type::AnyPointer(AnyKind::Struct) => Some("List(AnyStruct)"),
+                    Type::AnyPointer(AnyKind::Capability) => Some("List(Capability)"),
+                    _ => None,
+                };
+                if let Some(name) = unsupported {
+                    self.error_at(ctx, span, format!("'{}' is not supported.", name));
+                    return Decl::Type(Type::List(Box::new(Type::Void)));
+                }
+                Decl::Type(Type::List(Box::new(element)))
+            }
+            Decl::Entity(entity, brand) => {
+                let message = if !brand.params.is_empty() {
+                    Some("Double-application of generic parameters.")
+                } else if params.len() > brand.leaf_param_count {
+                    if brand.leaf_param_count == 0 {
+                        Some("Declaration does not accept generic parameters.")
+                    } else {
+                        Some("Too many generic parameters.")
+                    }
+                } else if params.len() < brand.leaf_param_count {
+                    Some("Not enough generic parameters.")
+                } else {
+                    None
+                };
+                if let Some(message) = message {
+                    self.error_at(ctx, expression.span, message.to_string());
+                    return Decl::Entity(entity, brand);
+                }
+                for &(ref param, span) in &params {
+                    let is_pointer = match *param {
+                        Type::Enum(..) => false,
+                        ref t => t.is_pointer(),
+                    };
+                    if !is_pointer {
+                        self.error_at(ctx, span, "Sorry, only pointer types can be used as generic parameters.".to_string());
+                    }
+                }
+                Decl::Entity(entity, Rc::new(BrandScope {
+                    parent: brand.parent.clone(),
+                    leaf_id: brand.leaf_id,
+                    leaf_param_count: brand.leaf_param_count,
+                    inherited: false,
+                    params: params.into_iter().map(|(t, _)| t).collect(),
+                }))
+            }
+            Decl::Type(t) => {
+                let message = match t {
+                    Type::Parameter { .. } | Type::ImplicitMethodParameter(_) =>
+                        "Can't apply parameters to a generic parameter.",
+                    _ => "Declaration does not accept generic parameters.",
+                };
+                self.error_at(ctx, expression.span, message.to_string());
+                Decl::Type(t)
+            }
+        }
+    }
+
+    /// Compiles an expression that should name a type.
+    pub(crate) fn compile_type(&mut self, expression: &Expression, ctx: &Context) -> Option<Type> {
+        match self.compile_decl_expression(expression, ctx)? {
+            Decl::Type(t) => Some(t),
+            Decl::List => {
+                self.error_at(ctx, expression.span, "'List' requires exactly one parameter.".to_string());
+                None
+            }
+            Decl::Entity(entity, brand) => {
+                let id = self.entities[entity].id;
+                match self.entities[entity].kind {
+                    EntityKind::Struct => Some(Type::Struct(id, brand.compile())),
+                    EntityKind::Enum => Some(Type::Enum(id, brand.compile())),
+                    EntityKind::Interface => Some(Type::Interface(id, brand.compile())),
+                    _ => {
+                        self.error_at(ctx, expression.span, format!(
+                            "'{}' is not a type.", expression_string(expression)));
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
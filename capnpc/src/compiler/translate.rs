@@ -0,0 +1,754 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Translation of declarations into schema nodes. This is where struct layouts, ordinals and
+//! the IDs of groups and parameter structs get decided.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::compiler::layout::{Scope, StructLayout};
+use crate::compiler::lexer::Span;
+use crate::compiler::node::{
+    value_cell, Annotation, Enumerant, Field, FieldKind, Method, Node, NodeBody, Struct,
+    Superclass, Type, Value, NO_DISCRIMINANT,
+};
+use crate::compiler::parser::{
+    expression_string, AnnotationApplication, AnnotationTarget, DeclKind, Declaration,
+    Expression, Located, ParamDecl, ParamList,
+};
+use crate::compiler::resolve::{Context, Decl};
+use crate::compiler::{type_id, Compiler, EntityKind};
+
+/// The ID of `StreamResult` in /capnp/stream.capnp, which is the result type of streaming methods.
+const STREAM_RESULT_ID: u64 = 0x995f9a3377c0b16e;
+
+/// Checks that ordinals are used in sequence, starting at zero.
+struct OrdinalChecker {
+    expected: u64,
+    last: Option<Located<u64>>,
+}
+
+impl OrdinalChecker {
+    fn new() -> OrdinalChecker {
+        OrdinalChecker { expected: 0, last: None }
+    }
+
+    fn check(&mut self, compiler: &mut Compiler, ctx: &Context, ordinal: &Located<u64>) {
+        if ordinal.value < self.expected {
+            compiler.error_at(ctx, ordinal.span, "Duplicate ordinal number.".to_string());
+            if let Some(last) = self.last.take() {
+                compiler.error_at(ctx, last.span, format!("Ordinal @{} originally used here.", last.value));
+            }
+        } else if ordinal.value > self.expected {
+            compiler.error_at(ctx, ordinal.span, format!(
+                "Skipped ordinal @{}.  Ordinals must be sequential with no holes.", self.expected));
+            self.expected = ordinal.value + 1;
+        } else {
+            self.expected += 1;
+            self.last = Some(ordinal.clone());
+        }
+    }
+}
+
+impl Compiler {
+    pub(crate) fn translate(&mut self, entity: usize) {
+        let decl = self.entities[entity].decl.clone();
+        let ctx = self.context(entity);
+        let scope_id = self.entities[entity].parent.map_or(0, |p| self.entities[p].id);
+        let mut node = Node::new(self.entities[entity].id, self.entities[entity].display_name.clone(),
+                                 scope_id, NodeBody::File);
+        node.parameters = decl.parameters.iter().map(|p| p.value.clone()).collect();
+        node.is_generic = self.is_generic(entity);
+        node.nested_nodes = self.entities[entity].nested.iter()
+            .map(|&n| (self.entities[n].decl.name.value.clone(), self.entities[n].id))
+            .collect();
+        node.doc_comment = decl.doc_comment.clone();
+
+        let target = match self.entities[entity].kind {
+            EntityKind::File => AnnotationTarget::File,
+            EntityKind::Struct => AnnotationTarget::Struct,
+            EntityKind::Enum => AnnotationTarget::Enum,
+            EntityKind::Interface => AnnotationTarget::Interface,
+            EntityKind::Const => AnnotationTarget::Const,
+            EntityKind::Annotation => AnnotationTarget::Annotation,
+            EntityKind::Alias => unreachable!(),
+        };
+        node.annotations = self.compile_annotations(&decl.annotations, target, &ctx);
+
+        let mut extra_nodes = Vec::new();
+        match decl.kind {
+            DeclKind::Struct => {
+                node.body = NodeBody::Struct(Struct::default());
+                let mut translator = StructTranslator::new(self, ctx, node);
+                translator.traverse_top_or_group(&decl.nested, 0, Scope::Top);
+                let mut nodes = translator.finish();
+                node = nodes.remove(0);
+                extra_nodes = nodes;
+            }
+            DeclKind::Enum => {
+                let mut enumerants: Vec<(u16, &Declaration)> = Vec::new();
+                for member in &decl.nested {
+                    if let DeclKind::Enumerant = member.kind {
+                        enumerants.push((enumerants.len() as u16, member));
+                    }
+                }
+                enumerants.sort_by_key(|&(_, member)| member.ordinal().map_or(0, |o| o.value));
+
+                let mut checker = OrdinalChecker::new();
+                let mut result = Vec::new();
+                for (code_order, member) in enumerants {
+                    if let Some(ordinal) = member.ordinal() {
+                        checker.check(self, &ctx, ordinal);
+                    }
+                    node.member_doc_comments.push(member.doc_comment.clone());
+                    result.push(Enumerant {
+                        name: member.name.value.clone(),
+                        code_order,
+                        annotations: self.compile_annotations(&member.annotations, AnnotationTarget::Enumerant, &ctx),
+                    });
+                }
+                node.body = NodeBody::Enum(result);
+            }
+            DeclKind::Interface { ref superclasses } => {
+                let superclasses = superclasses.iter()
+                    .filter_map(|s| self.compile_superclass(s, &ctx))
+                    .collect();
+                let methods = self.translate_methods(entity, &decl, &node, &ctx, &mut extra_nodes);
+                node.member_doc_comments = methods.iter().map(|m| m.1.clone()).collect();
+                node.body = NodeBody::Interface {
+                    methods: methods.into_iter().map(|m| m.0).collect(),
+                    superclasses,
+                };
+            }
+            DeclKind::Const { ref type_, ref value } => {
+                let type_ = self.compile_type(type_, &ctx);
+                let cell = value_cell();
+                if let Some(ref t) = type_ {
+                    self.values.push_value(cell.clone(), value.clone(), t.clone(), ctx.clone());
+                }
+                node.body = NodeBody::Const { type_: type_.unwrap_or(Type::Void), value: cell };
+            }
+            DeclKind::Annotation { ref type_, targets } => {
+                let type_ = self.compile_type(type_, &ctx).unwrap_or(Type::Void);
+                node.body = NodeBody::Annotation { type_, targets };
+            }
+            _ => (),
+        }
+
+        self.nodes.insert(node.id, node);
+        for extra in extra_nodes {
+            self.nodes.insert(extra.id, extra);
+        }
+    }
+
+    fn compile_superclass(&mut self, expression: &Expression, ctx: &Context) -> Option<Superclass> {
+        match self.compile_decl_expression(expression, ctx)? {
+            Decl::Entity(entity, brand) if self.entities[entity].kind == EntityKind::Interface => {
+                Some(Superclass { id: self.entities[entity].id, brand: brand.compile() })
+            }
+            Decl::Type(Type::Parameter { .. }) | Decl::Type(Type::ImplicitMethodParameter(_)) => {
+                self.error_at(ctx, expression.span, format!(
+                    "'{}' is an unbound generic parameter. Currently we don't support extending these.",
+                    expression_string(expression)));
+                None
+            }
+            _ => {
+                self.error_at(ctx, expression.span, format!(
+                    "'{}' is not an interface.", expression_string(expression)));
+                None
+            }
+        }
+    }
+
+    fn translate_methods(&mut self, entity: usize, decl: &Declaration, node: &Node, ctx: &Context,
+                         extra_nodes: &mut Vec<Node>) -> Vec<(Method, Option<String>)> {
+        let mut methods: Vec<(u16, &Declaration)> = Vec::new();
+        for member in &decl.nested {
+            if let DeclKind::Method { .. } = member.kind {
+                methods.push((methods.len() as u16, member));
+            }
+        }
+        methods.sort_by_key(|&(_, member)| member.ordinal().map_or(0, |o| o.value));
+
+        let mut checker = OrdinalChecker::new();
+        let mut result = Vec::new();
+        for (code_order, member) in methods {
+            let ordinal = match member.ordinal() {
+                Some(ordinal) => ordinal,
+                None => continue,
+            };
+            checker.check(self, ctx, ordinal);
+            let (params, results) = match member.kind {
+                DeclKind::Method { ref params, ref results } => (params, results),
+                _ => unreachable!(),
+            };
+            let implicit: Vec<String> = member.parameters.iter().map(|p| p.value.clone()).collect();
+
+            if let ParamList::Stream(span) = *params {
+                self.error_at(ctx, span, "'stream' can only appear after '->', not before.".to_string());
+            }
+            let (param_struct_type, param_brand) = self.compile_param_list(
+                entity, node, &member.name.value, ordinal.value as u16, false, Some(params),
+                &implicit, ctx, extra_nodes);
+            let (result_struct_type, result_brand) = self.compile_param_list(
+                entity, node, &member.name.value, ordinal.value as u16, true, results.as_ref(),
+                &implicit, ctx, extra_nodes);
+
+            result.push((Method {
+                name: member.name.value.clone(),
+                code_order,
+                implicit_parameters: implicit,
+                param_struct_type,
+                param_brand,
+                result_struct_type,
+                result_brand,
+                annotations: self.compile_annotations(&member.annotations, AnnotationTarget::Method, ctx),
+            }, member.doc_comment.clone()));
+        }
+        result
+    }
+
+    /// Returns the ID and brand of the struct holding a method's parameters or results,
+    /// creating the struct if the list is declared inline.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_param_list(&mut self, interface: usize, interface_node: &Node, method_name: &str,
+                          ordinal: u16, is_results: bool, list: Option<&ParamList>,
+                          implicit: &[String], ctx: &Context, extra_nodes: &mut Vec<Node>)
+                          -> (u64, Option<crate::compiler::node::Brand>) {
+        let empty = ParamList::Named(Vec::new());
+        match *list.unwrap_or(&empty) {
+            ParamList::Named(ref params) => {
+                let id = type_id::generate_method_params_id(interface_node.id, ordinal, is_results);
+                let display_name = format!("{}.{}{}", interface_node.display_name, method_name,
+                                           if is_results { "$Results" } else { "$Params" });
+                let mut node = Node::new(id, display_name, 0, NodeBody::Struct(Struct::default()));
+                node.is_generic = interface_node.is_generic || !implicit.is_empty();
+                node.parameters = implicit.to_vec();
+
+                // Inside the struct, the method's implicit parameters are regular generic
+                // parameters of the struct.
+                let param_ctx = Context {
+                    entity: ctx.entity,
+                    brand: ctx.brand.clone(),
+                    implicit_params: Rc::new((id, implicit.to_vec())),
+                };
+                let mut translator = StructTranslator::new(self, param_ctx, node);
+                translator.add_params(params);
+                extra_nodes.extend(translator.finish());
+
+                (id, self.method_param_brand(interface, id, implicit.len()))
+            }
+            ParamList::Type(ref expression) => {
+                let type_ctx = Context {
+                    entity: ctx.entity,
+                    brand: ctx.brand.clone(),
+                    implicit_params: Rc::new((0, implicit.to_vec())),
+                };
+                match self.compile_decl_expression(expression, &type_ctx) {
+                    Some(Decl::Entity(entity, brand)) if self.entities[entity].kind == EntityKind::Struct => {
+                        (self.entities[entity].id, brand.compile())
+                    }
+                    Some(Decl::Type(Type::Parameter { .. })) | Some(Decl::Type(Type::ImplicitMethodParameter(_))) => {
+                        self.error_at(ctx, expression.span,
+                                      "Cannot use generic parameter as whole input or output of a method. \
+                                       Instead, use a parameter/result list containing a field with this type."
+                                      .to_string());
+                        (0, None)
+                    }
+                    Some(_) => {
+                        self.error_at(ctx, expression.span, format!(
+                            "'{}' is not a struct type.", expression_string(expression)));
+                        (0, None)
+                    }
+                    None => (0, None),
+                }
+            }
+            ParamList::Stream(span) => {
+                // Streaming methods return the `StreamResult` struct, which we need to load.
+                let file = self.entities[ctx.entity].file;
+                if self.import(file, "/capnp/stream.capnp", span).is_some() {
+                    (STREAM_RESULT_ID, None)
+                } else {
+                    (0, None)
+                }
+            }
+        }
+    }
+
+    pub(crate) fn compile_annotations(&mut self, annotations: &[AnnotationApplication],
+                                      target: AnnotationTarget, ctx: &Context) -> Option<Vec<Annotation>> {
+        if annotations.is_empty() {
+            return None;
+        }
+
+        let mut result = Vec::new();
+        for application in annotations {
+            let mut annotation = Annotation { id: 0, brand: None, value: value_cell() };
+            match self.compile_decl_expression(&application.name, ctx) {
+                Some(Decl::Entity(entity, brand)) if self.entities[entity].kind == EntityKind::Annotation => {
+                    annotation.id = self.entities[entity].id;
+                    annotation.brand = brand.compile();
+                    let applies = match self.entities[entity].decl.kind {
+                        DeclKind::Annotation { ref targets, .. } => targets[target as usize],
+                        _ => false,
+                    };
+                    if !applies {
+                        self.error_at(ctx, application.name.span, format!(
+                            "'{}' cannot be applied to this kind of declaration.",
+                            expression_string(&application.name)));
+                    }
+                    self.values.push_annotation(annotation.value.clone(), annotation.id, annotation.brand.clone(),
+                                                application.clone(), ctx.clone());
+                }
+                Some(_) => {
+                    self.error_at(ctx, application.name.span, format!(
+                        "'{}' is not an annotation.", expression_string(&application.name)));
+                }
+                None => (),
+            }
+            result.push(annotation);
+        }
+        Some(result)
+    }
+}
+
+enum MemberKind {
+    Root,
+    Field { type_: Expression, default_value: Option<Expression>, is_param: bool },
+    Union,
+    Group,
+}
+
+/// A field, union or group of a struct that's being translated. Unnamed unions don't have
+/// members of their own; their fields are members of the enclosing struct or group.
+struct Member {
+    parent: Option<usize>,
+    code_order: u16,
+    /// Position in the parent's field list, once known.
+    index: u16,
+    child_count: u16,
+    child_initialized_count: u16,
+    union_discriminant_count: u16,
+    is_in_union: bool,
+    name: String,
+    kind: MemberKind,
+    annotations: Vec<AnnotationApplication>,
+    doc_comment: Option<String>,
+    /// Index of the member's field in its parent's node, once it has been created.
+    field: Option<usize>,
+    /// Index of the node for the root struct or a group.
+    node: Option<usize>,
+    /// Where a field gets allocated.
+    field_scope: Scope,
+    /// The union of a union member, or the unnamed union of a struct or group.
+    union_scope: Option<usize>,
+}
+
+struct OrdinalEntry {
+    ordinal: u64,
+    /// Present if the ordinal was given explicitly, which is always the case except for
+    /// method parameters.
+    located: Option<Located<u64>>,
+    member: usize,
+    /// For unions, the union whose discriminant the ordinal refers to.
+    union: Option<usize>,
+}
+
+/// Lays out a struct and its groups, following the C++ implementation. Members are visited in
+/// ordinal order, and each member's field is created lazily when it is first visited, which
+/// determines the order of the fields in the schema and therefore the IDs of groups.
+struct StructTranslator<'c> {
+    compiler: &'c mut Compiler,
+    ctx: Context,
+    layout: StructLayout,
+    members: Vec<Member>,
+    nodes: Vec<(Node, bool)>,
+    by_ordinal: Vec<OrdinalEntry>,
+    all_members: Vec<usize>,
+}
+
+fn struct_mut(node: &mut Node) -> &mut Struct {
+    match node.body {
+        NodeBody::Struct(ref mut s) => s,
+        _ => unreachable!("not a struct"),
+    }
+}
+
+impl <'c> StructTranslator<'c> {
+    fn new(compiler: &'c mut Compiler, ctx: Context, root: Node) -> StructTranslator<'c> {
+        let root_member = Member {
+            parent: None,
+            code_order: 0,
+            index: 0,
+            child_count: 0,
+            child_initialized_count: 0,
+            union_discriminant_count: 0,
+            is_in_union: false,
+            name: String::new(),
+            kind: MemberKind::Root,
+            annotations: Vec::new(),
+            doc_comment: None,
+            field: None,
+            node: Some(0),
+            field_scope: Scope::Top,
+            union_scope: None,
+        };
+        StructTranslator {
+            compiler,
+            ctx,
+            layout: StructLayout::new(),
+            members: vec![root_member],
+            nodes: vec![(root, false)],
+            by_ordinal: Vec::new(),
+            all_members: Vec::new(),
+        }
+    }
+
+    fn add_member(&mut self, parent: usize, code_order: u16, decl: &Declaration, kind: MemberKind,
+                  field_scope: Scope, is_in_union: bool) -> usize {
+        self.members[parent].child_count += 1;
+        let node = match kind {
+            MemberKind::Union | MemberKind::Group => {
+                let parent_node = &self.nodes[self.members[parent].node.unwrap()].0;
+                let mut node = Node::new(0, format!("{}.{}", parent_node.display_name, decl.name.value),
+                                         0, NodeBody::Struct(Struct { is_group: true, ..Struct::default() }));
+                node.display_name_prefix_length = (node.display_name.len() - decl.name.value.len()) as u32;
+                node.is_generic = parent_node.is_generic;
+                self.nodes.push((node, false));
+                Some(self.nodes.len() - 1)
+            }
+            _ => None,
+        };
+        self.members.push(Member {
+            parent: Some(parent),
+            code_order,
+            index: 0,
+            child_count: 0,
+            child_initialized_count: 0,
+            union_discriminant_count: 0,
+            is_in_union,
+            name: decl.name.value.clone(),
+            kind,
+            annotations: decl.annotations.clone(),
+            doc_comment: decl.doc_comment.clone(),
+            field: None,
+            node,
+            field_scope,
+            union_scope: None,
+        });
+        let member = self.members.len() - 1;
+        self.all_members.push(member);
+        member
+    }
+
+    fn field_kind(decl: &Declaration) -> MemberKind {
+        match decl.kind {
+            DeclKind::Field { ref type_, ref default_value } => MemberKind::Field {
+                type_: type_.clone(),
+                default_value: default_value.clone(),
+                is_param: false,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn add_ordinal(&mut self, decl: &Declaration, member: usize, union: Option<usize>) {
+        if let Some(ordinal) = decl.ordinal() {
+            self.by_ordinal.push(OrdinalEntry {
+                ordinal: ordinal.value,
+                located: Some(ordinal.clone()),
+                member,
+                union,
+            });
+        }
+    }
+
+    fn traverse_top_or_group(&mut self, decls: &[Declaration], parent: usize, scope: Scope) {
+        let mut code_order = 0;
+        for decl in decls {
+            match decl.kind {
+                DeclKind::Field { .. } => {
+                    let member = self.add_member(parent, code_order, decl, Self::field_kind(decl), scope, false);
+                    code_order += 1;
+                    self.add_ordinal(decl, member, None);
+                }
+                DeclKind::Union => {
+                    let union = self.layout.new_union(scope);
+                    let member = if decl.name.value.is_empty() {
+                        self.members[parent].union_scope = Some(union);
+                        self.traverse_union(decl, parent, union, &mut code_order);
+                        parent
+                    } else {
+                        let member = self.add_member(parent, code_order, decl, MemberKind::Union, scope, false);
+                        code_order += 1;
+                        self.members[member].union_scope = Some(union);
+                        self.traverse_union(decl, member, union, &mut 0);
+                        member
+                    };
+                    self.add_ordinal(decl, member, Some(union));
+                }
+                DeclKind::Group => {
+                    let member = self.add_member(parent, code_order, decl, MemberKind::Group, scope, false);
+                    code_order += 1;
+                    self.traverse_group(decl, member, scope);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn traverse_union(&mut self, decl: &Declaration, parent: usize, union: usize, code_order: &mut u16) {
+        if decl.nested.len() < 2 {
+            self.compiler.error_at(&self.ctx, decl.span, "Union must have at least two members.".to_string());
+        }
+
+        for member_decl in &decl.nested {
+            match member_decl.kind {
+                DeclKind::Field { .. } => {
+                    // For layout purposes, the field is enclosed in a group of its own.
+                    let group = self.layout.new_group(union);
+                    let member = self.add_member(parent, *code_order, member_decl, Self::field_kind(member_decl),
+                                                 group, true);
+                    *code_order += 1;
+                    self.add_ordinal(member_decl, member, None);
+                }
+                DeclKind::Union => {
+                    if member_decl.name.value.is_empty() {
+                        self.compiler.error_at(&self.ctx, member_decl.span,
+                                               "Unions cannot contain unnamed unions.".to_string());
+                        continue;
+                    }
+                    let group = self.layout.new_group(union);
+                    let inner = self.layout.new_union(group);
+                    let member = self.add_member(parent, *code_order, member_decl, MemberKind::Union, group, true);
+                    *code_order += 1;
+                    self.members[member].union_scope = Some(inner);
+                    self.traverse_union(member_decl, member, inner, &mut 0);
+                    self.add_ordinal(member_decl, member, Some(inner));
+                }
+                DeclKind::Group => {
+                    let group = self.layout.new_group(union);
+                    let member = self.add_member(parent, *code_order, member_decl, MemberKind::Group, group, true);
+                    *code_order += 1;
+                    self.traverse_group(member_decl, member, group);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn traverse_group(&mut self, decl: &Declaration, member: usize, scope: Scope) {
+        if decl.nested.is_empty() {
+            self.compiler.error_at(&self.ctx, decl.span, "Group must have at least one member.".to_string());
+        }
+        self.traverse_top_or_group(&decl.nested, member, scope);
+    }
+
+    fn add_params(&mut self, params: &[ParamDecl]) {
+        for (idx, param) in params.iter().enumerate() {
+            self.members[0].child_count += 1;
+            self.members.push(Member {
+                parent: Some(0),
+                code_order: idx as u16,
+                index: 0,
+                child_count: 0,
+                child_initialized_count: 0,
+                union_discriminant_count: 0,
+                is_in_union: false,
+                name: param.name.value.clone(),
+                kind: MemberKind::Field {
+                    type_: param.type_.clone(),
+                    default_value: param.default_value.clone(),
+                    is_param: true,
+                },
+                annotations: param.annotations.clone(),
+                doc_comment: None,
+                field: None,
+                node: None,
+                field_scope: Scope::Top,
+                union_scope: None,
+            });
+            let member = self.members.len() - 1;
+            self.all_members.push(member);
+            self.by_ordinal.push(OrdinalEntry { ordinal: idx as u64, located: None, member, union: None });
+        }
+    }
+
+    /// Returns the index of the member's field within its parent, creating the field if needed.
+    fn get_schema(&mut self, member: usize) -> usize {
+        if let Some(field) = self.members[member].field {
+            return field;
+        }
+        let parent = self.members[member].parent.expect("root has no field");
+        let index = self.members[parent].child_initialized_count as usize;
+
+        // Make sure that the parent itself exists in its own parent once its first child is added.
+        let parent_node = self.members[parent].node.unwrap();
+        if !self.nodes[parent_node].1 {
+            if self.members[parent].parent.is_some() {
+                self.get_schema(parent);
+            }
+            self.nodes[parent_node].1 = true;
+        }
+        self.members[parent].child_initialized_count += 1;
+
+        let discriminant_value = if self.members[member].is_in_union {
+            self.members[parent].union_discriminant_count += 1;
+            self.members[parent].union_discriminant_count - 1
+        } else {
+            NO_DISCRIMINANT
+        };
+        let field = Field {
+            name: self.members[member].name.clone(),
+            code_order: self.members[member].code_order,
+            annotations: None,
+            discriminant_value,
+            kind: FieldKind::Group(0),
+            ordinal: None,
+        };
+        let node = &mut self.nodes[parent_node].0;
+        struct_mut(node).fields.push(field);
+        node.member_doc_comments.push(self.members[member].doc_comment.clone());
+
+        self.members[member].field = Some(index);
+        self.members[member].index = index as u16;
+        index
+    }
+
+    fn field_mut(&mut self, member: usize) -> &mut Field {
+        let field = self.get_schema(member);
+        let parent_node = self.members[self.members[member].parent.unwrap()].node.unwrap();
+        &mut struct_mut(&mut self.nodes[parent_node].0).fields[field]
+    }
+
+    fn finish_group(&mut self, member: usize) {
+        let node = self.members[member].node.unwrap();
+        if let Some(union) = self.members[member].union_scope {
+            self.layout.add_discriminant(union);
+            let count = self.members[member].union_discriminant_count;
+            let offset = self.layout.discriminant_offset(union).unwrap();
+            let s = struct_mut(&mut self.nodes[node].0);
+            s.discriminant_count = count;
+            s.discriminant_offset = offset;
+        }
+
+        if let Some(parent) = self.members[member].parent {
+            self.get_schema(member);
+            let parent_id = self.nodes[self.members[parent].node.unwrap()].0.id;
+            let id = type_id::generate_group_id(parent_id, self.members[member].index);
+            self.nodes[node].0.id = id;
+            self.nodes[node].0.scope_id = parent_id;
+            self.nodes[node].0.doc_comment = self.members[member].doc_comment.clone();
+            self.field_mut(member).kind = FieldKind::Group(id);
+        }
+    }
+
+    fn translate_field(&mut self, member: usize) {
+        let (type_expr, default_value) = match self.members[member].kind {
+            MemberKind::Field { ref type_, ref default_value, .. } => (type_.clone(), default_value.clone()),
+            _ => unreachable!(),
+        };
+        let ctx = self.ctx.clone();
+        let type_ = self.compiler.compile_type(&type_expr, &ctx);
+        let had_explicit_default = type_.is_some() && default_value.is_some();
+        let type_ = type_.unwrap_or(Type::Void);
+        let cell = Rc::new(RefCell::new(Value::default_for(&type_)));
+        if let (true, Some(expression)) = (had_explicit_default, default_value) {
+            self.compiler.values.push_value(cell.clone(), expression, type_.clone(), ctx);
+        }
+
+        let scope = self.members[member].field_scope;
+        let offset = match type_.lg_data_size() {
+            Some(-1) => {
+                self.layout.add_void(scope);
+                0
+            }
+            Some(lg_size) => self.layout.add_data(scope, lg_size as u32),
+            None => self.layout.add_pointer(scope),
+        };
+        self.field_mut(member).kind = FieldKind::Slot { offset, type_, default_value: cell, had_explicit_default };
+    }
+
+    /// Lays out all members and returns the struct's node, followed by the nodes of its groups.
+    fn finish(mut self) -> Vec<Node> {
+        let mut entries = ::std::mem::take(&mut self.by_ordinal);
+        entries.sort_by_key(|e| e.ordinal);
+        let mut checker = OrdinalChecker::new();
+        for entry in entries {
+            if let Some(ref located) = entry.located {
+                checker.check(self.compiler, &self.ctx, located);
+            }
+            let member = entry.member;
+            match entry.union {
+                None => {
+                    self.field_mut(member).ordinal = Some(entry.ordinal as u16);
+                    self.translate_field(member);
+                }
+                Some(union) => {
+                    if let MemberKind::Union = self.members[member].kind {
+                        self.field_mut(member).ordinal = Some(entry.ordinal as u16);
+                    }
+                    if !self.layout.add_discriminant(union) {
+                        let span = entry.located.map_or(Span::default(), |l| l.span);
+                        self.compiler.error_at(&self.ctx, span,
+                            "Union ordinal, if specified, must be greater than no more than one of its \
+                             member ordinals (i.e. there can only be one field retroactively unionized)."
+                            .to_string());
+                    }
+                }
+            }
+        }
+
+        self.finish_group(0);
+        for idx in 0..self.all_members.len() {
+            let member = self.all_members[idx];
+            let target = match self.members[member].kind {
+                MemberKind::Field { is_param: true, .. } => AnnotationTarget::Param,
+                MemberKind::Field { .. } => AnnotationTarget::Field,
+                MemberKind::Union => {
+                    self.finish_group(member);
+                    AnnotationTarget::Union
+                }
+                MemberKind::Group => {
+                    self.finish_group(member);
+                    AnnotationTarget::Group
+                }
+                MemberKind::Root => unreachable!(),
+            };
+            let annotations = ::std::mem::take(&mut self.members[member].annotations);
+            let ctx = self.ctx.clone();
+            let compiled = self.compiler.compile_annotations(&annotations, target, &ctx);
+            self.field_mut(member).annotations = compiled;
+        }
+
+        let data_word_count = self.layout.data_word_count as u16;
+        let pointer_count = self.layout.pointer_count as u16;
+        self.nodes.into_iter().map(|(mut node, _)| {
+            {
+                let s = struct_mut(&mut node);
+                s.data_word_count = data_word_count;
+                s.pointer_count = pointer_count;
+            }
+            node
+        }).collect()
+    }
+}
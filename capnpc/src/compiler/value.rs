@@ -0,0 +1,559 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Compilation of constants, default values and annotation values.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use capnp::{message, serialize};
+
+use crate::compiler::lexer::Span;
+use crate::compiler::node::{
+    AnyKind, Brand, FieldKind, ListObject, NodeBody, Object, Struct, StructObject, Type,
+    Value, ValueCell, NO_DISCRIMINANT,
+};
+use crate::compiler::parser::{expression_string, AnnotationApplication, Expression, ExpressionKind, Param};
+use crate::compiler::resolve::{Context, Decl};
+use crate::compiler::{Compiler, EntityKind};
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Pending,
+    InProgress,
+    Done,
+}
+
+enum JobKind {
+    Value { expression: Expression, type_: Type },
+    Annotation { id: u64, brand: Option<Brand>, application: AnnotationApplication },
+}
+
+struct Job {
+    cell: ValueCell,
+    kind: Rc<JobKind>,
+    ctx: Context,
+    state: State,
+}
+
+/// Values waiting to be compiled, in the order in which they were encountered.
+#[derive(Default)]
+pub struct ValueJobs {
+    jobs: Vec<Job>,
+    by_cell: HashMap<*const RefCell<Value>, usize>,
+}
+
+impl ValueJobs {
+    fn push(&mut self, cell: ValueCell, kind: JobKind, ctx: Context) {
+        self.by_cell.insert(&*cell as *const _, self.jobs.len());
+        self.jobs.push(Job { cell, kind: Rc::new(kind), ctx, state: State::Pending });
+    }
+
+    pub fn push_value(&mut self, cell: ValueCell, expression: Expression, type_: Type, ctx: Context) {
+        self.push(cell, JobKind::Value { expression, type_ }, ctx);
+    }
+
+    pub fn push_annotation(&mut self, cell: ValueCell, id: u64, brand: Option<Brand>,
+                           application: AnnotationApplication, ctx: Context) {
+        self.push(cell, JobKind::Annotation { id, brand, application }, ctx);
+    }
+}
+
+/// A numeric literal, before being converted to its expected type.
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+/// Replaces generic parameters in `t` with their bindings in `brand`.
+fn substitute(t: &Type, brand: Option<&Brand>) -> Type {
+    match *t {
+        Type::Parameter { scope_id, index } => {
+            let scope = brand.and_then(|b| b.scopes.iter().find(|s| s.scope_id == scope_id));
+            match scope {
+                Some(scope) => match scope.bind {
+                    None => t.clone(),
+                    Some(ref bindings) => match bindings.get(index as usize) {
+                        Some(bound) => bound.clone(),
+                        None => Type::AnyPointer(AnyKind::Any),
+                    },
+                },
+                None => Type::AnyPointer(AnyKind::Any),
+            }
+        }
+        Type::List(ref element) => Type::List(Box::new(substitute(element, brand))),
+        Type::Struct(id, Some(ref inner)) => Type::Struct(id, Some(substitute_brand(inner, brand))),
+        Type::Interface(id, Some(ref inner)) => Type::Interface(id, Some(substitute_brand(inner, brand))),
+        _ => t.clone(),
+    }
+}
+
+fn substitute_brand(inner: &Brand, brand: Option<&Brand>) -> Brand {
+    let mut result = inner.clone();
+    for scope in &mut result.scopes {
+        match scope.bind {
+            Some(ref mut bindings) => {
+                for t in bindings {
+                    *t = substitute(t, brand);
+                }
+            }
+            None => {
+                // Inherited parameters take the bindings of the surrounding brand.
+                let outer = brand.and_then(|b| b.scopes.iter().find(|s| s.scope_id == scope.scope_id));
+                if let Some(outer) = outer {
+                    scope.bind = outer.bind.clone();
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Compares types, ignoring brands.
+fn same_type(a: &Type, b: &Type) -> bool {
+    match (a, b) {
+        (Type::List(a), Type::List(b)) => same_type(a, b),
+        (Type::Enum(a, _), Type::Enum(b, _)) |
+        (Type::Struct(a, _), Type::Struct(b, _)) |
+        (Type::Interface(a, _), Type::Interface(b, _)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn is_generic_parameter(t: &Type) -> bool {
+    matches!(*t, Type::Parameter { .. } | Type::ImplicitMethodParameter(_))
+}
+
+impl Compiler {
+    pub(crate) fn compile_values(&mut self) {
+        let mut idx = 0;
+        while idx < self.values.jobs.len() {
+            self.run_job(idx);
+            idx += 1;
+        }
+    }
+
+    /// Compiles the value of a job, returning false if the value is already being compiled,
+    /// i.e. if it depends on itself.
+    fn run_job(&mut self, idx: usize) -> bool {
+        match self.values.jobs[idx].state {
+            State::Done => return true,
+            State::InProgress => return false,
+            State::Pending => (),
+        }
+        self.values.jobs[idx].state = State::InProgress;
+        let kind = self.values.jobs[idx].kind.clone();
+        let ctx = self.values.jobs[idx].ctx.clone();
+
+        let value = match *kind {
+            JobKind::Value { ref expression, ref type_ } => {
+                self.compile_value(expression, type_, &ctx).unwrap_or_else(|| Value::default_for(type_))
+            }
+            JobKind::Annotation { id, ref brand, ref application } => {
+                self.translate_pending();
+                let type_ = match self.nodes.get(&id).map(|n| &n.body) {
+                    Some(NodeBody::Annotation { type_, .. }) => substitute(type_, brand.as_ref()),
+                    _ => Type::Void,
+                };
+                match application.value {
+                    Some(ref expression) => self.compile_value(expression, &type_, &ctx),
+                    None if type_ == Type::Void => Some(Value::Void),
+                    None => {
+                        self.error_at(&ctx, application.name.span, format!(
+                            "'{}' requires a value.", expression_string(&application.name)));
+                        None
+                    }
+                }.unwrap_or_else(|| Value::default_for(&type_))
+            }
+        };
+
+        let job = &mut self.values.jobs[idx];
+        *job.cell.borrow_mut() = value;
+        job.state = State::Done;
+        true
+    }
+
+    /// Makes sure that the value in `cell` has been compiled, if it's the result of a job.
+    fn ensure_value(&mut self, cell: &ValueCell) -> bool {
+        match self.values.by_cell.get(&(&**cell as *const _)) {
+            Some(&idx) => self.run_job(idx),
+            None => true,
+        }
+    }
+
+    fn struct_node(&mut self, id: u64) -> Option<Struct> {
+        self.translate_pending();
+        self.nodes.get(&id).and_then(|n| n.as_struct().cloned())
+    }
+
+    fn type_name(&self, t: &Type) -> String {
+        match *t {
+            Type::Void => "Void".into(),
+            Type::Bool => "Bool".into(),
+            Type::Int8 => "Int8".into(),
+            Type::Int16 => "Int16".into(),
+            Type::Int32 => "Int32".into(),
+            Type::Int64 => "Int64".into(),
+            Type::UInt8 => "UInt8".into(),
+            Type::UInt16 => "UInt16".into(),
+            Type::UInt32 => "UInt32".into(),
+            Type::UInt64 => "UInt64".into(),
+            Type::Float32 => "Float32".into(),
+            Type::Float64 => "Float64".into(),
+            Type::Text => "Text".into(),
+            Type::Data => "Data".into(),
+            Type::List(ref element) => format!("List({})", self.type_name(element)),
+            Type::Enum(id, _) | Type::Struct(id, _) | Type::Interface(id, _) => match self.nodes.get(&id) {
+                Some(node) => node.display_name[node.display_name_prefix_length as usize..].to_string(),
+                None => format!("@0x{:016x}", id),
+            },
+            Type::AnyPointer(AnyKind::Struct) => "AnyStruct".into(),
+            Type::AnyPointer(AnyKind::List) => "AnyList".into(),
+            Type::AnyPointer(AnyKind::Capability) => "Capability".into(),
+            Type::AnyPointer(AnyKind::Any) | Type::Parameter { .. } |
+            Type::ImplicitMethodParameter(_) => "AnyPointer".into(),
+        }
+    }
+
+    fn type_mismatch(&mut self, span: Span, t: &Type, ctx: &Context) {
+        let message = format!("Type mismatch; expected {}.", self.type_name(t));
+        self.error_at(ctx, span, message);
+    }
+
+    pub(crate) fn compile_value(&mut self, expression: &Expression, t: &Type, ctx: &Context) -> Option<Value> {
+        if is_generic_parameter(t) {
+            self.error_at(ctx, expression.span,
+                          "Cannot interpret value because the type is a generic type parameter which is not \
+                           yet bound. We don't know what type to expect here.".to_string());
+            return None;
+        }
+
+        match expression.kind {
+            ExpressionKind::RelativeName(ref name) => {
+                if let Type::Enum(id, _) = *t {
+                    self.translate_pending();
+                    if let Some(NodeBody::Enum(enumerants)) = self.nodes.get(&id).map(|n| &n.body) {
+                        if let Some(idx) = enumerants.iter().position(|e| &e.name == name) {
+                            return Some(Value::Enum(idx as u16));
+                        }
+                    }
+                } else {
+                    match &name[..] {
+                        "void" if *t == Type::Void => return Some(Value::Void),
+                        "true" | "false" if *t == Type::Bool => return Some(Value::Bool(name == "true")),
+                        "void" | "true" | "false" => {
+                            self.type_mismatch(expression.span, t, ctx);
+                            return None;
+                        }
+                        "nan" => return self.number(Number::Float(f64::NAN), t, expression.span, ctx),
+                        "inf" => return self.number(Number::Float(f64::INFINITY), t, expression.span, ctx),
+                        _ => (),
+                    }
+                }
+                self.constant(expression, t, ctx)
+            }
+            ExpressionKind::AbsoluteName(_) | ExpressionKind::Import(_) | ExpressionKind::Application(..) |
+            ExpressionKind::Member(..) => self.constant(expression, t, ctx),
+            ExpressionKind::Embed(ref name) => {
+                let file = self.entities[ctx.entity].file;
+                let bytes = self.read_embed(file, &name.value, name.span)?;
+                match *t {
+                    Type::Text => Some(Value::Text(Some(Object::Text(String::from_utf8_lossy(&bytes).into_owned())))),
+                    Type::Data => Some(Value::Data(Some(Object::Data(bytes)))),
+                    Type::Struct(..) => {
+                        let valid = bytes.len() % 8 == 0 &&
+                            serialize::read_message(&mut &bytes[..], message::ReaderOptions::new()).is_ok();
+                        if valid {
+                            Some(Value::Struct(Some(Object::Message(Rc::new(bytes)))))
+                        } else {
+                            self.error_at(ctx, expression.span,
+                                          "Embedded file is not a valid Cap'n Proto message.".to_string());
+                            None
+                        }
+                    }
+                    _ => {
+                        self.error_at(ctx, expression.span,
+                                      "Embeds can only be used when Text, Data, or a struct is expected.".to_string());
+                        None
+                    }
+                }
+            }
+            ExpressionKind::PositiveInt(v) => self.number(Number::Int(i128::from(v)), t, expression.span, ctx),
+            ExpressionKind::NegativeInt(v) => {
+                if v > (1 << 63) {
+                    self.error_at(ctx, expression.span, "Integer is too big to be negative.".to_string());
+                    None
+                } else {
+                    self.number(Number::Int(-i128::from(v)), t, expression.span, ctx)
+                }
+            }
+            ExpressionKind::Float(v) => self.number(Number::Float(v), t, expression.span, ctx),
+            ExpressionKind::String(ref bytes) => match *t {
+                Type::Text => Some(Value::Text(Some(Object::Text(String::from_utf8_lossy(bytes).into_owned())))),
+                Type::Data => Some(Value::Data(Some(Object::Data(bytes.clone())))),
+                _ => {
+                    self.type_mismatch(expression.span, t, ctx);
+                    None
+                }
+            },
+            ExpressionKind::Binary(ref bytes) => match *t {
+                Type::Data => Some(Value::Data(Some(Object::Data(bytes.clone())))),
+                _ => {
+                    self.type_mismatch(expression.span, t, ctx);
+                    None
+                }
+            },
+            ExpressionKind::List(ref items) => match *t {
+                Type::List(ref element) => {
+                    let list = self.list_value(element, items, ctx)?;
+                    Some(Value::List(Some(Object::List(list))))
+                }
+                _ => {
+                    self.type_mismatch(expression.span, t, ctx);
+                    None
+                }
+            },
+            ExpressionKind::Tuple(ref params) => match *t {
+                Type::Struct(id, ref brand) => {
+                    let s = self.struct_node(id)?;
+                    let mut object = StructObject::new(s.data_word_count, s.pointer_count);
+                    self.fill_struct(&mut object, &s, brand.as_ref(), params, ctx);
+                    Some(Value::Struct(Some(Object::Struct(object))))
+                }
+                _ => {
+                    self.type_mismatch(expression.span, t, ctx);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Converts a numeric literal to the expected type, checking its range.
+    fn number(&mut self, n: Number, t: &Type, span: Span, ctx: &Context) -> Option<Value> {
+        let v = match n {
+            Number::Float(f) => {
+                return match *t {
+                    Type::Float32 => Some(Value::Float32(f as f32)),
+                    Type::Float64 => Some(Value::Float64(f)),
+                    _ => {
+                        self.type_mismatch(span, t, ctx);
+                        None
+                    }
+                };
+            }
+            Number::Int(v) => v,
+        };
+        let (min, max): (i128, i128) = match *t {
+            Type::Int8 => (i8::MIN.into(), i8::MAX.into()),
+            Type::Int16 => (i16::MIN.into(), i16::MAX.into()),
+            Type::Int32 => (i32::MIN.into(), i32::MAX.into()),
+            Type::Int64 => (i64::MIN.into(), i64::MAX.into()),
+            Type::UInt8 => (0, u8::MAX.into()),
+            Type::UInt16 => (0, u16::MAX.into()),
+            Type::UInt32 => (0, u32::MAX.into()),
+            Type::UInt64 => (0, u64::MAX.into()),
+            Type::Float32 => return Some(Value::Float32(v as f32)),
+            Type::Float64 => return Some(Value::Float64(v as f64)),
+            _ => {
+                self.type_mismatch(span, t, ctx);
+                return None;
+            }
+        };
+        let v = if v < min || v > max {
+            self.error_at(ctx, span, "Integer value out of range.".to_string());
+            if v < min { min } else { max }
+        } else {
+            v
+        };
+        Some(match *t {
+            Type::Int8 => Value::Int8(v as i8),
+            Type::Int16 => Value::Int16(v as i16),
+            Type::Int32 => Value::Int32(v as i32),
+            Type::Int64 => Value::Int64(v as i64),
+            Type::UInt8 => Value::UInt8(v as u8),
+            Type::UInt16 => Value::UInt16(v as u16),
+            Type::UInt32 => Value::UInt32(v as u32),
+            _ => Value::UInt64(v as u64),
+        })
+    }
+
+    /// Compiles a reference to a constant, converting its value to the expected type.
+    fn constant(&mut self, expression: &Expression, t: &Type, ctx: &Context) -> Option<Value> {
+        let entity = match self.compile_decl_expression(expression, ctx)? {
+            Decl::Entity(entity, _) if self.entities[entity].kind == EntityKind::Const => entity,
+            _ => {
+                self.error_at(ctx, expression.span, format!(
+                    "'{}' does not refer to a constant.", expression_string(expression)));
+                return None;
+            }
+        };
+
+        if let ExpressionKind::RelativeName(ref name) = expression.kind {
+            // An unqualified name could be mistaken for a literal or an enumerant.
+            let parent = &self.entities[self.entities[entity].parent.unwrap()];
+            let scope = if parent.kind == EntityKind::File {
+                String::new()
+            } else {
+                let display_name = &parent.display_name;
+                let start = display_name.rfind(&['.', ':'][..]).map_or(0, |i| i + 1);
+                display_name[start..].to_string()
+            };
+            self.error_at(ctx, expression.span, format!(
+                "Constant names must be qualified to avoid confusion.  Please replace '{}' with '{}.{}', \
+                 if that's what you intended.", name, scope, name));
+        }
+
+        self.translate_pending();
+        let (const_type, cell) = match self.nodes.get(&self.entities[entity].id).map(|n| &n.body) {
+            Some(NodeBody::Const { type_, value }) => (type_.clone(), value.clone()),
+            _ => return None,
+        };
+        if !self.ensure_value(&cell) {
+            self.error_at(ctx, expression.span, format!(
+                "'{}' is defined in terms of itself.", expression_string(expression)));
+            return None;
+        }
+        let value = cell.borrow().clone();
+
+        let number = match value {
+            Value::Int8(v) => Some(Number::Int(v.into())),
+            Value::Int16(v) => Some(Number::Int(v.into())),
+            Value::Int32(v) => Some(Number::Int(v.into())),
+            Value::Int64(v) => Some(Number::Int(v.into())),
+            Value::UInt8(v) => Some(Number::Int(v.into())),
+            Value::UInt16(v) => Some(Number::Int(v.into())),
+            Value::UInt32(v) => Some(Number::Int(v.into())),
+            Value::UInt64(v) => Some(Number::Int(v.into())),
+            Value::Float32(v) => Some(Number::Float(v.into())),
+            Value::Float64(v) => Some(Number::Float(v)),
+            _ => None,
+        };
+        if let Some(number) = number {
+            return self.number(number, t, expression.span, ctx);
+        }
+
+        if same_type(&const_type, t) {
+            return Some(value);
+        }
+        let fits_any_pointer = matches!((t, &const_type),
+                                        (Type::AnyPointer(AnyKind::Any), Type::Struct(..)) |
+                                        (Type::AnyPointer(AnyKind::Any), Type::List(_)) |
+                                        (Type::AnyPointer(AnyKind::Struct), Type::Struct(..)) |
+                                        (Type::AnyPointer(AnyKind::List), Type::List(_)));
+        if fits_any_pointer {
+            return Some(Value::AnyPointer(value.into_object()));
+        }
+        self.type_mismatch(expression.span, t, ctx);
+        None
+    }
+
+    fn list_value(&mut self, element: &Type, items: &[Expression], ctx: &Context) -> Option<ListObject> {
+        let mut values = Vec::new();
+        for item in items {
+            let value = self.compile_value(item, element, ctx).unwrap_or_else(|| Value::default_for(element));
+            values.push(value);
+        }
+        let bits = |values: &[Value]| values.iter().map(|v| v.bits().unwrap_or(0)).collect::<Vec<u64>>();
+        Some(match element.lg_data_size() {
+            Some(-1) => ListObject::Void(values.len() as u32),
+            Some(0) => ListObject::Bit(bits(&values).into_iter().map(|b| b & 1 == 1).collect()),
+            Some(3) => ListObject::Byte(bits(&values).into_iter().map(|b| b as u8).collect()),
+            Some(4) => ListObject::TwoBytes(bits(&values).into_iter().map(|b| b as u16).collect()),
+            Some(5) => ListObject::FourBytes(bits(&values).into_iter().map(|b| b as u32).collect()),
+            Some(_) => ListObject::EightBytes(bits(&values)),
+            None => match *element {
+                Type::Struct(id, _) => {
+                    let s = self.struct_node(id)?;
+                    let mut elements = Vec::new();
+                    for (value, item) in values.into_iter().zip(items) {
+                        elements.push(match value.into_object() {
+                            Some(Object::Struct(object)) => object,
+                            None => StructObject::new(s.data_word_count, s.pointer_count),
+                            Some(_) => {
+                                self.error_at(ctx, item.span,
+                                              "Sorry, embedded structs can't be used as list elements.".to_string());
+                                StructObject::new(s.data_word_count, s.pointer_count)
+                            }
+                        });
+                    }
+                    ListObject::InlineComposite {
+                        data_words: s.data_word_count,
+                        pointer_count: s.pointer_count,
+                        elements,
+                    }
+                }
+                _ => ListObject::Pointer(values.into_iter().map(Value::into_object).collect()),
+            },
+        })
+    }
+
+    fn fill_struct(&mut self, object: &mut StructObject, s: &Struct, brand: Option<&Brand>,
+                   params: &[Param], ctx: &Context) {
+        for param in params {
+            let name = match param.name {
+                Some(ref name) => name,
+                None => {
+                    self.error_at(ctx, param.value.span, "Missing field name.".to_string());
+                    continue;
+                }
+            };
+            let field = match s.fields.iter().find(|f| f.name == name.value) {
+                Some(field) => field,
+                None => {
+                    self.error_at(ctx, name.span, format!("Struct has no field named '{}'.", name.value));
+                    continue;
+                }
+            };
+
+            if field.discriminant_value != NO_DISCRIMINANT {
+                object.set_bits(4, s.discriminant_offset, field.discriminant_value.into());
+            }
+            match field.kind {
+                FieldKind::Slot { offset, ref type_, ref default_value, .. } => {
+                    let type_ = substitute(type_, brand);
+                    let value = match self.compile_value(&param.value, &type_, ctx) {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    match type_.lg_data_size() {
+                        Some(lg_size) => {
+                            // Data fields are stored XORed with their defaults.
+                            self.ensure_value(default_value);
+                            let default_bits = default_value.borrow().bits().unwrap_or(0);
+                            object.set_bits(lg_size, offset, value.bits().unwrap_or(0) ^ default_bits);
+                        }
+                        None => object.pointers[offset as usize] = value.into_object(),
+                    }
+                }
+                FieldKind::Group(id) => match param.value.kind {
+                    ExpressionKind::Tuple(ref group_params) => {
+                        if let Some(group) = self.struct_node(id) {
+                            self.fill_struct(object, &group, brand, group_params, ctx);
+                        }
+                    }
+                    _ => self.error_at(ctx, param.value.span, "Type mismatch; expected group.".to_string()),
+                },
+            }
+        }
+    }
+}
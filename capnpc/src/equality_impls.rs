@@ -0,0 +1,287 @@
+// Copyright (c) 2013-2017 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Generation of the `PartialEq` and `Hash` implementations of struct readers, which compare the
+//! field values that the readers' getters return. Unlike `capnp::equality::struct_eq()`, they
+//! know the schema, so they apply the default values of pointer fields, and only look at the
+//! active member of a union.
+
+use crate::codegen::{FormattedText, GeneratorContext, camel_to_snake_case, get_field_name, get_params,
+                     snake_to_upper_case};
+use crate::codegen::FormattedText::{Indent, Line, Branch};
+use crate::codegen_types::{Leaf, RustTypeInfo, TypeParameterTexts};
+use crate::schema_capnp::{field, node, type_};
+
+/// Whether values of type `typ` can hold structs, which have to be compared through their
+/// generated implementations so that the defaults of their fields are applied.
+fn contains_structs(typ: type_::Reader) -> ::capnp::Result<bool> {
+    Ok(match typ.which()? {
+        type_::Struct(_) => true,
+        type_::List(ot) => contains_structs(ot.get_element_type()?)?,
+        _ => false,
+    })
+}
+
+/// Whether `typ` is, or is a list of, a type without a schema, which can only be compared as an
+/// untyped pointer. Such lists have no default other than null.
+fn is_untyped(typ: type_::Reader) -> ::capnp::Result<bool> {
+    Ok(match typ.which()? {
+        type_::Interface(_) | type_::AnyPointer(_) => true,
+        type_::List(ot) => is_untyped(ot.get_element_type()?)?,
+        _ => false,
+    })
+}
+
+/// Returns an expression that compares the values `a` and `b` of type `typ`, which are a text,
+/// data, struct or list reader.
+fn eq_expression(typ: type_::Reader, a: &str, b: &str) -> ::capnp::Result<String> {
+    Ok(match typ.which()? {
+        type_::List(ot) if contains_structs(typ)? => {
+            let element_type = ot.get_element_type()?;
+            let element_eq = match element_type.which()? {
+                type_::Struct(_) => "a == b".to_string(),
+                _ => format!("match (a, b) {{ (::core::result::Result::Ok(a), ::core::result::Result::Ok(b)) => {}, _ => false }}",
+                             eq_expression(element_type, "a", "b")?),
+            };
+            format!("::capnp::equality::iter_eq({}.iter(), {}.iter(), |a, b| {})", a, b, element_eq)
+        }
+        type_::List(_) =>
+            format!("::capnp::equality::list_eq(::capnp::traits::IntoInternalListReader::into_internal_list_reader({}), \
+                     ::capnp::traits::IntoInternalListReader::into_internal_list_reader({})).unwrap_or(false)", a, b),
+        _ => format!("{} == {}", a, b),
+    })
+}
+
+/// Returns a statement that feeds the value `a` of type `typ` into `state`, consistently with
+/// `eq_expression()`.
+fn hash_statement(typ: type_::Reader, a: &str) -> ::capnp::Result<String> {
+    Ok(match typ.which()? {
+        type_::List(ot) if contains_structs(typ)? => {
+            let element_type = ot.get_element_type()?;
+            let element_hash = match element_type.which()? {
+                type_::Struct(_) => "::core::hash::Hash::hash(&a, state)".to_string(),
+                _ => format!("if let ::core::result::Result::Ok(a) = a {{ {} }}", hash_statement(element_type, "a")?),
+            };
+            format!("::capnp::equality::iter_hash({}.iter(), state, |a, state| {});", a, element_hash)
+        }
+        type_::List(_) =>
+            format!("let _ = ::capnp::equality::list_hash(::capnp::traits::IntoInternalListReader::into_internal_list_reader({}), state);", a),
+        _ => format!("::core::hash::Hash::hash(&{}, state);", a),
+    })
+}
+
+/// The code that compares and hashes a field.
+enum FieldCode {
+    /// A condition under which the field differs between `self` and `other`, and a statement
+    /// that hashes the field of `self`.
+    Simple { mismatch: String, hash: String },
+
+    /// Statements that compare and hash the field, which need a block of their own.
+    Block { eq: FormattedText, hash: FormattedText },
+}
+
+/// Returns the code that compares and hashes the pointer field with index `index`, where a null
+/// pointer stands for `default`. Its values are readers of type `type_a` for `self` and `type_b`
+/// for `other`.
+fn pointer_field_code(typ: type_::Reader, index: u32, default: &str, type_a: &str, type_b: &str)
+                      -> ::capnp::Result<FieldCode>
+{
+    let get = |pointer: &str| format!(
+        "::capnp::traits::FromPointerReader::get_from_pointer(&{}, {})", pointer, default);
+    // When both pointers are null, the values are the default, and comparing them could recurse
+    // forever through a struct type that contains itself.
+    let eq = Branch(vec![
+        Line(format!("let (a, b) = (self.reader.get_pointer_field({0}), other.reader.get_pointer_field({0}));",
+                     index)),
+        Line("if !a.is_null() || !b.is_null() {".to_string()),
+        Indent(Box::new(Branch(vec![
+            Line(format!("let a: ::capnp::Result<{}> = {};", type_a, get("a"))),
+            Line(format!("let b: ::capnp::Result<{}> = {};", type_b, get("b"))),
+            Line("match (a, b) {".to_string()),
+            Indent(Box::new(Branch(vec![
+                Line(format!("(::core::result::Result::Ok(a), ::core::result::Result::Ok(b)) if {} => (),",
+                             eq_expression(typ, "a", "b")?)),
+                Line("_ => return false,".to_string()),
+            ]))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+    ]);
+    // Likewise, structs are only hashed if they differ from the default.
+    let hash = if let type_::Struct(_) = typ.which()? {
+        Branch(vec![
+            Line(format!("let a = self.reader.get_pointer_field({});", index)),
+            Line("if !a.is_null() {".to_string()),
+            Indent(Box::new(Branch(vec![
+                Line(format!("let a: ::capnp::Result<{}> = {};", type_a, get("a"))),
+                Line(format!("let default: ::capnp::Result<{}> = {};",
+                             type_a, get("::capnp::private::layout::PointerReader::new_default()"))),
+                Line("if let (::core::result::Result::Ok(a), ::core::result::Result::Ok(default)) = (a, default) {".to_string()),
+                Indent(Box::new(Line("if a != default { ::core::hash::Hash::hash(&a, state); }".to_string()))),
+                Line("}".to_string()),
+            ]))),
+            Line("}".to_string()),
+        ])
+    } else {
+        Branch(vec![
+            Line(format!("let a: ::capnp::Result<{}> = {};",
+                         type_a, get(&format!("self.reader.get_pointer_field({})", index)))),
+            Line(format!("if let ::core::result::Result::Ok(a) = a {{ {} }}", hash_statement(typ, "a")?)),
+        ])
+    };
+    Ok(FieldCode::Block { eq, hash })
+}
+
+fn field_code(gen: &GeneratorContext, field: field::Reader) -> ::capnp::Result<Option<FieldCode>> {
+    let slot = match field.which()? {
+        field::Group(group) => {
+            let params = get_params(gen, group.get_type_id())?;
+            let params_string = params.iter().map(|p| format!(",{}", p)).collect::<String>();
+            let the_mod = gen.scope_map[&group.get_type_id()].join("::");
+            let new = |name: &str, lifetime: &str, reader: &str| Line(format!(
+                "let {}: {}::Reader<{}{}> = ::capnp::traits::FromStructReader::new({}.reader);",
+                name, the_mod, lifetime, params_string, reader));
+            return Ok(Some(FieldCode::Block {
+                eq: Branch(vec![
+                    new("a", "'a", "self"),
+                    new("b", "'b", "other"),
+                    Line("if a != b { return false; }".to_string()),
+                ]),
+                hash: Branch(vec![
+                    new("a", "'a", "self"),
+                    Line("::core::hash::Hash::hash(&a, state);".to_string()),
+                ]),
+            }));
+        }
+        field::Slot(slot) => slot,
+    };
+    let typ = slot.get_type()?;
+    let offset = slot.get_offset();
+    let simple = |getter: String| Some(FieldCode::Simple {
+        mismatch: format!("self.reader.{0} != other.reader.{0}", getter),
+        hash: format!("::core::hash::Hash::hash(&self.reader.{}, state);", getter),
+    });
+    let data = |bits: &str| simple(format!("get_data_field::<{}>({})", bits, offset));
+    Ok(match typ.which()? {
+        type_::Void(()) => None,
+        type_::Bool(()) => simple(format!("get_bool_field({})", offset)),
+        type_::Int8(()) => data("i8"),
+        type_::Int16(()) => data("i16"),
+        type_::Int32(()) => data("i32"),
+        type_::Int64(()) => data("i64"),
+        type_::Uint8(()) => data("u8"),
+        type_::Uint16(()) | type_::Enum(_) => data("u16"),
+        // Floating-point values are compared by their bits, so that `NaN` equals itself.
+        type_::Uint32(()) | type_::Float32(()) => data("u32"),
+        type_::Uint64(()) | type_::Float64(()) => data("u64"),
+        _ if is_untyped(typ)? => Some(FieldCode::Simple {
+            mismatch: format!("!::capnp::equality::pointer_eq(self.reader.get_pointer_field({0}), \
+                               other.reader.get_pointer_field({0})).unwrap_or(false)", offset),
+            hash: format!("let _ = ::capnp::equality::pointer_hash(self.reader.get_pointer_field({}), state);", offset),
+        }),
+        // Text, data, lists and structs.
+        _ => {
+            // The same default as the getter's.
+            let default = if slot.get_had_explicit_default() {
+                format!("::core::option::Option::Some(&_private::DEFAULT_{}[..])",
+                        snake_to_upper_case(&camel_to_snake_case(get_field_name(field)?)))
+            } else {
+                "::core::option::Option::None".to_string()
+            };
+            Some(pointer_field_code(typ, offset, &default,
+                                    &typ.type_string(gen, Leaf::Reader("'a"))?,
+                                    &typ.type_string(gen, Leaf::Reader("'b"))?)?)
+        }
+    })
+}
+
+/// Generates the `PartialEq` and `Hash` implementations of the reader of the struct or group
+/// `node_id`.
+pub fn generate_reader_eq_and_hash(gen: &GeneratorContext,
+                                   node_id: u64,
+                                   params: &TypeParameterTexts) -> ::capnp::Result<FormattedText> {
+    let struct_reader = match gen.node_map[&node_id].which()? {
+        node::Struct(struct_reader) => struct_reader,
+        _ => return Err(::capnp::Error::failed("expected a struct node".to_string())),
+    };
+    let discriminant_offset = struct_reader.get_discriminant_offset();
+
+    let mut eq = Vec::new();
+    let mut hash = Vec::new();
+    if struct_reader.get_discriminant_count() > 0 {
+        let discriminant = format!("get_data_field::<u16>({})", discriminant_offset);
+        eq.push(Line(format!("if self.reader.{0} != other.reader.{0} {{ return false; }}", discriminant)));
+        hash.push(Line(format!("::core::hash::Hash::hash(&self.reader.{}, state);", discriminant)));
+    }
+    for field in struct_reader.get_fields()?.iter() {
+        // Members of a union are only compared if they are active.
+        let condition = if field.get_discriminant_value() != field::NO_DISCRIMINANT {
+            Some(format!("self.reader.get_data_field::<u16>({}) == {}",
+                         discriminant_offset, field.get_discriminant_value()))
+        } else {
+            None
+        };
+        match (field_code(gen, field)?, condition) {
+            (None, _) => (),
+            (Some(FieldCode::Simple { mismatch, hash: hash_statement }), None) => {
+                eq.push(Line(format!("if {} {{ return false; }}", mismatch)));
+                hash.push(Line(hash_statement));
+            }
+            (Some(FieldCode::Simple { mismatch, hash: hash_statement }), Some(condition)) => {
+                eq.push(Line(format!("if {} && {} {{ return false; }}", condition, mismatch)));
+                hash.push(Line(format!("if {} {{ {} }}", condition, hash_statement)));
+            }
+            (Some(FieldCode::Block { eq: eq_statements, hash: hash_statements }), condition) => {
+                let open = match condition {
+                    Some(condition) => format!("if {} {{", condition),
+                    None => "{".to_string(),
+                };
+                for (statements, block) in [(&mut eq, eq_statements), (&mut hash, hash_statements)] {
+                    statements.push(Line(open.clone()));
+                    statements.push(Indent(Box::new(block)));
+                    statements.push(Line("}".to_string()));
+                }
+            }
+        }
+    }
+    eq.push(Line("true".to_string()));
+    // A struct without fields compares equal to every other value of its type.
+    let (other, state) = if hash.is_empty() { ("_other", "_state") } else { ("other", "state") };
+
+    Ok(Branch(vec![
+        Line(format!("impl <'a,'b,{0}> ::core::cmp::PartialEq<Reader<'b,{0}>> for Reader<'a,{0}> {1} {{",
+                     params.params, params.where_clause)),
+        Indent(Box::new(Branch(vec![
+            Line(format!("fn eq(&self, {}: &Reader<'b,{}>) -> bool {{", other, params.params)),
+            Indent(Box::new(Branch(eq))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+        Line(format!("impl <'a,{0}> ::core::hash::Hash for Reader<'a,{0}> {1} {{",
+                     params.params, params.where_clause)),
+        Indent(Box::new(Branch(vec![
+            Line(format!("fn hash<H: ::core::hash::Hasher>(&self, {}: &mut H) {{", state)),
+            Indent(Box::new(Branch(hash))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+    ]))
+}
@@ -23,9 +23,10 @@
 //!
 //! This library allows you to do
 //! [Cap'n Proto code generation](https://capnproto.org/otherlang.html#how-to-write-compiler-plugins)
-//! within a Cargo build. You still need the `capnp` binary (implemented in C++).
+//! within a Cargo build. By default this runs the `capnp` binary (implemented in C++).
 //! (If you use a package manager, try looking for a package called
-//! `capnproto`.)
+//! `capnproto`.) Alternatively, [`CompilerCommand::native_compiler`] selects a schema
+//! compiler written in Rust, which removes the need for the binary.
 //!
 //! In your Cargo.toml:
 //!
@@ -55,6 +56,14 @@
 //!   capnp compile -orust:$OUT_DIR --src-prefix=schema schema/foo.capnp schema/bar.capnp
 //! ```
 //!
+//! The generated code for each file then goes in a module of your crate. Alternatively,
+//! [`CompilerCommand::entrypoint`] generates a file that declares all of these modules, so that
+//! the crate only needs to include that file at its root:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/capnp_generated.rs"));
+//! ```
+//!
 
 /// Code generated from
 /// [schema.capnp](https://github.com/capnproto/capnproto/blob/master/c%2B%2B/src/capnp/schema.capnp).
@@ -62,7 +71,18 @@ pub mod schema_capnp;
 
 pub mod codegen;
 pub mod codegen_types;
+pub mod compatibility;
+pub mod compiler;
+mod annotation_constants;
+mod attributes;
+mod equality_impls;
+mod module_tree;
+mod node_selection;
+mod plain_structs;
 mod pointer_constants;
+mod random_impls;
+mod serde_impls;
+mod unknown_variants;
 
 use std::path::{Path, PathBuf};
 
@@ -106,6 +126,18 @@ pub struct CompilerCommand {
     output_path: Option<PathBuf>,
     default_parent_module: Vec<String>,
     raw_code_generator_request_path: Option<PathBuf>,
+    native_compiler: bool,
+    plain_structs: bool,
+    serde: bool,
+    reader_equality: bool,
+    unknown_variants: bool,
+    only_nodes: Vec<String>,
+    skip_interfaces: bool,
+    skip_structs: bool,
+    async_servers: bool,
+    annotation_constants: bool,
+    random_generators: bool,
+    entrypoint: Option<PathBuf>,
 }
 
 impl CompilerCommand {
@@ -120,6 +152,18 @@ impl CompilerCommand {
             output_path: None,
             default_parent_module: Vec::new(),
             raw_code_generator_request_path: None,
+            native_compiler: false,
+            plain_structs: false,
+            serde: false,
+            reader_equality: false,
+            unknown_variants: false,
+            only_nodes: Vec::new(),
+            skip_interfaces: false,
+            skip_structs: false,
+            async_servers: false,
+            annotation_constants: false,
+            random_generators: false,
+            entrypoint: None,
         }
     }
 
@@ -212,6 +256,96 @@ impl CompilerCommand {
         self
     }
 
+    /// Compiles the schema files with the schema compiler in the [`compiler`] module instead of
+    /// the `capnp` executable. It produces the same schema nodes as `capnp`, which the tests of
+    /// this crate check where `capnp` is installed, so the generated code is the same either way.
+    /// Source information, such as doc comments, is not compared and may differ.
+    pub fn native_compiler(&mut self) -> &mut CompilerCommand {
+        self.native_compiler = true;
+        self
+    }
+
+    /// Sets whether to generate plain owned structs alongside readers and builders.
+    /// See [`codegen::CodeGenerationCommand::plain_structs`].
+    pub fn plain_structs(&mut self, plain_structs: bool) -> &mut CompilerCommand {
+        self.plain_structs = plain_structs;
+        self
+    }
+
+    /// Sets whether to generate serde support for readers and builders.
+    /// See [`codegen::CodeGenerationCommand::serde`].
+    pub fn serde(&mut self, serde: bool) -> &mut CompilerCommand {
+        self.serde = serde;
+        self
+    }
+
+    /// Sets whether to implement `PartialEq` and `Hash` for struct readers.
+    /// See [`codegen::CodeGenerationCommand::reader_equality`].
+    pub fn reader_equality(&mut self, reader_equality: bool) -> &mut CompilerCommand {
+        self.reader_equality = reader_equality;
+        self
+    }
+
+    /// Sets whether to give enums and unions an `Unknown(u16)` variant for values that are not
+    /// in the schema. See [`codegen::CodeGenerationCommand::unknown_variants`].
+    pub fn unknown_variants(&mut self, unknown_variants: bool) -> &mut CompilerCommand {
+        self.unknown_variants = unknown_variants;
+        self
+    }
+
+    /// Restricts code generation to the given nodes and their dependencies.
+    /// See [`codegen::CodeGenerationCommand::only_nodes`].
+    pub fn only_nodes(&mut self, only_nodes: Vec<String>) -> &mut CompilerCommand {
+        self.only_nodes = only_nodes;
+        self
+    }
+
+    /// Sets whether to skip generating code for interfaces.
+    /// See [`codegen::CodeGenerationCommand::skip_interfaces`].
+    pub fn skip_interfaces(&mut self, skip_interfaces: bool) -> &mut CompilerCommand {
+        self.skip_interfaces = skip_interfaces;
+        self
+    }
+
+    /// Sets whether to skip generating code for structs, enums and constants.
+    /// See [`codegen::CodeGenerationCommand::skip_structs`].
+    pub fn skip_structs(&mut self, skip_structs: bool) -> &mut CompilerCommand {
+        self.skip_structs = skip_structs;
+        self
+    }
+
+    /// Sets whether to generate `AsyncServer` traits, whose methods return futures.
+    /// See [`codegen::CodeGenerationCommand::async_servers`].
+    pub fn async_servers(&mut self, async_servers: bool) -> &mut CompilerCommand {
+        self.async_servers = async_servers;
+        self
+    }
+
+    /// Sets whether to generate constants holding the values of annotations on schema items.
+    /// See [`codegen::CodeGenerationCommand::annotation_constants`].
+    pub fn annotation_constants(&mut self, annotation_constants: bool) -> &mut CompilerCommand {
+        self.annotation_constants = annotation_constants;
+        self
+    }
+
+    /// Sets whether to generate random value generators for structs.
+    /// See [`codegen::CodeGenerationCommand::random_generators`].
+    pub fn random_generators(&mut self, random_generators: bool) -> &mut CompilerCommand {
+        self.random_generators = random_generators;
+        self
+    }
+
+    /// Sets the name of an entrypoint file to generate, which declares the module tree of the
+    /// generated code and is meant to be included at the root of the crate.
+    /// See [`codegen::CodeGenerationCommand::entrypoint`].
+    pub fn entrypoint<P>(&mut self, file_name: P) -> &mut CompilerCommand
+    where
+        P: AsRef<Path>,
+    {
+        self.entrypoint = Some(file_name.as_ref().to_path_buf());
+        self
+    }
+
     /// Runs the command.
     /// Returns an error if `OUT_DIR` or a custom output directory was not set, or if `capnp compile` fails.
     pub fn run(&mut self) -> ::capnp::Result<()> {
@@ -274,10 +408,35 @@ impl CompilerCommand {
         let mut code_generation_command = crate::codegen::CodeGenerationCommand::new();
         code_generation_command
             .output_directory(output_path)
-            .default_parent_module(self.default_parent_module.clone());
+            .default_parent_module(self.default_parent_module.clone())
+            .plain_structs(self.plain_structs)
+            .serde(self.serde)
+            .reader_equality(self.reader_equality)
+            .unknown_variants(self.unknown_variants)
+            .only_nodes(self.only_nodes.clone())
+            .skip_interfaces(self.skip_interfaces)
+            .skip_structs(self.skip_structs)
+            .async_servers(self.async_servers)
+            .annotation_constants(self.annotation_constants)
+            .random_generators(self.random_generators);
         if let Some(raw_code_generator_request_path) = &self.raw_code_generator_request_path {
             code_generation_command.raw_code_generator_request_path(raw_code_generator_request_path.clone());
         }
+        if let Some(entrypoint) = &self.entrypoint {
+            code_generation_command.entrypoint(entrypoint);
+        }
+
+        if self.native_compiler {
+            let mut import_paths = self.import_paths.clone();
+            if !self.no_standard_import {
+                import_paths.push(PathBuf::from("/usr/local/include"));
+                import_paths.push(PathBuf::from("/usr/include"));
+            }
+            let message = compiler::compile(&self.files, &self.src_prefixes, &import_paths)?;
+            let mut bytes = Vec::new();
+            capnp::serialize::write_message(&mut bytes, &message)?;
+            return code_generation_command.run(&bytes[..]);
+        }
 
         run_command(command, code_generation_command).map_err(|error| {
             ::capnp::Error::failed(format!(
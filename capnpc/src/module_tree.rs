@@ -0,0 +1,81 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Generation of an entrypoint file that declares the module tree of the generated code, so that
+//! a crate can include all of it with a single `include!()`.
+
+use std::collections::BTreeMap;
+
+use crate::codegen::{FormattedText, GeneratorContext};
+use crate::codegen::FormattedText::{Indent, Line, Branch, BlankLine};
+
+/// A module of the tree, holding the generated file that is included in it, if any, and the
+/// modules nested in it.
+#[derive(Default)]
+struct Module {
+    include: Option<String>,
+    children: BTreeMap<String, Module>,
+}
+
+impl Module {
+    fn lines(&self) -> Vec<FormattedText> {
+        let mut lines = Vec::new();
+        if let Some(path) = &self.include {
+            lines.push(Line(format!("include!(\"{}\");", path.escape_default())));
+        }
+        for (name, child) in &self.children {
+            lines.push(Line(format!("pub mod {} {{", name)));
+            lines.push(Indent(Box::new(Branch(child.lines()))));
+            lines.push(Line("}".to_string()));
+        }
+        lines
+    }
+}
+
+/// Generates the entrypoint for the requested files, given as pairs of a file id and the path of
+/// the generated file relative to the entrypoint. The entrypoint declares the module in which
+/// the code for each file expects to live, according to the default parent module and the
+/// `parentModule` annotations, and so is meant to be included at the root of the crate.
+pub fn generate_module_tree(gen: &GeneratorContext, files: &[(u64, String)]) -> ::capnp::Result<FormattedText> {
+    let mut root = Module::default();
+    for &(id, ref path) in files {
+        let scope = &gen.scope_map[&id];
+        let mut module = &mut root;
+        // The first name of a scope is always `crate`.
+        for name in &scope[1..] {
+            module = module.children.entry(name.clone()).or_insert_with(Module::default);
+        }
+        if module.include.is_some() {
+            return Err(::capnp::Error::failed(format!(
+                "{} and another file are generated into the same module {}", path, scope.join("::"))));
+        }
+        module.include = Some(path.clone());
+    }
+
+    Ok(Branch(vec![
+        Line("// @generated by the capnpc-rust plugin to the Cap'n Proto schema compiler.".to_string()),
+        Line("// DO NOT EDIT.".to_string()),
+        Line("// Include this file at the root of the crate.".to_string()),
+        BlankLine,
+        Branch(root.lines()),
+    ]))
+}
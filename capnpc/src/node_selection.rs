@@ -0,0 +1,212 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Selection of the nodes to generate code for, when code generation is restricted by
+//! `CodeGenerationCommand::only_nodes()`, `skip_interfaces()` or `skip_structs()`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::codegen::{Reached, walk_scope};
+use crate::schema_capnp::{brand, field, node, type_};
+
+/// The nodes to generate code for.
+pub struct NodeSelection {
+    /// Nodes that get their full generated code.
+    pub generated: HashSet<u64>,
+
+    /// Nodes that are not in `generated` but have nested nodes that are, and so get a module
+    /// holding only those nested nodes.
+    pub containers: HashSet<u64>,
+}
+
+struct Closure<'a, 'b> {
+    node_map: &'b HashMap<u64, node::Reader<'a>>,
+    generated: HashSet<u64>,
+    pending: Vec<u64>,
+}
+
+impl <'a, 'b> Closure<'a, 'b> {
+    fn add(&mut self, node_id: u64) {
+        if self.node_map.contains_key(&node_id) && self.generated.insert(node_id) {
+            self.pending.push(node_id);
+        }
+    }
+
+    fn add_brand(&mut self, brand: brand::Reader) -> ::capnp::Result<()> {
+        for scope in brand.get_scopes()?.iter() {
+            if let brand::scope::Bind(bindings) = scope.which()? {
+                for binding in bindings?.iter() {
+                    if let brand::binding::Type(t) = binding.which()? {
+                        self.add_type(t?)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_type(&mut self, typ: type_::Reader) -> ::capnp::Result<()> {
+        match typ.which()? {
+            type_::Struct(st) => {
+                self.add(st.get_type_id());
+                self.add_brand(st.get_brand()?)?;
+            }
+            type_::Enum(en) => {
+                self.add(en.get_type_id());
+                self.add_brand(en.get_brand()?)?;
+            }
+            type_::Interface(interface) => {
+                self.add(interface.get_type_id());
+                self.add_brand(interface.get_brand()?)?;
+            }
+            type_::List(ot) => self.add_type(ot.get_element_type()?)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Adds the nodes that the generated code of `node_id` refers to.
+    fn add_dependencies(&mut self, node_id: u64) -> ::capnp::Result<()> {
+        match self.node_map[&node_id].which()? {
+            node::Struct(struct_reader) => {
+                for field in struct_reader.get_fields()?.iter() {
+                    match field.which()? {
+                        field::Slot(slot) => self.add_type(slot.get_type()?)?,
+                        field::Group(group) => self.add(group.get_type_id()),
+                    }
+                }
+            }
+            node::Interface(interface) => {
+                for superclass in interface.get_superclasses()?.iter() {
+                    self.add(superclass.get_id());
+                    self.add_brand(superclass.get_brand()?)?;
+                }
+                for method in interface.get_methods()?.iter() {
+                    self.add(method.get_param_struct_type());
+                    self.add_brand(method.get_param_brand()?)?;
+                    self.add(method.get_result_struct_type());
+                    self.add_brand(method.get_result_brand()?)?;
+                }
+            }
+            node::Const(c) => self.add_type(c.get_type()?)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Finds the nodes that `name` refers to: an ID such as `"0xa4c9e2d1b3f70e85"`, a display name
+/// such as `"foo.capnp:Foo.Bar"`, or a name within its file such as `"Foo.Bar"`.
+fn resolve(node_map: &HashMap<u64, node::Reader>, name: &str) -> ::capnp::Result<u64> {
+    if name.starts_with("0x") {
+        let (_, hex) = name.split_at(2);
+        return match u64::from_str_radix(hex, 16) {
+            Ok(id) if node_map.contains_key(&id) => Ok(id),
+            _ => Err(::capnp::Error::failed(format!("no node with ID {}", name))),
+        };
+    }
+    let mut matches = Vec::new();
+    for (&id, node) in node_map {
+        let display_name = node.get_display_name()?;
+        let matched = if name.contains(':') {
+            display_name == name
+        } else {
+            display_name.find(':').map(|colon| &display_name[colon + 1..]) == Some(name)
+        };
+        if matched {
+            matches.push(id);
+        }
+    }
+    match matches.len() {
+        0 => Err(::capnp::Error::failed(format!("no node named {}", name))),
+        1 => Ok(matches[0]),
+        _ => Err(::capnp::Error::failed(format!(
+            "{} is ambiguous; use the display name or ID of the node instead", name))),
+    }
+}
+
+/// Determines the nodes to generate code for. Returns `None` if code should be generated for
+/// everything in the requested files.
+///
+/// The roots of the selection are the nodes named in `only`, or if it is empty, all nodes of
+/// the requested files. Interfaces are removed from the roots if `skip_interfaces` is set, and
+/// structs, enums and constants if `skip_structs` is set. The selection consists of the roots
+/// and everything that their generated code depends on, whatever its kind.
+pub fn select_nodes(request: crate::schema_capnp::code_generator_request::Reader,
+                    node_map: &HashMap<u64, node::Reader>,
+                    only: &[String],
+                    skip_interfaces: bool,
+                    skip_structs: bool) -> ::capnp::Result<Option<NodeSelection>> {
+    if only.is_empty() && !skip_interfaces && !skip_structs {
+        return Ok(None);
+    }
+
+    let mut roots = Vec::new();
+    if only.is_empty() {
+        for requested_file in request.get_requested_files()?.iter() {
+            // Groups and the implicit structs of methods come along with the node whose scope
+            // they are in.
+            walk_scope(node_map, requested_file.get_id(), &(), &mut |id, _, reached, _| {
+                if reached != Reached::Nested {
+                    return Ok(None);
+                }
+                roots.push(id);
+                Ok(Some(()))
+            })?;
+        }
+    } else {
+        for name in only {
+            roots.push(resolve(node_map, name)?);
+        }
+    }
+
+    let mut closure = Closure { node_map, generated: HashSet::new(), pending: Vec::new() };
+    for id in roots {
+        let skipped = match node_map[&id].which()? {
+            node::Interface(_) => skip_interfaces,
+            node::Struct(_) | node::Enum(_) | node::Const(_) => skip_structs,
+            _ => false,
+        };
+        if !skipped {
+            closure.add(id);
+        }
+    }
+    while let Some(id) = closure.pending.pop() {
+        closure.add_dependencies(id)?;
+    }
+
+    let mut containers = HashSet::new();
+    for &id in &closure.generated {
+        let mut scope_id = node_map[&id].get_scope_id();
+        while let Some(scope) = node_map.get(&scope_id) {
+            if let node::File(()) = scope.which()? {
+                break
+            }
+            if !closure.generated.contains(&scope_id) {
+                containers.insert(scope_id);
+            }
+            scope_id = scope.get_scope_id();
+        }
+    }
+
+    Ok(Some(NodeSelection { generated: closure.generated, containers }))
+}
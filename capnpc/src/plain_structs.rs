@@ -0,0 +1,415 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Generation of plain owned structs, which hold a copy of a struct's contents in ordinary
+//! Rust types (`String`, `Vec<T>`, enums for unions) and convert from a `Reader` and into a
+//! `Builder`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::attributes::{PLAIN_DERIVES, derive_line};
+use crate::codegen::{FormattedText, GeneratorContext, bool_annotation,
+                     camel_to_snake_case, capitalize_first_letter, get_field_name, module_name, walk_scope};
+use crate::codegen::FormattedText::{Indent, Line, Branch, BlankLine};
+use crate::codegen_types::RustTypeInfo;
+use crate::schema_capnp::{field, node, type_};
+
+const PLAIN_ANNOTATION_ID: u64 = 0xd19732bac47f3211;
+
+fn is_supported_type(selected: &HashSet<u64>, typ: type_::Reader) -> ::capnp::Result<bool> {
+    Ok(match typ.which()? {
+        type_::Struct(st) => selected.contains(&st.get_type_id()),
+        type_::List(ot) => is_supported_type(selected, ot.get_element_type()?)?,
+        type_::Interface(_) | type_::AnyPointer(_) => false,
+        _ => true,
+    })
+}
+
+/// Returns a description of why the struct `node_id` cannot have a plain struct, given that
+/// the structs in `selected` do.
+fn unsupported_reason(node_map: &HashMap<u64, node::Reader>,
+                      selected: &HashSet<u64>,
+                      node_id: u64) -> ::capnp::Result<Option<String>> {
+    let node_reader = &node_map[&node_id];
+    if node_reader.get_is_generic() {
+        return Ok(Some("generic structs are not supported".to_string()));
+    }
+    if let node::Struct(struct_reader) = node_reader.which()? {
+        for field in struct_reader.get_fields()?.iter() {
+            let supported = match field.which()? {
+                field::Slot(slot) => is_supported_type(selected, slot.get_type()?)?,
+                field::Group(group) => selected.contains(&group.get_type_id()),
+            };
+            if !supported {
+                return Ok(Some(format!("field `{}` has no plain representation", field.get_name()?)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Determines which structs of the requested files get a plain struct. `default` applies to
+/// everything that is not covered by a `$Rust.plain` annotation. Structs that cannot be
+/// represented (because they are generic, or contain interfaces, `AnyPointer`s or structs
+/// without a plain struct) are dropped, or reported as an error if they were explicitly
+/// annotated.
+pub fn select_plain_structs(request: crate::schema_capnp::code_generator_request::Reader,
+                            node_map: &HashMap<u64, node::Reader>,
+                            default: bool) -> ::capnp::Result<HashSet<u64>> {
+    let mut wanted = HashMap::new();
+    let mut explicit = HashSet::new();
+    for requested_file in request.get_requested_files()?.iter() {
+        let id = requested_file.get_id();
+        let file_enabled = match node_map.get(&id) {
+            Some(node) => bool_annotation(node.get_annotations()?, PLAIN_ANNOTATION_ID, "plain")?.unwrap_or(default),
+            None => continue,
+        };
+        // Walks the structs of the file, recording for each whether a plain struct is wanted,
+        // and collecting those that explicitly asked for one into `explicit`.
+        walk_scope(node_map, id, &file_enabled, &mut |id, node_reader, _, &inherited| {
+            let annotated = bool_annotation(node_reader.get_annotations()?, PLAIN_ANNOTATION_ID, "plain")?;
+            let enabled = annotated.unwrap_or(inherited);
+            if let node::Struct(_) = node_reader.which()? {
+                wanted.insert(id, enabled);
+                if annotated == Some(true) {
+                    explicit.insert(id);
+                }
+            }
+            Ok(Some(enabled))
+        })?;
+    }
+
+    let mut selected: HashSet<u64> =
+        wanted.into_iter().filter(|&(_, enabled)| enabled).map(|(id, _)| id).collect();
+    loop {
+        let mut dropped = Vec::new();
+        for &id in &selected {
+            if let Some(reason) = unsupported_reason(node_map, &selected, id)? {
+                if explicit.contains(&id) {
+                    return Err(::capnp::Error::failed(format!(
+                        "cannot generate a plain struct for {}: {}",
+                        node_map[&id].get_display_name()?, reason)));
+                }
+                dropped.push(id);
+            }
+        }
+        if dropped.is_empty() {
+            return Ok(selected);
+        }
+        for id in dropped {
+            selected.remove(&id);
+        }
+    }
+}
+
+fn plain_type(gen: &GeneratorContext, typ: type_::Reader) -> ::capnp::Result<String> {
+    Ok(match typ.which()? {
+        type_::Void(()) => "()".to_string(),
+        type_::Bool(()) => "bool".to_string(),
+        type_::Int8(()) => "i8".to_string(),
+        type_::Int16(()) => "i16".to_string(),
+        type_::Int32(()) => "i32".to_string(),
+        type_::Int64(()) => "i64".to_string(),
+        type_::Uint8(()) => "u8".to_string(),
+        type_::Uint16(()) => "u16".to_string(),
+        type_::Uint32(()) => "u32".to_string(),
+        type_::Uint64(()) => "u64".to_string(),
+        type_::Float32(()) => "f32".to_string(),
+        type_::Float64(()) => "f64".to_string(),
+        type_::Text(()) => "::capnp::private::alloc::String".to_string(),
+        type_::Data(()) => "::capnp::private::alloc::Vec<u8>".to_string(),
+        type_::Enum(en) => gen.scope_map[&en.get_type_id()].join("::"),
+        type_::Struct(st) => format!("{}::Plain", gen.scope_map[&st.get_type_id()].join("::")),
+        type_::List(ot) => format!("::capnp::private::alloc::Vec<{}>",
+                                   plain_type(gen, ot.get_element_type()?)?),
+        type_::Interface(_) | type_::AnyPointer(_) =>
+            return Err(::capnp::Error::failed("type has no plain representation".to_string())),
+    })
+}
+
+/// Converts `value`, a value of type `typ` as returned by a reader (with any `Result`
+/// already unwrapped), into its plain representation.
+fn read_value(gen: &GeneratorContext, typ: type_::Reader, value: &str, depth: usize)
+              -> ::capnp::Result<String>
+{
+    Ok(match typ.which()? {
+        type_::Text(()) => format!("::capnp::private::alloc::String::from({})", value),
+        type_::Data(()) => format!("{}.to_vec()", value),
+        type_::Struct(st) => format!("{}::Plain::from_reader({})?",
+                                     gen.scope_map[&st.get_type_id()].join("::"), value),
+        type_::List(ot) => {
+            let element_type = ot.get_element_type()?;
+            let element = format!("e{}", depth);
+            let unwrapped = match element_type.which()? {
+                type_::Enum(_) | type_::Text(()) | type_::Data(()) | type_::List(_) => format!("{}?", element),
+                type_::Struct(_) => element.clone(),
+                _ => return Ok(format!("{}.iter().collect::<::capnp::private::alloc::Vec<_>>()", value)),
+            };
+            format!("{}.iter().map(|{}| -> ::capnp::Result<_> {{ ::core::result::Result::Ok({}) }}).collect::<::capnp::Result<::capnp::private::alloc::Vec<_>>>()?",
+                    value, element, read_value(gen, element_type, &unwrapped, depth + 1)?)
+        }
+        _ => value.to_string(),
+    })
+}
+
+/// Returns statements that fill `list`, a freshly initialized list builder, with the elements
+/// of `elements`, a reference to a `Vec` of plain values of type `element_type`.
+fn write_list(element_type: type_::Reader, list: &str, elements: &str, depth: usize)
+              -> ::capnp::Result<FormattedText>
+{
+    let index = format!("i{}", depth);
+    let element = format!("v{}", depth);
+    let body = match element_type.which()? {
+        type_::Void(()) => return Ok(Branch(Vec::new())),
+        type_::Text(()) | type_::Data(()) =>
+            Line(format!("{}.set({} as u32, {});", list, index, element)),
+        type_::Struct(_) =>
+            Line(format!("{}.write_into({}.reborrow().get({} as u32))?;", element, list, index)),
+        type_::List(ot) => {
+            let inner_type = ot.get_element_type()?;
+            let init = format!("{}.reborrow().init({} as u32, {}.len() as u32)", list, index, element);
+            if let type_::Void(()) = inner_type.which()? {
+                Line(format!("{};", init))
+            } else {
+                let inner = format!("l{}", depth + 1);
+                Branch(vec![
+                    Line(format!("let mut {} = {};", inner, init)),
+                    write_list(inner_type, &inner, &element, depth + 1)?,
+                ])
+            }
+        }
+        _ => Line(format!("{}.set({} as u32, *{});", list, index, element)),
+    };
+    Ok(Branch(vec![
+        Line(format!("for ({}, {}) in {}.iter().enumerate() {{", index, element, elements)),
+        Indent(Box::new(body)),
+        Line("}".to_string()),
+    ]))
+}
+
+/// Returns statements that write a plain value of type `typ` into `builder` through the
+/// setter or initializer of the field `styled_name`. The value is passed as three expressions:
+/// the value itself, a reference to it, and a form that is usable as a method receiver.
+fn write_value(typ: type_::Reader, styled_name: &str,
+               value: &str, reference: &str, receiver: &str) -> ::capnp::Result<FormattedText>
+{
+    Ok(match typ.which()? {
+        type_::Text(()) | type_::Data(()) =>
+            Line(format!("builder.set_{}({});", styled_name, reference)),
+        type_::Struct(_) =>
+            Line(format!("{}.write_into(builder.reborrow().init_{}())?;", receiver, styled_name)),
+        type_::List(ot) => {
+            let element_type = ot.get_element_type()?;
+            let init = format!("builder.reborrow().init_{}({}.len() as u32)", styled_name, receiver);
+            if let type_::Void(()) = element_type.which()? {
+                Line(format!("{};", init))
+            } else {
+                Branch(vec![
+                    Line("{".to_string()),
+                    Indent(Box::new(Branch(vec![
+                        Line(format!("let mut l0 = {};", init)),
+                        write_list(element_type, "l0", receiver, 0)?,
+                    ]))),
+                    Line("}".to_string()),
+                ])
+            }
+        }
+        _ => Line(format!("builder.set_{}({});", styled_name, value)),
+    })
+}
+
+/// Generates the `Plain` struct (and `PlainWhich` enum, for a struct with a union) of the
+/// struct `node_id`. The output goes in the module of the struct, next to its `Reader` and
+/// `Builder`.
+pub fn generate_plain_struct(gen: &GeneratorContext, node_id: u64) -> ::capnp::Result<FormattedText> {
+    let struct_reader = match gen.node_map[&node_id].which()? {
+        node::Struct(struct_reader) => struct_reader,
+        _ => return Err(::capnp::Error::failed("expected a struct node".to_string())),
+    };
+
+    let mut members = Vec::new();
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    let mut variants = Vec::new();
+    let mut read_arms = Vec::new();
+    let mut write_arms = Vec::new();
+
+    for field in struct_reader.get_fields()?.iter() {
+        let name = get_field_name(field)?;
+        let styled_name = camel_to_snake_case(name);
+        let member = module_name(name);
+
+        if field.get_discriminant_value() == field::NO_DISCRIMINANT {
+            match field.which()? {
+                field::Group(group) => {
+                    let path = gen.scope_map[&group.get_type_id()].join("::");
+                    members.push(Line(format!("pub {}: {}::Plain,", member, path)));
+                    reads.push(Line(format!("{}: {}::Plain::from_reader(reader.get_{}())?,",
+                                            member, path, styled_name)));
+                    writes.push(Line(format!("self.{}.write_into(builder.reborrow().init_{}())?;",
+                                             member, styled_name)));
+                }
+                field::Slot(slot) => {
+                    let typ = slot.get_type()?;
+                    match typ.which()? {
+                        type_::Void(()) => continue,
+                        type_::Struct(_) => {
+                            members.push(Line(format!("pub {}: ::core::option::Option<::capnp::private::alloc::Box<{}>>,",
+                                                      member, plain_type(gen, typ)?)));
+                            reads.push(Branch(vec![
+                                Line(format!("{}: if reader.has_{}() {{", member, styled_name)),
+                                Indent(Box::new(Line(format!(
+                                    "::core::option::Option::Some(::capnp::private::alloc::Box::new({}))",
+                                    read_value(gen, typ, &format!("reader.get_{}()?", styled_name), 0)?)))),
+                                Line("} else {".to_string()),
+                                Indent(Box::new(Line("::core::option::Option::None".to_string()))),
+                                Line("},".to_string()),
+                            ]));
+                            writes.push(Branch(vec![
+                                Line(format!("if let ::core::option::Option::Some(ref v) = self.{} {{", member)),
+                                Indent(Box::new(write_value(typ, &styled_name, "**v", "v", "v")?)),
+                                Line("}".to_string()),
+                            ]));
+                        }
+                        _ => {
+                            members.push(Line(format!("pub {}: {},", member, plain_type(gen, typ)?)));
+                            let getter = if typ.is_prim()? {
+                                format!("reader.get_{}()", styled_name)
+                            } else {
+                                format!("reader.get_{}()?", styled_name)
+                            };
+                            reads.push(Line(format!("{}: {},", member, read_value(gen, typ, &getter, 0)?)));
+                            writes.push(write_value(typ, &styled_name,
+                                                    &format!("self.{}", member),
+                                                    &format!("&self.{}", member),
+                                                    &format!("self.{}", member))?);
+                        }
+                    }
+                }
+            }
+        } else {
+            let variant = capitalize_first_letter(name);
+            let (payload, read, write) = match field.which()? {
+                field::Group(group) => {
+                    let path = gen.scope_map[&group.get_type_id()].join("::");
+                    (Some(format!("{}::Plain", path)),
+                     format!("{}::Plain::from_reader(v)?", path),
+                     Line(format!("v.write_into(builder.reborrow().init_{}())?;", styled_name)))
+                }
+                field::Slot(slot) => {
+                    let typ = slot.get_type()?;
+                    match typ.which()? {
+                        type_::Void(()) =>
+                            (None, String::new(), Line(format!("builder.set_{}(());", styled_name))),
+                        type_::Struct(_) =>
+                            (Some(format!("::capnp::private::alloc::Box<{}>", plain_type(gen, typ)?)),
+                             format!("::capnp::private::alloc::Box::new({})", read_value(gen, typ, "v?", 0)?),
+                             write_value(typ, &styled_name, "**v", "v", "v")?),
+                        _ => {
+                            let unwrapped = if typ.is_prim()? { "v" } else { "v?" };
+                            (Some(plain_type(gen, typ)?),
+                             read_value(gen, typ, unwrapped, 0)?,
+                             write_value(typ, &styled_name, "*v", "v", "v")?)
+                        }
+                    }
+                }
+            };
+            match payload {
+                None => {
+                    variants.push(Line(format!("{},", variant)));
+                    read_arms.push(Line(format!("Which::{}(()) => PlainWhich::{},", variant, variant)));
+                    write_arms.push(Branch(vec![
+                        Line(format!("PlainWhich::{} => {{", variant)),
+                        Indent(Box::new(write)),
+                        Line("}".to_string()),
+                    ]));
+                }
+                Some(payload) => {
+                    variants.push(Line(format!("{}({}),", variant, payload)));
+                    read_arms.push(Line(format!("Which::{}(v) => PlainWhich::{}({}),", variant, variant, read)));
+                    write_arms.push(Branch(vec![
+                        Line(format!("PlainWhich::{}(ref v) => {{", variant)),
+                        Indent(Box::new(write)),
+                        Line("}".to_string()),
+                    ]));
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    if struct_reader.get_discriminant_count() > 0 {
+        if gen.unknown_variants.contains(&node_id) {
+            variants.push(Line("Unknown(u16),".to_string()));
+            read_arms.push(Line("Which::Unknown(d) => PlainWhich::Unknown(d),".to_string()));
+            write_arms.push(Line("PlainWhich::Unknown(d) => builder.set_which_unknown(d),".to_string()));
+        }
+        members.push(Line("pub which: PlainWhich,".to_string()));
+        reads.push(Branch(vec![
+            Line("which: match reader.which()? {".to_string()),
+            Indent(Box::new(Branch(read_arms))),
+            Line("},".to_string()),
+        ]));
+        writes.push(Branch(vec![
+            Line("match self.which {".to_string()),
+            Indent(Box::new(Branch(write_arms))),
+            Line("}".to_string()),
+        ]));
+        result.push(Branch(vec![
+            BlankLine,
+            derive_line(PLAIN_DERIVES, gen.derives.get(&node_id)),
+            Line("pub enum PlainWhich {".to_string()),
+            Indent(Box::new(Branch(variants))),
+            Line("}".to_string()),
+        ]));
+    }
+
+    let reader_name = if reads.is_empty() { "_reader" } else { "reader" };
+    let builder_name = if writes.is_empty() { "_builder" } else { "mut builder" };
+    result.insert(0, Branch(vec![
+        BlankLine,
+        derive_line(PLAIN_DERIVES, gen.derives.get(&node_id)),
+        Line("pub struct Plain {".to_string()),
+        Indent(Box::new(Branch(members))),
+        Line("}".to_string()),
+        BlankLine,
+        Line("impl Plain {".to_string()),
+        Indent(Box::new(Branch(vec![
+            Line(format!("pub fn from_reader({}: Reader<'_>) -> ::capnp::Result<Plain> {{", reader_name)),
+            Indent(Box::new(Branch(vec![
+                Line("::core::result::Result::Ok(Plain {".to_string()),
+                Indent(Box::new(Branch(reads))),
+                Line("})".to_string()),
+            ]))),
+            Line("}".to_string()),
+            BlankLine,
+            Line(format!("pub fn write_into(&self, {}: Builder<'_>) -> ::capnp::Result<()> {{", builder_name)),
+            Indent(Box::new(Branch(vec![
+                Branch(writes),
+                Line("::core::result::Result::Ok(())".to_string()),
+            ]))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+    ]));
+    Ok(Branch(result))
+}
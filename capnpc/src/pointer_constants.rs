@@ -33,12 +33,29 @@ pub struct WordArrayDeclarationOptions {
 pub fn word_array_declaration(name: &str,
                               value: any_pointer::Reader,
                               options: WordArrayDeclarationOptions) -> ::capnp::Result<FormattedText> {
+    let (words_lines, word_count) = word_lines(value, options.omit_first_word)?;
+    let vis = if options.public { "pub " } else { "" };
+    Ok(Branch(vec![
+        Line(format!("{}static {}: [capnp::Word; {}] = [", vis, name, word_count)),
+        Indent(Box::new(Branch(words_lines))),
+        Line("];".to_string())
+    ]))
+}
+
+/// Formats `value` as `capnp::word()` lines, preceded by a root pointer unless
+/// `omit_first_word` is set. Also returns the number of lines.
+fn word_lines(value: any_pointer::Reader, omit_first_word: bool) -> ::capnp::Result<(Vec<FormattedText>, usize)> {
     let allocator = message::HeapAllocator::new()
         .first_segment_words(value.target_size()?.word_count as u32 + 1);
     let mut message = message::Builder::new(allocator);
     message.set_root(value)?;
-    let mut words = message.get_segments_for_output()[0];
-    if options.omit_first_word { words = &words[8..] }
+    let segments = message.get_segments_for_output();
+    if segments.len() != 1 {
+        return Err(::capnp::Error::failed(
+            format!("expected constant to fit in one segment, but it took {}", segments.len())));
+    }
+    let mut words = segments[0];
+    if omit_first_word { words = &words[8..] }
     let mut words_lines = Vec::new();
     for index in 0..(words.len() / 8) {
         let bytes = &words[(index * 8)..(index +1)*8];
@@ -47,15 +64,12 @@ pub fn word_array_declaration(name: &str,
                     bytes[0], bytes[1], bytes[2], bytes[3],
                     bytes[4], bytes[5], bytes[6], bytes[7])));
     }
-
-    let vis = if options.public { "pub " } else { "" };
-    Ok(Branch(vec![
-        Line(format!("{}static {}: [capnp::Word; {}] = [", vis, name, words.len() / 8)),
-        Indent(Box::new(Branch(words_lines))),
-        Line("];".to_string())
-    ]))
+    Ok((words_lines, words.len() / 8))
 }
 
+/// Generates a `pub const` holding a `::capnp::constant::Reader` whose words are a copy of
+/// `value`. Being a `const` rather than a `static`, it can also be used to initialize other
+/// constants and statics.
 pub fn generate_pointer_constant(
     gen: &GeneratorContext,
     styled_name: &str,
@@ -63,17 +77,15 @@ pub fn generate_pointer_constant(
     value: any_pointer::Reader)
     -> ::capnp::Result<FormattedText>
 {
+    let (words_lines, _) = word_lines(value, false)?;
     Ok(Branch(vec![
-        Line(format!("pub static {}: ::capnp::constant::Reader<{}> = {{",
+        Line(format!("pub const {}: ::capnp::constant::Reader<{}> = ::capnp::constant::Reader {{",
                      styled_name, typ.type_string(gen, Leaf::Owned)?)),
         Indent(Box::new(Branch(vec![
-            word_array_declaration("WORDS", value, WordArrayDeclarationOptions { public: false, omit_first_word: false })?,
-            Line("::capnp::constant::Reader {".into()),
-            Indent(Box::new(Branch(vec![
-                Line("phantom: ::std::marker::PhantomData,".into()),
-                Line("words: &WORDS,".into()),
-            ]))),
-            Line("}".into()),
+            Line("phantom: ::core::marker::PhantomData,".into()),
+            Line("words: &[".into()),
+            Indent(Box::new(Branch(words_lines))),
+            Line("],".into()),
         ]))),
         Line("};".to_string())
     ]))
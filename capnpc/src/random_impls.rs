@@ -0,0 +1,234 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Generation of `capnp::random::FillRandom` implementations, which fill in builders with random
+//! values for property-based testing and fuzzing.
+
+use crate::codegen::{FormattedText, GeneratorContext, camel_to_snake_case, get_field_name};
+use crate::codegen::FormattedText::{Indent, Line, Branch, BlankLine};
+use crate::codegen_types::{Leaf, RustTypeInfo};
+use crate::schema_capnp::{field, node, type_};
+
+/// Whether random values of type `typ` can be generated. Fields of other types, such as
+/// interfaces and `AnyPointer`s, are left null.
+fn is_supported_type(gen: &GeneratorContext, typ: type_::Reader) -> ::capnp::Result<bool> {
+    Ok(match typ.which()? {
+        type_::Struct(st) => gen.random_nodes.contains(&st.get_type_id()),
+        type_::Enum(en) => gen.node_map.contains_key(&en.get_type_id()),
+        type_::List(ot) => is_supported_type(gen, ot.get_element_type()?)?,
+        type_::Interface(_) | type_::AnyPointer(_) => false,
+        _ => true,
+    })
+}
+
+fn is_supported_field(gen: &GeneratorContext, field: field::Reader) -> ::capnp::Result<bool> {
+    Ok(match field.which()? {
+        field::Slot(slot) => is_supported_type(gen, slot.get_type()?)?,
+        field::Group(group) => gen.random_nodes.contains(&group.get_type_id()),
+    })
+}
+
+fn is_void(field: field::Reader) -> ::capnp::Result<bool> {
+    Ok(match field.which()? {
+        field::Slot(slot) => matches!(slot.get_type()?.which()?, type_::Void(())),
+        field::Group(_) => false,
+    })
+}
+
+/// Where a generated value goes: the field with the given styled name of `builder`, or the
+/// current element of the list being filled at the given nesting level.
+#[derive(Clone, Copy)]
+enum Target<'a> {
+    Field(&'a str),
+    Element(u32),
+}
+
+impl <'a> Target<'a> {
+    fn set(self, value: &str) -> String {
+        match self {
+            Target::Field(name) => format!("builder.set_{}({});", name, value),
+            Target::Element(level) => format!("list{0}.set(i{0}, {1});", level, value),
+        }
+    }
+
+    fn init_struct(self) -> String {
+        match self {
+            Target::Field(name) => format!("builder.reborrow().init_{}()", name),
+            Target::Element(level) => format!("list{0}.reborrow().get(i{0})", level),
+        }
+    }
+
+    fn init_list(self, len: &str) -> String {
+        match self {
+            Target::Field(name) => format!("builder.reborrow().init_{}({})", name, len),
+            Target::Element(level) => format!("list{0}.reborrow().init(i{0}, {1})", level, len),
+        }
+    }
+}
+
+/// Returns the expression for a random value of `typ`, or `None` if values of `typ` are structs
+/// or lists, which get initialized in place.
+fn random_value(gen: &GeneratorContext, typ: type_::Reader) -> ::capnp::Result<Option<String>> {
+    Ok(Some(match typ.which()? {
+        type_::Void(()) => "()".to_string(),
+        type_::Text(()) => "&generator.text()".to_string(),
+        type_::Data(()) => "&generator.data()".to_string(),
+        type_::Enum(en) => {
+            let count = match gen.node_map[&en.get_type_id()].which()? {
+                node::Enum(enum_reader) => enum_reader.get_enumerants()?.len(),
+                _ => return Err(::capnp::Error::failed("expected an enum node".to_string())),
+            };
+            format!("::capnp::traits::FromU16::from_u16(generator.below({}) as u16)?", count)
+        }
+        type_::Struct(_) | type_::List(_) | type_::Interface(_) | type_::AnyPointer(_) => return Ok(None),
+        _ => "generator.primitive()".to_string(),
+    }))
+}
+
+/// Returns the statements that fill `target` with a random value of the supported type `typ`.
+/// `level` is the nesting level of the lists that these statements declare, and `optional` says
+/// whether a struct may be left null.
+fn fill_value(gen: &GeneratorContext, typ: type_::Reader, target: Target,
+              level: u32, optional: bool) -> ::capnp::Result<FormattedText>
+{
+    if let Some(value) = random_value(gen, typ)? {
+        return Ok(Line(target.set(&value)));
+    }
+    Ok(match typ.which()? {
+        type_::Struct(_) => {
+            let fill = |value: &str| -> ::capnp::Result<FormattedText> {
+                Ok(Branch(vec![
+                    Line("generator.enter();".to_string()),
+                    Line(format!("<{} as ::capnp::random::FillRandom>::fill_random({}, generator)?;",
+                                 typ.type_string(gen, Leaf::Owned)?, value)),
+                    Line("generator.leave();".to_string()),
+                ]))
+            };
+            match target {
+                Target::Element(_) =>
+                    Line(format!("<{} as ::capnp::random::FillRandom>::fill_random({}, generator)?;",
+                                 typ.type_string(gen, Leaf::Owned)?, target.init_struct())),
+                Target::Field(_) if optional => Branch(vec![
+                    Line("if generator.present() {".to_string()),
+                    Indent(Box::new(fill(&target.init_struct())?)),
+                    Line("}".to_string()),
+                ]),
+                Target::Field(_) => Branch(vec![
+                    Line(format!("let value = {};", target.init_struct())),
+                    Line("if generator.present() {".to_string()),
+                    Indent(Box::new(fill("value")?)),
+                    Line("}".to_string()),
+                ]),
+            }
+        }
+        type_::List(ot) => {
+            let element_type = ot.get_element_type()?;
+            let len = format!("len{}", level);
+            let mut statements = vec![Line(format!("let {} = generator.list_len();", len))];
+            if let type_::Void(()) = element_type.which()? {
+                statements.push(Line(format!("{};", target.init_list(&len))));
+            } else {
+                // Only structs and lists can hold further pointers.
+                let nested = matches!(element_type.which()?, type_::Struct(_) | type_::List(_));
+                statements.push(Line(format!("let mut list{} = {};", level, target.init_list(&len))));
+                if nested { statements.push(Line("generator.enter();".to_string())); }
+                statements.push(Line(format!("for i{} in 0..{} {{", level, len)));
+                statements.push(Indent(Box::new(
+                    fill_value(gen, element_type, Target::Element(level), level + 1, false)?)));
+                statements.push(Line("}".to_string()));
+                if nested { statements.push(Line("generator.leave();".to_string())); }
+            }
+            Branch(vec![
+                Line("{".to_string()),
+                Indent(Box::new(Branch(statements))),
+                Line("}".to_string()),
+            ])
+        }
+        _ => return Err(::capnp::Error::failed("unsupported type for a random value".to_string())),
+    })
+}
+
+/// Generates the `FillRandom` implementation for the struct `node_id`, which goes in the module
+/// of the struct.
+pub fn generate_fill_random(gen: &GeneratorContext, node_id: u64) -> ::capnp::Result<FormattedText> {
+    let struct_reader = match gen.node_map[&node_id].which()? {
+        node::Struct(struct_reader) => struct_reader,
+        _ => return Err(::capnp::Error::failed("expected a struct node".to_string())),
+    };
+
+    let mut statements = Vec::new();
+    let mut union_arms = Vec::new();
+    for field in struct_reader.get_fields()?.iter() {
+        let styled_name = camel_to_snake_case(get_field_name(field)?);
+        let is_union_field = field.get_discriminant_value() != field::NO_DISCRIMINANT;
+        let fill = if !is_supported_field(gen, field)? {
+            if !is_union_field { continue }
+            Line(format!("builder.builder.set_data_field::<u16>({}, {});",
+                         struct_reader.get_discriminant_offset(), field.get_discriminant_value()))
+        } else if !is_union_field && is_void(field)? {
+            continue
+        } else {
+            match field.which()? {
+                field::Group(group) =>
+                    Line(format!("<{}::Owned as ::capnp::random::FillRandom>::fill_random(builder.reborrow().init_{}(), generator)?;",
+                                 gen.scope_map[&group.get_type_id()].join("::"), styled_name)),
+                field::Slot(slot) =>
+                    fill_value(gen, slot.get_type()?, Target::Field(&styled_name), 0, !is_union_field)?,
+            }
+        };
+        if is_union_field {
+            union_arms.push((field.get_discriminant_value(), fill));
+        } else {
+            statements.push(fill);
+        }
+    }
+
+    if !union_arms.is_empty() {
+        union_arms.sort_by_key(|&(discriminant, _)| discriminant);
+        let last = union_arms.len() - 1;
+        let arms = union_arms.into_iter().enumerate().map(|(index, (discriminant, fill))| {
+            Branch(vec![
+                Line(if index == last { "_ => {".to_string() } else { format!("{} => {{", discriminant) }),
+                Indent(Box::new(fill)),
+                Line("}".to_string()),
+            ])
+        }).collect();
+        statements.push(Branch(vec![
+            Line(format!("match generator.below({}) {{", struct_reader.get_discriminant_count())),
+            Indent(Box::new(Branch(arms))),
+            Line("}".to_string()),
+        ]));
+    }
+
+    let params = if statements.is_empty() { "_builder: Builder<'_>, _generator" } else { "mut builder: Builder<'_>, generator" };
+    statements.push(Line("::core::result::Result::Ok(())".to_string()));
+    Ok(Branch(vec![
+        BlankLine,
+        Line("impl ::capnp::random::FillRandom for Owned {".to_string()),
+        Indent(Box::new(Branch(vec![
+            Line(format!("fn fill_random({}: &mut ::capnp::random::Generator<'_>) -> ::capnp::Result<()> {{", params)),
+            Indent(Box::new(Branch(statements))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+    ]))
+}
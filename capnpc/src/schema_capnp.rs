@@ -2,6 +2,9 @@
 // DO NOT EDIT.
 // source: schema.capnp
 
+// Fails to compile if the `capnp` crate does not support this code. Use matching versions of
+// `capnp` and `capnpc`.
+const _: () = ::capnp::generated_code_version::V1;
 
 pub mod node {
   pub use self::Which::{File,Struct,Enum,Interface,Const,Annotation};
@@ -55,6 +58,10 @@ pub mod node {
     pub fn get_id(self) -> u64 {
       self.reader.get_data_field::<u64>(0)
     }
+    /// Name to present to humans to identify this Node.  You should not attempt to parse this.  Its
+    /// format could change.  It is not guaranteed to be unique.
+    ///
+    /// (On Zooko's triangle, this is the node's nickname.)
     #[inline]
     pub fn get_display_name(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -62,14 +69,22 @@ pub mod node {
     pub fn has_display_name(&self) -> bool {
       !self.reader.get_pointer_field(0).is_null()
     }
+    /// If you want a shorter version of `displayName` (just naming this node, without its surrounding
+    /// scope), chop off this many characters from the beginning of `displayName`.
     #[inline]
     pub fn get_display_name_prefix_length(self) -> u32 {
       self.reader.get_data_field::<u32>(2)
     }
+    /// ID of the lexical parent node.  Typically, the scope node will have a NestedNode pointing back
+    /// at this node, but robust code should avoid relying on this (and, in fact, group nodes are not
+    /// listed in the outer struct's nestedNodes, since they are listed in the fields).  `scopeId` is
+    /// zero if the node has no parent, which is normally only the case with files, but should be
+    /// allowed for any kind of node (in order to make runtime type generation easier).
     #[inline]
     pub fn get_scope_id(self) -> u64 {
       self.reader.get_data_field::<u64>(2)
     }
+    /// List of nodes nested within this node, along with the names under which they were declared.
     #[inline]
     pub fn get_nested_nodes(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::node::nested_node::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::core::option::Option::None)
@@ -77,6 +92,7 @@ pub mod node {
     pub fn has_nested_nodes(&self) -> bool {
       !self.reader.get_pointer_field(1).is_null()
     }
+    /// Annotations applied to this node.
     #[inline]
     pub fn get_annotations(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::annotation::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(2), ::core::option::Option::None)
@@ -85,18 +101,45 @@ pub mod node {
       !self.reader.get_pointer_field(2).is_null()
     }
     #[inline]
+    pub fn is_file(&self) -> bool {
+      self.reader.get_data_field::<u16>(6) == 0
+    }
+    #[inline]
+    pub fn is_struct(&self) -> bool {
+      self.reader.get_data_field::<u16>(6) == 1
+    }
+    #[inline]
+    pub fn is_enum(&self) -> bool {
+      self.reader.get_data_field::<u16>(6) == 2
+    }
+    #[inline]
+    pub fn is_interface(&self) -> bool {
+      self.reader.get_data_field::<u16>(6) == 3
+    }
+    #[inline]
+    pub fn is_const(&self) -> bool {
+      self.reader.get_data_field::<u16>(6) == 4
+    }
+    #[inline]
+    pub fn is_annotation(&self) -> bool {
+      self.reader.get_data_field::<u16>(6) == 5
+    }
+    /// If this node is parameterized (generic), the list of parameters. Empty for non-generic types.
+    #[inline]
     pub fn get_parameters(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::node::parameter::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(5), ::core::option::Option::None)
     }
     pub fn has_parameters(&self) -> bool {
       !self.reader.get_pointer_field(5).is_null()
     }
+    /// True if this node is generic, meaning that it or one of its parent scopes has a non-empty
+    /// `parameters`.
     #[inline]
     pub fn get_is_generic(self) -> bool {
       self.reader.get_bool_field(288)
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
+    pub fn which(self) -> ::core::result::Result<WhichReader<'a>, ::capnp::NotInSchema> {
       match self.reader.get_data_field::<u16>(6) {
         0 => {
           ::core::result::Result::Ok(File(
@@ -189,14 +232,26 @@ pub mod node {
     pub fn set_id(&mut self, value: u64)  {
       self.builder.set_data_field::<u64>(0, value);
     }
+    /// Name to present to humans to identify this Node.  You should not attempt to parse this.  Its
+    /// format could change.  It is not guaranteed to be unique.
+    ///
+    /// (On Zooko's triangle, this is the node's nickname.)
     #[inline]
     pub fn get_display_name(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
     }
+    /// Name to present to humans to identify this Node.  You should not attempt to parse this.  Its
+    /// format could change.  It is not guaranteed to be unique.
+    ///
+    /// (On Zooko's triangle, this is the node's nickname.)
     #[inline]
     pub fn set_display_name(&mut self, value: ::capnp::text::Reader<'_>)  {
       self.builder.get_pointer_field(0).set_text(value);
     }
+    /// Name to present to humans to identify this Node.  You should not attempt to parse this.  Its
+    /// format could change.  It is not guaranteed to be unique.
+    ///
+    /// (On Zooko's triangle, this is the node's nickname.)
     #[inline]
     pub fn init_display_name(self, size: u32) -> ::capnp::text::Builder<'a> {
       self.builder.get_pointer_field(0).init_text(size)
@@ -205,29 +260,50 @@ pub mod node {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn clear_display_name(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
+    /// If you want a shorter version of `displayName` (just naming this node, without its surrounding
+    /// scope), chop off this many characters from the beginning of `displayName`.
+    #[inline]
     pub fn get_display_name_prefix_length(self) -> u32 {
       self.builder.get_data_field::<u32>(2)
     }
+    /// If you want a shorter version of `displayName` (just naming this node, without its surrounding
+    /// scope), chop off this many characters from the beginning of `displayName`.
     #[inline]
     pub fn set_display_name_prefix_length(&mut self, value: u32)  {
       self.builder.set_data_field::<u32>(2, value);
     }
+    /// ID of the lexical parent node.  Typically, the scope node will have a NestedNode pointing back
+    /// at this node, but robust code should avoid relying on this (and, in fact, group nodes are not
+    /// listed in the outer struct's nestedNodes, since they are listed in the fields).  `scopeId` is
+    /// zero if the node has no parent, which is normally only the case with files, but should be
+    /// allowed for any kind of node (in order to make runtime type generation easier).
     #[inline]
     pub fn get_scope_id(self) -> u64 {
       self.builder.get_data_field::<u64>(2)
     }
+    /// ID of the lexical parent node.  Typically, the scope node will have a NestedNode pointing back
+    /// at this node, but robust code should avoid relying on this (and, in fact, group nodes are not
+    /// listed in the outer struct's nestedNodes, since they are listed in the fields).  `scopeId` is
+    /// zero if the node has no parent, which is normally only the case with files, but should be
+    /// allowed for any kind of node (in order to make runtime type generation easier).
     #[inline]
     pub fn set_scope_id(&mut self, value: u64)  {
       self.builder.set_data_field::<u64>(2, value);
     }
+    /// List of nodes nested within this node, along with the names under which they were declared.
     #[inline]
     pub fn get_nested_nodes(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::node::nested_node::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::core::option::Option::None)
     }
+    /// List of nodes nested within this node, along with the names under which they were declared.
     #[inline]
     pub fn set_nested_nodes(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::node::nested_node::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
     }
+    /// List of nodes nested within this node, along with the names under which they were declared.
     #[inline]
     pub fn init_nested_nodes(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::node::nested_node::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
@@ -236,13 +312,20 @@ pub mod node {
       !self.builder.get_pointer_field(1).is_null()
     }
     #[inline]
+    pub fn clear_nested_nodes(&mut self) {
+      self.builder.get_pointer_field(1).clear()
+    }
+    /// Annotations applied to this node.
+    #[inline]
     pub fn get_annotations(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::annotation::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(2), ::core::option::Option::None)
     }
+    /// Annotations applied to this node.
     #[inline]
     pub fn set_annotations(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::annotation::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(2), value, false)
     }
+    /// Annotations applied to this node.
     #[inline]
     pub fn init_annotations(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::annotation::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(2), size)
@@ -251,10 +334,18 @@ pub mod node {
       !self.builder.get_pointer_field(2).is_null()
     }
     #[inline]
+    pub fn clear_annotations(&mut self) {
+      self.builder.get_pointer_field(2).clear()
+    }
+    #[inline]
     pub fn set_file(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(6, 0);
     }
     #[inline]
+    pub fn is_file(&self) -> bool {
+      self.builder.get_data_field::<u16>(6) == 0
+    }
+    #[inline]
     pub fn init_struct(self, ) -> crate::schema_capnp::node::struct_::Builder<'a> {
       self.builder.set_data_field::<u16>(6, 1);
       self.builder.set_data_field::<u16>(7, 0u16);
@@ -267,12 +358,20 @@ pub mod node {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_struct(&self) -> bool {
+      self.builder.get_data_field::<u16>(6) == 1
+    }
+    #[inline]
     pub fn init_enum(self, ) -> crate::schema_capnp::node::enum_::Builder<'a> {
       self.builder.set_data_field::<u16>(6, 2);
       self.builder.get_pointer_field(3).clear();
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_enum(&self) -> bool {
+      self.builder.get_data_field::<u16>(6) == 2
+    }
+    #[inline]
     pub fn init_interface(self, ) -> crate::schema_capnp::node::interface::Builder<'a> {
       self.builder.set_data_field::<u16>(6, 3);
       self.builder.get_pointer_field(3).clear();
@@ -280,6 +379,10 @@ pub mod node {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_interface(&self) -> bool {
+      self.builder.get_data_field::<u16>(6) == 3
+    }
+    #[inline]
     pub fn init_const(self, ) -> crate::schema_capnp::node::const_::Builder<'a> {
       self.builder.set_data_field::<u16>(6, 4);
       self.builder.get_pointer_field(3).clear();
@@ -287,6 +390,10 @@ pub mod node {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_const(&self) -> bool {
+      self.builder.get_data_field::<u16>(6) == 4
+    }
+    #[inline]
     pub fn init_annotation(self, ) -> crate::schema_capnp::node::annotation::Builder<'a> {
       self.builder.set_data_field::<u16>(6, 5);
       self.builder.get_pointer_field(3).clear();
@@ -305,13 +412,20 @@ pub mod node {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_annotation(&self) -> bool {
+      self.builder.get_data_field::<u16>(6) == 5
+    }
+    /// If this node is parameterized (generic), the list of parameters. Empty for non-generic types.
+    #[inline]
     pub fn get_parameters(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::node::parameter::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(5), ::core::option::Option::None)
     }
+    /// If this node is parameterized (generic), the list of parameters. Empty for non-generic types.
     #[inline]
     pub fn set_parameters(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::node::parameter::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(5), value, false)
     }
+    /// If this node is parameterized (generic), the list of parameters. Empty for non-generic types.
     #[inline]
     pub fn init_parameters(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::node::parameter::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(5), size)
@@ -320,15 +434,23 @@ pub mod node {
       !self.builder.get_pointer_field(5).is_null()
     }
     #[inline]
+    pub fn clear_parameters(&mut self) {
+      self.builder.get_pointer_field(5).clear()
+    }
+    /// True if this node is generic, meaning that it or one of its parent scopes has a non-empty
+    /// `parameters`.
+    #[inline]
     pub fn get_is_generic(self) -> bool {
       self.builder.get_bool_field(288)
     }
+    /// True if this node is generic, meaning that it or one of its parent scopes has a non-empty
+    /// `parameters`.
     #[inline]
     pub fn set_is_generic(&mut self, value: bool)  {
       self.builder.set_bool_field(288, value);
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
+    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a>, ::capnp::NotInSchema> {
       match self.builder.get_data_field::<u16>(6) {
         0 => {
           ::core::result::Result::Ok(File(
@@ -363,6 +485,10 @@ pub mod node {
         x => ::core::result::Result::Err(::capnp::NotInSchema(x))
       }
     }
+    #[inline]
+    pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder<'_>, ::capnp::NotInSchema> {
+      self.reborrow().which()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -386,9 +512,10 @@ pub mod node {
     Const(A3),
     Annotation(A4),
   }
-  pub type WhichReader<'a,> = Which<crate::schema_capnp::node::struct_::Reader<'a>,crate::schema_capnp::node::enum_::Reader<'a>,crate::schema_capnp::node::interface::Reader<'a>,crate::schema_capnp::node::const_::Reader<'a>,crate::schema_capnp::node::annotation::Reader<'a>>;
-  pub type WhichBuilder<'a,> = Which<crate::schema_capnp::node::struct_::Builder<'a>,crate::schema_capnp::node::enum_::Builder<'a>,crate::schema_capnp::node::interface::Builder<'a>,crate::schema_capnp::node::const_::Builder<'a>,crate::schema_capnp::node::annotation::Builder<'a>>;
+  pub type WhichReader<'a> = Which<crate::schema_capnp::node::struct_::Reader<'a>,crate::schema_capnp::node::enum_::Reader<'a>,crate::schema_capnp::node::interface::Reader<'a>,crate::schema_capnp::node::const_::Reader<'a>,crate::schema_capnp::node::annotation::Reader<'a>>;
+  pub type WhichBuilder<'a> = Which<crate::schema_capnp::node::struct_::Builder<'a>,crate::schema_capnp::node::enum_::Builder<'a>,crate::schema_capnp::node::interface::Builder<'a>,crate::schema_capnp::node::const_::Builder<'a>,crate::schema_capnp::node::annotation::Builder<'a>>;
 
+  /// Information about one of the node's parameters.
   pub mod parameter {
     #[derive(Copy, Clone)]
     pub struct Owned(());
@@ -507,6 +634,10 @@ pub mod node {
       pub fn has_name(&self) -> bool {
         !self.builder.get_pointer_field(0).is_null()
       }
+      #[inline]
+      pub fn clear_name(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -570,6 +701,9 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// Unqualified symbol name.  Unlike Node.displayName, this *can* be used programmatically.
+      ///
+      /// (On Zooko's triangle, this is the node's petname according to its parent scope.)
       #[inline]
       pub fn get_name(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -577,6 +711,8 @@ pub mod node {
       pub fn has_name(&self) -> bool {
         !self.reader.get_pointer_field(0).is_null()
       }
+      /// ID of the nested node.  Typically, the target node's scopeId points back to this node, but
+      /// robust code should avoid relying on this.
       #[inline]
       pub fn get_id(self) -> u64 {
         self.reader.get_data_field::<u64>(0)
@@ -631,14 +767,23 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// Unqualified symbol name.  Unlike Node.displayName, this *can* be used programmatically.
+      ///
+      /// (On Zooko's triangle, this is the node's petname according to its parent scope.)
       #[inline]
       pub fn get_name(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
       }
+      /// Unqualified symbol name.  Unlike Node.displayName, this *can* be used programmatically.
+      ///
+      /// (On Zooko's triangle, this is the node's petname according to its parent scope.)
       #[inline]
       pub fn set_name(&mut self, value: ::capnp::text::Reader<'_>)  {
         self.builder.get_pointer_field(0).set_text(value);
       }
+      /// Unqualified symbol name.  Unlike Node.displayName, this *can* be used programmatically.
+      ///
+      /// (On Zooko's triangle, this is the node's petname according to its parent scope.)
       #[inline]
       pub fn init_name(self, size: u32) -> ::capnp::text::Builder<'a> {
         self.builder.get_pointer_field(0).init_text(size)
@@ -647,9 +792,17 @@ pub mod node {
         !self.builder.get_pointer_field(0).is_null()
       }
       #[inline]
+      pub fn clear_name(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
+      /// ID of the nested node.  Typically, the target node's scopeId points back to this node, but
+      /// robust code should avoid relying on this.
+      #[inline]
       pub fn get_id(self) -> u64 {
         self.builder.get_data_field::<u64>(0)
       }
+      /// ID of the nested node.  Typically, the target node's scopeId points back to this node, but
+      /// robust code should avoid relying on this.
       #[inline]
       pub fn set_id(&mut self, value: u64)  {
         self.builder.set_data_field::<u64>(0, value);
@@ -671,6 +824,10 @@ pub mod node {
     }
   }
 
+  /// Additional information about a node which is not needed at runtime, but may be useful for
+  /// documentation or debugging purposes. This is kept in a separate struct to make sure it
+  /// doesn't accidentally get included in contexts where it is not needed. The
+  /// `CodeGeneratorRequest` includes this information in a separate array.
   pub mod source_info {
     #[derive(Copy, Clone)]
     pub struct Owned(());
@@ -717,10 +874,12 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// ID of the Node which this info describes.
       #[inline]
       pub fn get_id(self) -> u64 {
         self.reader.get_data_field::<u64>(0)
       }
+      /// The top-level doc comment for the Node.
       #[inline]
       pub fn get_doc_comment(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -728,6 +887,11 @@ pub mod node {
       pub fn has_doc_comment(&self) -> bool {
         !self.reader.get_pointer_field(0).is_null()
       }
+      /// Information about each member -- i.e. fields (for structs), enumerants (for enums), or
+      /// methods (for interfaces).
+      ///
+      /// This list is the same length and order as the corresponding list in the Node, i.e.
+      /// Node.struct.fields, Node.enum.enumerants, or Node.interface.methods.
       #[inline]
       pub fn get_members(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::node::source_info::member::Owned>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::core::option::Option::None)
@@ -785,22 +949,27 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// ID of the Node which this info describes.
       #[inline]
       pub fn get_id(self) -> u64 {
         self.builder.get_data_field::<u64>(0)
       }
+      /// ID of the Node which this info describes.
       #[inline]
       pub fn set_id(&mut self, value: u64)  {
         self.builder.set_data_field::<u64>(0, value);
       }
+      /// The top-level doc comment for the Node.
       #[inline]
       pub fn get_doc_comment(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
       }
+      /// The top-level doc comment for the Node.
       #[inline]
       pub fn set_doc_comment(&mut self, value: ::capnp::text::Reader<'_>)  {
         self.builder.get_pointer_field(0).set_text(value);
       }
+      /// The top-level doc comment for the Node.
       #[inline]
       pub fn init_doc_comment(self, size: u32) -> ::capnp::text::Builder<'a> {
         self.builder.get_pointer_field(0).init_text(size)
@@ -809,13 +978,32 @@ pub mod node {
         !self.builder.get_pointer_field(0).is_null()
       }
       #[inline]
+      pub fn clear_doc_comment(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
+      /// Information about each member -- i.e. fields (for structs), enumerants (for enums), or
+      /// methods (for interfaces).
+      ///
+      /// This list is the same length and order as the corresponding list in the Node, i.e.
+      /// Node.struct.fields, Node.enum.enumerants, or Node.interface.methods.
+      #[inline]
       pub fn get_members(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::node::source_info::member::Owned>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::core::option::Option::None)
       }
+      /// Information about each member -- i.e. fields (for structs), enumerants (for enums), or
+      /// methods (for interfaces).
+      ///
+      /// This list is the same length and order as the corresponding list in the Node, i.e.
+      /// Node.struct.fields, Node.enum.enumerants, or Node.interface.methods.
       #[inline]
       pub fn set_members(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::node::source_info::member::Owned>) -> ::capnp::Result<()> {
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
       }
+      /// Information about each member -- i.e. fields (for structs), enumerants (for enums), or
+      /// methods (for interfaces).
+      ///
+      /// This list is the same length and order as the corresponding list in the Node, i.e.
+      /// Node.struct.fields, Node.enum.enumerants, or Node.interface.methods.
       #[inline]
       pub fn init_members(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::node::source_info::member::Owned> {
         ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
@@ -823,6 +1011,10 @@ pub mod node {
       pub fn has_members(&self) -> bool {
         !self.builder.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn clear_members(&mut self) {
+        self.builder.get_pointer_field(1).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -885,6 +1077,7 @@ pub mod node {
         pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
           self.reader.total_size()
         }
+        /// Doc comment on the member.
         #[inline]
         pub fn get_doc_comment(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
           ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -942,14 +1135,17 @@ pub mod node {
         pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
           self.builder.into_reader().total_size()
         }
+        /// Doc comment on the member.
         #[inline]
         pub fn get_doc_comment(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
           ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
         }
+        /// Doc comment on the member.
         #[inline]
         pub fn set_doc_comment(&mut self, value: ::capnp::text::Reader<'_>)  {
           self.builder.get_pointer_field(0).set_text(value);
         }
+        /// Doc comment on the member.
         #[inline]
         pub fn init_doc_comment(self, size: u32) -> ::capnp::text::Builder<'a> {
           self.builder.get_pointer_field(0).init_text(size)
@@ -957,6 +1153,10 @@ pub mod node {
         pub fn has_doc_comment(&self) -> bool {
           !self.builder.get_pointer_field(0).is_null()
         }
+        #[inline]
+        pub fn clear_doc_comment(&mut self) {
+          self.builder.get_pointer_field(0).clear()
+        }
       }
 
       pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1021,30 +1221,66 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// Size of the data section, in words.
       #[inline]
       pub fn get_data_word_count(self) -> u16 {
         self.reader.get_data_field::<u16>(7)
       }
+      /// Size of the pointer section, in pointers (which are one word each).
       #[inline]
       pub fn get_pointer_count(self) -> u16 {
         self.reader.get_data_field::<u16>(12)
       }
+      /// The preferred element size to use when encoding a list of this struct.  If this is anything
+      /// other than `inlineComposite` then the struct is one word or less in size and is a candidate
+      /// for list packing optimization.
       #[inline]
       pub fn get_preferred_list_encoding(self) -> ::core::result::Result<crate::schema_capnp::ElementSize,::capnp::NotInSchema> {
         ::capnp::traits::FromU16::from_u16(self.reader.get_data_field::<u16>(13))
       }
+      /// If true, then this "struct" node is actually not an independent node, but merely represents
+      /// some named union or group within a particular parent struct.  This node's scopeId refers
+      /// to the parent struct, which may itself be a union/group in yet another struct.
+      ///
+      /// All group nodes share the same dataWordCount and pointerCount as the top-level
+      /// struct, and their fields live in the same ordinal and offset spaces as all other fields in
+      /// the struct.
+      ///
+      /// Note that a named union is considered a special kind of group -- in fact, a named union
+      /// is exactly equivalent to a group that contains nothing but an unnamed union.
       #[inline]
       pub fn get_is_group(self) -> bool {
         self.reader.get_bool_field(224)
       }
+      /// Number of fields in this struct which are members of an anonymous union, and thus may
+      /// overlap.  If this is non-zero, then a 16-bit discriminant is present indicating which
+      /// of the overlapping fields is active.  This can never be 1 -- if it is non-zero, it must be
+      /// two or more.
+      ///
+      /// Note that the fields of an unnamed union are considered fields of the scope containing the
+      /// union -- an unnamed union is not its own group.  So, a top-level struct may contain a
+      /// non-zero discriminant count.  Named unions, on the other hand, are equivalent to groups
+      /// containing unnamed unions.  So, a named union has its own independent schema node, with
+      /// `isGroup` = true.
       #[inline]
       pub fn get_discriminant_count(self) -> u16 {
         self.reader.get_data_field::<u16>(15)
       }
+      /// If `discriminantCount` is non-zero, this is the offset of the union discriminant, in
+      /// multiples of 16 bits.
       #[inline]
       pub fn get_discriminant_offset(self) -> u32 {
         self.reader.get_data_field::<u32>(8)
       }
+      /// Fields defined within this scope (either the struct's top-level fields, or the fields of
+      /// a particular group; see `isGroup`).
+      ///
+      /// The fields are sorted by ordinal number, but note that because groups share the same
+      /// ordinal space, the field's index in this list is not necessarily exactly its ordinal.
+      /// On the other hand, the field's position in this list does remain the same even as the
+      /// protocol evolves, since it is not possible to insert or remove an earlier ordinal.
+      /// Therefore, for most use cases, if you want to identify a field by number, it may make the
+      /// most sense to use the field's index in this list rather than its ordinal.
       #[inline]
       pub fn get_fields(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::field::Owned>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(3), ::core::option::Option::None)
@@ -1102,62 +1338,143 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// Size of the data section, in words.
       #[inline]
       pub fn get_data_word_count(self) -> u16 {
         self.builder.get_data_field::<u16>(7)
       }
+      /// Size of the data section, in words.
       #[inline]
       pub fn set_data_word_count(&mut self, value: u16)  {
         self.builder.set_data_field::<u16>(7, value);
       }
+      /// Size of the pointer section, in pointers (which are one word each).
       #[inline]
       pub fn get_pointer_count(self) -> u16 {
         self.builder.get_data_field::<u16>(12)
       }
+      /// Size of the pointer section, in pointers (which are one word each).
       #[inline]
       pub fn set_pointer_count(&mut self, value: u16)  {
         self.builder.set_data_field::<u16>(12, value);
       }
+      /// The preferred element size to use when encoding a list of this struct.  If this is anything
+      /// other than `inlineComposite` then the struct is one word or less in size and is a candidate
+      /// for list packing optimization.
       #[inline]
       pub fn get_preferred_list_encoding(self) -> ::core::result::Result<crate::schema_capnp::ElementSize,::capnp::NotInSchema> {
         ::capnp::traits::FromU16::from_u16(self.builder.get_data_field::<u16>(13))
       }
+      /// The preferred element size to use when encoding a list of this struct.  If this is anything
+      /// other than `inlineComposite` then the struct is one word or less in size and is a candidate
+      /// for list packing optimization.
       #[inline]
       pub fn set_preferred_list_encoding(&mut self, value: crate::schema_capnp::ElementSize)  {
-        self.builder.set_data_field::<u16>(13, value as u16)
-      }
+        self.builder.set_data_field::<u16>(13, ::capnp::traits::ToU16::to_u16(value))
+      }
+      /// If true, then this "struct" node is actually not an independent node, but merely represents
+      /// some named union or group within a particular parent struct.  This node's scopeId refers
+      /// to the parent struct, which may itself be a union/group in yet another struct.
+      ///
+      /// All group nodes share the same dataWordCount and pointerCount as the top-level
+      /// struct, and their fields live in the same ordinal and offset spaces as all other fields in
+      /// the struct.
+      ///
+      /// Note that a named union is considered a special kind of group -- in fact, a named union
+      /// is exactly equivalent to a group that contains nothing but an unnamed union.
       #[inline]
       pub fn get_is_group(self) -> bool {
         self.builder.get_bool_field(224)
       }
+      /// If true, then this "struct" node is actually not an independent node, but merely represents
+      /// some named union or group within a particular parent struct.  This node's scopeId refers
+      /// to the parent struct, which may itself be a union/group in yet another struct.
+      ///
+      /// All group nodes share the same dataWordCount and pointerCount as the top-level
+      /// struct, and their fields live in the same ordinal and offset spaces as all other fields in
+      /// the struct.
+      ///
+      /// Note that a named union is considered a special kind of group -- in fact, a named union
+      /// is exactly equivalent to a group that contains nothing but an unnamed union.
       #[inline]
       pub fn set_is_group(&mut self, value: bool)  {
         self.builder.set_bool_field(224, value);
       }
+      /// Number of fields in this struct which are members of an anonymous union, and thus may
+      /// overlap.  If this is non-zero, then a 16-bit discriminant is present indicating which
+      /// of the overlapping fields is active.  This can never be 1 -- if it is non-zero, it must be
+      /// two or more.
+      ///
+      /// Note that the fields of an unnamed union are considered fields of the scope containing the
+      /// union -- an unnamed union is not its own group.  So, a top-level struct may contain a
+      /// non-zero discriminant count.  Named unions, on the other hand, are equivalent to groups
+      /// containing unnamed unions.  So, a named union has its own independent schema node, with
+      /// `isGroup` = true.
       #[inline]
       pub fn get_discriminant_count(self) -> u16 {
         self.builder.get_data_field::<u16>(15)
       }
+      /// Number of fields in this struct which are members of an anonymous union, and thus may
+      /// overlap.  If this is non-zero, then a 16-bit discriminant is present indicating which
+      /// of the overlapping fields is active.  This can never be 1 -- if it is non-zero, it must be
+      /// two or more.
+      ///
+      /// Note that the fields of an unnamed union are considered fields of the scope containing the
+      /// union -- an unnamed union is not its own group.  So, a top-level struct may contain a
+      /// non-zero discriminant count.  Named unions, on the other hand, are equivalent to groups
+      /// containing unnamed unions.  So, a named union has its own independent schema node, with
+      /// `isGroup` = true.
       #[inline]
       pub fn set_discriminant_count(&mut self, value: u16)  {
         self.builder.set_data_field::<u16>(15, value);
       }
+      /// If `discriminantCount` is non-zero, this is the offset of the union discriminant, in
+      /// multiples of 16 bits.
       #[inline]
       pub fn get_discriminant_offset(self) -> u32 {
         self.builder.get_data_field::<u32>(8)
       }
+      /// If `discriminantCount` is non-zero, this is the offset of the union discriminant, in
+      /// multiples of 16 bits.
       #[inline]
       pub fn set_discriminant_offset(&mut self, value: u32)  {
         self.builder.set_data_field::<u32>(8, value);
       }
+      /// Fields defined within this scope (either the struct's top-level fields, or the fields of
+      /// a particular group; see `isGroup`).
+      ///
+      /// The fields are sorted by ordinal number, but note that because groups share the same
+      /// ordinal space, the field's index in this list is not necessarily exactly its ordinal.
+      /// On the other hand, the field's position in this list does remain the same even as the
+      /// protocol evolves, since it is not possible to insert or remove an earlier ordinal.
+      /// Therefore, for most use cases, if you want to identify a field by number, it may make the
+      /// most sense to use the field's index in this list rather than its ordinal.
       #[inline]
       pub fn get_fields(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::field::Owned>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::core::option::Option::None)
       }
+      /// Fields defined within this scope (either the struct's top-level fields, or the fields of
+      /// a particular group; see `isGroup`).
+      ///
+      /// The fields are sorted by ordinal number, but note that because groups share the same
+      /// ordinal space, the field's index in this list is not necessarily exactly its ordinal.
+      /// On the other hand, the field's position in this list does remain the same even as the
+      /// protocol evolves, since it is not possible to insert or remove an earlier ordinal.
+      /// Therefore, for most use cases, if you want to identify a field by number, it may make the
+      /// most sense to use the field's index in this list rather than its ordinal.
       #[inline]
       pub fn set_fields(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::field::Owned>) -> ::capnp::Result<()> {
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(3), value, false)
       }
+      /// Fields defined within this scope (either the struct's top-level fields, or the fields of
+      /// a particular group; see `isGroup`).
+      ///
+      /// The fields are sorted by ordinal number, but note that because groups share the same
+      /// ordinal space, the field's index in this list is not necessarily exactly its ordinal.
+      /// On the other hand, the field's position in this list does remain the same even as the
+      /// protocol evolves, since it is not possible to insert or remove an earlier ordinal.
+      /// Therefore, for most use cases, if you want to identify a field by number, it may make the
+      /// most sense to use the field's index in this list rather than its ordinal.
       #[inline]
       pub fn init_fields(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::field::Owned> {
         ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(3), size)
@@ -1165,6 +1482,10 @@ pub mod node {
       pub fn has_fields(&self) -> bool {
         !self.builder.get_pointer_field(3).is_null()
       }
+      #[inline]
+      pub fn clear_fields(&mut self) {
+        self.builder.get_pointer_field(3).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1228,6 +1549,7 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// Enumerants ordered by numeric value (ordinal).
       #[inline]
       pub fn get_enumerants(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::enumerant::Owned>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(3), ::core::option::Option::None)
@@ -1285,14 +1607,17 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// Enumerants ordered by numeric value (ordinal).
       #[inline]
       pub fn get_enumerants(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::enumerant::Owned>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::core::option::Option::None)
       }
+      /// Enumerants ordered by numeric value (ordinal).
       #[inline]
       pub fn set_enumerants(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::enumerant::Owned>) -> ::capnp::Result<()> {
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(3), value, false)
       }
+      /// Enumerants ordered by numeric value (ordinal).
       #[inline]
       pub fn init_enumerants(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::enumerant::Owned> {
         ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(3), size)
@@ -1300,6 +1625,10 @@ pub mod node {
       pub fn has_enumerants(&self) -> bool {
         !self.builder.get_pointer_field(3).is_null()
       }
+      #[inline]
+      pub fn clear_enumerants(&mut self) {
+        self.builder.get_pointer_field(3).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1363,6 +1692,7 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// Methods ordered by ordinal.
       #[inline]
       pub fn get_methods(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::method::Owned>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(3), ::core::option::Option::None)
@@ -1370,6 +1700,7 @@ pub mod node {
       pub fn has_methods(&self) -> bool {
         !self.reader.get_pointer_field(3).is_null()
       }
+      /// Superclasses of this interface.
       #[inline]
       pub fn get_superclasses(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::superclass::Owned>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(4), ::core::option::Option::None)
@@ -1427,14 +1758,17 @@ pub mod node {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// Methods ordered by ordinal.
       #[inline]
       pub fn get_methods(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::method::Owned>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::core::option::Option::None)
       }
+      /// Methods ordered by ordinal.
       #[inline]
       pub fn set_methods(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::method::Owned>) -> ::capnp::Result<()> {
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(3), value, false)
       }
+      /// Methods ordered by ordinal.
       #[inline]
       pub fn init_methods(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::method::Owned> {
         ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(3), size)
@@ -1443,13 +1777,20 @@ pub mod node {
         !self.builder.get_pointer_field(3).is_null()
       }
       #[inline]
+      pub fn clear_methods(&mut self) {
+        self.builder.get_pointer_field(3).clear()
+      }
+      /// Superclasses of this interface.
+      #[inline]
       pub fn get_superclasses(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::superclass::Owned>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(4), ::core::option::Option::None)
       }
+      /// Superclasses of this interface.
       #[inline]
       pub fn set_superclasses(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::superclass::Owned>) -> ::capnp::Result<()> {
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(4), value, false)
       }
+      /// Superclasses of this interface.
       #[inline]
       pub fn init_superclasses(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::superclass::Owned> {
         ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(4), size)
@@ -1457,6 +1798,10 @@ pub mod node {
       pub fn has_superclasses(&self) -> bool {
         !self.builder.get_pointer_field(4).is_null()
       }
+      #[inline]
+      pub fn clear_superclasses(&mut self) {
+        self.builder.get_pointer_field(4).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1600,6 +1945,10 @@ pub mod node {
         !self.builder.get_pointer_field(3).is_null()
       }
       #[inline]
+      pub fn clear_type(&mut self) {
+        self.builder.get_pointer_field(3).clear()
+      }
+      #[inline]
       pub fn get_value(self) -> ::capnp::Result<crate::schema_capnp::value::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(4), ::core::option::Option::None)
       }
@@ -1614,6 +1963,10 @@ pub mod node {
       pub fn has_value(&self) -> bool {
         !self.builder.get_pointer_field(4).is_null()
       }
+      #[inline]
+      pub fn clear_value(&mut self) {
+        self.builder.get_pointer_field(4).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -1804,6 +2157,10 @@ pub mod node {
         !self.builder.get_pointer_field(3).is_null()
       }
       #[inline]
+      pub fn clear_type(&mut self) {
+        self.builder.get_pointer_field(3).clear()
+      }
+      #[inline]
       pub fn get_targets_file(self) -> bool {
         self.builder.get_bool_field(112)
       }
@@ -1920,6 +2277,7 @@ pub mod node {
   }
 }
 
+/// Schema for a field of a struct.
 pub mod field {
   pub use self::Which::{Slot,Group};
 
@@ -1975,6 +2333,12 @@ pub mod field {
     pub fn has_name(&self) -> bool {
       !self.reader.get_pointer_field(0).is_null()
     }
+    /// Indicates where this member appeared in the code, relative to other members.
+    /// Code ordering may have semantic relevance -- programmers tend to place related fields
+    /// together.  So, using code ordering makes sense in human-readable formats where ordering is
+    /// otherwise irrelevant, like JSON.  The values of codeOrder are tightly-packed, so the maximum
+    /// value is count(members) - 1.  Fields that are members of a union are only ordered relative to
+    /// the other members of that union, so the maximum value there is count(union.members).
     #[inline]
     pub fn get_code_order(self) -> u16 {
       self.reader.get_data_field::<u16>(0)
@@ -1986,16 +2350,26 @@ pub mod field {
     pub fn has_annotations(&self) -> bool {
       !self.reader.get_pointer_field(1).is_null()
     }
+    /// If the field is in a union, this is the value which the union's discriminant should take when
+    /// the field is active.  If the field is not in a union, this is 0xffff.
     #[inline]
     pub fn get_discriminant_value(self) -> u16 {
       self.reader.get_data_field_mask::<u16>(1, 65535)
     }
     #[inline]
+    pub fn is_slot(&self) -> bool {
+      self.reader.get_data_field::<u16>(4) == 0
+    }
+    #[inline]
+    pub fn is_group(&self) -> bool {
+      self.reader.get_data_field::<u16>(4) == 1
+    }
+    #[inline]
     pub fn get_ordinal(self) -> crate::schema_capnp::field::ordinal::Reader<'a> {
       ::capnp::traits::FromStructReader::new(self.reader)
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
+    pub fn which(self) -> ::core::result::Result<WhichReader<'a>, ::capnp::NotInSchema> {
       match self.reader.get_data_field::<u16>(4) {
         0 => {
           ::core::result::Result::Ok(Slot(
@@ -2076,9 +2450,25 @@ pub mod field {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn clear_name(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
+    /// Indicates where this member appeared in the code, relative to other members.
+    /// Code ordering may have semantic relevance -- programmers tend to place related fields
+    /// together.  So, using code ordering makes sense in human-readable formats where ordering is
+    /// otherwise irrelevant, like JSON.  The values of codeOrder are tightly-packed, so the maximum
+    /// value is count(members) - 1.  Fields that are members of a union are only ordered relative to
+    /// the other members of that union, so the maximum value there is count(union.members).
+    #[inline]
     pub fn get_code_order(self) -> u16 {
       self.builder.get_data_field::<u16>(0)
     }
+    /// Indicates where this member appeared in the code, relative to other members.
+    /// Code ordering may have semantic relevance -- programmers tend to place related fields
+    /// together.  So, using code ordering makes sense in human-readable formats where ordering is
+    /// otherwise irrelevant, like JSON.  The values of codeOrder are tightly-packed, so the maximum
+    /// value is count(members) - 1.  Fields that are members of a union are only ordered relative to
+    /// the other members of that union, so the maximum value there is count(union.members).
     #[inline]
     pub fn set_code_order(&mut self, value: u16)  {
       self.builder.set_data_field::<u16>(0, value);
@@ -2099,13 +2489,22 @@ pub mod field {
       !self.builder.get_pointer_field(1).is_null()
     }
     #[inline]
+    pub fn clear_annotations(&mut self) {
+      self.builder.get_pointer_field(1).clear()
+    }
+    /// If the field is in a union, this is the value which the union's discriminant should take when
+    /// the field is active.  If the field is not in a union, this is 0xffff.
+    #[inline]
     pub fn get_discriminant_value(self) -> u16 {
       self.builder.get_data_field_mask::<u16>(1, 65535)
     }
+    /// If the field is in a union, this is the value which the union's discriminant should take when
+    /// the field is active.  If the field is not in a union, this is 0xffff.
     #[inline]
     pub fn set_discriminant_value(&mut self, value: u16)  {
       self.builder.set_data_field_mask::<u16>(1, value, 65535);
     }
+    /// A regular, non-group, non-fixed-list field.
     #[inline]
     pub fn init_slot(self, ) -> crate::schema_capnp::field::slot::Builder<'a> {
       self.builder.set_data_field::<u16>(4, 0);
@@ -2116,12 +2515,21 @@ pub mod field {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_slot(&self) -> bool {
+      self.builder.get_data_field::<u16>(4) == 0
+    }
+    /// A group.
+    #[inline]
     pub fn init_group(self, ) -> crate::schema_capnp::field::group::Builder<'a> {
       self.builder.set_data_field::<u16>(4, 1);
       self.builder.set_data_field::<u64>(2, 0u64);
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_group(&self) -> bool {
+      self.builder.get_data_field::<u16>(4) == 1
+    }
+    #[inline]
     pub fn get_ordinal(self) -> crate::schema_capnp::field::ordinal::Builder<'a> {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
@@ -2132,7 +2540,7 @@ pub mod field {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
+    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a>, ::capnp::NotInSchema> {
       match self.builder.get_data_field::<u16>(4) {
         0 => {
           ::core::result::Result::Ok(Slot(
@@ -2147,6 +2555,10 @@ pub mod field {
         x => ::core::result::Result::Err(::capnp::NotInSchema(x))
       }
     }
+    #[inline]
+    pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder<'_>, ::capnp::NotInSchema> {
+      self.reborrow().which()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -2166,13 +2578,16 @@ pub mod field {
     pub const TYPE_ID: u64 = 0x9aad_50a4_1f4a_f45f;
   }
   pub enum Which<A0,A1> {
+    /// A regular, non-group, non-fixed-list field.
     Slot(A0),
+    /// A group.
     Group(A1),
   }
-  pub type WhichReader<'a,> = Which<crate::schema_capnp::field::slot::Reader<'a>,crate::schema_capnp::field::group::Reader<'a>>;
-  pub type WhichBuilder<'a,> = Which<crate::schema_capnp::field::slot::Builder<'a>,crate::schema_capnp::field::group::Builder<'a>>;
+  pub type WhichReader<'a> = Which<crate::schema_capnp::field::slot::Reader<'a>,crate::schema_capnp::field::group::Reader<'a>>;
+  pub type WhichBuilder<'a> = Which<crate::schema_capnp::field::slot::Builder<'a>,crate::schema_capnp::field::group::Builder<'a>>;
   pub const NO_DISCRIMINANT: u16 = 65535;
 
+  /// A regular, non-group, non-fixed-list field.
   pub mod slot {
     #[derive(Copy, Clone)]
     pub struct Owned(());
@@ -2219,6 +2634,9 @@ pub mod field {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// Offset, in units of the field's size, from the beginning of the section in which the field
+      /// resides.  E.g. for a UInt32 field, multiply this by 4 to get the byte offset from the
+      /// beginning of the data section.
       #[inline]
       pub fn get_offset(self) -> u32 {
         self.reader.get_data_field::<u32>(1)
@@ -2237,6 +2655,10 @@ pub mod field {
       pub fn has_default_value(&self) -> bool {
         !self.reader.get_pointer_field(3).is_null()
       }
+      /// Whether the default value was specified explicitly.  Non-explicit default values are always
+      /// zero or empty values.  Usually, whether the default value was explicit shouldn't matter.
+      /// The main use case for this flag is for structs representing method parameters:
+      /// explicitly-defaulted parameters may be allowed to be omitted when calling the method.
       #[inline]
       pub fn get_had_explicit_default(self) -> bool {
         self.reader.get_bool_field(128)
@@ -2291,10 +2713,16 @@ pub mod field {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// Offset, in units of the field's size, from the beginning of the section in which the field
+      /// resides.  E.g. for a UInt32 field, multiply this by 4 to get the byte offset from the
+      /// beginning of the data section.
       #[inline]
       pub fn get_offset(self) -> u32 {
         self.builder.get_data_field::<u32>(1)
       }
+      /// Offset, in units of the field's size, from the beginning of the section in which the field
+      /// resides.  E.g. for a UInt32 field, multiply this by 4 to get the byte offset from the
+      /// beginning of the data section.
       #[inline]
       pub fn set_offset(&mut self, value: u32)  {
         self.builder.set_data_field::<u32>(1, value);
@@ -2315,6 +2743,10 @@ pub mod field {
         !self.builder.get_pointer_field(2).is_null()
       }
       #[inline]
+      pub fn clear_type(&mut self) {
+        self.builder.get_pointer_field(2).clear()
+      }
+      #[inline]
       pub fn get_default_value(self) -> ::capnp::Result<crate::schema_capnp::value::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::core::option::Option::None)
       }
@@ -2330,9 +2762,21 @@ pub mod field {
         !self.builder.get_pointer_field(3).is_null()
       }
       #[inline]
+      pub fn clear_default_value(&mut self) {
+        self.builder.get_pointer_field(3).clear()
+      }
+      /// Whether the default value was specified explicitly.  Non-explicit default values are always
+      /// zero or empty values.  Usually, whether the default value was explicit shouldn't matter.
+      /// The main use case for this flag is for structs representing method parameters:
+      /// explicitly-defaulted parameters may be allowed to be omitted when calling the method.
+      #[inline]
       pub fn get_had_explicit_default(self) -> bool {
         self.builder.get_bool_field(128)
       }
+      /// Whether the default value was specified explicitly.  Non-explicit default values are always
+      /// zero or empty values.  Usually, whether the default value was explicit shouldn't matter.
+      /// The main use case for this flag is for structs representing method parameters:
+      /// explicitly-defaulted parameters may be allowed to be omitted when calling the method.
       #[inline]
       pub fn set_had_explicit_default(&mut self, value: bool)  {
         self.builder.set_bool_field(128, value);
@@ -2360,6 +2804,7 @@ pub mod field {
     }
   }
 
+  /// A group.
   pub mod group {
     #[derive(Copy, Clone)]
     pub struct Owned(());
@@ -2406,6 +2851,7 @@ pub mod field {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// The ID of the group's node.
       #[inline]
       pub fn get_type_id(self) -> u64 {
         self.reader.get_data_field::<u64>(2)
@@ -2460,10 +2906,12 @@ pub mod field {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// The ID of the group's node.
       #[inline]
       pub fn get_type_id(self) -> u64 {
         self.builder.get_data_field::<u64>(2)
       }
+      /// The ID of the group's node.
       #[inline]
       pub fn set_type_id(&mut self, value: u64)  {
         self.builder.set_data_field::<u64>(2, value);
@@ -2534,6 +2982,14 @@ pub mod field {
         self.reader.total_size()
       }
       #[inline]
+      pub fn is_implicit(&self) -> bool {
+        self.reader.get_data_field::<u16>(5) == 0
+      }
+      #[inline]
+      pub fn is_explicit(&self) -> bool {
+        self.reader.get_data_field::<u16>(5) == 1
+      }
+      #[inline]
       pub fn which(self) -> ::core::result::Result<WhichReader, ::capnp::NotInSchema> {
         match self.reader.get_data_field::<u16>(5) {
           0 => {
@@ -2604,11 +3060,23 @@ pub mod field {
         self.builder.set_data_field::<u16>(5, 0);
       }
       #[inline]
+      pub fn is_implicit(&self) -> bool {
+        self.builder.get_data_field::<u16>(5) == 0
+      }
+      /// The original ordinal number given to the field.  You probably should NOT use this; if you need
+      /// a numeric identifier for a field, use its position within the field array for its scope.
+      /// The ordinal is given here mainly just so that the original schema text can be reproduced given
+      /// the compiled version -- i.e. so that `capnp compile -ocapnp` can do its job.
+      #[inline]
       pub fn set_explicit(&mut self, value: u16)  {
         self.builder.set_data_field::<u16>(5, 1);
         self.builder.set_data_field::<u16>(6, value);
       }
       #[inline]
+      pub fn is_explicit(&self) -> bool {
+        self.builder.get_data_field::<u16>(5) == 1
+      }
+      #[inline]
       pub fn which(self) -> ::core::result::Result<WhichBuilder, ::capnp::NotInSchema> {
         match self.builder.get_data_field::<u16>(5) {
           0 => {
@@ -2624,6 +3092,10 @@ pub mod field {
           x => ::core::result::Result::Err(::capnp::NotInSchema(x))
         }
       }
+      #[inline]
+      pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder, ::capnp::NotInSchema> {
+        self.reborrow().which()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -2641,6 +3113,10 @@ pub mod field {
     }
     pub enum Which {
       Implicit(()),
+      /// The original ordinal number given to the field.  You probably should NOT use this; if you need
+      /// a numeric identifier for a field, use its position within the field array for its scope.
+      /// The ordinal is given here mainly just so that the original schema text can be reproduced given
+      /// the compiled version -- i.e. so that `capnp compile -ocapnp` can do its job.
       Explicit(u16),
     }
     pub type WhichReader = Which;
@@ -2648,6 +3124,7 @@ pub mod field {
   }
 }
 
+/// Schema for member of an enum.
 pub mod enumerant {
   #[derive(Copy, Clone)]
   pub struct Owned(());
@@ -2701,6 +3178,8 @@ pub mod enumerant {
     pub fn has_name(&self) -> bool {
       !self.reader.get_pointer_field(0).is_null()
     }
+    /// Specifies order in which the enumerants were declared in the code.
+    /// Like Struct.Field.codeOrder.
     #[inline]
     pub fn get_code_order(self) -> u16 {
       self.reader.get_data_field::<u16>(0)
@@ -2778,9 +3257,17 @@ pub mod enumerant {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn clear_name(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
+    /// Specifies order in which the enumerants were declared in the code.
+    /// Like Struct.Field.codeOrder.
+    #[inline]
     pub fn get_code_order(self) -> u16 {
       self.builder.get_data_field::<u16>(0)
     }
+    /// Specifies order in which the enumerants were declared in the code.
+    /// Like Struct.Field.codeOrder.
     #[inline]
     pub fn set_code_order(&mut self, value: u16)  {
       self.builder.set_data_field::<u16>(0, value);
@@ -2800,6 +3287,10 @@ pub mod enumerant {
     pub fn has_annotations(&self) -> bool {
       !self.builder.get_pointer_field(1).is_null()
     }
+    #[inline]
+    pub fn clear_annotations(&mut self) {
+      self.builder.get_pointer_field(1).clear()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -2947,6 +3438,10 @@ pub mod superclass {
     pub fn has_brand(&self) -> bool {
       !self.builder.get_pointer_field(0).is_null()
     }
+    #[inline]
+    pub fn clear_brand(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -2967,6 +3462,7 @@ pub mod superclass {
   }
 }
 
+/// Schema for method of an interface.
 pub mod method {
   #[derive(Copy, Clone)]
   pub struct Owned(());
@@ -3020,14 +3516,24 @@ pub mod method {
     pub fn has_name(&self) -> bool {
       !self.reader.get_pointer_field(0).is_null()
     }
+    /// Specifies order in which the methods were declared in the code.
+    /// Like Struct.Field.codeOrder.
     #[inline]
     pub fn get_code_order(self) -> u16 {
       self.reader.get_data_field::<u16>(0)
     }
+    /// ID of the parameter struct type.  If a named parameter list was specified in the method
+    /// declaration (rather than a single struct parameter type) then a corresponding struct type is
+    /// auto-generated.  Such an auto-generated type will not be listed in the interface's
+    /// `nestedNodes` and its `scopeId` will be zero -- it is completely detached from the namespace.
+    /// (Awkwardly, it does of course inherit generic parameters from the method's scope, which makes
+    /// this a situation where you can't just climb the scope chain to find where a particular
+    /// generic parameter was introduced. Making the `scopeId` zero was a mistake.)
     #[inline]
     pub fn get_param_struct_type(self) -> u64 {
       self.reader.get_data_field::<u64>(1)
     }
+    /// ID of the return struct type; similar to `paramStructType`.
     #[inline]
     pub fn get_result_struct_type(self) -> u64 {
       self.reader.get_data_field::<u64>(2)
@@ -3039,6 +3545,7 @@ pub mod method {
     pub fn has_annotations(&self) -> bool {
       !self.reader.get_pointer_field(1).is_null()
     }
+    /// Brand of param struct type.
     #[inline]
     pub fn get_param_brand(self) -> ::capnp::Result<crate::schema_capnp::brand::Reader<'a>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(2), ::core::option::Option::None)
@@ -3046,6 +3553,7 @@ pub mod method {
     pub fn has_param_brand(&self) -> bool {
       !self.reader.get_pointer_field(2).is_null()
     }
+    /// Brand of result struct type.
     #[inline]
     pub fn get_result_brand(self) -> ::capnp::Result<crate::schema_capnp::brand::Reader<'a>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(3), ::core::option::Option::None)
@@ -3053,6 +3561,8 @@ pub mod method {
     pub fn has_result_brand(&self) -> bool {
       !self.reader.get_pointer_field(3).is_null()
     }
+    /// The parameters listed in [] (typically, type / generic parameters), whose bindings are intended
+    /// to be inferred rather than specified explicitly, although not all languages support this.
     #[inline]
     pub fn get_implicit_parameters(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::node::parameter::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(4), ::core::option::Option::None)
@@ -3126,25 +3636,49 @@ pub mod method {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn clear_name(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
+    /// Specifies order in which the methods were declared in the code.
+    /// Like Struct.Field.codeOrder.
+    #[inline]
     pub fn get_code_order(self) -> u16 {
       self.builder.get_data_field::<u16>(0)
     }
+    /// Specifies order in which the methods were declared in the code.
+    /// Like Struct.Field.codeOrder.
     #[inline]
     pub fn set_code_order(&mut self, value: u16)  {
       self.builder.set_data_field::<u16>(0, value);
     }
+    /// ID of the parameter struct type.  If a named parameter list was specified in the method
+    /// declaration (rather than a single struct parameter type) then a corresponding struct type is
+    /// auto-generated.  Such an auto-generated type will not be listed in the interface's
+    /// `nestedNodes` and its `scopeId` will be zero -- it is completely detached from the namespace.
+    /// (Awkwardly, it does of course inherit generic parameters from the method's scope, which makes
+    /// this a situation where you can't just climb the scope chain to find where a particular
+    /// generic parameter was introduced. Making the `scopeId` zero was a mistake.)
     #[inline]
     pub fn get_param_struct_type(self) -> u64 {
       self.builder.get_data_field::<u64>(1)
     }
+    /// ID of the parameter struct type.  If a named parameter list was specified in the method
+    /// declaration (rather than a single struct parameter type) then a corresponding struct type is
+    /// auto-generated.  Such an auto-generated type will not be listed in the interface's
+    /// `nestedNodes` and its `scopeId` will be zero -- it is completely detached from the namespace.
+    /// (Awkwardly, it does of course inherit generic parameters from the method's scope, which makes
+    /// this a situation where you can't just climb the scope chain to find where a particular
+    /// generic parameter was introduced. Making the `scopeId` zero was a mistake.)
     #[inline]
     pub fn set_param_struct_type(&mut self, value: u64)  {
       self.builder.set_data_field::<u64>(1, value);
     }
+    /// ID of the return struct type; similar to `paramStructType`.
     #[inline]
     pub fn get_result_struct_type(self) -> u64 {
       self.builder.get_data_field::<u64>(2)
     }
+    /// ID of the return struct type; similar to `paramStructType`.
     #[inline]
     pub fn set_result_struct_type(&mut self, value: u64)  {
       self.builder.set_data_field::<u64>(2, value);
@@ -3165,13 +3699,20 @@ pub mod method {
       !self.builder.get_pointer_field(1).is_null()
     }
     #[inline]
+    pub fn clear_annotations(&mut self) {
+      self.builder.get_pointer_field(1).clear()
+    }
+    /// Brand of param struct type.
+    #[inline]
     pub fn get_param_brand(self) -> ::capnp::Result<crate::schema_capnp::brand::Builder<'a>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(2), ::core::option::Option::None)
     }
+    /// Brand of param struct type.
     #[inline]
     pub fn set_param_brand(&mut self, value: crate::schema_capnp::brand::Reader<'_>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(2), value, false)
     }
+    /// Brand of param struct type.
     #[inline]
     pub fn init_param_brand(self, ) -> crate::schema_capnp::brand::Builder<'a> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(2), 0)
@@ -3180,13 +3721,20 @@ pub mod method {
       !self.builder.get_pointer_field(2).is_null()
     }
     #[inline]
+    pub fn clear_param_brand(&mut self) {
+      self.builder.get_pointer_field(2).clear()
+    }
+    /// Brand of result struct type.
+    #[inline]
     pub fn get_result_brand(self) -> ::capnp::Result<crate::schema_capnp::brand::Builder<'a>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::core::option::Option::None)
     }
+    /// Brand of result struct type.
     #[inline]
     pub fn set_result_brand(&mut self, value: crate::schema_capnp::brand::Reader<'_>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(3), value, false)
     }
+    /// Brand of result struct type.
     #[inline]
     pub fn init_result_brand(self, ) -> crate::schema_capnp::brand::Builder<'a> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(3), 0)
@@ -3195,13 +3743,23 @@ pub mod method {
       !self.builder.get_pointer_field(3).is_null()
     }
     #[inline]
+    pub fn clear_result_brand(&mut self) {
+      self.builder.get_pointer_field(3).clear()
+    }
+    /// The parameters listed in [] (typically, type / generic parameters), whose bindings are intended
+    /// to be inferred rather than specified explicitly, although not all languages support this.
+    #[inline]
     pub fn get_implicit_parameters(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::node::parameter::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(4), ::core::option::Option::None)
     }
+    /// The parameters listed in [] (typically, type / generic parameters), whose bindings are intended
+    /// to be inferred rather than specified explicitly, although not all languages support this.
     #[inline]
     pub fn set_implicit_parameters(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::node::parameter::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(4), value, false)
     }
+    /// The parameters listed in [] (typically, type / generic parameters), whose bindings are intended
+    /// to be inferred rather than specified explicitly, although not all languages support this.
     #[inline]
     pub fn init_implicit_parameters(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::node::parameter::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(4), size)
@@ -3209,6 +3767,10 @@ pub mod method {
     pub fn has_implicit_parameters(&self) -> bool {
       !self.builder.get_pointer_field(4).is_null()
     }
+    #[inline]
+    pub fn clear_implicit_parameters(&mut self) {
+      self.builder.get_pointer_field(4).clear()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -3232,6 +3794,7 @@ pub mod method {
   }
 }
 
+/// Represents a type expression.
 pub mod type_ {
   pub use self::Which::{Void,Bool,Int8,Int16,Int32,Int64,Uint8,Uint16,Uint32,Uint64,Float32,Float64,Text,Data,List,Enum,Struct,Interface,AnyPointer};
 
@@ -3281,7 +3844,83 @@ pub mod type_ {
       self.reader.total_size()
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
+    pub fn is_void(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 0
+    }
+    #[inline]
+    pub fn is_bool(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 1
+    }
+    #[inline]
+    pub fn is_int8(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 2
+    }
+    #[inline]
+    pub fn is_int16(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 3
+    }
+    #[inline]
+    pub fn is_int32(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 4
+    }
+    #[inline]
+    pub fn is_int64(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 5
+    }
+    #[inline]
+    pub fn is_uint8(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 6
+    }
+    #[inline]
+    pub fn is_uint16(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 7
+    }
+    #[inline]
+    pub fn is_uint32(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 8
+    }
+    #[inline]
+    pub fn is_uint64(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 9
+    }
+    #[inline]
+    pub fn is_float32(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 10
+    }
+    #[inline]
+    pub fn is_float64(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 11
+    }
+    #[inline]
+    pub fn is_text(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 12
+    }
+    #[inline]
+    pub fn is_data(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 13
+    }
+    #[inline]
+    pub fn is_list(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 14
+    }
+    #[inline]
+    pub fn is_enum(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 15
+    }
+    #[inline]
+    pub fn is_struct(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 16
+    }
+    #[inline]
+    pub fn is_interface(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 17
+    }
+    #[inline]
+    pub fn is_any_pointer(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 18
+    }
+    #[inline]
+    pub fn which(self) -> ::core::result::Result<WhichReader<'a>, ::capnp::NotInSchema> {
       match self.reader.get_data_field::<u16>(0) {
         0 => {
           ::core::result::Result::Ok(Void(
@@ -3436,64 +4075,124 @@ pub mod type_ {
       self.builder.set_data_field::<u16>(0, 0);
     }
     #[inline]
+    pub fn is_void(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 0
+    }
+    #[inline]
     pub fn set_bool(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 1);
     }
     #[inline]
+    pub fn is_bool(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 1
+    }
+    #[inline]
     pub fn set_int8(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 2);
     }
     #[inline]
+    pub fn is_int8(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 2
+    }
+    #[inline]
     pub fn set_int16(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 3);
     }
     #[inline]
+    pub fn is_int16(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 3
+    }
+    #[inline]
     pub fn set_int32(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 4);
     }
     #[inline]
+    pub fn is_int32(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 4
+    }
+    #[inline]
     pub fn set_int64(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 5);
     }
     #[inline]
+    pub fn is_int64(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 5
+    }
+    #[inline]
     pub fn set_uint8(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 6);
     }
     #[inline]
+    pub fn is_uint8(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 6
+    }
+    #[inline]
     pub fn set_uint16(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 7);
     }
     #[inline]
+    pub fn is_uint16(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 7
+    }
+    #[inline]
     pub fn set_uint32(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 8);
     }
     #[inline]
+    pub fn is_uint32(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 8
+    }
+    #[inline]
     pub fn set_uint64(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 9);
     }
     #[inline]
+    pub fn is_uint64(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 9
+    }
+    #[inline]
     pub fn set_float32(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 10);
     }
     #[inline]
+    pub fn is_float32(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 10
+    }
+    #[inline]
     pub fn set_float64(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 11);
     }
     #[inline]
+    pub fn is_float64(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 11
+    }
+    #[inline]
     pub fn set_text(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 12);
     }
     #[inline]
+    pub fn is_text(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 12
+    }
+    #[inline]
     pub fn set_data(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 13);
     }
     #[inline]
+    pub fn is_data(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 13
+    }
+    #[inline]
     pub fn init_list(self, ) -> crate::schema_capnp::type_::list::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 14);
       self.builder.get_pointer_field(0).clear();
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_list(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 14
+    }
+    #[inline]
     pub fn init_enum(self, ) -> crate::schema_capnp::type_::enum_::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 15);
       self.builder.set_data_field::<u64>(1, 0u64);
@@ -3501,6 +4200,10 @@ pub mod type_ {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_enum(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 15
+    }
+    #[inline]
     pub fn init_struct(self, ) -> crate::schema_capnp::type_::struct_::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 16);
       self.builder.set_data_field::<u64>(1, 0u64);
@@ -3508,6 +4211,10 @@ pub mod type_ {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_struct(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 16
+    }
+    #[inline]
     pub fn init_interface(self, ) -> crate::schema_capnp::type_::interface::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 17);
       self.builder.set_data_field::<u64>(1, 0u64);
@@ -3515,6 +4222,10 @@ pub mod type_ {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
+    pub fn is_interface(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 17
+    }
+    #[inline]
     pub fn init_any_pointer(self, ) -> crate::schema_capnp::type_::any_pointer::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 18);
       self.builder.set_data_field::<u16>(4, 0);
@@ -3525,7 +4236,11 @@ pub mod type_ {
       ::capnp::traits::FromStructBuilder::new(self.builder)
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
+    pub fn is_any_pointer(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 18
+    }
+    #[inline]
+    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a>, ::capnp::NotInSchema> {
       match self.builder.get_data_field::<u16>(0) {
         0 => {
           ::core::result::Result::Ok(Void(
@@ -3625,6 +4340,10 @@ pub mod type_ {
         x => ::core::result::Result::Err(::capnp::NotInSchema(x))
       }
     }
+    #[inline]
+    pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder<'_>, ::capnp::NotInSchema> {
+      self.reborrow().which()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -3661,8 +4380,8 @@ pub mod type_ {
     Interface(A3),
     AnyPointer(A4),
   }
-  pub type WhichReader<'a,> = Which<crate::schema_capnp::type_::list::Reader<'a>,crate::schema_capnp::type_::enum_::Reader<'a>,crate::schema_capnp::type_::struct_::Reader<'a>,crate::schema_capnp::type_::interface::Reader<'a>,crate::schema_capnp::type_::any_pointer::Reader<'a>>;
-  pub type WhichBuilder<'a,> = Which<crate::schema_capnp::type_::list::Builder<'a>,crate::schema_capnp::type_::enum_::Builder<'a>,crate::schema_capnp::type_::struct_::Builder<'a>,crate::schema_capnp::type_::interface::Builder<'a>,crate::schema_capnp::type_::any_pointer::Builder<'a>>;
+  pub type WhichReader<'a> = Which<crate::schema_capnp::type_::list::Reader<'a>,crate::schema_capnp::type_::enum_::Reader<'a>,crate::schema_capnp::type_::struct_::Reader<'a>,crate::schema_capnp::type_::interface::Reader<'a>,crate::schema_capnp::type_::any_pointer::Reader<'a>>;
+  pub type WhichBuilder<'a> = Which<crate::schema_capnp::type_::list::Builder<'a>,crate::schema_capnp::type_::enum_::Builder<'a>,crate::schema_capnp::type_::struct_::Builder<'a>,crate::schema_capnp::type_::interface::Builder<'a>,crate::schema_capnp::type_::any_pointer::Builder<'a>>;
 
   pub mod list {
     #[derive(Copy, Clone)]
@@ -3782,6 +4501,10 @@ pub mod type_ {
       pub fn has_element_type(&self) -> bool {
         !self.builder.get_pointer_field(0).is_null()
       }
+      #[inline]
+      pub fn clear_element_type(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -3932,6 +4655,10 @@ pub mod type_ {
       pub fn has_brand(&self) -> bool {
         !self.builder.get_pointer_field(0).is_null()
       }
+      #[inline]
+      pub fn clear_brand(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -4082,6 +4809,10 @@ pub mod type_ {
       pub fn has_brand(&self) -> bool {
         !self.builder.get_pointer_field(0).is_null()
       }
+      #[inline]
+      pub fn clear_brand(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -4232,6 +4963,10 @@ pub mod type_ {
       pub fn has_brand(&self) -> bool {
         !self.builder.get_pointer_field(0).is_null()
       }
+      #[inline]
+      pub fn clear_brand(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -4301,7 +5036,19 @@ pub mod type_ {
         self.reader.total_size()
       }
       #[inline]
-      pub fn which(self) -> ::core::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
+      pub fn is_unconstrained(&self) -> bool {
+        self.reader.get_data_field::<u16>(4) == 0
+      }
+      #[inline]
+      pub fn is_parameter(&self) -> bool {
+        self.reader.get_data_field::<u16>(4) == 1
+      }
+      #[inline]
+      pub fn is_implicit_method_parameter(&self) -> bool {
+        self.reader.get_data_field::<u16>(4) == 2
+      }
+      #[inline]
+      pub fn which(self) -> ::core::result::Result<WhichReader<'a>, ::capnp::NotInSchema> {
         match self.reader.get_data_field::<u16>(4) {
           0 => {
             ::core::result::Result::Ok(Unconstrained(
@@ -4371,6 +5118,11 @@ pub mod type_ {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// A regular AnyPointer.
+      ///
+      /// The name "unconstrained" means as opposed to constraining it to match a type parameter.
+      /// In retrospect this name is probably a poor choice given that it may still be constrained
+      /// to be a struct, list, or capability.
       #[inline]
       pub fn init_unconstrained(self, ) -> crate::schema_capnp::type_::any_pointer::unconstrained::Builder<'a> {
         self.builder.set_data_field::<u16>(4, 0);
@@ -4378,6 +5130,11 @@ pub mod type_ {
         ::capnp::traits::FromStructBuilder::new(self.builder)
       }
       #[inline]
+      pub fn is_unconstrained(&self) -> bool {
+        self.builder.get_data_field::<u16>(4) == 0
+      }
+      /// This is actually a reference to a type parameter defined within this scope.
+      #[inline]
       pub fn init_parameter(self, ) -> crate::schema_capnp::type_::any_pointer::parameter::Builder<'a> {
         self.builder.set_data_field::<u16>(4, 1);
         self.builder.set_data_field::<u64>(2, 0u64);
@@ -4385,13 +5142,23 @@ pub mod type_ {
         ::capnp::traits::FromStructBuilder::new(self.builder)
       }
       #[inline]
+      pub fn is_parameter(&self) -> bool {
+        self.builder.get_data_field::<u16>(4) == 1
+      }
+      /// This is actually a reference to an implicit (generic) parameter of a method. The only
+      /// legal context for this type to appear is inside Method.paramBrand or Method.resultBrand.
+      #[inline]
       pub fn init_implicit_method_parameter(self, ) -> crate::schema_capnp::type_::any_pointer::implicit_method_parameter::Builder<'a> {
         self.builder.set_data_field::<u16>(4, 2);
         self.builder.set_data_field::<u16>(5, 0u16);
         ::capnp::traits::FromStructBuilder::new(self.builder)
       }
       #[inline]
-      pub fn which(self) -> ::core::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
+      pub fn is_implicit_method_parameter(&self) -> bool {
+        self.builder.get_data_field::<u16>(4) == 2
+      }
+      #[inline]
+      pub fn which(self) -> ::core::result::Result<WhichBuilder<'a>, ::capnp::NotInSchema> {
         match self.builder.get_data_field::<u16>(4) {
           0 => {
             ::core::result::Result::Ok(Unconstrained(
@@ -4411,6 +5178,10 @@ pub mod type_ {
           x => ::core::result::Result::Err(::capnp::NotInSchema(x))
         }
       }
+      #[inline]
+      pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder<'_>, ::capnp::NotInSchema> {
+        self.reborrow().which()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -4427,13 +5198,26 @@ pub mod type_ {
       pub const TYPE_ID: u64 = 0xc257_3fe8_a23e_49f1;
     }
     pub enum Which<A0,A1,A2> {
+      /// A regular AnyPointer.
+      ///
+      /// The name "unconstrained" means as opposed to constraining it to match a type parameter.
+      /// In retrospect this name is probably a poor choice given that it may still be constrained
+      /// to be a struct, list, or capability.
       Unconstrained(A0),
+      /// This is actually a reference to a type parameter defined within this scope.
       Parameter(A1),
+      /// This is actually a reference to an implicit (generic) parameter of a method. The only
+      /// legal context for this type to appear is inside Method.paramBrand or Method.resultBrand.
       ImplicitMethodParameter(A2),
     }
-    pub type WhichReader<'a,> = Which<crate::schema_capnp::type_::any_pointer::unconstrained::Reader<'a>,crate::schema_capnp::type_::any_pointer::parameter::Reader<'a>,crate::schema_capnp::type_::any_pointer::implicit_method_parameter::Reader<'a>>;
-    pub type WhichBuilder<'a,> = Which<crate::schema_capnp::type_::any_pointer::unconstrained::Builder<'a>,crate::schema_capnp::type_::any_pointer::parameter::Builder<'a>,crate::schema_capnp::type_::any_pointer::implicit_method_parameter::Builder<'a>>;
+    pub type WhichReader<'a> = Which<crate::schema_capnp::type_::any_pointer::unconstrained::Reader<'a>,crate::schema_capnp::type_::any_pointer::parameter::Reader<'a>,crate::schema_capnp::type_::any_pointer::implicit_method_parameter::Reader<'a>>;
+    pub type WhichBuilder<'a> = Which<crate::schema_capnp::type_::any_pointer::unconstrained::Builder<'a>,crate::schema_capnp::type_::any_pointer::parameter::Builder<'a>,crate::schema_capnp::type_::any_pointer::implicit_method_parameter::Builder<'a>>;
 
+    /// A regular AnyPointer.
+    ///
+    /// The name "unconstrained" means as opposed to constraining it to match a type parameter.
+    /// In retrospect this name is probably a poor choice given that it may still be constrained
+    /// to be a struct, list, or capability.
     pub mod unconstrained {
       pub use self::Which::{AnyKind,Struct,List,Capability};
 
@@ -4483,6 +5267,22 @@ pub mod type_ {
           self.reader.total_size()
         }
         #[inline]
+        pub fn is_any_kind(&self) -> bool {
+          self.reader.get_data_field::<u16>(5) == 0
+        }
+        #[inline]
+        pub fn is_struct(&self) -> bool {
+          self.reader.get_data_field::<u16>(5) == 1
+        }
+        #[inline]
+        pub fn is_list(&self) -> bool {
+          self.reader.get_data_field::<u16>(5) == 2
+        }
+        #[inline]
+        pub fn is_capability(&self) -> bool {
+          self.reader.get_data_field::<u16>(5) == 3
+        }
+        #[inline]
         pub fn which(self) -> ::core::result::Result<WhichReader, ::capnp::NotInSchema> {
           match self.reader.get_data_field::<u16>(5) {
             0 => {
@@ -4558,23 +5358,43 @@ pub mod type_ {
         pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
           self.builder.into_reader().total_size()
         }
+        /// truly AnyPointer
         #[inline]
         pub fn set_any_kind(&mut self, _value: ())  {
           self.builder.set_data_field::<u16>(5, 0);
         }
         #[inline]
+        pub fn is_any_kind(&self) -> bool {
+          self.builder.get_data_field::<u16>(5) == 0
+        }
+        /// AnyStruct
+        #[inline]
         pub fn set_struct(&mut self, _value: ())  {
           self.builder.set_data_field::<u16>(5, 1);
         }
         #[inline]
+        pub fn is_struct(&self) -> bool {
+          self.builder.get_data_field::<u16>(5) == 1
+        }
+        /// AnyList
+        #[inline]
         pub fn set_list(&mut self, _value: ())  {
           self.builder.set_data_field::<u16>(5, 2);
         }
         #[inline]
+        pub fn is_list(&self) -> bool {
+          self.builder.get_data_field::<u16>(5) == 2
+        }
+        /// Capability
+        #[inline]
         pub fn set_capability(&mut self, _value: ())  {
           self.builder.set_data_field::<u16>(5, 3);
         }
         #[inline]
+        pub fn is_capability(&self) -> bool {
+          self.builder.get_data_field::<u16>(5) == 3
+        }
+        #[inline]
         pub fn which(self) -> ::core::result::Result<WhichBuilder, ::capnp::NotInSchema> {
           match self.builder.get_data_field::<u16>(5) {
             0 => {
@@ -4600,6 +5420,10 @@ pub mod type_ {
             x => ::core::result::Result::Err(::capnp::NotInSchema(x))
           }
         }
+        #[inline]
+        pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder, ::capnp::NotInSchema> {
+          self.reborrow().which()
+        }
       }
 
       pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -4616,15 +5440,20 @@ pub mod type_ {
         pub const TYPE_ID: u64 = 0x8e3b_5f79_fe59_3656;
       }
       pub enum Which {
+        /// truly AnyPointer
         AnyKind(()),
+        /// AnyStruct
         Struct(()),
+        /// AnyList
         List(()),
+        /// Capability
         Capability(()),
       }
       pub type WhichReader = Which;
       pub type WhichBuilder = Which;
     }
 
+    /// This is actually a reference to a type parameter defined within this scope.
     pub mod parameter {
       #[derive(Copy, Clone)]
       pub struct Owned(());
@@ -4671,10 +5500,13 @@ pub mod type_ {
         pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
           self.reader.total_size()
         }
+        /// ID of the generic type whose parameter we're referencing. This should be a parent of the
+        /// current scope.
         #[inline]
         pub fn get_scope_id(self) -> u64 {
           self.reader.get_data_field::<u64>(2)
         }
+        /// Index of the parameter within the generic type's parameter list.
         #[inline]
         pub fn get_parameter_index(self) -> u16 {
           self.reader.get_data_field::<u16>(5)
@@ -4729,18 +5561,24 @@ pub mod type_ {
         pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
           self.builder.into_reader().total_size()
         }
+        /// ID of the generic type whose parameter we're referencing. This should be a parent of the
+        /// current scope.
         #[inline]
         pub fn get_scope_id(self) -> u64 {
           self.builder.get_data_field::<u64>(2)
         }
+        /// ID of the generic type whose parameter we're referencing. This should be a parent of the
+        /// current scope.
         #[inline]
         pub fn set_scope_id(&mut self, value: u64)  {
           self.builder.set_data_field::<u64>(2, value);
         }
+        /// Index of the parameter within the generic type's parameter list.
         #[inline]
         pub fn get_parameter_index(self) -> u16 {
           self.builder.get_data_field::<u16>(5)
         }
+        /// Index of the parameter within the generic type's parameter list.
         #[inline]
         pub fn set_parameter_index(&mut self, value: u16)  {
           self.builder.set_data_field::<u16>(5, value);
@@ -4762,6 +5600,8 @@ pub mod type_ {
       }
     }
 
+    /// This is actually a reference to an implicit (generic) parameter of a method. The only
+    /// legal context for this type to appear is inside Method.paramBrand or Method.resultBrand.
     pub mod implicit_method_parameter {
       #[derive(Copy, Clone)]
       pub struct Owned(());
@@ -4889,6 +5729,8 @@ pub mod type_ {
   }
 }
 
+/// Specifies bindings for parameters of generics. Since these bindings turn a generic into a
+/// non-generic, we call it the "brand".
 pub mod brand {
   #[derive(Copy, Clone)]
   pub struct Owned(());
@@ -4935,6 +5777,9 @@ pub mod brand {
     pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
       self.reader.total_size()
     }
+    /// For each of the target type and each of its parent scopes, a parameterization may be included
+    /// in this list. If no parameterization is included for a particular relevant scope, then either
+    /// that scope has no parameters or all parameters should be considered to be `AnyPointer`.
     #[inline]
     pub fn get_scopes(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::brand::scope::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -4992,14 +5837,23 @@ pub mod brand {
     pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
       self.builder.into_reader().total_size()
     }
+    /// For each of the target type and each of its parent scopes, a parameterization may be included
+    /// in this list. If no parameterization is included for a particular relevant scope, then either
+    /// that scope has no parameters or all parameters should be considered to be `AnyPointer`.
     #[inline]
     pub fn get_scopes(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::brand::scope::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
     }
+    /// For each of the target type and each of its parent scopes, a parameterization may be included
+    /// in this list. If no parameterization is included for a particular relevant scope, then either
+    /// that scope has no parameters or all parameters should be considered to be `AnyPointer`.
     #[inline]
     pub fn set_scopes(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::brand::scope::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
     }
+    /// For each of the target type and each of its parent scopes, a parameterization may be included
+    /// in this list. If no parameterization is included for a particular relevant scope, then either
+    /// that scope has no parameters or all parameters should be considered to be `AnyPointer`.
     #[inline]
     pub fn init_scopes(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::brand::scope::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(0), size)
@@ -5007,6 +5861,10 @@ pub mod brand {
     pub fn has_scopes(&self) -> bool {
       !self.builder.get_pointer_field(0).is_null()
     }
+    #[inline]
+    pub fn clear_scopes(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -5071,6 +5929,7 @@ pub mod brand {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// ID of the scope to which these params apply.
       #[inline]
       pub fn get_scope_id(self) -> u64 {
         self.reader.get_data_field::<u64>(0)
@@ -5080,7 +5939,15 @@ pub mod brand {
         !self.reader.get_pointer_field(0).is_null()
       }
       #[inline]
-      pub fn which(self) -> ::core::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
+      pub fn is_bind(&self) -> bool {
+        self.reader.get_data_field::<u16>(4) == 0
+      }
+      #[inline]
+      pub fn is_inherit(&self) -> bool {
+        self.reader.get_data_field::<u16>(4) == 1
+      }
+      #[inline]
+      pub fn which(self) -> ::core::result::Result<WhichReader<'a>, ::capnp::NotInSchema> {
         match self.reader.get_data_field::<u16>(4) {
           0 => {
             ::core::result::Result::Ok(Bind(
@@ -5145,19 +6012,23 @@ pub mod brand {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// ID of the scope to which these params apply.
       #[inline]
       pub fn get_scope_id(self) -> u64 {
         self.builder.get_data_field::<u64>(0)
       }
+      /// ID of the scope to which these params apply.
       #[inline]
       pub fn set_scope_id(&mut self, value: u64)  {
         self.builder.set_data_field::<u64>(0, value);
       }
+      /// List of parameter bindings.
       #[inline]
       pub fn set_bind(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::brand::binding::Owned>) -> ::capnp::Result<()> {
         self.builder.set_data_field::<u16>(4, 0);
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
       }
+      /// List of parameter bindings.
       #[inline]
       pub fn init_bind(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::brand::binding::Owned> {
         self.builder.set_data_field::<u16>(4, 0);
@@ -5168,11 +6039,39 @@ pub mod brand {
         !self.builder.get_pointer_field(0).is_null()
       }
       #[inline]
+      pub fn is_bind(&self) -> bool {
+        self.builder.get_data_field::<u16>(4) == 0
+      }
+      #[inline]
+      pub fn clear_bind(&mut self) {
+        self.builder.set_data_field::<u16>(4, 0);
+        self.builder.get_pointer_field(0).clear()
+      }
+      /// The place where the Brand appears is within this scope or a sub-scope, and bindings
+      /// for this scope are deferred to later Brand applications. This is equivalent to a
+      /// pass-through binding list, where each of this scope's parameters is bound to itself.
+      /// For example:
+      ///
+      ///   struct Outer(T) {
+      ///     struct Inner {
+      ///       value @0 :T;
+      ///     }
+      ///     innerInherit @0 :Inner;            # Outer Brand.Scope is `inherit`.
+      ///     innerBindSelf @1 :Outer(T).Inner;  # Outer Brand.Scope explicitly binds T to T.
+      ///   }
+      ///
+      /// The innerInherit and innerBindSelf fields have equivalent types, but different Brand
+      /// styles.
+      #[inline]
       pub fn set_inherit(&mut self, _value: ())  {
         self.builder.set_data_field::<u16>(4, 1);
       }
       #[inline]
-      pub fn which(self) -> ::core::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
+      pub fn is_inherit(&self) -> bool {
+        self.builder.get_data_field::<u16>(4) == 1
+      }
+      #[inline]
+      pub fn which(self) -> ::core::result::Result<WhichBuilder<'a>, ::capnp::NotInSchema> {
         match self.builder.get_data_field::<u16>(4) {
           0 => {
             ::core::result::Result::Ok(Bind(
@@ -5187,6 +6086,10 @@ pub mod brand {
           x => ::core::result::Result::Err(::capnp::NotInSchema(x))
         }
       }
+      #[inline]
+      pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder<'_>, ::capnp::NotInSchema> {
+        self.reborrow().which()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -5203,11 +6106,27 @@ pub mod brand {
       pub const TYPE_ID: u64 = 0xabd7_3485_a963_6bc9;
     }
     pub enum Which<A0> {
+      /// List of parameter bindings.
       Bind(A0),
+      /// The place where the Brand appears is within this scope or a sub-scope, and bindings
+      /// for this scope are deferred to later Brand applications. This is equivalent to a
+      /// pass-through binding list, where each of this scope's parameters is bound to itself.
+      /// For example:
+      ///
+      ///   struct Outer(T) {
+      ///     struct Inner {
+      ///       value @0 :T;
+      ///     }
+      ///     innerInherit @0 :Inner;            # Outer Brand.Scope is `inherit`.
+      ///     innerBindSelf @1 :Outer(T).Inner;  # Outer Brand.Scope explicitly binds T to T.
+      ///   }
+      ///
+      /// The innerInherit and innerBindSelf fields have equivalent types, but different Brand
+      /// styles.
       Inherit(()),
     }
-    pub type WhichReader<'a,> = Which<::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::brand::binding::Owned>>>;
-    pub type WhichBuilder<'a,> = Which<::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::brand::binding::Owned>>>;
+    pub type WhichReader<'a> = Which<::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::brand::binding::Owned>>>;
+    pub type WhichBuilder<'a> = Which<::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::brand::binding::Owned>>>;
   }
 
   pub mod binding {
@@ -5258,12 +6177,20 @@ pub mod brand {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      #[inline]
+      pub fn is_unbound(&self) -> bool {
+        self.reader.get_data_field::<u16>(0) == 0
+      }
       pub fn has_type(&self) -> bool {
         if self.reader.get_data_field::<u16>(0) != 1 { return false; }
         !self.reader.get_pointer_field(0).is_null()
       }
       #[inline]
-      pub fn which(self) -> ::core::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
+      pub fn is_type(&self) -> bool {
+        self.reader.get_data_field::<u16>(0) == 1
+      }
+      #[inline]
+      pub fn which(self) -> ::core::result::Result<WhichReader<'a>, ::capnp::NotInSchema> {
         match self.reader.get_data_field::<u16>(0) {
           0 => {
             ::core::result::Result::Ok(Unbound(
@@ -5333,6 +6260,10 @@ pub mod brand {
         self.builder.set_data_field::<u16>(0, 0);
       }
       #[inline]
+      pub fn is_unbound(&self) -> bool {
+        self.builder.get_data_field::<u16>(0) == 0
+      }
+      #[inline]
       pub fn set_type(&mut self, value: crate::schema_capnp::type_::Reader<'_>) -> ::capnp::Result<()> {
         self.builder.set_data_field::<u16>(0, 1);
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
@@ -5347,7 +6278,16 @@ pub mod brand {
         !self.builder.get_pointer_field(0).is_null()
       }
       #[inline]
-      pub fn which(self) -> ::core::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
+      pub fn is_type(&self) -> bool {
+        self.builder.get_data_field::<u16>(0) == 1
+      }
+      #[inline]
+      pub fn clear_type(&mut self) {
+        self.builder.set_data_field::<u16>(0, 1);
+        self.builder.get_pointer_field(0).clear()
+      }
+      #[inline]
+      pub fn which(self) -> ::core::result::Result<WhichBuilder<'a>, ::capnp::NotInSchema> {
         match self.builder.get_data_field::<u16>(0) {
           0 => {
             ::core::result::Result::Ok(Unbound(
@@ -5362,6 +6302,10 @@ pub mod brand {
           x => ::core::result::Result::Err(::capnp::NotInSchema(x))
         }
       }
+      #[inline]
+      pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder<'_>, ::capnp::NotInSchema> {
+        self.reborrow().which()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -5381,11 +6325,12 @@ pub mod brand {
       Unbound(()),
       Type(A0),
     }
-    pub type WhichReader<'a,> = Which<::capnp::Result<crate::schema_capnp::type_::Reader<'a>>>;
-    pub type WhichBuilder<'a,> = Which<::capnp::Result<crate::schema_capnp::type_::Builder<'a>>>;
+    pub type WhichReader<'a> = Which<::capnp::Result<crate::schema_capnp::type_::Reader<'a>>>;
+    pub type WhichBuilder<'a> = Which<::capnp::Result<crate::schema_capnp::type_::Builder<'a>>>;
   }
 }
 
+/// Represents a value, e.g. a field default value, constant value, or annotation value.
 pub mod value {
   pub use self::Which::{Void,Bool,Int8,Int16,Int32,Int64,Uint8,Uint16,Uint32,Uint64,Float32,Float64,Text,Data,List,Enum,Struct,Interface,AnyPointer};
 
@@ -5434,28 +6379,104 @@ pub mod value {
     pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
       self.reader.total_size()
     }
+    #[inline]
+    pub fn is_void(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 0
+    }
+    #[inline]
+    pub fn is_bool(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 1
+    }
+    #[inline]
+    pub fn is_int8(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 2
+    }
+    #[inline]
+    pub fn is_int16(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 3
+    }
+    #[inline]
+    pub fn is_int32(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 4
+    }
+    #[inline]
+    pub fn is_int64(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 5
+    }
+    #[inline]
+    pub fn is_uint8(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 6
+    }
+    #[inline]
+    pub fn is_uint16(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 7
+    }
+    #[inline]
+    pub fn is_uint32(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 8
+    }
+    #[inline]
+    pub fn is_uint64(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 9
+    }
+    #[inline]
+    pub fn is_float32(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 10
+    }
+    #[inline]
+    pub fn is_float64(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 11
+    }
     pub fn has_text(&self) -> bool {
       if self.reader.get_data_field::<u16>(0) != 12 { return false; }
       !self.reader.get_pointer_field(0).is_null()
     }
+    #[inline]
+    pub fn is_text(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 12
+    }
     pub fn has_data(&self) -> bool {
       if self.reader.get_data_field::<u16>(0) != 13 { return false; }
       !self.reader.get_pointer_field(0).is_null()
     }
+    #[inline]
+    pub fn is_data(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 13
+    }
     pub fn has_list(&self) -> bool {
       if self.reader.get_data_field::<u16>(0) != 14 { return false; }
       !self.reader.get_pointer_field(0).is_null()
     }
+    #[inline]
+    pub fn is_list(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 14
+    }
+    #[inline]
+    pub fn is_enum(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 15
+    }
     pub fn has_struct(&self) -> bool {
       if self.reader.get_data_field::<u16>(0) != 16 { return false; }
       !self.reader.get_pointer_field(0).is_null()
     }
+    #[inline]
+    pub fn is_struct(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 16
+    }
+    #[inline]
+    pub fn is_interface(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 17
+    }
     pub fn has_any_pointer(&self) -> bool {
       if self.reader.get_data_field::<u16>(0) != 18 { return false; }
       !self.reader.get_pointer_field(0).is_null()
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichReader<'a,>, ::capnp::NotInSchema> {
+    pub fn is_any_pointer(&self) -> bool {
+      self.reader.get_data_field::<u16>(0) == 18
+    }
+    #[inline]
+    pub fn which(self) -> ::core::result::Result<WhichReader<'a>, ::capnp::NotInSchema> {
       match self.reader.get_data_field::<u16>(0) {
         0 => {
           ::core::result::Result::Ok(Void(
@@ -5610,61 +6631,109 @@ pub mod value {
       self.builder.set_data_field::<u16>(0, 0);
     }
     #[inline]
+    pub fn is_void(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 0
+    }
+    #[inline]
     pub fn set_bool(&mut self, value: bool)  {
       self.builder.set_data_field::<u16>(0, 1);
       self.builder.set_bool_field(16, value);
     }
     #[inline]
+    pub fn is_bool(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 1
+    }
+    #[inline]
     pub fn set_int8(&mut self, value: i8)  {
       self.builder.set_data_field::<u16>(0, 2);
       self.builder.set_data_field::<i8>(2, value);
     }
     #[inline]
+    pub fn is_int8(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 2
+    }
+    #[inline]
     pub fn set_int16(&mut self, value: i16)  {
       self.builder.set_data_field::<u16>(0, 3);
       self.builder.set_data_field::<i16>(1, value);
     }
     #[inline]
+    pub fn is_int16(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 3
+    }
+    #[inline]
     pub fn set_int32(&mut self, value: i32)  {
       self.builder.set_data_field::<u16>(0, 4);
       self.builder.set_data_field::<i32>(1, value);
     }
     #[inline]
+    pub fn is_int32(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 4
+    }
+    #[inline]
     pub fn set_int64(&mut self, value: i64)  {
       self.builder.set_data_field::<u16>(0, 5);
       self.builder.set_data_field::<i64>(1, value);
     }
     #[inline]
+    pub fn is_int64(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 5
+    }
+    #[inline]
     pub fn set_uint8(&mut self, value: u8)  {
       self.builder.set_data_field::<u16>(0, 6);
       self.builder.set_data_field::<u8>(2, value);
     }
     #[inline]
+    pub fn is_uint8(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 6
+    }
+    #[inline]
     pub fn set_uint16(&mut self, value: u16)  {
       self.builder.set_data_field::<u16>(0, 7);
       self.builder.set_data_field::<u16>(1, value);
     }
     #[inline]
+    pub fn is_uint16(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 7
+    }
+    #[inline]
     pub fn set_uint32(&mut self, value: u32)  {
       self.builder.set_data_field::<u16>(0, 8);
       self.builder.set_data_field::<u32>(1, value);
     }
     #[inline]
+    pub fn is_uint32(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 8
+    }
+    #[inline]
     pub fn set_uint64(&mut self, value: u64)  {
       self.builder.set_data_field::<u16>(0, 9);
       self.builder.set_data_field::<u64>(1, value);
     }
     #[inline]
+    pub fn is_uint64(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 9
+    }
+    #[inline]
     pub fn set_float32(&mut self, value: f32)  {
       self.builder.set_data_field::<u16>(0, 10);
       self.builder.set_data_field::<f32>(1, value);
     }
     #[inline]
+    pub fn is_float32(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 10
+    }
+    #[inline]
     pub fn set_float64(&mut self, value: f64)  {
       self.builder.set_data_field::<u16>(0, 11);
       self.builder.set_data_field::<f64>(1, value);
     }
     #[inline]
+    pub fn is_float64(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 11
+    }
+    #[inline]
     pub fn set_text(&mut self, value: ::capnp::text::Reader<'_>)  {
       self.builder.set_data_field::<u16>(0, 12);
       self.builder.get_pointer_field(0).set_text(value);
@@ -5679,6 +6748,15 @@ pub mod value {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn is_text(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 12
+    }
+    #[inline]
+    pub fn clear_text(&mut self) {
+      self.builder.set_data_field::<u16>(0, 12);
+      self.builder.get_pointer_field(0).clear()
+    }
+    #[inline]
     pub fn set_data(&mut self, value: ::capnp::data::Reader<'_>)  {
       self.builder.set_data_field::<u16>(0, 13);
       self.builder.get_pointer_field(0).set_data(value);
@@ -5693,6 +6771,15 @@ pub mod value {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn is_data(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 13
+    }
+    #[inline]
+    pub fn clear_data(&mut self) {
+      self.builder.set_data_field::<u16>(0, 13);
+      self.builder.get_pointer_field(0).clear()
+    }
+    #[inline]
     pub fn init_list(self, ) -> ::capnp::any_pointer::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 14);
       let mut result = ::capnp::any_pointer::Builder::new(self.builder.get_pointer_field(0));
@@ -5704,11 +6791,24 @@ pub mod value {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn is_list(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 14
+    }
+    #[inline]
+    pub fn clear_list(&mut self) {
+      self.builder.set_data_field::<u16>(0, 14);
+      self.builder.get_pointer_field(0).clear()
+    }
+    #[inline]
     pub fn set_enum(&mut self, value: u16)  {
       self.builder.set_data_field::<u16>(0, 15);
       self.builder.set_data_field::<u16>(1, value);
     }
     #[inline]
+    pub fn is_enum(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 15
+    }
+    #[inline]
     pub fn init_struct(self, ) -> ::capnp::any_pointer::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 16);
       let mut result = ::capnp::any_pointer::Builder::new(self.builder.get_pointer_field(0));
@@ -5720,10 +6820,25 @@ pub mod value {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn is_struct(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 16
+    }
+    #[inline]
+    pub fn clear_struct(&mut self) {
+      self.builder.set_data_field::<u16>(0, 16);
+      self.builder.get_pointer_field(0).clear()
+    }
+    /// The only interface value that can be represented statically is "null", whose methods always
+    /// throw exceptions.
+    #[inline]
     pub fn set_interface(&mut self, _value: ())  {
       self.builder.set_data_field::<u16>(0, 17);
     }
     #[inline]
+    pub fn is_interface(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 17
+    }
+    #[inline]
     pub fn init_any_pointer(self, ) -> ::capnp::any_pointer::Builder<'a> {
       self.builder.set_data_field::<u16>(0, 18);
       let mut result = ::capnp::any_pointer::Builder::new(self.builder.get_pointer_field(0));
@@ -5735,7 +6850,16 @@ pub mod value {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
-    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a,>, ::capnp::NotInSchema> {
+    pub fn is_any_pointer(&self) -> bool {
+      self.builder.get_data_field::<u16>(0) == 18
+    }
+    #[inline]
+    pub fn clear_any_pointer(&mut self) {
+      self.builder.set_data_field::<u16>(0, 18);
+      self.builder.get_pointer_field(0).clear()
+    }
+    #[inline]
+    pub fn which(self) -> ::core::result::Result<WhichBuilder<'a>, ::capnp::NotInSchema> {
       match self.builder.get_data_field::<u16>(0) {
         0 => {
           ::core::result::Result::Ok(Void(
@@ -5835,6 +6959,10 @@ pub mod value {
         x => ::core::result::Result::Err(::capnp::NotInSchema(x))
       }
     }
+    #[inline]
+    pub fn which_mut(&mut self) -> ::core::result::Result<WhichBuilder<'_>, ::capnp::NotInSchema> {
+      self.reborrow().which()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -5868,13 +6996,17 @@ pub mod value {
     List(A2),
     Enum(u16),
     Struct(A3),
+    /// The only interface value that can be represented statically is "null", whose methods always
+    /// throw exceptions.
     Interface(()),
     AnyPointer(A4),
   }
-  pub type WhichReader<'a,> = Which<::capnp::Result<::capnp::text::Reader<'a>>,::capnp::Result<::capnp::data::Reader<'a>>,::capnp::any_pointer::Reader<'a>,::capnp::any_pointer::Reader<'a>,::capnp::any_pointer::Reader<'a>>;
-  pub type WhichBuilder<'a,> = Which<::capnp::Result<::capnp::text::Builder<'a>>,::capnp::Result<::capnp::data::Builder<'a>>,::capnp::any_pointer::Builder<'a>,::capnp::any_pointer::Builder<'a>,::capnp::any_pointer::Builder<'a>>;
+  pub type WhichReader<'a> = Which<::capnp::Result<::capnp::text::Reader<'a>>,::capnp::Result<::capnp::data::Reader<'a>>,::capnp::any_pointer::Reader<'a>,::capnp::any_pointer::Reader<'a>,::capnp::any_pointer::Reader<'a>>;
+  pub type WhichBuilder<'a> = Which<::capnp::Result<::capnp::text::Builder<'a>>,::capnp::Result<::capnp::data::Builder<'a>>,::capnp::any_pointer::Builder<'a>,::capnp::any_pointer::Builder<'a>,::capnp::any_pointer::Builder<'a>>;
 }
 
+/// Describes an annotation applied to a declaration.  Note AnnotationNode describes the
+/// annotation's declaration, while this describes a use of the annotation.
 pub mod annotation {
   #[derive(Copy, Clone)]
   pub struct Owned(());
@@ -5921,6 +7053,7 @@ pub mod annotation {
     pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
       self.reader.total_size()
     }
+    /// ID of the annotation node.
     #[inline]
     pub fn get_id(self) -> u64 {
       self.reader.get_data_field::<u64>(0)
@@ -5932,6 +7065,9 @@ pub mod annotation {
     pub fn has_value(&self) -> bool {
       !self.reader.get_pointer_field(0).is_null()
     }
+    /// Brand of the annotation.
+    ///
+    /// Note that the annotation itself is not allowed to be parameterized, but its scope might be.
     #[inline]
     pub fn get_brand(self) -> ::capnp::Result<crate::schema_capnp::brand::Reader<'a>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::core::option::Option::None)
@@ -5989,10 +7125,12 @@ pub mod annotation {
     pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
       self.builder.into_reader().total_size()
     }
+    /// ID of the annotation node.
     #[inline]
     pub fn get_id(self) -> u64 {
       self.builder.get_data_field::<u64>(0)
     }
+    /// ID of the annotation node.
     #[inline]
     pub fn set_id(&mut self, value: u64)  {
       self.builder.set_data_field::<u64>(0, value);
@@ -6013,13 +7151,26 @@ pub mod annotation {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn clear_value(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
+    /// Brand of the annotation.
+    ///
+    /// Note that the annotation itself is not allowed to be parameterized, but its scope might be.
+    #[inline]
     pub fn get_brand(self) -> ::capnp::Result<crate::schema_capnp::brand::Builder<'a>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::core::option::Option::None)
     }
+    /// Brand of the annotation.
+    ///
+    /// Note that the annotation itself is not allowed to be parameterized, but its scope might be.
     #[inline]
     pub fn set_brand(&mut self, value: crate::schema_capnp::brand::Reader<'_>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
     }
+    /// Brand of the annotation.
+    ///
+    /// Note that the annotation itself is not allowed to be parameterized, but its scope might be.
     #[inline]
     pub fn init_brand(self, ) -> crate::schema_capnp::brand::Builder<'a> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), 0)
@@ -6027,6 +7178,10 @@ pub mod annotation {
     pub fn has_brand(&self) -> bool {
       !self.builder.get_pointer_field(1).is_null()
     }
+    #[inline]
+    pub fn clear_brand(&mut self) {
+      self.builder.get_pointer_field(1).clear()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -6050,9 +7205,12 @@ pub mod annotation {
   }
 }
 
+/// Possible element sizes for encoded lists.  These correspond exactly to the possible values of
+/// the 3-bit element size component of a list pointer.
 #[repr(u16)]
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ElementSize {
+  /// aka "void", but that's a keyword.
   Empty = 0,
   Bit = 1,
   Byte = 2,
@@ -6282,6 +7440,8 @@ pub mod code_generator_request {
     pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
       self.reader.total_size()
     }
+    /// All nodes parsed by the compiler, including for the files on the command line and their
+    /// imports.
     #[inline]
     pub fn get_nodes(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::node::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -6289,6 +7449,7 @@ pub mod code_generator_request {
     pub fn has_nodes(&self) -> bool {
       !self.reader.get_pointer_field(0).is_null()
     }
+    /// Files which were listed on the command line.
     #[inline]
     pub fn get_requested_files(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::code_generator_request::requested_file::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::core::option::Option::None)
@@ -6296,6 +7457,12 @@ pub mod code_generator_request {
     pub fn has_requested_files(&self) -> bool {
       !self.reader.get_pointer_field(1).is_null()
     }
+    /// Version of the `capnp` executable. Generally, code generators should ignore this, but the code
+    /// generators that ship with `capnp` itself will print a warning if this mismatches since that
+    /// probably indicates something is misconfigured.
+    ///
+    /// The first version of 'capnp' to set this was 0.6.0. So, if it's missing, the compiler version
+    /// is older than that.
     #[inline]
     pub fn get_capnp_version(self) -> ::capnp::Result<crate::schema_capnp::capnp_version::Reader<'a>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(2), ::core::option::Option::None)
@@ -6303,6 +7470,8 @@ pub mod code_generator_request {
     pub fn has_capnp_version(&self) -> bool {
       !self.reader.get_pointer_field(2).is_null()
     }
+    /// Information about the original source code for each node, where available. This array may be
+    /// omitted or may be missing some nodes if no info is available for them.
     #[inline]
     pub fn get_source_info(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::node::source_info::Owned>> {
       ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(3), ::core::option::Option::None)
@@ -6360,14 +7529,20 @@ pub mod code_generator_request {
     pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
       self.builder.into_reader().total_size()
     }
+    /// All nodes parsed by the compiler, including for the files on the command line and their
+    /// imports.
     #[inline]
     pub fn get_nodes(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::node::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
     }
+    /// All nodes parsed by the compiler, including for the files on the command line and their
+    /// imports.
     #[inline]
     pub fn set_nodes(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::node::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(0), value, false)
     }
+    /// All nodes parsed by the compiler, including for the files on the command line and their
+    /// imports.
     #[inline]
     pub fn init_nodes(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::node::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(0), size)
@@ -6376,13 +7551,20 @@ pub mod code_generator_request {
       !self.builder.get_pointer_field(0).is_null()
     }
     #[inline]
+    pub fn clear_nodes(&mut self) {
+      self.builder.get_pointer_field(0).clear()
+    }
+    /// Files which were listed on the command line.
+    #[inline]
     pub fn get_requested_files(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::code_generator_request::requested_file::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::core::option::Option::None)
     }
+    /// Files which were listed on the command line.
     #[inline]
     pub fn set_requested_files(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::code_generator_request::requested_file::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
     }
+    /// Files which were listed on the command line.
     #[inline]
     pub fn init_requested_files(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::code_generator_request::requested_file::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
@@ -6391,13 +7573,35 @@ pub mod code_generator_request {
       !self.builder.get_pointer_field(1).is_null()
     }
     #[inline]
+    pub fn clear_requested_files(&mut self) {
+      self.builder.get_pointer_field(1).clear()
+    }
+    /// Version of the `capnp` executable. Generally, code generators should ignore this, but the code
+    /// generators that ship with `capnp` itself will print a warning if this mismatches since that
+    /// probably indicates something is misconfigured.
+    ///
+    /// The first version of 'capnp' to set this was 0.6.0. So, if it's missing, the compiler version
+    /// is older than that.
+    #[inline]
     pub fn get_capnp_version(self) -> ::capnp::Result<crate::schema_capnp::capnp_version::Builder<'a>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(2), ::core::option::Option::None)
     }
+    /// Version of the `capnp` executable. Generally, code generators should ignore this, but the code
+    /// generators that ship with `capnp` itself will print a warning if this mismatches since that
+    /// probably indicates something is misconfigured.
+    ///
+    /// The first version of 'capnp' to set this was 0.6.0. So, if it's missing, the compiler version
+    /// is older than that.
     #[inline]
     pub fn set_capnp_version(&mut self, value: crate::schema_capnp::capnp_version::Reader<'_>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(2), value, false)
     }
+    /// Version of the `capnp` executable. Generally, code generators should ignore this, but the code
+    /// generators that ship with `capnp` itself will print a warning if this mismatches since that
+    /// probably indicates something is misconfigured.
+    ///
+    /// The first version of 'capnp' to set this was 0.6.0. So, if it's missing, the compiler version
+    /// is older than that.
     #[inline]
     pub fn init_capnp_version(self, ) -> crate::schema_capnp::capnp_version::Builder<'a> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(2), 0)
@@ -6406,13 +7610,23 @@ pub mod code_generator_request {
       !self.builder.get_pointer_field(2).is_null()
     }
     #[inline]
+    pub fn clear_capnp_version(&mut self) {
+      self.builder.get_pointer_field(2).clear()
+    }
+    /// Information about the original source code for each node, where available. This array may be
+    /// omitted or may be missing some nodes if no info is available for them.
+    #[inline]
     pub fn get_source_info(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::node::source_info::Owned>> {
       ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(3), ::core::option::Option::None)
     }
+    /// Information about the original source code for each node, where available. This array may be
+    /// omitted or may be missing some nodes if no info is available for them.
     #[inline]
     pub fn set_source_info(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::node::source_info::Owned>) -> ::capnp::Result<()> {
       ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(3), value, false)
     }
+    /// Information about the original source code for each node, where available. This array may be
+    /// omitted or may be missing some nodes if no info is available for them.
     #[inline]
     pub fn init_source_info(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::node::source_info::Owned> {
       ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(3), size)
@@ -6420,6 +7634,10 @@ pub mod code_generator_request {
     pub fn has_source_info(&self) -> bool {
       !self.builder.get_pointer_field(3).is_null()
     }
+    #[inline]
+    pub fn clear_source_info(&mut self) {
+      self.builder.get_pointer_field(3).clear()
+    }
   }
 
   pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -6485,10 +7703,13 @@ pub mod code_generator_request {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.reader.total_size()
       }
+      /// ID of the file.
       #[inline]
       pub fn get_id(self) -> u64 {
         self.reader.get_data_field::<u64>(0)
       }
+      /// Name of the file as it appeared on the command-line (minus the src-prefix).  You may use
+      /// this to decide where to write the output.
       #[inline]
       pub fn get_filename(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -6496,6 +7717,7 @@ pub mod code_generator_request {
       pub fn has_filename(&self) -> bool {
         !self.reader.get_pointer_field(0).is_null()
       }
+      /// List of all imported paths seen in this file.
       #[inline]
       pub fn get_imports(self) -> ::capnp::Result<::capnp::struct_list::Reader<'a,crate::schema_capnp::code_generator_request::requested_file::import::Owned>> {
         ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(1), ::core::option::Option::None)
@@ -6553,22 +7775,30 @@ pub mod code_generator_request {
       pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
         self.builder.into_reader().total_size()
       }
+      /// ID of the file.
       #[inline]
       pub fn get_id(self) -> u64 {
         self.builder.get_data_field::<u64>(0)
       }
+      /// ID of the file.
       #[inline]
       pub fn set_id(&mut self, value: u64)  {
         self.builder.set_data_field::<u64>(0, value);
       }
+      /// Name of the file as it appeared on the command-line (minus the src-prefix).  You may use
+      /// this to decide where to write the output.
       #[inline]
       pub fn get_filename(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
       }
+      /// Name of the file as it appeared on the command-line (minus the src-prefix).  You may use
+      /// this to decide where to write the output.
       #[inline]
       pub fn set_filename(&mut self, value: ::capnp::text::Reader<'_>)  {
         self.builder.get_pointer_field(0).set_text(value);
       }
+      /// Name of the file as it appeared on the command-line (minus the src-prefix).  You may use
+      /// this to decide where to write the output.
       #[inline]
       pub fn init_filename(self, size: u32) -> ::capnp::text::Builder<'a> {
         self.builder.get_pointer_field(0).init_text(size)
@@ -6577,13 +7807,20 @@ pub mod code_generator_request {
         !self.builder.get_pointer_field(0).is_null()
       }
       #[inline]
+      pub fn clear_filename(&mut self) {
+        self.builder.get_pointer_field(0).clear()
+      }
+      /// List of all imported paths seen in this file.
+      #[inline]
       pub fn get_imports(self) -> ::capnp::Result<::capnp::struct_list::Builder<'a,crate::schema_capnp::code_generator_request::requested_file::import::Owned>> {
         ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(1), ::core::option::Option::None)
       }
+      /// List of all imported paths seen in this file.
       #[inline]
       pub fn set_imports(&mut self, value: ::capnp::struct_list::Reader<'a,crate::schema_capnp::code_generator_request::requested_file::import::Owned>) -> ::capnp::Result<()> {
         ::capnp::traits::SetPointerBuilder::set_pointer_builder(self.builder.get_pointer_field(1), value, false)
       }
+      /// List of all imported paths seen in this file.
       #[inline]
       pub fn init_imports(self, size: u32) -> ::capnp::struct_list::Builder<'a,crate::schema_capnp::code_generator_request::requested_file::import::Owned> {
         ::capnp::traits::FromPointerBuilder::init_pointer(self.builder.get_pointer_field(1), size)
@@ -6591,6 +7828,10 @@ pub mod code_generator_request {
       pub fn has_imports(&self) -> bool {
         !self.builder.get_pointer_field(1).is_null()
       }
+      #[inline]
+      pub fn clear_imports(&mut self) {
+        self.builder.get_pointer_field(1).clear()
+      }
     }
 
     pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
@@ -6653,10 +7894,17 @@ pub mod code_generator_request {
         pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
           self.reader.total_size()
         }
+        /// ID of the imported file.
         #[inline]
         pub fn get_id(self) -> u64 {
           self.reader.get_data_field::<u64>(0)
         }
+        /// Name which *this* file used to refer to the foreign file.  This may be a relative name.
+        /// This information is provided because it might be useful for code generation, e.g. to
+        /// generate #include directives in C++.  We don't put this in Node.file because this
+        /// information is only meaningful at compile time anyway.
+        ///
+        /// (On Zooko's triangle, this is the import's petname according to the importing file.)
         #[inline]
         pub fn get_name(self) -> ::capnp::Result<::capnp::text::Reader<'a>> {
           ::capnp::traits::FromPointerReader::get_from_pointer(&self.reader.get_pointer_field(0), ::core::option::Option::None)
@@ -6714,22 +7962,42 @@ pub mod code_generator_request {
         pub fn total_size(&self) -> ::capnp::Result<::capnp::MessageSize> {
           self.builder.into_reader().total_size()
         }
+        /// ID of the imported file.
         #[inline]
         pub fn get_id(self) -> u64 {
           self.builder.get_data_field::<u64>(0)
         }
+        /// ID of the imported file.
         #[inline]
         pub fn set_id(&mut self, value: u64)  {
           self.builder.set_data_field::<u64>(0, value);
         }
+        /// Name which *this* file used to refer to the foreign file.  This may be a relative name.
+        /// This information is provided because it might be useful for code generation, e.g. to
+        /// generate #include directives in C++.  We don't put this in Node.file because this
+        /// information is only meaningful at compile time anyway.
+        ///
+        /// (On Zooko's triangle, this is the import's petname according to the importing file.)
         #[inline]
         pub fn get_name(self) -> ::capnp::Result<::capnp::text::Builder<'a>> {
           ::capnp::traits::FromPointerBuilder::get_from_pointer(self.builder.get_pointer_field(0), ::core::option::Option::None)
         }
+        /// Name which *this* file used to refer to the foreign file.  This may be a relative name.
+        /// This information is provided because it might be useful for code generation, e.g. to
+        /// generate #include directives in C++.  We don't put this in Node.file because this
+        /// information is only meaningful at compile time anyway.
+        ///
+        /// (On Zooko's triangle, this is the import's petname according to the importing file.)
         #[inline]
         pub fn set_name(&mut self, value: ::capnp::text::Reader<'_>)  {
           self.builder.get_pointer_field(0).set_text(value);
         }
+        /// Name which *this* file used to refer to the foreign file.  This may be a relative name.
+        /// This information is provided because it might be useful for code generation, e.g. to
+        /// generate #include directives in C++.  We don't put this in Node.file because this
+        /// information is only meaningful at compile time anyway.
+        ///
+        /// (On Zooko's triangle, this is the import's petname according to the importing file.)
         #[inline]
         pub fn init_name(self, size: u32) -> ::capnp::text::Builder<'a> {
           self.builder.get_pointer_field(0).init_text(size)
@@ -6737,6 +8005,10 @@ pub mod code_generator_request {
         pub fn has_name(&self) -> bool {
           !self.builder.get_pointer_field(0).is_null()
         }
+        #[inline]
+        pub fn clear_name(&mut self) {
+          self.builder.get_pointer_field(0).clear()
+        }
       }
 
       pub struct Pipeline { _typeless: ::capnp::any_pointer::Pipeline }
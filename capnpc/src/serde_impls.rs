@@ -0,0 +1,338 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//! Generation of serde support: `Serialize` for readers and `DeserializeSeed` for builders,
+//! built on the helpers of the `capnp::serde` module.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::codegen::{FormattedText, GeneratorContext, Reached,
+                     camel_to_snake_case, capitalize_first_letter, get_field_name, walk_scope};
+use crate::codegen::FormattedText::{Indent, Line, Branch, BlankLine};
+use crate::codegen_types::{Leaf, RustTypeInfo};
+use crate::schema_capnp::{field, node, type_};
+
+/// Returns the structs and enums of the requested files that get serde support. Generic structs
+/// are left out.
+pub fn select_serde_nodes(request: crate::schema_capnp::code_generator_request::Reader,
+                          node_map: &HashMap<u64, node::Reader>) -> ::capnp::Result<HashSet<u64>> {
+    let mut nodes = HashSet::new();
+    for requested_file in request.get_requested_files()?.iter() {
+        // The value passed down is whether the node is a generic interface.
+        walk_scope(node_map, requested_file.get_id(), &false, &mut |id, node_reader, reached, &generic_interface| {
+            // The implicit Params and Results structs of a method take the type parameters of
+            // the interface.
+            if reached == Reached::MethodStruct && generic_interface {
+                return Ok(None);
+            }
+            match node_reader.which()? {
+                node::Struct(_) if !node_reader.get_is_generic() => { nodes.insert(id); }
+                node::Enum(_) => { nodes.insert(id); }
+                node::Interface(_) => return Ok(Some(node_reader.get_is_generic())),
+                _ => {}
+            }
+            Ok(Some(false))
+        })?;
+    }
+    Ok(nodes)
+}
+
+/// Whether values of type `typ` can be serialized and deserialized. Fields of other types, such
+/// as interfaces and `AnyPointer`s, are skipped.
+fn is_supported_type(gen: &GeneratorContext, typ: type_::Reader) -> ::capnp::Result<bool> {
+    Ok(match typ.which()? {
+        type_::Struct(st) => gen.serde_nodes.contains(&st.get_type_id()),
+        type_::Enum(en) => gen.serde_nodes.contains(&en.get_type_id()),
+        type_::List(ot) => is_supported_type(gen, ot.get_element_type()?)?,
+        type_::Interface(_) | type_::AnyPointer(_) => false,
+        _ => true,
+    })
+}
+
+fn is_supported_field(gen: &GeneratorContext, field: field::Reader) -> ::capnp::Result<bool> {
+    Ok(match field.which()? {
+        field::Slot(slot) => is_supported_type(gen, slot.get_type()?)?,
+        field::Group(group) => gen.serde_nodes.contains(&group.get_type_id()),
+    })
+}
+
+fn short_name<'a>(node_reader: node::Reader<'a>) -> ::capnp::Result<&'a str> {
+    let display_name = node_reader.get_display_name()?;
+    Ok(&display_name[node_reader.get_display_name_prefix_length() as usize..])
+}
+
+/// Returns the expression that serializes the value `value` of type `typ`, which is wrapped in
+/// a `Result` if `is_result` is set.
+fn serialized_value(typ: type_::Reader, value: &str, is_result: bool) -> ::capnp::Result<String> {
+    let value = if is_result {
+        format!("{}.map_err(S::Error::custom)?", value)
+    } else {
+        value.to_string()
+    };
+    Ok(match typ.which()? {
+        type_::Data(()) => format!("&::capnp::serde::Bytes({})", value),
+        _ => format!("&{}", value),
+    })
+}
+
+/// Whether getters for values of type `typ` return a `Result`.
+fn is_result(typ: type_::Reader) -> ::capnp::Result<bool> {
+    Ok(!matches!(typ.which()?,
+                 type_::Void(()) | type_::Bool(()) |
+                 type_::Int8(()) | type_::Int16(()) | type_::Int32(()) | type_::Int64(()) |
+                 type_::Uint8(()) | type_::Uint16(()) | type_::Uint32(()) | type_::Uint64(()) |
+                 type_::Float32(()) | type_::Float64(())))
+}
+
+/// Returns the statements of `DeserializeFields::deserialize_field()` that set the field with
+/// the setter or initializer `styled_name` from the next value of the map.
+fn deserialize_value(gen: &GeneratorContext, typ: type_::Reader, styled_name: &str,
+                     offset: u32) -> ::capnp::Result<FormattedText>
+{
+    Ok(match typ.which()? {
+        type_::Void(()) => Branch(vec![
+            Line("map.next_value::<::capnp::serde::de::IgnoredAny>()?;".to_string()),
+            Line(format!("self.set_{}(());", styled_name)),
+        ]),
+        type_::Struct(_) =>
+            Line(format!("map.next_value_seed(self.reborrow().init_{}())?;", styled_name)),
+        type_::Text(()) | type_::Data(()) | type_::List(_) =>
+            Line(format!(
+                "map.next_value_seed(::capnp::serde::PointerSeed::<{}>::new(::capnp::any_pointer::Builder::new(self.builder.get_pointer_field({}))))?;",
+                typ.type_string(gen, Leaf::Owned)?, offset)),
+        _ => Line(format!("self.set_{}(map.next_value()?);", styled_name)),
+    })
+}
+
+/// Generates the serde implementations for the struct `node_id`, which go in the module of the
+/// struct.
+pub fn generate_struct_impls(gen: &GeneratorContext, node_id: u64) -> ::capnp::Result<FormattedText> {
+    let node_reader = gen.node_map[&node_id];
+    let struct_reader = match node_reader.which()? {
+        node::Struct(struct_reader) => struct_reader,
+        _ => return Err(::capnp::Error::failed("expected a struct node".to_string())),
+    };
+    let name = short_name(node_reader)?;
+
+    let mut serialize_fields = Vec::new();
+    let mut serialize_arms = Vec::new();
+    let mut deserialize_arms = Vec::new();
+    let mut field_count = 0;
+
+    for field in struct_reader.get_fields()?.iter() {
+        let key = field.get_name()?;
+        let styled_name = camel_to_snake_case(get_field_name(field)?);
+        let is_union_field = field.get_discriminant_value() != field::NO_DISCRIMINANT;
+        let supported = is_supported_field(gen, field)?;
+
+        if is_union_field {
+            let variant = format!("Which::{}", capitalize_first_letter(get_field_name(field)?));
+            serialize_arms.push(if !supported {
+                Line(format!("{}(_) => state.skip_field(\"{}\")?,", variant, key))
+            } else {
+                match field.which()? {
+                    field::Group(_) =>
+                        Line(format!("{}(v) => state.serialize_field(\"{}\", &v)?,", variant, key)),
+                    field::Slot(slot) => {
+                        let typ = slot.get_type()?;
+                        if let type_::Void(()) = typ.which()? {
+                            Line(format!("{}(()) => state.serialize_field(\"{}\", &())?,", variant, key))
+                        } else {
+                            Line(format!("{}(v) => state.serialize_field(\"{}\", {})?,", variant, key,
+                                         serialized_value(typ, "v", is_result(typ)?)?))
+                        }
+                    }
+                }
+            });
+        }
+
+        if !supported { continue }
+
+        match field.which()? {
+            field::Group(_) => {
+                if !is_union_field {
+                    field_count += 1;
+                    serialize_fields.push(Line(format!(
+                        "state.serialize_field(\"{}\", &self.get_{}())?;", key, styled_name)));
+                }
+                deserialize_arms.push(Line(format!(
+                    "\"{}\" => map.next_value_seed(self.reborrow().init_{}())?,", key, styled_name)));
+            }
+            field::Slot(slot) => {
+                let typ = slot.get_type()?;
+                if !is_union_field {
+                    match typ.which()? {
+                        type_::Void(()) => continue,
+                        type_::Struct(_) => {
+                            serialize_fields.push(Branch(vec![
+                                Line(format!("if self.has_{}() {{", styled_name)),
+                                Indent(Box::new(Line(format!(
+                                    "state.serialize_field(\"{}\", &self.get_{}().map_err(S::Error::custom)?)?;",
+                                    key, styled_name)))),
+                                Line("} else {".to_string()),
+                                Indent(Box::new(Line(format!("state.skip_field(\"{}\")?;", key)))),
+                                Line("}".to_string()),
+                            ]));
+                        }
+                        _ => {
+                            serialize_fields.push(Line(format!(
+                                "state.serialize_field(\"{}\", {})?;", key,
+                                serialized_value(typ, &format!("self.get_{}()", styled_name), is_result(typ)?)?)));
+                        }
+                    }
+                    field_count += 1;
+                }
+
+                let mut statements = Vec::new();
+                if is_union_field {
+                    if let type_::Text(()) | type_::Data(()) | type_::List(_) = typ.which()? {
+                        statements.push(Line(format!("self.builder.set_data_field::<u16>({}, {});",
+                                                     struct_reader.get_discriminant_offset(),
+                                                     field.get_discriminant_value())));
+                    }
+                }
+                statements.push(deserialize_value(gen, typ, &styled_name, slot.get_offset())?);
+                deserialize_arms.push(Branch(vec![
+                    Line(format!("\"{}\" => {{", key)),
+                    Indent(Box::new(Branch(statements))),
+                    Line("}".to_string()),
+                ]));
+            }
+        }
+    }
+
+    if struct_reader.get_discriminant_count() > 0 {
+        if gen.unknown_variants.contains(&node_id) {
+            serialize_arms.push(Line(
+                "Which::Unknown(d) => return ::core::result::Result::Err(S::Error::custom(::capnp::NotInSchema(d))),"
+                    .to_string()));
+        }
+        field_count += 1;
+        serialize_fields.push(Branch(vec![
+            Line("match self.which().map_err(S::Error::custom)? {".to_string()),
+            Indent(Box::new(Branch(serialize_arms))),
+            Line("}".to_string()),
+        ]));
+    }
+    let (key_name, map_name) = if deserialize_arms.is_empty() { ("_key", "_map") } else { ("key", "map") };
+    let deserialize_body = if deserialize_arms.is_empty() {
+        Line("::core::result::Result::Ok(false)".to_string())
+    } else {
+        deserialize_arms.push(Line("_ => return ::core::result::Result::Ok(false),".to_string()));
+        Branch(vec![
+            Line("match key {".to_string()),
+            Indent(Box::new(Branch(deserialize_arms))),
+            Line("}".to_string()),
+            Line("::core::result::Result::Ok(true)".to_string()),
+        ])
+    };
+
+    Ok(Branch(vec![
+        BlankLine,
+        Line("impl <'a> ::capnp::serde::ser::Serialize for Reader<'a> {".to_string()),
+        Indent(Box::new(Branch(vec![
+            Line("fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>".to_string()),
+            Indent(Box::new(Line("where S: ::capnp::serde::ser::Serializer".to_string()))),
+            Line("{".to_string()),
+            Indent(Box::new(Branch(vec![
+                Line("#[allow(unused_imports)]".to_string()),
+                Line("use ::capnp::serde::ser::{Error as _, SerializeStruct as _};".to_string()),
+                Line(format!("let {}state = serializer.serialize_struct(\"{}\", {})?;",
+                             if field_count == 0 { "" } else { "mut " }, name, field_count)),
+                Branch(serialize_fields),
+                Line("state.end()".to_string()),
+            ]))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+        BlankLine,
+        Line("impl <'a> ::capnp::serde::DeserializeFields for Builder<'a> {".to_string()),
+        Indent(Box::new(Branch(vec![
+            Line(format!("fn deserialize_field<'de, A>(&mut self, {}: &str, {}: &mut A) -> ::core::result::Result<bool, A::Error>",
+                         key_name, map_name)),
+            Indent(Box::new(Line("where A: ::capnp::serde::de::MapAccess<'de>".to_string()))),
+            Line("{".to_string()),
+            Indent(Box::new(deserialize_body)),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+        BlankLine,
+        Line("impl <'de, 'a> ::capnp::serde::de::DeserializeSeed<'de> for Builder<'a> {".to_string()),
+        Indent(Box::new(Branch(vec![
+            Line("type Value = ();".to_string()),
+            Line("fn deserialize<D>(self, deserializer: D) -> ::core::result::Result<(), D::Error>".to_string()),
+            Indent(Box::new(Line("where D: ::capnp::serde::de::Deserializer<'de>".to_string()))),
+            Line("{".to_string()),
+            Indent(Box::new(Line(format!("::capnp::serde::deserialize_struct(deserializer, \"{}\", self)", name)))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+        BlankLine,
+        Line("impl ::capnp::serde::DeserializeStruct for Owned {".to_string()),
+        Indent(Box::new(Branch(vec![
+            Line("fn deserialize_struct<'a, 'de, D>(builder: Builder<'a>, deserializer: D) -> ::core::result::Result<(), D::Error>".to_string()),
+            Indent(Box::new(Line("where D: ::capnp::serde::de::Deserializer<'de>".to_string()))),
+            Line("{".to_string()),
+            Indent(Box::new(Line("::capnp::serde::de::DeserializeSeed::deserialize(builder, deserializer)".to_string()))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+    ]))
+}
+
+/// Generates the serde implementations for the enum `node_id`, which go next to the enum.
+pub fn generate_enum_impls(gen: &GeneratorContext, node_id: u64) -> ::capnp::Result<FormattedText> {
+    let node_reader = gen.node_map[&node_id];
+    let enumerants = match node_reader.which()? {
+        node::Enum(enum_reader) => enum_reader.get_enumerants()?,
+        _ => return Err(::capnp::Error::failed("expected an enum node".to_string())),
+    };
+    let name = short_name(node_reader)?;
+    let last_name = gen.get_last_name(node_id)?;
+    let mut names = Vec::new();
+    for enumerant in enumerants.iter() {
+        names.push(format!("\"{}\"", enumerant.get_name()?));
+    }
+    let names = format!("&[{}]", names.join(", "));
+
+    Ok(Branch(vec![
+        Line(format!("impl ::capnp::serde::ser::Serialize for {} {{", last_name)),
+        Indent(Box::new(Branch(vec![
+            Line("fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>".to_string()),
+            Indent(Box::new(Line("where S: ::capnp::serde::ser::Serializer".to_string()))),
+            Line("{".to_string()),
+            Indent(Box::new(Line(format!(
+                "::capnp::serde::serialize_enum(serializer, \"{}\", {}, ::capnp::traits::ToU16::to_u16(*self))", name, names)))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+        Line(format!("impl <'de> ::capnp::serde::de::Deserialize<'de> for {} {{", last_name)),
+        Indent(Box::new(Branch(vec![
+            Line(format!("fn deserialize<D>(deserializer: D) -> ::core::result::Result<{}, D::Error>", last_name)),
+            Indent(Box::new(Line("where D: ::capnp::serde::de::Deserializer<'de>".to_string()))),
+            Line("{".to_string()),
+            Indent(Box::new(Line(format!(
+                "::capnp::serde::deserialize_enum(deserializer, \"{}\", {})", name, names)))),
+            Line("}".to_string()),
+        ]))),
+        Line("}".to_string()),
+    ]))
+}
@@ -0,0 +1,79 @@
+// Copyright (c) 2013-2014 Sandstorm Development Group, Inc. and contributors
+// Licensed under the MIT License:
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+
+//! Selection of the enums and unions that get an `Unknown(u16)` variant, which preserves
+//! discriminants that are not in the schema known to the generated code.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::codegen::{bool_annotation, capitalize_first_letter, get_enumerant_name, get_field_name, walk_scope};
+use crate::schema_capnp::{field, node};
+
+const UNKNOWN_VARIANTS_ANNOTATION_ID: u64 = 0x8e9ea2d3f2bf9c51;
+
+/// Determines which enums, and which structs with unions, of the requested files get an
+/// `Unknown(u16)` variant. `default` applies to everything that is not covered by a
+/// `$Rust.unknownVariants` annotation.
+pub fn select_unknown_variants(request: crate::schema_capnp::code_generator_request::Reader,
+                               node_map: &HashMap<u64, node::Reader>,
+                               default: bool) -> ::capnp::Result<HashSet<u64>> {
+    let mut selected = HashSet::new();
+    for requested_file in request.get_requested_files()?.iter() {
+        let id = requested_file.get_id();
+        let file_enabled = match node_map.get(&id) {
+            Some(node) => bool_annotation(node.get_annotations()?, UNKNOWN_VARIANTS_ANNOTATION_ID, "unknownVariants")?
+                .unwrap_or(default),
+            None => continue,
+        };
+        walk_scope(node_map, id, &file_enabled, &mut |id, node_reader, _, &inherited| {
+            let enabled = bool_annotation(node_reader.get_annotations()?, UNKNOWN_VARIANTS_ANNOTATION_ID, "unknownVariants")?
+                .unwrap_or(inherited);
+            match node_reader.which()? {
+                node::Enum(enum_reader) if enabled => {
+                    for enumerant in enum_reader.get_enumerants()?.iter() {
+                        if capitalize_first_letter(get_enumerant_name(enumerant)?) == "Unknown" {
+                            return Err(::capnp::Error::failed(format!(
+                                "enumerant `{}` of {} conflicts with the generated `Unknown` variant",
+                                enumerant.get_name()?, node_reader.get_display_name()?)));
+                        }
+                    }
+                    selected.insert(id);
+                }
+                node::Struct(struct_reader) if enabled && struct_reader.get_discriminant_count() > 0 => {
+                    for field in struct_reader.get_fields()?.iter() {
+                        if field.get_discriminant_value() != field::NO_DISCRIMINANT &&
+                            capitalize_first_letter(get_field_name(field)?) == "Unknown"
+                        {
+                            return Err(::capnp::Error::failed(format!(
+                                "union field `{}` of {} conflicts with the generated `Unknown` variant",
+                                field.get_name()?, node_reader.get_display_name()?)));
+                        }
+                    }
+                    selected.insert(id);
+                }
+                _ => {}
+            }
+            Ok(Some(enabled))
+        })?;
+    }
+    Ok(selected)
+}
@@ -1,19 +1,39 @@
 fn main() {
-     capnpc::CompilerCommand::new()
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR env var is not set");
+
+    // Compile the schemas with the reference `capnp` executable, so that the code generator is
+    // tested against its output. Fall back to the native compiler where `capnp` is not installed.
+    let have_capnp = std::process::Command::new("capnp").arg("--version").output().is_ok();
+    if !have_capnp {
+        println!("cargo:warning=`capnp` executable not found; compiling test schemas with the native compiler");
+    }
+    println!("cargo:rustc-env=CAPNPC_TEST_SCHEMA_COMPILER={}", if have_capnp { "capnp" } else { "native" });
+    let capnp_command = || {
+        let mut command = capnpc::CompilerCommand::new();
+        if !have_capnp {
+            command.native_compiler();
+        }
+        command
+    };
+
+    capnp_command()
+        .plain_structs(true)
+        .serde(true)
+        .reader_equality(true)
+        .async_servers(true)
+        .annotation_constants(true)
+        .random_generators(true)
         .file("test.capnp")
         .file("in-submodule.capnp")
         .file("in-other-submodule.capnp")
         .file("schema/test-in-dir.capnp")
         .file("schema-with-src-prefix/test-in-src-prefix-dir.capnp")
         .src_prefix("schema-with-src-prefix")
-        .raw_code_generator_request_path(
-            std::env::var("OUT_DIR").expect("OUT_DIR env var is not set")
-                + "/raw_code_gen_request.bin",
-        )
+        .raw_code_generator_request_path(out_dir.clone() + "/raw_code_gen_request.bin")
         .run()
         .expect("compiling schema");
 
-    capnpc::CompilerCommand::new()
+    capnp_command()
         .file("test-default-parent-module.capnp")
         .file("test-default-parent-module-override.capnp")
         .default_parent_module(vec!["test_default_parent_module".into(),
@@ -21,4 +41,47 @@ fn main() {
         .run()
         .expect("compiling schema");
 
+    // The same schemas again, compiled by the native compiler, so that tests can check that it
+    // produces the same nodes as `capnp`. Only the code for test.capnp is included.
+    capnpc::CompilerCommand::new()
+        .native_compiler()
+        .serde(true)
+        .file("test.capnp")
+        .file("in-submodule.capnp")
+        .file("in-other-submodule.capnp")
+        .file("schema/test-in-dir.capnp")
+        .file("schema-with-src-prefix/test-in-src-prefix-dir.capnp")
+        .src_prefix("schema-with-src-prefix")
+        .default_parent_module(vec!["native".into()])
+        .output_path(out_dir.clone() + "/native")
+        .raw_code_generator_request_path(out_dir.clone() + "/native/raw_code_gen_request.bin")
+        .run()
+        .expect("compiling schema");
+
+    capnp_command()
+        .file("schema/tree/test-module-tree.capnp")
+        .file("schema/tree/test-module-tree-nested.capnp")
+        .default_parent_module(vec!["module_tree".into()])
+        .output_path(out_dir.clone() + "/module_tree")
+        .entrypoint("capnp_generated.rs")
+        .run()
+        .expect("compiling schema");
+
+    capnp_command()
+        .file("test-only-nodes.capnp")
+        .only_nodes(vec!["Outer.Used".into()])
+        .run()
+        .expect("compiling schema");
+
+    capnp_command()
+        .file("test-skip-interfaces.capnp")
+        .skip_interfaces(true)
+        .run()
+        .expect("compiling schema");
+
+    capnp_command()
+        .file("test-skip-structs.capnp")
+        .skip_structs(true)
+        .run()
+        .expect("compiling schema");
 }
@@ -25,6 +25,13 @@
 extern crate core;
 
 extern crate capnp;
+extern crate capnpc;
+#[cfg(test)]
+extern crate quickcheck;
+#[cfg(test)]
+extern crate serde;
+#[cfg(test)]
+extern crate serde_json;
 
 pub mod test_capnp {
     include!(concat!(env!("OUT_DIR"), "/test_capnp.rs"));
@@ -60,6 +67,18 @@ pub mod test_default_parent_module {
 
 }
 
+pub mod test_only_nodes_capnp {
+    include!(concat!(env!("OUT_DIR"), "/test_only_nodes_capnp.rs"));
+}
+
+pub mod test_skip_interfaces_capnp {
+    include!(concat!(env!("OUT_DIR"), "/test_skip_interfaces_capnp.rs"));
+}
+
+pub mod test_skip_structs_capnp {
+    include!(concat!(env!("OUT_DIR"), "/test_skip_structs_capnp.rs"));
+}
+
 pub mod test_in_dir_capnp {
     include!(concat!(env!("OUT_DIR"), "/schema/test_in_dir_capnp.rs"));
 }
@@ -69,6 +88,16 @@ pub mod test_in_src_prefix_dir_capnp {
   include!(concat!(env!("OUT_DIR"), "/test_in_src_prefix_dir_capnp.rs"));
 }
 
+pub mod native {
+    // test.capnp compiled by the native schema compiler instead of `capnp`.
+    pub mod test_capnp {
+        include!(concat!(env!("OUT_DIR"), "/native/test_capnp.rs"));
+    }
+}
+
+// Declares `module_tree::test_module_tree_capnp` and `module_tree::nested::test_module_tree_nested_capnp`.
+include!(concat!(env!("OUT_DIR"), "/module_tree/capnp_generated.rs"));
+
 #[cfg(test)]
 mod test_util;
 
@@ -158,6 +187,60 @@ mod tests {
         assert_eq!(test_prim_list_reader.get_void_list().unwrap().len(), 1025);
     }
 
+    #[test]
+    fn test_list_setters() {
+        use test_capnp::{test_complex_list, test_prim_list, test_union_list, AnEnum};
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut prim_lists = message.init_root::<test_prim_list::Builder<'_>>();
+            prim_lists.set_int32_list_from_slice(&[1, -2, 3]).unwrap();
+            prim_lists.set_uint8_list_from_slice(&[4, 5]).unwrap();
+            prim_lists.set_bool_list_from_iter((0..10).map(|i| i % 3 == 0)).unwrap();
+            prim_lists.set_float32_list_from_iter(vec![0.5, 1.5]).unwrap();
+
+            let prim_lists = prim_lists.into_reader();
+            assert_eq!(prim_lists.get_int32_list().unwrap().iter().collect::<Vec<_>>(), vec![1, -2, 3]);
+            assert_eq!(prim_lists.get_uint8_list().unwrap().iter().collect::<Vec<_>>(), vec![4, 5]);
+            let bool_list = prim_lists.get_bool_list().unwrap();
+            assert_eq!(bool_list.len(), 10);
+            assert!(bool_list.get(0) && bool_list.get(9) && !bool_list.get(1));
+            assert_eq!(prim_lists.get_float32_list().unwrap().iter().collect::<Vec<_>>(), vec![0.5, 1.5]);
+        }
+
+        {
+            let mut complex_lists = message.init_root::<test_complex_list::Builder<'_>>();
+            complex_lists.set_enum_list_from_slice(&[AnEnum::Qux, AnEnum::Foo]).unwrap();
+            let names = ["abc".to_string(), "de".to_string()];
+            complex_lists.set_text_list_from_iter(names.iter().map(|s| &s[..])).unwrap();
+            complex_lists.set_data_list_from_slice(&[b"xy", b""]).unwrap();
+
+            let complex_lists = complex_lists.into_reader();
+            let enum_list = complex_lists.get_enum_list().unwrap();
+            assert_eq!(enum_list.len(), 2);
+            assert!(enum_list.get(0) == Ok(AnEnum::Qux));
+            assert!(enum_list.get(1) == Ok(AnEnum::Foo));
+            let text_list = complex_lists.get_text_list().unwrap();
+            assert_eq!(text_list.iter().collect::<::capnp::Result<Vec<_>>>().unwrap(), vec!["abc", "de"]);
+            let data_list = complex_lists.get_data_list().unwrap();
+            assert_eq!(data_list.get(0).unwrap(), b"xy");
+            assert_eq!(data_list.get(1).unwrap(), b"");
+        }
+
+        {
+            let mut union_list = message.init_root::<test_union_list::Builder<'_>>();
+            union_list.set_text_list_from_slice(&["a"]).unwrap();
+            assert!(matches!(union_list.reborrow().which(), Ok(test_union_list::TextList(_))));
+            union_list.set_int32_list_from_iter(1..4).unwrap();
+            match union_list.into_reader().which() {
+                Ok(test_union_list::Int32List(list)) => {
+                    assert_eq!(list.unwrap().iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+                }
+                _ => panic!("expected int32List"),
+            }
+        }
+    }
+
     #[test]
     fn test_struct_list () {
         use test_capnp::test_struct_list;
@@ -749,6 +832,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_generic_interfaces() {
+        use capnp::{data, text};
+        use capnp::capability::Request;
+        use test_capnp::{test_generic_store, test_generic_sub_store, test_generics,
+                         test_generics_wrapper, test_text_store, test_text_sub_store};
+        use test_capnp::test_generic_store::{swap_params, swap_results};
+
+        type SwapRequest = Request<swap_params::Owned<text::Owned, data::Owned>,
+                                   swap_results::Owned<text::Owned, data::Owned>>;
+        type WrapRequest = Request<test_generics::Owned<text::Owned, data::Owned>,
+                                   test_generics_wrapper::Owned<data::Owned, text::Owned>>;
+        type KeysRequest = Request<test_generic_sub_store::keys_params::Owned<data::Owned>,
+                                   test_generic_sub_store::keys_results::Owned<data::Owned>>;
+
+        // Method implicit parameters are chosen by the caller.
+        let _: fn(&test_generic_store::Client<text::Owned>) -> SwapRequest =
+            |client| client.swap_request::<data::Owned>();
+        let _: fn(&test_generic_store::Client<text::Owned>) -> WrapRequest = |client| client.wrap_request();
+        let _: fn(&test_generic_sub_store::Client<data::Owned>) -> KeysRequest =
+            test_generic_sub_store::Client::keys_request;
+
+        // Clients convert into the clients of their superclasses.
+        let _: fn(test_text_store::Client) -> test_generic_store::Client<text::Owned> = From::from;
+        let _: fn(test_generic_sub_store::Client<data::Owned>) -> test_generic_store::Client<data::Owned> =
+            From::from;
+        let _: fn(test_text_sub_store::Client) -> test_generic_store::Client<text::Owned> = From::from;
+        let _: fn(test_text_sub_store::Client) -> test_text_store::Client = From::from;
+        let _: fn(test_text_sub_store::Client) -> test_generic_sub_store::Client<text::Owned> = From::from;
+
+        // Servers receive them as `AnyPointer`, and can cast to the types they expect.
+        struct Store;
+        impl test_generic_store::Server<text::Owned> for Store {
+            fn swap(&mut self,
+                    params: test_generic_store::SwapParams<text::Owned>,
+                    results: test_generic_store::SwapResults<text::Owned>)
+                    -> ::capnp::capability::Promise<(), ::capnp::Error>
+            {
+                let params: test_generic_store::SwapParams<text::Owned, data::Owned> = params.cast();
+                let mut results: test_generic_store::SwapResults<text::Owned, data::Owned> = results.cast();
+                match params.get().and_then(|params| results.get().set_new(params.get_value()?)) {
+                    Ok(()) => ::capnp::capability::Promise::ok(()),
+                    Err(e) => ::capnp::capability::Promise::err(e),
+                }
+            }
+        }
+        impl test_text_store::Server for Store {}
+        let _: Box<dyn test_text_store::Server> = Box::new(Store);
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut params = message.init_root::<swap_params::Builder<'_, text::Owned, data::Owned>>();
+            params.set_key("k");
+            params.set_value(b"\x00\x01").unwrap();
+        }
+        let params = message.get_root_as_reader::<swap_params::Reader<'_, text::Owned, ::capnp::any_pointer::Owned>>().unwrap();
+        assert_eq!(params.get_value().unwrap().get_as::<data::Reader<'_>>().unwrap(), b"\x00\x01");
+    }
+
     #[test]
     fn test_union() {
         use test_capnp::test_union;
@@ -873,6 +1015,303 @@ mod tests {
         assert_eq!(struct_list.get(2).get_text_field().unwrap(), "structlist 3");
     }
 
+    #[test]
+    fn test_pointer_constants() {
+        use capnp::{constant, text_list};
+        use test_capnp::{test_all_types, test_constants, test_groups, TestEnum};
+
+        const STRUCT_COPY: constant::Reader<test_all_types::Owned> = test_constants::STRUCT_CONST;
+        static TEXT_LIST: constant::Reader<text_list::Owned> = test_constants::TEXT_LIST_CONST;
+        assert_eq!(STRUCT_COPY.get().unwrap().get_text_field().unwrap(), "baz");
+        assert_eq!(TEXT_LIST.get().unwrap().get(1).unwrap(), "xyzzy");
+
+        let any_struct = test_constants::ANY_POINTER_CONST.get().unwrap();
+        let any_struct = any_struct.get_as::<test_all_types::Reader<'_>>().unwrap();
+        assert_eq!(any_struct.get_int16_field(), 3456);
+        assert_eq!(any_struct.get_struct_field().unwrap().get_text_field().unwrap(), "nested");
+        let any_list = test_constants::ANY_LIST_CONST.get().unwrap();
+        let any_list = any_list.get_as::<text_list::Reader<'_>>().unwrap();
+        assert_eq!(any_list.len(), 3);
+        assert_eq!(any_list.get(2).unwrap(), "thud");
+
+        let nested_list = test_constants::NESTED_LIST_CONST.get().unwrap();
+        assert_eq!(nested_list.len(), 3);
+        assert_eq!(nested_list.get(0).unwrap().get(1), 2);
+        assert_eq!(nested_list.get(1).unwrap().len(), 0);
+        assert_eq!(nested_list.get(2).unwrap().get(0), 3);
+
+        match test_constants::GROUPS_CONST.get().unwrap().get_groups().which().unwrap() {
+            test_groups::groups::Baz(baz) => {
+                assert_eq!(baz.get_corge(), 12);
+                assert_eq!(baz.get_grault().unwrap(), "x");
+                assert_eq!(baz.get_garply().unwrap(), "");
+                assert_eq!(baz.get_quz(), 1.5);
+                assert_eq!(baz.get_an_enum().unwrap(), TestEnum::Bar);
+            }
+            _ => panic!("expected baz"),
+        }
+
+        let lists = test_constants::LISTS_CONST.get().unwrap();
+        assert_eq!(lists.get_list0().unwrap().len(), 2);
+        assert!(lists.get_list1().unwrap().get(0).get_f());
+        assert!(!lists.get_list1().unwrap().get(1).get_f());
+        assert_eq!(lists.get_list8().unwrap().get(0).get_f(), 8);
+        assert_eq!(lists.get_list64().unwrap().get(0).get_f(), 64);
+        assert_eq!(lists.get_list_p().unwrap().get(0).get_f().unwrap(), "p");
+        assert_eq!(lists.get_list16().unwrap().len(), 0);
+        assert_eq!(lists.get_int32_list_list().unwrap().get(1).unwrap().get(1), 3);
+        assert_eq!(lists.get_text_list_list().unwrap().get(0).unwrap().get(0).unwrap(), "a");
+        assert_eq!(lists.get_text_list_list().unwrap().get(1).unwrap().len(), 0);
+        let struct_list_list = lists.get_struct_list_list().unwrap();
+        assert_eq!(struct_list_list.get(0).unwrap().get(0).get_int8_field(), 5);
+        assert_eq!(struct_list_list.get(1).unwrap().get(0).get_text_field().unwrap(), "t");
+
+        // Fields that the constant does not set read as the struct's defaults.
+        let defaults = test_constants::DEFAULTS_CONST.get().unwrap();
+        assert_eq!(defaults.get_int32_field(), 7);
+        assert_eq!(defaults.get_int8_field(), -123);
+        assert_eq!(defaults.get_text_field().unwrap(), "foo");
+        assert_eq!(defaults.get_struct_field().unwrap().get_text_field().unwrap(), "baz");
+
+        assert_eq!(test_constants::EMPTY_STRUCT_CONST.get().unwrap().total_size().unwrap().word_count, 0);
+    }
+
+    #[test]
+    fn test_struct_equality() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        use capnp::primitive_list;
+        use capnp::struct_list;
+        use capnp::traits::IntoInternalStructReader;
+        use test_capnp::{test_all_types, test_any_pointer, test_lists, test_new_version, test_old_version,
+                         test_union};
+
+        fn hash_of<T: Hash>(value: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        // The same contents, one of them spread over single-word segments with far pointers.
+        let mut message1 = message::Builder::new_default();
+        ::test_util::init_test_message(message1.init_root());
+        let mut message2 = message::Builder::new(message::HeapAllocator::new()
+            .first_segment_words(1).allocation_strategy(message::AllocationStrategy::FixedSize));
+        ::test_util::init_test_message(message2.init_root());
+        assert!(message2.get_segments_for_output().len() > 1);
+        {
+            let reader1 = message1.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap();
+            let reader2 = message2.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap();
+            assert!(reader1 == reader2);
+            assert_eq!(hash_of(&reader1), hash_of(&reader2));
+            assert!(::capnp::equality::struct_eq(reader1.into_internal_struct_reader(),
+                                                 reader2.into_internal_struct_reader()).unwrap());
+        }
+        message2.get_root::<test_all_types::Builder<'_>>().unwrap()
+            .get_struct_list().unwrap().get(2).set_text_field("changed");
+        {
+            let reader1 = message1.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap();
+            let reader2 = message2.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap();
+            assert!(reader1 != reader2);
+        }
+
+        // A struct written with an older, smaller schema equals one with the new fields at
+        // their defaults.
+        let mut old_message = message::Builder::new_default();
+        {
+            let mut old = old_message.init_root::<test_old_version::Builder<'_>>();
+            old.set_old1(123);
+            old.set_old2("foo");
+        }
+        let mut new_message = message::Builder::new_default();
+        {
+            let mut new = new_message.init_root::<test_new_version::Builder<'_>>();
+            new.set_old1(123);
+            new.set_old2("foo");
+            new.set_new1(987);
+        }
+        {
+            let old = old_message.get_root_as_reader::<test_new_version::Reader<'_>>().unwrap();
+            let new = new_message.get_root_as_reader::<test_new_version::Reader<'_>>().unwrap();
+            assert!(old == new);
+            assert_eq!(hash_of(&old), hash_of(&new));
+        }
+        {
+            // A null pointer is the same as a pointer to the default value, in nested structs too.
+            let mut new = new_message.get_root::<test_new_version::Builder<'_>>().unwrap();
+            new.set_new2("baz");
+            new.init_new3();
+        }
+        {
+            let old = old_message.get_root_as_reader::<test_new_version::Reader<'_>>().unwrap();
+            let new = new_message.get_root_as_reader::<test_new_version::Reader<'_>>().unwrap();
+            assert!(old == new);
+            assert_eq!(hash_of(&old), hash_of(&new));
+            // Without the schema, they differ.
+            assert!(!::capnp::equality::struct_eq(old.into_internal_struct_reader(),
+                                                  new.into_internal_struct_reader()).unwrap());
+        }
+        new_message.get_root::<test_new_version::Builder<'_>>().unwrap().set_new2("qux");
+        {
+            let old = old_message.get_root_as_reader::<test_new_version::Reader<'_>>().unwrap();
+            let new = new_message.get_root_as_reader::<test_new_version::Reader<'_>>().unwrap();
+            assert!(old != new);
+        }
+
+        // Only the active member of a union is compared.
+        let mut union_message1 = message::Builder::new_default();
+        {
+            let mut union0 = union_message1.init_root::<test_union::Builder<'_>>().get_union0();
+            union0.set_u0f0s32(1234);
+            union0.set_u0f0s8(5);
+        }
+        let mut union_message2 = message::Builder::new_default();
+        union_message2.init_root::<test_union::Builder<'_>>().get_union0().set_u0f0s8(5);
+        {
+            let union1 = union_message1.get_root_as_reader::<test_union::Reader<'_>>().unwrap();
+            let union2 = union_message2.get_root_as_reader::<test_union::Reader<'_>>().unwrap();
+            assert!(union1 == union2);
+            assert_eq!(hash_of(&union1), hash_of(&union2));
+        }
+
+        // A primitive list equals a list of structs, however wide, holding the same values.
+        let mut primitive_message = message::Builder::new_default();
+        {
+            let mut list = primitive_message.init_root::<test_any_pointer::Builder<'_>>()
+                .get_any_pointer_field().initn_as::<primitive_list::Builder<'_, u8>>(2);
+            list.set(0, 1);
+            list.set(1, 2);
+        }
+        let mut struct_message = message::Builder::new_default();
+        {
+            let mut list = struct_message.init_root::<test_any_pointer::Builder<'_>>()
+                .get_any_pointer_field().initn_as::<struct_list::Builder<'_, test_lists::struct8c::Owned>>(2);
+            list.reborrow().get(0).set_f(1);
+            list.reborrow().get(1).set_f(2);
+        }
+        {
+            let primitive = primitive_message.get_root_as_reader::<test_any_pointer::Reader<'_>>().unwrap();
+            let structs = struct_message.get_root_as_reader::<test_any_pointer::Reader<'_>>().unwrap();
+            assert!(primitive == structs);
+            assert_eq!(hash_of(&primitive), hash_of(&structs));
+        }
+        struct_message.get_root::<test_any_pointer::Builder<'_>>().unwrap()
+            .get_any_pointer_field().get_as::<struct_list::Builder<'_, test_lists::struct8c::Owned>>().unwrap()
+            .get(1).set_pad("x");
+        {
+            let primitive = primitive_message.get_root_as_reader::<test_any_pointer::Reader<'_>>().unwrap();
+            let structs = struct_message.get_root_as_reader::<test_any_pointer::Reader<'_>>().unwrap();
+            assert!(primitive != structs);
+        }
+    }
+
+    #[test]
+    fn test_clear_and_union_predicates() {
+        use test_capnp::{test_all_types, test_union};
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut root = message.init_root::<test_all_types::Builder<'_>>();
+            root.set_text_field("hello");
+            root.reborrow().init_struct_field().set_text_field("nested");
+            root.reborrow().init_int32_list(3).set(1, 17);
+            let size = root.reborrow_as_reader().total_size().unwrap().word_count;
+
+            assert!(root.has_text_field());
+            root.clear_text_field();
+            assert!(!root.has_text_field());
+            assert_eq!(root.reborrow().get_text_field().unwrap().len(), 0);
+            root.clear_struct_field();
+            assert!(!root.has_struct_field());
+            root.clear_int32_list();
+            assert!(!root.has_int32_list());
+            assert_eq!(root.reborrow().get_int32_list().unwrap().len(), 0);
+            assert!(root.reborrow_as_reader().total_size().unwrap().word_count < size);
+        }
+        // The cleared objects were zeroed.
+        let segment = message.get_segments_for_output()[0];
+        assert!(!segment.windows(5).any(|window| window == b"hello"));
+        assert!(!segment.windows(6).any(|window| window == b"nested"));
+
+        let mut message = message::Builder::new_default();
+        let mut union0 = message.init_root::<test_union::Builder<'_>>().get_union0();
+        union0.set_u0f0sp("text");
+        assert!(union0.is_u0f0sp());
+        assert!(!union0.is_u0f1sp());
+        assert!(union0.reborrow_as_reader().is_u0f0sp());
+        for _ in 0..2 {
+            match union0.which_mut().unwrap() {
+                test_union::union0::U0f0sp(text) => assert_eq!(text.unwrap().len(), 4),
+                _ => panic!("expected u0f0sp"),
+            }
+        }
+
+        union0.clear_u0f1sp();
+        assert!(union0.is_u0f1sp());
+        assert!(!union0.has_u0f1sp());
+        assert!(!union0.has_u0f0sp());
+        union0.set_u0f0s8(3);
+        assert!(union0.is_u0f0s8());
+        assert!(!union0.reborrow_as_reader().is_u0f0sp());
+    }
+
+    #[test]
+    fn test_random_generators() {
+        use capnp::random::{Generator, RandomMessage};
+        use test_capnp::{test_all_types, test_union};
+
+        fn depth(root: test_all_types::Reader<'_>) -> u32 {
+            let field = if root.has_struct_field() { depth(root.get_struct_field().unwrap()) + 1 } else { 0 };
+            root.get_struct_list().unwrap().iter().map(|s| depth(s) + 1).fold(field, u32::max)
+        }
+
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || { state ^= state << 13; state ^= state >> 7; state ^= state << 17; state };
+        let mut variants = ::std::collections::HashSet::new();
+        for _ in 0..50 {
+            let mut generator = Generator::new(&mut next);
+            generator.max_list_len = 3;
+            generator.max_depth = 2;
+            let message = RandomMessage::<test_all_types::Owned>::generate(&mut generator).unwrap();
+            let root = message.get().unwrap();
+            assert!(depth(root) <= 2);
+            assert!(root.get_int32_list().unwrap().len() <= 3);
+            assert!(root.get_text_field().unwrap().len() <= 3);
+            assert!(root.get_text_list().unwrap().iter().all(|t| t.is_ok()));
+
+            let message = RandomMessage::<test_union::Owned>::generate(&mut Generator::new(&mut next)).unwrap();
+            let union0 = message.get().unwrap().get_union0();
+            let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+            ::std::hash::Hash::hash(&::std::mem::discriminant(&union0.which().unwrap()), &mut hasher);
+            variants.insert(::std::hash::Hasher::finish(&hasher));
+        }
+        assert!(variants.len() > 5);
+
+        fn roundtrip(message: RandomMessage<test_all_types::Owned>) -> bool {
+            let mut copy = message::Builder::new_default();
+            copy.set_root(message.get().unwrap()).unwrap();
+            copy.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap() == message.get().unwrap()
+        }
+        quickcheck::QuickCheck::new().tests(20).quickcheck(roundtrip as fn(RandomMessage<test_all_types::Owned>) -> bool);
+    }
+
+    #[test]
+    fn test_module_tree() {
+        use module_tree::test_module_tree_capnp::foo;
+        use module_tree::nested::test_module_tree_nested_capnp::bar;
+
+        let mut message = message::Builder::new_default();
+        message.init_root::<foo::Builder<'_>>().init_bar().set_n(17);
+        let bar: bar::Reader<'_> = message.get_root_as_reader::<foo::Reader<'_>>().unwrap().get_bar().unwrap();
+        assert_eq!(bar.get_n(), 17);
+    }
+
+    #[test]
+    fn test_generated_code_version() {
+        assert!(::capnp::generated_code_version::supports(capnpc::codegen::GENERATED_CODE_VERSION));
+        assert!(!::capnp::generated_code_version::supports(::capnp::generated_code_version::LATEST + 1));
+    }
+
     #[test]
     fn test_set_root() {
         use test_capnp::test_big_struct;
@@ -1646,6 +2085,500 @@ mod tests {
         ::test_util::CheckTestMessage::check_test_message(message_reader.get().unwrap());
     }
 
+    #[test]
+    fn test_plain_struct_round_trip() {
+        use test_capnp::{test_all_types, TestEnum};
+
+        let mut message = message::Builder::new_default();
+        ::test_util::init_test_message(message.init_root());
+        let plain = test_all_types::Plain::from_reader(
+            message.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap()).unwrap();
+        assert_eq!(plain.text_field, "foo");
+        let sub = plain.struct_field.as_ref().unwrap();
+        assert_eq!(sub.u_int32_field, 56789012);
+        assert_eq!(sub.struct_field.as_ref().unwrap().text_field, "nested");
+        assert_eq!(sub.void_list.len(), 3);
+        assert_eq!(sub.enum_list, vec![TestEnum::Qux, TestEnum::Bar, TestEnum::Grault]);
+
+        let mut message2 = message::Builder::new_default();
+        plain.write_into(message2.init_root()).unwrap();
+        let reader2 = message2.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap();
+        ::test_util::CheckTestMessage::check_test_message(reader2);
+        assert_eq!(test_all_types::Plain::from_reader(reader2).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_plain_struct_unions_and_groups() {
+        use test_capnp::{test_groups, test_union, TestEnum};
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut baz = message.init_root::<test_groups::Builder<'_>>().init_groups().init_baz();
+            baz.set_corge(1);
+            baz.set_grault("two");
+            baz.set_garply("three");
+            baz.set_an_enum(TestEnum::Bar);
+        }
+        let plain = test_groups::Plain::from_reader(message.get_root_as_reader().unwrap()).unwrap();
+        match plain.groups.which {
+            test_groups::groups::PlainWhich::Baz(ref baz) => {
+                assert_eq!(baz.corge, 1);
+                assert_eq!(baz.grault, "two");
+                assert_eq!(baz.garply, "three");
+                assert_eq!(baz.an_enum, TestEnum::Bar);
+            }
+            _ => panic!("expected Baz"),
+        }
+
+        let mut message2 = message::Builder::new_default();
+        plain.write_into(message2.init_root()).unwrap();
+        match message2.get_root_as_reader::<test_groups::Reader<'_>>().unwrap().get_groups().which().unwrap() {
+            test_groups::groups::Baz(baz) => assert_eq!(baz.get_garply().unwrap(), "three"),
+            _ => panic!("expected Baz"),
+        }
+
+        let mut plain = test_union::Plain::from_reader(
+            message::Builder::new_default().get_root_as_reader().unwrap()).unwrap();
+        plain.union0.which = test_union::union0::PlainWhich::U0f1sp("abc".to_string());
+        plain.union1.which = test_union::union1::PlainWhich::U1f0s0;
+        plain.bit0 = true;
+        let mut message3 = message::Builder::new_default();
+        plain.write_into(message3.init_root()).unwrap();
+        let reader3 = message3.get_root_as_reader::<test_union::Reader<'_>>().unwrap();
+        match reader3.get_union0().which().unwrap() {
+            test_union::union0::U0f1sp(Ok("abc")) => (),
+            _ => panic!("expected U0f1sp"),
+        }
+        assert_eq!(test_union::Plain::from_reader(reader3).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_plain_struct_nested_lists() {
+        use test_capnp::{test_all_types, test_lists};
+
+        let mut inner = test_all_types::Plain::from_reader(
+            message::Builder::new_default().get_root_as_reader().unwrap()).unwrap();
+        inner.int8_field = -1;
+        let mut plain = test_lists::Plain::from_reader(
+            message::Builder::new_default().get_root_as_reader().unwrap()).unwrap();
+        plain.list0 = vec![test_lists::struct0::Plain {}; 3];
+        plain.int32_list_list = vec![vec![1, 2], vec![], vec![3]];
+        plain.text_list_list = vec![vec!["a".to_string()], vec!["b".to_string(), "c".to_string()]];
+        plain.struct_list_list = vec![vec![inner]];
+
+        let mut message = message::Builder::new_default();
+        plain.write_into(message.init_root()).unwrap();
+        let reader = message.get_root_as_reader::<test_lists::Reader<'_>>().unwrap();
+        assert_eq!(reader.get_list0().unwrap().len(), 3);
+        assert_eq!(reader.get_text_list_list().unwrap().get(1).unwrap().get(1).unwrap(), "c");
+        assert_eq!(reader.get_struct_list_list().unwrap().get(0).unwrap().get(0).get_int8_field(), -1);
+        assert_eq!(test_lists::Plain::from_reader(reader).unwrap(), plain);
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        use serde::de::DeserializeSeed;
+        use test_capnp::test_all_types;
+
+        let mut message = message::Builder::new_default();
+        ::test_util::init_test_message(message.init_root());
+        let json = serde_json::to_string(
+            &message.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap()).unwrap();
+
+        let mut message2 = message::Builder::new_default();
+        message2.init_root::<test_all_types::Builder<'_>>()
+            .deserialize(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+        ::test_util::CheckTestMessage::check_test_message(
+            message2.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap());
+        assert_eq!(serde_json::to_string(
+            &message2.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap()).unwrap(), json);
+    }
+
+    #[test]
+    fn test_native_compiler_matches_capnp() {
+        use capnp::traits::{HasTypeId, IntoInternalStructReader};
+        use capnpc::schema_capnp::{code_generator_request, node};
+        use native::test_capnp as native;
+        use std::collections::HashMap;
+        use test_capnp::test_all_types;
+
+        if env!("CAPNPC_TEST_SCHEMA_COMPILER") != "capnp" {
+            // Both sets of schemas come from the native compiler, so there is nothing to compare.
+            eprintln!("skipping test_native_compiler_matches_capnp: `capnp` executable not found");
+            return;
+        }
+
+        // The build script saved the requests of both compilers for the same files.
+        let read_request = |path: &str| {
+            let file = ::std::fs::File::open(path).unwrap();
+            ::capnp::serialize::read_message(&mut ::std::io::BufReader::new(file),
+                                             message::ReaderOptions::new()).unwrap()
+        };
+        let capnp_message = read_request(concat!(env!("OUT_DIR"), "/raw_code_gen_request.bin"));
+        let native_message = read_request(concat!(env!("OUT_DIR"), "/native/raw_code_gen_request.bin"));
+        fn nodes<'a>(message: &'a message::Reader<::capnp::serialize::OwnedSegments>)
+                     -> (HashMap<u64, node::Reader<'a>>, Vec<String>)
+        {
+            let request = message.get_root::<code_generator_request::Reader<'_>>().unwrap();
+            let mut nodes = HashMap::new();
+            for node in request.get_nodes().unwrap().iter() {
+                nodes.insert(node.get_id(), node);
+            }
+            let requested_files: Vec<String> = request.get_requested_files().unwrap().iter()
+                .map(|file| file.get_filename().unwrap().to_string()).collect();
+            (nodes, requested_files)
+        }
+        let (capnp_nodes, requested_files) = nodes(&capnp_message);
+        let (native_nodes, native_requested_files) = nodes(&native_message);
+        assert_eq!(native_requested_files, requested_files);
+
+        // The nodes of imported files are only compared if both compilers include them, since
+        // unused ones may be left out.
+        let is_requested = |node: &node::Reader<'_>| {
+            let display_name = node.get_display_name().unwrap();
+            requested_files.iter().any(|file| display_name.split(':').next() == Some(&file[..]))
+        };
+        for (id, capnp_node) in &capnp_nodes {
+            let display_name = capnp_node.get_display_name().unwrap();
+            match native_nodes.get(id) {
+                Some(native_node) => assert!(
+                    ::capnp::equality::struct_eq((*native_node).into_internal_struct_reader(),
+                                                 (*capnp_node).into_internal_struct_reader()).unwrap(),
+                    "the native compiler produces a different node for {}", display_name),
+                None => assert!(!is_requested(capnp_node),
+                                "the native compiler produces no node for {}", display_name),
+            }
+        }
+        for (id, native_node) in &native_nodes {
+            assert!(capnp_nodes.contains_key(id) || !is_requested(native_node),
+                    "the native compiler produces an extra node {}", native_node.get_display_name().unwrap());
+        }
+
+        let mut message = message::Builder::new_default();
+        ::test_util::init_test_message(message.init_root());
+        let json = serde_json::to_string(
+            &message.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap()).unwrap();
+        let native_json = serde_json::to_string(
+            &message.get_root_as_reader::<native::test_all_types::Reader<'_>>().unwrap()).unwrap();
+        assert_eq!(native_json, json);
+
+        // Default values are encoded by the schema compiler, so compare those too.
+        let mut message = message::Builder::new_default();
+        message.init_root::<::test_capnp::test_defaults::Builder<'_>>();
+        let json = serde_json::to_string(
+            &message.get_root_as_reader::<::test_capnp::test_defaults::Reader<'_>>().unwrap()).unwrap();
+        let native_json = serde_json::to_string(
+            &message.get_root_as_reader::<native::test_defaults::Reader<'_>>().unwrap()).unwrap();
+        assert_eq!(native_json, json);
+
+        assert_eq!(native::test_all_types::Builder::type_id(), test_all_types::Builder::type_id());
+        assert_eq!(native::test_interface::Client::type_id(),
+                   ::test_capnp::test_interface::Client::type_id());
+    }
+
+    #[test]
+    fn test_serde_long_lists() {
+        use serde::de::DeserializeSeed;
+        use test_capnp::{test_all_types, test_lists};
+
+        // Long enough that the scratch lists the elements are collected in have to grow.
+        let structs: Vec<String> = (0..100).map(|i| format!("{{\"int32Field\": {}}}", i)).collect();
+        let lists: Vec<String> = (0..100).map(|i| format!("[{}]", i)).collect();
+        let json = format!("{{\"structList\": [{}]}}", structs.join(","));
+        let mut message = message::Builder::new_default();
+        message.init_root::<test_all_types::Builder<'_>>()
+            .deserialize(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+        let struct_list = message.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap()
+            .get_struct_list().unwrap();
+        assert_eq!(struct_list.len(), 100);
+        for (i, element) in struct_list.iter().enumerate() {
+            assert_eq!(element.get_int32_field(), i as i32);
+        }
+
+        let json = format!("{{\"int32ListList\": [{}]}}", lists.join(","));
+        let mut message = message::Builder::new_default();
+        message.init_root::<test_lists::Builder<'_>>()
+            .deserialize(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+        let list_list = message.get_root_as_reader::<test_lists::Reader<'_>>().unwrap()
+            .get_int32_list_list().unwrap();
+        assert_eq!(list_list.len(), 100);
+        for (i, element) in list_list.iter().enumerate() {
+            assert_eq!(element.unwrap().get(0), i as i32);
+        }
+    }
+
+    #[test]
+    fn test_serde_json_representation() {
+        use serde::de::DeserializeSeed;
+        use test_capnp::{test_all_types, test_groups, TestEnum};
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut root = message.init_root::<test_all_types::Builder<'_>>();
+            root.set_int8_field(-3);
+            root.set_data_field(b"hi");
+            root.set_enum_field(TestEnum::Grault);
+        }
+        let value = serde_json::to_value(
+            message.get_root_as_reader::<test_all_types::Reader<'_>>().unwrap()).unwrap();
+        assert_eq!(value["int8Field"], -3);
+        assert_eq!(value["dataField"], serde_json::json!([104, 105]));
+        assert_eq!(value["enumField"], "grault");
+        assert_eq!(value["textList"], serde_json::json!([]));
+        assert!(value.get("structField").is_none());
+        assert!(value.get("voidField").is_none());
+
+        let mut message = message::Builder::new_default();
+        message.init_root::<test_groups::Builder<'_>>().deserialize(
+            &mut serde_json::Deserializer::from_str(
+                r#"{"groups": {"baz": {"corge": 5, "grault": "x", "anEnum": "qux"}}, "unknown": [1]}"#)).unwrap();
+        let reader = message.get_root_as_reader::<test_groups::Reader<'_>>().unwrap();
+        match reader.get_groups().which().unwrap() {
+            test_groups::groups::Baz(baz) => {
+                assert_eq!(baz.get_corge(), 5);
+                assert_eq!(baz.get_grault().unwrap(), "x");
+                assert_eq!(baz.get_an_enum().unwrap(), TestEnum::Qux);
+            }
+            _ => panic!("expected Baz"),
+        }
+        assert_eq!(serde_json::to_value(reader).unwrap(),
+                   serde_json::json!({"groups": {"baz": {"corge": 5, "grault": "x", "garply": "",
+                                                         "quz": 0.0, "anEnum": "qux"}}}));
+
+        let mut message = message::Builder::new_default();
+        let result = message.init_root::<test_all_types::Builder<'_>>().deserialize(
+            &mut serde_json::Deserializer::from_str(r#"{"enumField": "nope"}"#));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_serde_nested_lists() {
+        use serde::de::DeserializeSeed;
+        use test_capnp::test_lists;
+
+        let json = r#"{"list0": [{}, {}], "listP": [{"f": "a"}], "int32ListList": [[1, 2], [], [3]],
+                       "textListList": [["x"], ["y", "z"]],
+                       "structListList": [[{"int8Field": -1, "textList": ["t"]}], []]}"#;
+        let mut message = message::Builder::new_default();
+        message.init_root::<test_lists::Builder<'_>>()
+            .deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+        let reader = message.get_root_as_reader::<test_lists::Reader<'_>>().unwrap();
+        assert_eq!(reader.get_list0().unwrap().len(), 2);
+        assert_eq!(reader.get_list_p().unwrap().get(0).get_f().unwrap(), "a");
+        let int32_list_list = reader.get_int32_list_list().unwrap();
+        assert_eq!(int32_list_list.len(), 3);
+        assert_eq!(int32_list_list.get(0).unwrap().get(1), 2);
+        assert_eq!(int32_list_list.get(1).unwrap().len(), 0);
+        assert_eq!(reader.get_text_list_list().unwrap().get(1).unwrap().get(1).unwrap(), "z");
+        let inner = reader.get_struct_list_list().unwrap().get(0).unwrap().get(0);
+        assert_eq!(inner.get_int8_field(), -1);
+        assert_eq!(inner.get_text_list().unwrap().get(0).unwrap(), "t");
+
+        let value = serde_json::to_value(reader).unwrap();
+        assert_eq!(value["int32ListList"], serde_json::json!([[1, 2], [], [3]]));
+        assert_eq!(value["structListList"][0][0]["int8Field"], -1);
+    }
+
+    #[test]
+    fn test_unknown_variants() {
+        use test_capnp::{test_unknown_variants, test_unknown_variants_newer};
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut root = message.init_root::<test_unknown_variants_newer::Builder<'_>>();
+            root.set_color(test_unknown_variants_newer::Color::Blue);
+            {
+                let mut colors = root.reborrow().init_colors(2);
+                colors.set(0, test_unknown_variants_newer::Color::Blue);
+                colors.set(1, test_unknown_variants_newer::Color::Green);
+            }
+            root.set_c("new");
+        }
+
+        let old = message.get_root_as_reader::<test_unknown_variants::Reader<'_>>().unwrap();
+        assert_eq!(old.get_color().unwrap(), test_unknown_variants::Color::Unknown(2));
+        let colors = old.get_colors().unwrap();
+        assert_eq!(colors.get(0).unwrap(), test_unknown_variants::Color::Unknown(2));
+        assert_eq!(colors.get(1).unwrap(), test_unknown_variants::Color::Green);
+        match old.which().unwrap() {
+            test_unknown_variants::Unknown(2) => (),
+            _ => panic!("expected Unknown(2)"),
+        }
+
+        // Write the unknown values back through an older builder.
+        let mut message2 = message::Builder::new_default();
+        {
+            let mut root = message2.init_root::<test_unknown_variants::Builder<'_>>();
+            root.set_color(old.get_color().unwrap());
+            root.set_colors(old.get_colors().unwrap()).unwrap();
+            match old.which().unwrap() {
+                test_unknown_variants::Unknown(d) => root.set_which_unknown(d),
+                _ => panic!("expected Unknown"),
+            }
+        }
+        let newer = message2.get_root_as_reader::<test_unknown_variants_newer::Reader<'_>>().unwrap();
+        assert_eq!(newer.get_color().unwrap(), test_unknown_variants_newer::Color::Blue);
+        assert_eq!(newer.get_colors().unwrap().get(0).unwrap(), test_unknown_variants_newer::Color::Blue);
+        assert!(!newer.has_c());
+        match newer.which().unwrap() {
+            test_unknown_variants_newer::C(_) => (),
+            _ => panic!("expected C"),
+        }
+
+        // Plain structs keep the unknown values too.
+        let plain = test_unknown_variants::Plain::from_reader(old).unwrap();
+        assert_eq!(plain.which, test_unknown_variants::PlainWhich::Unknown(2));
+        let mut message3 = message::Builder::new_default();
+        plain.write_into(message3.init_root()).unwrap();
+        let newer = message3.get_root_as_reader::<test_unknown_variants_newer::Reader<'_>>().unwrap();
+        assert_eq!(newer.get_color().unwrap(), test_unknown_variants_newer::Color::Blue);
+        match newer.which().unwrap() {
+            test_unknown_variants_newer::C(_) => (),
+            _ => panic!("expected C"),
+        }
+
+        // Enums without the annotation still reject unknown values.
+        let mut message4 = message::Builder::new_default();
+        message4.init_root::<test_unknown_variants::Builder<'_>>()
+            .set_color(test_unknown_variants::Color::Unknown(70));
+        let reader = message4.get_root_as_reader::<test_unknown_variants_newer::Reader<'_>>().unwrap();
+        assert!(reader.get_color().is_err());
+        assert!(reader.which().is_ok());
+    }
+
+    #[test]
+    fn test_unknown_variants_serde() {
+        use serde::de::DeserializeSeed;
+        use test_capnp::test_unknown_variants;
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut root = message.init_root::<test_unknown_variants::Builder<'_>>();
+            root.set_color(test_unknown_variants::Color::Unknown(9));
+            root.set_b(3);
+        }
+        let json = serde_json::to_value(
+            message.get_root_as_reader::<test_unknown_variants::Reader<'_>>().unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({"color": 9, "colors": [], "b": 3}));
+
+        let mut message2 = message::Builder::new_default();
+        message2.init_root::<test_unknown_variants::Builder<'_>>()
+            .deserialize(&mut serde_json::Deserializer::from_str(&json.to_string())).unwrap();
+        let reader = message2.get_root_as_reader::<test_unknown_variants::Reader<'_>>().unwrap();
+        assert_eq!(reader.get_color().unwrap(), test_unknown_variants::Color::Unknown(9));
+
+        message.get_root::<test_unknown_variants::Builder<'_>>().unwrap().set_which_unknown(7);
+        assert!(serde_json::to_value(
+            message.get_root_as_reader::<test_unknown_variants::Reader<'_>>().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_derive_annotation() {
+        use std::collections::{BTreeSet, HashSet};
+        use test_capnp::test_derives;
+
+        assert!(test_derives::Mode::Slow < test_derives::Mode::Fast);
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut root = message.init_root::<test_derives::Builder<'_>>();
+            root.set_mode(test_derives::Mode::Fast);
+            root.set_name("a");
+            root.set_some("x");
+        }
+        let a = test_derives::Plain::from_reader(
+            message.get_root_as_reader::<test_derives::Reader<'_>>().unwrap()).unwrap();
+        let mut b = a.clone();
+        b.inner.x = 3;
+        b.which = test_derives::PlainWhich::None;
+
+        let hashed: HashSet<test_derives::Plain> = vec![a.clone(), b.clone(), a.clone()].into_iter().collect();
+        assert_eq!(hashed.len(), 2);
+        let ordered: BTreeSet<test_derives::Plain> = vec![b.clone(), a.clone()].into_iter().collect();
+        assert_eq!(ordered.into_iter().collect::<Vec<_>>(), vec![a, b]);
+    }
+
+    #[test]
+    fn test_annotation_constants() {
+        use test_capnp::{test_annotated, test_annotated_enum_annotations, test_annotated_interface,
+                         TestAnnotatedEnum, TestEnum};
+
+        assert_eq!(test_annotated::_annotations::PRIORITY, 3);
+        let limits = test_annotated::_annotations::LIMITS.get().unwrap();
+        assert_eq!(limits.get_max(), 10);
+        assert_eq!(limits.get_burst(), 2);
+        assert_eq!(test_annotated::_annotations::speed::UNIT, "m/s");
+        assert_eq!(test_annotated::_annotations::speed::PRIORITY, 1);
+        assert_eq!(test_annotated::_annotations::speed::LEVEL, TestEnum::Baz);
+        let markers: [(); 2] = [test_annotated::_annotations::name::MARKER,
+                                test_annotated::_annotations::offset::MARKER];
+        assert_eq!(markers.len(), 2);
+        assert_eq!(test_annotated::_annotations::weight::UNIT, "kg");
+        assert_eq!(test_annotated::offset::_annotations::x::UNIT, "mm");
+
+        assert_eq!(test_annotated_enum_annotations::PRIORITY, 7);
+        let tags = test_annotated_enum_annotations::TAGS.get().unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags.get(0).unwrap(), "size");
+        assert_eq!(tags.get(1).unwrap(), "dimension");
+        assert_eq!(test_annotated_enum_annotations::small::UNIT, "g");
+        assert_eq!(test_annotated_enum_annotations::unit_for_enumerant(TestAnnotatedEnum::Small), Some("g"));
+        assert_eq!(test_annotated_enum_annotations::unit_for_enumerant(TestAnnotatedEnum::Medium), None);
+        assert_eq!(test_annotated_enum_annotations::unit_for_enumerant(TestAnnotatedEnum::Large), Some("kg"));
+        assert_eq!(test_annotated_enum_annotations::priority_for_enumerant(TestAnnotatedEnum::Large), Some(2));
+        assert_eq!(test_annotated_enum_annotations::priority_for_enumerant(TestAnnotatedEnum::Small), None);
+
+        assert_eq!(test_annotated_interface::_annotations::AUTH_SCOPE, "read");
+        assert_eq!(test_annotated_interface::_annotations::TAGS.get().unwrap().get(0).unwrap(), "storage");
+        assert_eq!(test_annotated_interface::_annotations::auth_scope_for_method(0), Some("read"));
+        assert_eq!(test_annotated_interface::_annotations::auth_scope_for_method(1), Some("write"));
+        assert_eq!(test_annotated_interface::_annotations::auth_scope_for_method(2), None);
+        assert_eq!(test_annotated_interface::_annotations::auth_scope_for_method(17), None);
+        let put_limits = test_annotated_interface::_annotations::limits_for_method(1).unwrap();
+        assert_eq!(put_limits.get().unwrap().get_max(), 5);
+        assert!(test_annotated_interface::_annotations::limits_for_method(0).is_none());
+        assert_eq!(test_annotated_interface::put_params::_annotations::value::UNIT, "bytes");
+    }
+
+    #[test]
+    fn test_selective_code_generation() {
+        use std::fs;
+        use test_only_nodes_capnp::{outer, dep, Color};
+        use test_skip_interfaces_capnp::{holder, used};
+        use test_skip_structs_capnp::{service, arg, Color as SkipStructsColor};
+
+        let mut message = message::Builder::new_default();
+        {
+            let mut used = message.init_root::<outer::used::Builder<'_>>();
+            used.reborrow().init_dep().set_x(5);
+            used.set_color(Color::Green);
+            used.init_deps(1).get(0).set_x(6);
+        }
+        let used = message.get_root_as_reader::<outer::used::Reader<'_>>().unwrap();
+        assert_eq!(used.get_dep().unwrap().get_x(), 5);
+        assert_eq!(used.get_color().unwrap(), Color::Green);
+        assert_eq!(used.get_deps().unwrap().get(0).get_x(), 6);
+        let _: Option<dep::Reader<'_>> = None;
+
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        let only_nodes = fs::read_to_string(format!("{}/test_only_nodes_capnp.rs", out_dir)).unwrap();
+        assert!(!only_nodes.contains("pub mod unrelated"));
+        assert!(!only_nodes.contains("pub mod unused"));
+        assert!(!only_nodes.contains("UNRELATED_CONST"));
+        assert!(!only_nodes.contains("pub fn get_a("));
+
+        let _: Option<holder::Reader<'_>> = None;
+        let _: Option<used::Client> = None;
+        let skip_interfaces = fs::read_to_string(format!("{}/test_skip_interfaces_capnp.rs", out_dir)).unwrap();
+        assert!(!skip_interfaces.contains("pub mod unused"));
+
+        let _: Option<service::Client> = None;
+        let _: Option<arg::Reader<'_>> = None;
+        let _: Option<SkipStructsColor> = None;
+        let skip_structs = fs::read_to_string(format!("{}/test_skip_structs_capnp.rs", out_dir)).unwrap();
+        assert!(!skip_structs.contains("pub mod unused"));
+    }
+
     #[test] 
     fn test_raw_code_generator_request_path() {
         use std::fs;